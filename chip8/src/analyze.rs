@@ -0,0 +1,423 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Write};
+
+use crate::{disassemble, instruction_length, Quirks, ROM_ADDR};
+
+/// The shortest run of printable ASCII that's marked as text rather than data
+const MIN_TEXT_LENGTH: usize = 4;
+
+/// What a stretch of a ROM that's never run as code probably holds
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RegionKind {
+    /// Drawn by a DXYN after an ANNN pointed I at it
+    Sprite,
+    /// A run of printable ASCII
+    Text,
+    Data,
+}
+
+impl RegionKind {
+    fn name(&self) -> &'static str {
+        match self {
+            RegionKind::Sprite => "sprite",
+            RegionKind::Text => "text",
+            RegionKind::Data => "data",
+        }
+    }
+}
+
+/// A stretch of a ROM that isn't code, as an address range
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub start: u16,
+    /// The address just past the end of the region
+    pub end: u16,
+    pub kind: RegionKind,
+}
+
+/// A sprite drawn from the ROM, found from the ANNN before a DXYN
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Sprite {
+    pub addr: u16,
+    /// The number of rows, which are a byte wide, or two bytes wide for the SCHIP 16x16 sprites
+    /// drawn with DXY0
+    pub height: u8,
+    pub wide: bool,
+}
+
+impl Sprite {
+    fn len(&self) -> usize {
+        self.height as usize * if self.wide { 2 } else { 1 }
+    }
+
+    /// Draw the sprite as rows of '#' for set pixels and '.' for unset ones, reading it from
+    /// `rom`. Rows past the end of the ROM are left out.
+    pub fn ascii_art(&self, rom: &[u8]) -> String {
+        let start = (self.addr as usize).saturating_sub(ROM_ADDR);
+        let end = (start + self.len()).min(rom.len());
+        let bytes = rom.get(start..end).unwrap_or_default();
+        let row_bytes = if self.wide { 2 } else { 1 };
+        bytes
+            .chunks(row_bytes)
+            .map(|row| {
+                let mut line: String = row
+                    .iter()
+                    .flat_map(|byte| (0..8).map(move |bit| byte & (0x80 >> bit) != 0))
+                    .map(|set| if set { '#' } else { '.' })
+                    .collect();
+                line.push('\n');
+                line
+            })
+            .collect()
+    }
+}
+
+/// The extension of CHIP-8 a ROM was written for, going by the instructions it uses. Later
+/// variants order after the ones they extend.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Variant {
+    #[default]
+    Chip8,
+    Schip,
+    XoChip,
+}
+
+impl Variant {
+    /// The earliest variant that has `opcode`
+    fn of(opcode: u16) -> Self {
+        let nn = opcode & 0xFF;
+        match opcode >> 12 {
+            0x0 if opcode & 0xFFF0 == 0x00D0 => Variant::XoChip,
+            0x0 if matches!(opcode, 0x00C0..=0x00CF | 0x00FB..=0x00FF) => Variant::Schip,
+            0x5 if matches!(opcode & 0xF, 0x2 | 0x3) => Variant::XoChip,
+            0xD if opcode & 0xF == 0 => Variant::Schip,
+            0xF if matches!(opcode, 0xF000 | 0xF002) || matches!(nn, 0x01 | 0x3A) => {
+                Variant::XoChip
+            }
+            0xF if matches!(nn, 0x30 | 0x75 | 0x85) => Variant::Schip,
+            _ => Variant::Chip8,
+        }
+    }
+
+    /// The quirks of the interpreter programs for the variant were usually written against
+    pub fn quirks(&self) -> Quirks {
+        match self {
+            Variant::Chip8 => Quirks::cosmac_vip(),
+            Variant::Schip => Quirks::schip(),
+            Variant::XoChip => Quirks::xo_chip(),
+        }
+    }
+}
+
+impl Display for Variant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Variant::Chip8 => "CHIP-8",
+            Variant::Schip => "SUPER-CHIP",
+            Variant::XoChip => "XO-CHIP",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A static look at what a ROM contains, found by following every path from the entry point
+/// without running it. Anything the paths never reach is data, split into sprites, text and
+/// everything else. Jumps through `BNNN` can't be followed, so code only reached through them is
+/// reported as data.
+pub struct Analysis {
+    /// Whether each byte of the ROM is part of an instruction that can be reached
+    code: Vec<bool>,
+    /// The sprites drawn, by address
+    pub sprites: Vec<Sprite>,
+    /// The stretches that aren't code, in address order
+    pub regions: Vec<Region>,
+    /// The latest variant whose instructions the reachable code uses
+    pub variant: Variant,
+}
+
+impl Analysis {
+    pub fn new(rom: &[u8]) -> Self {
+        let mut code = vec![false; rom.len()];
+        let mut sprites = BTreeMap::new();
+        let opcode_at = |addr: usize| -> Option<u16> {
+            let offset = addr.checked_sub(ROM_ADDR)?;
+            Some(u16::from_be_bytes([
+                *rom.get(offset)?,
+                *rom.get(offset + 1)?,
+            ]))
+        };
+
+        // each path carries the value of I, while it's known, to find what DXYN draws
+        let mut visited = vec![false; rom.len()];
+        let mut variant = Variant::Chip8;
+        let mut paths = vec![(ROM_ADDR, None::<u16>)];
+        while let Some((mut addr, mut index)) = paths.pop() {
+            while let Some(opcode) = opcode_at(addr) {
+                let offset = addr - ROM_ADDR;
+                if visited[offset] {
+                    break;
+                }
+                visited[offset] = true;
+                variant = variant.max(Variant::of(opcode));
+                let length = instruction_length(opcode);
+                let end = (offset + length).min(rom.len());
+                code[offset..end].fill(true);
+                // a skip jumps over the whole of the next instruction
+                let skipped = addr + 2 + opcode_at(addr + 2).map_or(2, instruction_length);
+
+                let x = (opcode >> 8) & 0xF;
+                let nn = opcode & 0xFF;
+                let nnn = opcode & 0xFFF;
+                match opcode >> 12 {
+                    0x0 if matches!(opcode, 0x00EE | 0x00FD) => break,
+                    0x0 if matches!(
+                        opcode,
+                        0x00C0..=0x00DF | 0x00E0 | 0x00FB | 0x00FC | 0x00FE | 0x00FF
+                    ) => {}
+                    0x1 => {
+                        addr = nnn as usize;
+                        continue;
+                    }
+                    0x2 => paths.push((nnn as usize, index)),
+                    0x5 if matches!(opcode & 0xF, 0x2 | 0x3) => {}
+                    0x3 | 0x4 | 0x5 | 0x9 => paths.push((skipped, index)),
+                    0xE if matches!(nn, 0x9E | 0xA1 | 0xF2 | 0xF5) => paths.push((skipped, index)),
+                    0xA => index = Some(nnn),
+                    0xD => {
+                        if let Some(sprite_addr) = index {
+                            let height = opcode & 0xF;
+                            sprites.entry(sprite_addr).or_insert(Sprite {
+                                addr: sprite_addr,
+                                height: if height == 0 { 16 } else { height as u8 },
+                                wide: height == 0,
+                            });
+                        }
+                    }
+                    0xF if opcode == 0xF000 => index = opcode_at(addr + 2),
+                    0xF if x == 0 && nn == 0x02 => {}
+                    0xF if matches!(nn, 0x1E | 0x29 | 0x30 | 0x55 | 0x65) => index = None,
+                    0xB | 0x0 => break,
+                    _ if disassemble(opcode).is_none() => break,
+                    _ => {}
+                }
+                addr += length;
+            }
+        }
+
+        let sprites: Vec<Sprite> = sprites.into_values().collect();
+        let regions = Self::regions(rom, &code, &sprites);
+        Self {
+            code,
+            sprites,
+            regions,
+            variant,
+        }
+    }
+
+    /// Split the bytes that aren't code into regions
+    fn regions(rom: &[u8], code: &[bool], sprites: &[Sprite]) -> Vec<Region> {
+        let in_sprite = |offset: usize| {
+            let addr = offset + ROM_ADDR;
+            sprites.iter().any(|sprite| {
+                (sprite.addr as usize..sprite.addr as usize + sprite.len()).contains(&addr)
+            })
+        };
+        let printable = |byte: u8| byte.is_ascii_graphic() || byte == b' ';
+
+        let mut kinds: Vec<Option<RegionKind>> = (0..rom.len())
+            .map(|offset| match code[offset] {
+                true => None,
+                false if in_sprite(offset) => Some(RegionKind::Sprite),
+                false => Some(RegionKind::Data),
+            })
+            .collect();
+
+        // runs of printable data long enough to be words become text
+        let mut start = 0;
+        while start < rom.len() {
+            let is_text = |offset: &usize| {
+                kinds[*offset] == Some(RegionKind::Data) && printable(rom[*offset])
+            };
+            let end = (start..rom.len())
+                .find(|offset| !is_text(offset))
+                .unwrap_or(rom.len());
+            if end - start >= MIN_TEXT_LENGTH {
+                kinds[start..end].fill(Some(RegionKind::Text));
+            }
+            start = end + 1;
+        }
+
+        let mut regions: Vec<Region> = Vec::new();
+        for (offset, kind) in kinds.into_iter().enumerate() {
+            let Some(kind) = kind else {
+                continue;
+            };
+            let addr = (offset + ROM_ADDR) as u16;
+            match regions.last_mut() {
+                Some(region) if region.end == addr && region.kind == kind => region.end += 1,
+                _ => regions.push(Region {
+                    start: addr,
+                    end: addr + 1,
+                    kind,
+                }),
+            }
+        }
+        regions
+    }
+
+    /// Whether the byte at `addr` is part of an instruction that can be reached
+    pub fn is_code(&self, addr: u16) -> bool {
+        (addr as usize)
+            .checked_sub(ROM_ADDR)
+            .and_then(|offset| self.code.get(offset))
+            .copied()
+            .unwrap_or(false)
+    }
+
+    /// A disassembly of `rom` with the regions that aren't code shown as bytes and labelled with
+    /// what they probably hold
+    pub fn listing(&self, rom: &[u8]) -> String {
+        let mut listing = String::new();
+        let mut offset = 0;
+        while offset < rom.len() {
+            let addr = (offset + ROM_ADDR) as u16;
+            if let Some(region) = self.regions.iter().find(|region| region.start == addr) {
+                let bytes = &rom[offset..(region.end as usize - ROM_ADDR)];
+                for (row, chunk) in bytes.chunks(8).enumerate() {
+                    let hex: Vec<String> =
+                        chunk.iter().map(|byte| format!("{:02X}", byte)).collect();
+                    let note = match region.kind {
+                        RegionKind::Text => format!("\"{}\"", String::from_utf8_lossy(chunk)),
+                        kind => kind.name().to_string(),
+                    };
+                    let _ = writeln!(
+                        listing,
+                        "{:#05x}: {:<24}; {}",
+                        addr as usize + row * 8,
+                        hex.join(" "),
+                        note
+                    );
+                }
+                offset += bytes.len();
+                continue;
+            }
+
+            let opcode = match rom.get(offset..offset + 2) {
+                Some(bytes) => u16::from_be_bytes([bytes[0], bytes[1]]),
+                None => rom[offset] as u16,
+            };
+            let mut mnemonic = disassemble(opcode).unwrap_or_default();
+            let mut length = 2;
+            // F000's address is shown with it rather than as an instruction of its own
+            if let Some([high, low]) = rom.get(offset + 2..offset + instruction_length(opcode)) {
+                let _ = write!(mnemonic, " {:#06x}", u16::from_be_bytes([*high, *low]));
+                length = 4;
+            }
+            let _ = writeln!(listing, "{:#05x}: {:04X}  {}", addr, opcode, mnemonic);
+            offset += length;
+        }
+        listing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Analysis, Region, RegionKind, Sprite, Variant};
+
+    #[test]
+    fn test_analyze_sprites_and_text() {
+        let rom = [
+            0xA2, 0x08, // 0x200: LD I, 0x208
+            0xD0, 0x12, // 0x202: DRW V0, V1, 2
+            0x12, 0x04, // 0x204: JP 0x204
+            0x00, 0x00, // 0x206: padding
+            0x81, 0xFF, // 0x208: sprite
+            b'C', b'H', b'I', b'P', // 0x20A: text
+        ];
+        let analysis = Analysis::new(&rom);
+        assert_eq!(
+            analysis.sprites,
+            [Sprite {
+                addr: 0x208,
+                height: 2,
+                wide: false
+            }]
+        );
+        assert_eq!(analysis.sprites[0].ascii_art(&rom), "#......#\n########\n");
+        assert_eq!(
+            analysis.regions,
+            [
+                Region {
+                    start: 0x206,
+                    end: 0x208,
+                    kind: RegionKind::Data
+                },
+                Region {
+                    start: 0x208,
+                    end: 0x20A,
+                    kind: RegionKind::Sprite
+                },
+                Region {
+                    start: 0x20A,
+                    end: 0x20E,
+                    kind: RegionKind::Text
+                },
+            ]
+        );
+        assert_eq!(analysis.is_code(0x204), true);
+        assert_eq!(analysis.is_code(0x206), false);
+
+        let listing = analysis.listing(&rom);
+        assert!(listing.contains("0x202: D012  DRW V0, V1, 2\n"));
+        assert!(listing.contains("0x208: 81 FF                   ; sprite\n"));
+        assert!(listing.contains("0x20a: 43 48 49 50             ; \"CHIP\"\n"));
+    }
+
+    #[test]
+    fn test_analyze_long_index() {
+        let rom = [
+            0xF0, 0x00, 0x02, 0x10, // 0x200: LD I, LONG 0x210
+            0xD0, 0x12, // 0x204: DRW V0, V1, 2
+            0x30, 0x00, // 0x206: SE V0, 0
+            0xF0, 0x00, 0x12, 0x10, // 0x208: LD I, LONG 0x1210, which is skipped whole
+            0xD0, 0x12, // 0x20C: DRW V0, V1, 2
+            0x12, 0x0E, // 0x20E: JP 0x20E
+            0x81, 0xFF, // 0x210: sprite
+        ];
+        let analysis = Analysis::new(&rom);
+        let sprite = |addr| Sprite {
+            addr,
+            height: 2,
+            wide: false,
+        };
+        assert_eq!(analysis.sprites, [sprite(0x210), sprite(0x1210)]);
+        // landing on the address would have run it as JP 0x210
+        assert_eq!(
+            analysis.regions,
+            [Region {
+                start: 0x210,
+                end: 0x212,
+                kind: RegionKind::Sprite
+            }]
+        );
+        let listing = analysis.listing(&rom);
+        assert!(listing.contains("0x200: F000  LD I, LONG 0x0210\n0x204: D012"));
+    }
+
+    #[test]
+    fn test_analyze_variant() {
+        // the F000 in the data after the loop is never run, so it doesn't count
+        let rom = [0x00, 0xE0, 0x12, 0x02, 0xF0, 0x00];
+        assert_eq!(Analysis::new(&rom).variant, Variant::Chip8);
+
+        let rom = [0x00, 0xFF, 0xD0, 0x10, 0x12, 0x04];
+        let analysis = Analysis::new(&rom);
+        assert_eq!(analysis.variant, Variant::Schip);
+        assert_eq!(analysis.variant.to_string(), "SUPER-CHIP");
+
+        let rom = [0x00, 0xFF, 0xF1, 0x01, 0x12, 0x04];
+        let analysis = Analysis::new(&rom);
+        assert_eq!(analysis.variant, Variant::XoChip);
+        assert_eq!(analysis.variant.quirks().wrap_sprites, true);
+    }
+}
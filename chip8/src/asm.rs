@@ -0,0 +1,295 @@
+use std::fmt::Display;
+
+use anyhow::{bail, ensure, Context};
+
+use crate::{disassemble, instruction_length, ROM_ADDR};
+
+/// An operand of an instruction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operand {
+    Register(u16),
+    Number(u16),
+    /// One of the named operands, like `I`, `DT` or `[I]`
+    Name(&'static str),
+}
+
+const NAMES: [&str; 10] = ["I", "DT", "ST", "K", "F", "HF", "B", "R", "LONG", "[I]"];
+
+fn parse_operand(text: &str) -> anyhow::Result<Operand> {
+    let upper = text.to_ascii_uppercase();
+    if let Some(name) = NAMES.iter().find(|name| **name == upper) {
+        return Ok(Operand::Name(name));
+    }
+    if let Some(x) = upper.strip_prefix('V').filter(|x| x.len() == 1) {
+        let x =
+            u16::from_str_radix(x, 16).with_context(|| format!("invalid register '{}'", text))?;
+        return Ok(Operand::Register(x));
+    }
+    let value = match upper.strip_prefix("0X") {
+        Some(digits) => u16::from_str_radix(digits, 16),
+        None => upper.parse(),
+    };
+    value
+        .map(Operand::Number)
+        .with_context(|| format!("invalid operand '{}'", text))
+}
+
+/// Assemble a single instruction written in the notation `disassemble` produces
+pub fn assemble_instruction(text: &str) -> anyhow::Result<u16> {
+    use Operand::{Name, Number, Register};
+
+    let text = text.trim();
+    let (mnemonic, operands) = text.split_once(' ').unwrap_or((text, ""));
+    let operands = operands
+        .split(',')
+        .map(str::trim)
+        .filter(|operand| !operand.is_empty())
+        .map(parse_operand)
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let byte = |value: u16| -> anyhow::Result<u16> {
+        ensure!(value <= 0xFF, "{:#x} doesn't fit in a byte", value);
+        Ok(value)
+    };
+    let addr = |value: u16| -> anyhow::Result<u16> {
+        ensure!(value <= 0xFFF, "{:#x} doesn't fit in an address", value);
+        Ok(value)
+    };
+    let xy = |opcode: u16, x: u16, y: u16| opcode | x << 8 | y << 4;
+
+    let opcode = match (mnemonic.to_ascii_uppercase().as_str(), &operands[..]) {
+        ("CLS", []) => 0x00E0,
+        ("RET", []) => 0x00EE,
+        ("BGND", []) => 0x02A0,
+        ("SCD", [Number(n)]) => {
+            ensure!(*n <= 0xF, "scroll distance {} is more than 15", n);
+            0x00C0 | n
+        }
+        ("SCU", [Number(n)]) => {
+            ensure!(*n <= 0xF, "scroll distance {} is more than 15", n);
+            0x00D0 | n
+        }
+        ("SCR", []) => 0x00FB,
+        ("SCL", []) => 0x00FC,
+        ("EXIT", []) => 0x00FD,
+        ("LOW", []) => 0x00FE,
+        ("HIGH", []) => 0x00FF,
+        ("PLANE", [Number(n)]) => {
+            ensure!(*n <= 0xF, "plane mask {} is more than 15", n);
+            0xF001 | n << 8
+        }
+        ("AUDIO", []) => 0xF002,
+        ("JP", [Number(nnn)]) => 0x1000 | addr(*nnn)?,
+        ("JP", [Register(0), Number(nnn)]) => 0xB000 | addr(*nnn)?,
+        ("CALL", [Number(nnn)]) => 0x2000 | addr(*nnn)?,
+        ("SE", [Register(x), Number(nn)]) => 0x3000 | x << 8 | byte(*nn)?,
+        ("SNE", [Register(x), Number(nn)]) => 0x4000 | x << 8 | byte(*nn)?,
+        ("SE", [Register(x), Register(y)]) => xy(0x5000, *x, *y),
+        ("SNE", [Register(x), Register(y)]) => xy(0x9000, *x, *y),
+        ("LD", [Register(x), Number(nn)]) => 0x6000 | x << 8 | byte(*nn)?,
+        ("ADD", [Register(x), Number(nn)]) => 0x7000 | x << 8 | byte(*nn)?,
+        ("LD", [Register(x), Register(y)]) => xy(0x8000, *x, *y),
+        ("OR", [Register(x), Register(y)]) => xy(0x8001, *x, *y),
+        ("AND", [Register(x), Register(y)]) => xy(0x8002, *x, *y),
+        ("XOR", [Register(x), Register(y)]) => xy(0x8003, *x, *y),
+        ("ADD", [Register(x), Register(y)]) => xy(0x8004, *x, *y),
+        ("SUB", [Register(x), Register(y)]) => xy(0x8005, *x, *y),
+        ("SHR", [Register(x), Register(y)]) => xy(0x8006, *x, *y),
+        ("SUBN", [Register(x), Register(y)]) => xy(0x8007, *x, *y),
+        ("SHL", [Register(x), Register(y)]) => xy(0x800E, *x, *y),
+        ("LD", [Name("I"), Number(nnn)]) => 0xA000 | addr(*nnn)?,
+        ("LD", [Name("I"), Name("LONG")]) => 0xF000,
+        ("RND", [Register(x), Number(nn)]) => 0xC000 | x << 8 | byte(*nn)?,
+        ("DRW", [Register(x), Register(y), Number(n)]) => {
+            ensure!(*n <= 0xF, "sprite height {} is more than 15", n);
+            xy(0xD000, *x, *y) | n
+        }
+        ("SKP", [Register(x)]) => 0xE09E | x << 8,
+        ("SKNP", [Register(x)]) => 0xE0A1 | x << 8,
+        ("SKP2", [Register(x)]) => 0xE0F2 | x << 8,
+        ("SKNP2", [Register(x)]) => 0xE0F5 | x << 8,
+        ("LD", [Register(x), Name("DT")]) => 0xF007 | x << 8,
+        ("LD", [Register(x), Name("K")]) => 0xF00A | x << 8,
+        ("LD", [Name("DT"), Register(x)]) => 0xF015 | x << 8,
+        ("LD", [Name("ST"), Register(x)]) => 0xF018 | x << 8,
+        ("ADD", [Name("I"), Register(x)]) => 0xF01E | x << 8,
+        ("LD", [Name("F"), Register(x)]) => 0xF029 | x << 8,
+        ("LD", [Name("HF"), Register(x)]) => 0xF030 | x << 8,
+        ("LD", [Name("B"), Register(x)]) => 0xF033 | x << 8,
+        ("PITCH", [Register(x)]) => 0xF03A | x << 8,
+        ("LD", [Name("[I]"), Register(x)]) => 0xF055 | x << 8,
+        ("LD", [Register(x), Name("[I]")]) => 0xF065 | x << 8,
+        ("LD", [Name("[I]"), Register(x), Register(y)]) => xy(0x5002, *x, *y),
+        ("LD", [Register(x), Register(y), Name("[I]")]) => xy(0x5003, *x, *y),
+        ("LD", [Name("R"), Register(x)]) => 0xF075 | x << 8,
+        ("LD", [Register(x), Name("R")]) => 0xF085 | x << 8,
+        _ => bail!("unknown instruction '{}'", text),
+    };
+    Ok(opcode)
+}
+
+/// Write `rom` as a listing that `assemble` turns back into the same bytes, with a `DW` line for
+/// each word that isn't an instruction, including the address after `LD I, LONG`, and a `DB`
+/// line for a trailing odd byte
+pub fn disassemble_rom(rom: &[u8]) -> String {
+    let mut listing = String::new();
+    let mut operand = false;
+    for chunk in rom.chunks(2) {
+        let line = match chunk {
+            [high, low] => {
+                let opcode = u16::from_be_bytes([*high, *low]);
+                match disassemble(opcode) {
+                    Some(text) if !operand => {
+                        operand = instruction_length(opcode) > 2;
+                        text
+                    }
+                    _ => {
+                        operand = false;
+                        format!("DW {:#06x}", opcode)
+                    }
+                }
+            }
+            [byte] => format!("DB {:#04x}", byte),
+            _ => unreachable!(),
+        };
+        listing.push_str(&line);
+        listing.push('\n');
+    }
+    listing
+}
+
+/// Assemble a listing of instructions and `DW`/`DB` data lines, one per line, with `;` starting
+/// a comment
+pub fn assemble(listing: &str) -> anyhow::Result<Vec<u8>> {
+    let mut rom = Vec::new();
+    for (n, line) in listing.lines().enumerate() {
+        let line = line.split(';').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let data = |digits: &str, max: u16| -> anyhow::Result<u16> {
+            match parse_operand(digits.trim()) {
+                Ok(Operand::Number(value)) if value <= max => Ok(value),
+                _ => bail!("invalid data '{}'", digits.trim()),
+            }
+        };
+        let upper = line.to_ascii_uppercase();
+        if let Some(value) = upper.strip_prefix("DW ") {
+            let value = data(value, 0xFFFF).with_context(|| format!("line {}", n + 1))?;
+            rom.extend_from_slice(&value.to_be_bytes());
+        } else if let Some(value) = upper.strip_prefix("DB ") {
+            let value = data(value, 0xFF).with_context(|| format!("line {}", n + 1))?;
+            rom.push(value as u8);
+        } else {
+            let opcode = assemble_instruction(line).with_context(|| format!("line {}", n + 1))?;
+            rom.extend_from_slice(&opcode.to_be_bytes());
+        }
+    }
+    Ok(rom)
+}
+
+/// A word whose disassembly assembles into something else, because the disassembly drops some
+/// of its bits
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RoundTripMismatch {
+    pub addr: u16,
+    pub opcode: u16,
+    pub text: String,
+    pub reassembled: Option<u16>,
+}
+
+impl Display for RoundTripMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:#05x}: {:04X} disassembles to '{}', which ",
+            self.addr, self.opcode, self.text
+        )?;
+        match self.reassembled {
+            Some(opcode) => write!(f, "assembles to {:04X}", opcode),
+            None => write!(f, "doesn't assemble"),
+        }
+    }
+}
+
+/// Disassemble `rom`, assemble the listing again and return every word that didn't come back
+/// the same. The words without a mnemonic are kept as `DW` data, so only the instructions the
+/// disassembler can't express exactly are reported.
+pub fn verify_round_trip(rom: &[u8]) -> Vec<RoundTripMismatch> {
+    let mut mismatches = Vec::new();
+    let mut offset = 0;
+    while let Some(word) = rom.get(offset..offset + 2) {
+        let opcode = u16::from_be_bytes([word[0], word[1]]);
+        let Some(text) = disassemble(opcode) else {
+            offset += 2;
+            continue;
+        };
+        let reassembled = assemble_instruction(&text).ok();
+        if reassembled != Some(opcode) {
+            mismatches.push(RoundTripMismatch {
+                addr: (ROM_ADDR + offset) as u16,
+                opcode,
+                text,
+                reassembled,
+            });
+        }
+        offset += instruction_length(opcode);
+    }
+    mismatches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{assemble, assemble_instruction, disassemble_rom, verify_round_trip};
+    use crate::disassemble;
+
+    #[test]
+    fn test_assemble_every_instruction() {
+        // every opcode whose disassembly is exact comes back unchanged
+        for opcode in 0..=0xFFFF {
+            let Some(text) = disassemble(opcode) else {
+                continue;
+            };
+            let lossy = match opcode >> 12 {
+                0x5 => !matches!(opcode & 0xF, 0x0 | 0x2 | 0x3),
+                0x9 => opcode & 0xF != 0,
+                _ => false,
+            };
+            assert_eq!(
+                assemble_instruction(&text).unwrap() == opcode,
+                !lossy,
+                "{:04X} '{}'",
+                opcode,
+                text
+            );
+        }
+        assert!(assemble_instruction("LD V0, 0x100").is_err());
+        assert!(assemble_instruction("JP VG, 0x200").is_err());
+        assert!(assemble_instruction("NOP").is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let rom = [0x00, 0xE0, 0xA2, 0x2A, 0x51, 0x21, 0x01, 0x23, 0xFF];
+        let listing = disassemble_rom(&rom);
+        assert_eq!(listing, "CLS\nLD I, 0x22a\nSE V1, V2\nDW 0x0123\nDB 0xff\n");
+
+        // 5121 has a nonzero low nibble that the disassembly drops
+        assert_ne!(assemble(&listing).unwrap(), rom);
+        let mismatches = verify_round_trip(&rom);
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].addr, 0x204);
+        assert_eq!(mismatches[0].reassembled, Some(0x5120));
+
+        let rom = [0x00, 0xE0, 0x12, 0x00, 0x01];
+        assert_eq!(assemble(&disassemble_rom(&rom)).unwrap(), rom);
+        assert_eq!(verify_round_trip(&rom), []);
+
+        // the address after F000 is data even when it looks like an instruction
+        let rom = [0xF0, 0x00, 0x51, 0x23, 0x00, 0xE0];
+        let listing = disassemble_rom(&rom);
+        assert_eq!(listing, "LD I, LONG\nDW 0x5123\nCLS\n");
+        assert_eq!(assemble(&listing).unwrap(), rom);
+        assert_eq!(verify_round_trip(&rom), []);
+    }
+}
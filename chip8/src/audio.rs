@@ -0,0 +1,323 @@
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use anyhow::bail;
+
+/// The default frequency of the buzzer tone in Hz
+pub const BUZZER_FREQUENCY: f32 = 440.0;
+
+/// The peak amplitude of the generated samples, kept well below 1.0 since square waves are loud
+pub const BUZZER_AMPLITUDE: f32 = 0.25;
+
+/// The length of the XO-CHIP audio pattern buffer in bytes
+pub const AUDIO_PATTERN_LENGTH: usize = 16;
+
+/// The pitch register value at which XO-CHIP patterns play back at 4000 bits per second
+pub const DEFAULT_PITCH: u8 = 64;
+
+/// The default length of the attack and release ramps, long enough to avoid audible pops
+pub const DEFAULT_ENVELOPE_MS: f32 = 5.0;
+
+/// The rate at which the sound timer is decremented, and so the rate at which the tone is gated
+const FRAME_RATE: f32 = 60.0;
+
+/// The maximum number of emulated frames waiting to be rendered before the oldest are dropped
+const MAX_PENDING_FRAMES: usize = 8;
+
+/// The shape of the buzzer tone
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Waveform {
+    Square,
+    Triangle,
+    Sine,
+}
+
+impl Waveform {
+    /// Sample the waveform at `phase` in the range 0.0..1.0, returning a value in -1.0..=1.0
+    pub fn sample(&self, phase: f32) -> f32 {
+        match self {
+            Waveform::Square => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+            Waveform::Sine => (phase * std::f32::consts::TAU).sin(),
+        }
+    }
+}
+
+impl FromStr for Waveform {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "square" => Ok(Waveform::Square),
+            "triangle" => Ok(Waveform::Triangle),
+            "sine" => Ok(Waveform::Sine),
+            _ => bail!(
+                "unknown waveform '{}' (expected square, triangle or sine)",
+                s
+            ),
+        }
+    }
+}
+
+impl Display for Waveform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Waveform::Square => "square",
+            Waveform::Triangle => "triangle",
+            Waveform::Sine => "sine",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+pub struct Buzzer {
+    /// The position within the current wave period, in the range 0.0..1.0
+    pub(crate) phase: f32,
+    /// The 1-bit XO-CHIP sample pattern loaded with F002, played instead of the tone once set
+    pub(crate) pattern: Option<[u8; AUDIO_PATTERN_LENGTH]>,
+    /// The XO-CHIP pitch register set with FX3A
+    pub(crate) pitch: u8,
+    /// The frequency of the tone in Hz
+    pub(crate) frequency: f32,
+    /// The shape of the tone
+    pub(crate) waveform: Waveform,
+    /// The output gain in the range 0.0..=1.0
+    pub(crate) volume: f32,
+    /// Silences the output without losing the configured volume
+    pub(crate) muted: bool,
+    /// Whether the tone is on for each emulated frame that hasn't been rendered yet
+    pub(crate) pending_frames: VecDeque<bool>,
+    /// Whether the tone is on for the frame currently being rendered
+    pub(crate) frame_on: bool,
+    /// The number of samples left to render for the current frame
+    pub(crate) frame_samples_left: f32,
+    /// The length of the attack and release ramps in milliseconds, 0.0 hard-cuts the tone
+    pub(crate) envelope_ms: f32,
+    /// The current envelope level in the range 0.0..=1.0
+    pub(crate) envelope: f32,
+}
+
+impl Buzzer {
+    pub fn new() -> Self {
+        Self {
+            phase: 0.0,
+            pattern: None,
+            pitch: DEFAULT_PITCH,
+            frequency: BUZZER_FREQUENCY,
+            waveform: Waveform::Square,
+            volume: 1.0,
+            muted: false,
+            pending_frames: VecDeque::new(),
+            frame_on: false,
+            frame_samples_left: 0.0,
+            envelope_ms: DEFAULT_ENVELOPE_MS,
+            envelope: 0.0,
+        }
+    }
+
+    /// Record whether the tone is on for an emulated frame, to be rendered by `render_frames`
+    pub fn push_frame(&mut self, on: bool) {
+        if self.pending_frames.len() == MAX_PENDING_FRAMES {
+            self.pending_frames.pop_front();
+        }
+        self.pending_frames.push_back(on);
+    }
+
+    /// Fill `out` with samples, starting and stopping the tone exactly on the frame boundaries
+    /// recorded with `push_frame`. Each frame lasts 1/60th of a second worth of samples, so tone
+    /// onsets are aligned to sound timer decrements rather than to when this happens to be called.
+    /// When no frames are pending the tone keeps following `on`.
+    pub fn render_frames(&mut self, out: &mut [f32], sample_rate: u32, on: bool) {
+        let samples_per_frame = sample_rate as f32 / FRAME_RATE;
+        let mut start = 0;
+        while start < out.len() {
+            if self.frame_samples_left <= 0.0 {
+                self.frame_on = self.pending_frames.pop_front().unwrap_or(on);
+                self.frame_samples_left += samples_per_frame;
+            }
+
+            let len = (self.frame_samples_left.ceil() as usize).clamp(1, out.len() - start);
+            self.render(&mut out[start..start + len], sample_rate, self.frame_on);
+            self.frame_samples_left -= len as f32;
+            start += len;
+        }
+    }
+
+    /// The rate at which pattern bits are played back, as defined by the XO-CHIP specification
+    pub fn playback_rate(&self) -> f32 {
+        4000.0 * 2f32.powf((self.pitch as f32 - 64.0) / 48.0)
+    }
+
+    /// Fill `out` with mono samples at `sample_rate`: the tone or pattern while `on`, silence otherwise
+    /// Starting and stopping is smoothed by a linear attack/release ramp of `envelope_ms`
+    pub fn render(&mut self, out: &mut [f32], sample_rate: u32, on: bool) {
+        if !on && self.envelope == 0.0 {
+            out.fill(0.0);
+            return;
+        }
+
+        match self.pattern {
+            Some(pattern) => self.render_pattern(out, sample_rate, &pattern),
+            None => self.render_tone(out, sample_rate),
+        }
+
+        let target = if on { 1.0 } else { 0.0 };
+        let ramp_samples = self.envelope_ms * sample_rate as f32 / 1000.0;
+        let step = if ramp_samples > 1.0 {
+            1.0 / ramp_samples
+        } else {
+            1.0
+        };
+
+        let gain = if self.muted { 0.0 } else { self.volume };
+        for sample in out.iter_mut() {
+            self.envelope = if self.envelope < target {
+                (self.envelope + step).min(target)
+            } else {
+                (self.envelope - step).max(target)
+            };
+            *sample *= gain * self.envelope;
+        }
+    }
+
+    fn render_tone(&mut self, out: &mut [f32], sample_rate: u32) {
+        let step = self.frequency / sample_rate as f32;
+        for sample in out.iter_mut() {
+            *sample = self.waveform.sample(self.phase) * BUZZER_AMPLITUDE;
+            self.phase = (self.phase + step).fract();
+        }
+    }
+
+    /// Resample the 128-bit pattern at the commanded pitch, with the phase covering the whole pattern
+    fn render_pattern(
+        &mut self,
+        out: &mut [f32],
+        sample_rate: u32,
+        pattern: &[u8; AUDIO_PATTERN_LENGTH],
+    ) {
+        const BITS: usize = AUDIO_PATTERN_LENGTH * 8;
+        let step = self.playback_rate() / sample_rate as f32 / BITS as f32;
+        for sample in out.iter_mut() {
+            let bit = (self.phase * BITS as f32) as usize % BITS;
+            *sample = if pattern[bit / 8] >> (7 - bit % 8) & 0x1 == 1 {
+                BUZZER_AMPLITUDE
+            } else {
+                -BUZZER_AMPLITUDE
+            };
+            self.phase = (self.phase + step).fract();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Buzzer, Waveform, AUDIO_PATTERN_LENGTH, BUZZER_AMPLITUDE};
+
+    #[test]
+    fn test_render() {
+        let mut buzzer = Buzzer::new();
+        buzzer.envelope_ms = 0.0;
+        let mut out = [1.0; 100];
+        buzzer.render(&mut out, 44100, false);
+        assert!(out.iter().all(|s| *s == 0.0));
+
+        // 440 Hz at 880 samples per second gives one period every two samples
+        buzzer.render(&mut out, 880, true);
+        assert_eq!(out[0], BUZZER_AMPLITUDE);
+        assert_eq!(out[1], -BUZZER_AMPLITUDE);
+        assert_eq!(out[2], BUZZER_AMPLITUDE);
+    }
+
+    #[test]
+    fn test_render_frames() {
+        let mut buzzer = Buzzer::new();
+        buzzer.envelope_ms = 0.0;
+        buzzer.push_frame(false);
+        buzzer.push_frame(true);
+
+        // at 120 samples per second each frame lasts two samples
+        let mut out = [1.0; 6];
+        buzzer.render_frames(&mut out, 120, false);
+        assert_eq!(out[0..2], [0.0, 0.0]);
+        assert!(out[2..4].iter().all(|s| *s != 0.0));
+        assert_eq!(out[4..6], [0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_envelope() {
+        let mut buzzer = Buzzer::new();
+        // a 1ms ramp at 4000 samples per second takes four samples
+        buzzer.envelope_ms = 1.0;
+
+        let mut out = [0.0; 6];
+        buzzer.render(&mut out, 4000, true);
+        assert_eq!(out[0].abs(), BUZZER_AMPLITUDE * 0.25);
+        assert_eq!(out[1].abs(), BUZZER_AMPLITUDE * 0.5);
+        assert_eq!(out[3].abs(), BUZZER_AMPLITUDE);
+        assert_eq!(out[5].abs(), BUZZER_AMPLITUDE);
+
+        buzzer.render(&mut out, 4000, false);
+        assert_eq!(out[0].abs(), BUZZER_AMPLITUDE * 0.75);
+        assert_eq!(out[3], 0.0);
+        assert_eq!(buzzer.envelope, 0.0);
+    }
+
+    #[test]
+    fn test_volume() {
+        let mut buzzer = Buzzer::new();
+        buzzer.envelope_ms = 0.0;
+        let mut out = [0.0; 1];
+        buzzer.volume = 0.5;
+        buzzer.render(&mut out, 880, true);
+        assert_eq!(out[0], BUZZER_AMPLITUDE * 0.5);
+
+        buzzer.muted = true;
+        buzzer.render(&mut out, 880, true);
+        assert_eq!(out[0], 0.0);
+    }
+
+    #[test]
+    fn test_waveforms() {
+        assert_eq!(Waveform::Square.sample(0.25), 1.0);
+        assert_eq!(Waveform::Square.sample(0.75), -1.0);
+        assert_eq!(Waveform::Triangle.sample(0.0), -1.0);
+        assert_eq!(Waveform::Triangle.sample(0.5), 1.0);
+        assert!((Waveform::Sine.sample(0.25) - 1.0).abs() < 1e-6);
+        assert_eq!("Sine".parse::<Waveform>().unwrap(), Waveform::Sine);
+        assert!("noise".parse::<Waveform>().is_err());
+    }
+
+    #[test]
+    fn test_render_pattern() {
+        let mut buzzer = Buzzer::new();
+        buzzer.envelope_ms = 0.0;
+        let mut pattern = [0; AUDIO_PATTERN_LENGTH];
+        pattern[0] = 0b10100000;
+        buzzer.pattern = Some(pattern);
+        assert_eq!(buzzer.playback_rate(), 4000.0);
+
+        // sampling at the playback rate yields exactly one bit per sample
+        let mut out = [0.0; 4];
+        buzzer.render(&mut out, 4000, true);
+        assert_eq!(
+            out,
+            [
+                BUZZER_AMPLITUDE,
+                -BUZZER_AMPLITUDE,
+                BUZZER_AMPLITUDE,
+                -BUZZER_AMPLITUDE
+            ]
+        );
+
+        buzzer.pitch = 112;
+        assert_eq!(buzzer.playback_rate(), 8000.0);
+    }
+}
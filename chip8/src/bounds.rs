@@ -0,0 +1,533 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Display;
+
+use crate::{
+    disassemble, instruction_length, BIG_FONT_ADDR, BIG_FONT_CHAR_LENGTH, BIG_FONT_DATA, FONT_ADDR,
+    FONT_CHAR_LENGTH, FONT_DATA, MEM_SIZE, ROM_ADDR,
+};
+
+/// How many times the state at an address can grow before its ranges are widened to
+/// everything, which keeps loops from being walked one value at a time
+const WIDEN_AFTER: u32 = 8;
+
+/// The values a register might hold, as an inclusive range
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Interval {
+    lo: u32,
+    hi: u32,
+}
+
+impl Interval {
+    const fn exact(value: u32) -> Self {
+        Self {
+            lo: value,
+            hi: value,
+        }
+    }
+
+    const fn new(lo: u32, hi: u32) -> Self {
+        Self { lo, hi }
+    }
+
+    fn join(self, other: Self) -> Self {
+        Self::new(self.lo.min(other.lo), self.hi.max(other.hi))
+    }
+
+    /// Add `other`, giving every value in `0..=max` if the sum can wrap around past `max`
+    fn add(self, other: Self, max: u32) -> Self {
+        if self.hi + other.hi <= max {
+            Self::new(self.lo + other.lo, self.hi + other.hi)
+        } else {
+            Self::new(0, max)
+        }
+    }
+}
+
+const BYTE: Interval = Interval::new(0, 0xFF);
+const FLAG: Interval = Interval::new(0, 1);
+const ADDRESS: Interval = Interval::new(0, 0xFFFF);
+
+/// What's known about the machine at an instruction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct State {
+    v: [Interval; 16],
+    i: Interval,
+}
+
+impl State {
+    fn join(&self, other: &Self) -> Self {
+        let mut v = self.v;
+        for (value, other) in v.iter_mut().zip(other.v) {
+            *value = value.join(other);
+        }
+        Self {
+            v,
+            i: self.i.join(other.i),
+        }
+    }
+
+    fn widen(&self, grown: &Self) -> Self {
+        let widen = |old: Interval, new: Interval, full: Interval| {
+            if old == new {
+                old
+            } else {
+                full
+            }
+        };
+        let mut v = self.v;
+        for (value, new) in v.iter_mut().zip(grown.v) {
+            *value = widen(*value, new, BYTE);
+        }
+        Self {
+            v,
+            i: widen(self.i, grown.i, ADDRESS),
+        }
+    }
+}
+
+/// Whether a memory access reads or writes
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+/// A problem the bounds verifier found at an instruction
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FindingKind {
+    /// The instruction might access memory from `start` up to and including `end`, which goes
+    /// past the end of memory
+    OutOfBounds {
+        access: Access,
+        start: u32,
+        end: u32,
+    },
+    /// The instruction might read memory from `start` up to and including `end`, which the ROM
+    /// doesn't fill and nothing might have written to
+    Uninitialized { start: u32, end: u32 },
+    /// BNNN jumps somewhere that depends on a register, so what follows it isn't checked
+    UnfollowedJump,
+    /// The word reached isn't an instruction
+    InvalidInstruction,
+}
+
+/// A problem found at the instruction at `addr`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Finding {
+    pub addr: u16,
+    pub opcode: u16,
+    pub kind: FindingKind,
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:#05x}: {:04X}", self.addr, self.opcode)?;
+        if let Some(mnemonic) = disassemble(self.opcode) {
+            write!(f, " {}", mnemonic)?;
+        }
+        match self.kind {
+            FindingKind::OutOfBounds { access, start, end } => write!(
+                f,
+                " might {} {:#05x}..={:#05x}, past the end of memory",
+                if access == Access::Read {
+                    "read"
+                } else {
+                    "write"
+                },
+                start,
+                end
+            ),
+            FindingKind::Uninitialized { start, end } => write!(
+                f,
+                " might read uninitialized memory in {:#05x}..={:#05x}",
+                start, end
+            ),
+            FindingKind::UnfollowedJump => write!(f, " jumps to a computed address, not followed"),
+            FindingKind::InvalidInstruction => write!(f, " isn't a valid instruction"),
+        }
+    }
+}
+
+/// Checks a ROM without running it, by following every path from the entry point while tracking
+/// the range of values each register and I can hold. Any instruction that might access memory
+/// past the end, or read memory nothing has initialized, is reported. The ranges are a safe
+/// overestimate, so a ROM with no out of bounds findings never makes such an access, though a
+/// finding might not be reachable in practice. Uninitialized reads are a best effort, since a
+/// write anywhere in the ROM counts as initializing that memory for every read.
+pub struct BoundsVerifier {
+    memory: Vec<u8>,
+    memory_size: usize,
+    /// The memory the font or ROM fills, or that some instruction might write to
+    initialized: Vec<bool>,
+    states: BTreeMap<usize, (State, u32)>,
+    worklist: BTreeSet<usize>,
+    /// The addresses straight after every CALL, which every RET might return to
+    return_sites: BTreeSet<usize>,
+    /// Everything known at every RET, joined together
+    return_state: Option<State>,
+    findings: BTreeSet<Finding>,
+}
+
+impl BoundsVerifier {
+    pub fn new(rom: &[u8], memory_size: usize) -> Self {
+        let mut memory = vec![0; memory_size.max(ROM_ADDR)];
+        let mut initialized = vec![false; memory.len()];
+        for (addr, byte) in FONT_DATA.iter().enumerate() {
+            memory[FONT_ADDR + addr] = *byte;
+            initialized[FONT_ADDR + addr] = true;
+        }
+        for (addr, byte) in BIG_FONT_DATA.iter().enumerate() {
+            memory[BIG_FONT_ADDR + addr] = *byte;
+            initialized[BIG_FONT_ADDR + addr] = true;
+        }
+        let end = (ROM_ADDR + rom.len()).min(memory.len());
+        memory[ROM_ADDR..end].copy_from_slice(&rom[..end - ROM_ADDR]);
+        initialized[ROM_ADDR..end].fill(true);
+
+        Self {
+            memory,
+            memory_size,
+            initialized,
+            states: BTreeMap::new(),
+            worklist: BTreeSet::new(),
+            return_sites: BTreeSet::new(),
+            return_state: None,
+            findings: BTreeSet::new(),
+        }
+    }
+
+    /// Check `rom` with the default memory size
+    pub fn verify(rom: &[u8]) -> Vec<Finding> {
+        Self::new(rom, MEM_SIZE).run()
+    }
+
+    /// Follow every path and return what was found, in address order
+    pub fn run(mut self) -> Vec<Finding> {
+        let entry = State {
+            v: [Interval::exact(0); 16],
+            i: Interval::exact(0),
+        };
+        self.flow(ROM_ADDR, entry);
+        while let Some(pc) = self.worklist.pop_first() {
+            let state = self.states[&pc].0;
+            self.step(pc, state);
+        }
+        self.findings.into_iter().collect()
+    }
+
+    /// Merge `state` into what's known at `pc`, queueing it if that changed anything
+    fn flow(&mut self, pc: usize, state: State) {
+        match self.states.get_mut(&pc) {
+            None => {
+                self.states.insert(pc, (state, 0));
+            }
+            Some((known, grown)) => {
+                let joined = known.join(&state);
+                if joined == *known {
+                    return;
+                }
+                *grown += 1;
+                *known = if *grown > WIDEN_AFTER {
+                    known.widen(&joined)
+                } else {
+                    joined
+                };
+            }
+        }
+        self.worklist.insert(pc);
+    }
+
+    fn report(&mut self, pc: usize, opcode: u16, kind: FindingKind) {
+        self.findings.insert(Finding {
+            addr: pc as u16,
+            opcode,
+            kind,
+        });
+    }
+
+    /// Check an access to the `len` bytes from each address I might hold
+    fn access(&mut self, pc: usize, opcode: u16, i: Interval, len: u32, access: Access) {
+        if len == 0 {
+            return;
+        }
+        let (start, end) = (i.lo, i.hi + len - 1);
+        let size = self.memory_size as u32;
+        if end >= size {
+            let kind = FindingKind::OutOfBounds { access, start, end };
+            self.report(pc, opcode, kind);
+        }
+
+        let in_bounds = start.min(size)..(end + 1).min(size);
+        match access {
+            Access::Write => {
+                for addr in in_bounds {
+                    self.initialized[addr as usize] = true;
+                }
+            }
+            Access::Read => {
+                if in_bounds
+                    .clone()
+                    .any(|addr| !self.initialized[addr as usize])
+                {
+                    self.report(pc, opcode, FindingKind::Uninitialized { start, end });
+                }
+            }
+        }
+    }
+
+    fn step(&mut self, pc: usize, mut state: State) {
+        let Some(bytes) = self.memory.get(pc..pc + 2) else {
+            return;
+        };
+        let opcode = u16::from_be_bytes([bytes[0], bytes[1]]);
+        let x = ((opcode >> 8) & 0xF) as usize;
+        let y = ((opcode >> 4) & 0xF) as usize;
+        let n = (opcode & 0xF) as u32;
+        let nn = (opcode & 0xFF) as u32;
+        let nnn = (opcode & 0xFFF) as usize;
+        if disassemble(opcode).is_none() {
+            self.report(pc, opcode, FindingKind::InvalidInstruction);
+            return;
+        }
+
+        let (vx, vy) = (state.v[x], state.v[y]);
+        // the word after, which a skip has to skip the whole instruction of and F000 loads
+        let next = self
+            .memory
+            .get(pc + 2..pc + 4)
+            .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]));
+        let skipped = pc + 2 + next.map_or(2, instruction_length);
+        match opcode >> 12 {
+            0x0 if opcode == 0x00EE => {
+                let joined = match self.return_state {
+                    Some(known) => known.join(&state),
+                    None => state,
+                };
+                if self.return_state != Some(joined) {
+                    self.return_state = Some(joined);
+                    for site in self.return_sites.clone() {
+                        self.flow(site, joined);
+                    }
+                }
+                return;
+            }
+            // nothing runs after the program exits
+            0x0 if opcode == 0x00FD => return,
+            0x1 => {
+                self.flow(nnn, state);
+                return;
+            }
+            0x2 => {
+                if self.return_sites.insert(pc + 2) {
+                    if let Some(returned) = self.return_state {
+                        self.flow(pc + 2, returned);
+                    }
+                }
+                self.flow(nnn, state);
+                return;
+            }
+            0x5 if matches!(n, 0x2 | 0x3) => {
+                let len = x.abs_diff(y) as u32 + 1;
+                if n == 0x2 {
+                    self.access(pc, opcode, state.i, len, Access::Write);
+                } else {
+                    self.access(pc, opcode, state.i, len, Access::Read);
+                    for value in &mut state.v[x.min(y)..=x.max(y)] {
+                        *value = BYTE;
+                    }
+                }
+            }
+            0x3 | 0x4 | 0x5 | 0x9 => self.flow(skipped, state),
+            0xE => self.flow(skipped, state),
+            0x6 => state.v[x] = Interval::exact(nn),
+            0x7 => state.v[x] = vx.add(Interval::exact(nn), 0xFF),
+            0x8 => {
+                let (value, flag) = match n {
+                    0x0 => (vy, None),
+                    0x2 => (Interval::new(0, vx.hi.min(vy.hi)), None),
+                    0x1 | 0x3 => (BYTE, None),
+                    0x4 => (vx.add(vy, 0xFF), Some(FLAG)),
+                    0x5 if vx.lo >= vy.hi => {
+                        (Interval::new(vx.lo - vy.hi, vx.hi - vy.lo), Some(FLAG))
+                    }
+                    0x7 if vy.lo >= vx.hi => {
+                        (Interval::new(vy.lo - vx.hi, vy.hi - vx.lo), Some(FLAG))
+                    }
+                    0x5 | 0x7 => (BYTE, Some(FLAG)),
+                    // the shifts read VX or VY depending on the legacy shift quirk
+                    0x6 => {
+                        let source = vx.join(vy);
+                        (Interval::new(source.lo >> 1, source.hi >> 1), Some(FLAG))
+                    }
+                    _ => {
+                        let source = vx.join(vy);
+                        if source.hi < 0x80 {
+                            (Interval::new(source.lo << 1, source.hi << 1), Some(FLAG))
+                        } else {
+                            (BYTE, Some(FLAG))
+                        }
+                    }
+                };
+                state.v[x] = value;
+                if let Some(flag) = flag {
+                    state.v[0xF] = flag;
+                }
+            }
+            0xA => state.i = Interval::exact(nnn as u32),
+            0xB => {
+                self.report(pc, opcode, FindingKind::UnfollowedJump);
+                return;
+            }
+            0xC => state.v[x] = Interval::new(0, nn),
+            0xD => {
+                // DXY0 draws a 16x16 sprite of 32 bytes in high resolution, which covers the empty
+                // sprite it draws otherwise
+                let len = if n == 0 && cfg!(feature = "schip") {
+                    32
+                } else {
+                    n
+                };
+                self.access(pc, opcode, state.i, len, Access::Read);
+                state.v[0xF] = FLAG;
+            }
+            0xF if opcode == 0xF000 => {
+                let Some(nnnn) = next else {
+                    return;
+                };
+                state.i = Interval::exact(nnnn as u32);
+                self.flow(pc + 4, state);
+                return;
+            }
+            0xF => match nn {
+                0x07 => state.v[x] = BYTE,
+                0x0A => state.v[x] = Interval::new(0, 0xF),
+                0x1E => state.i = state.i.add(vx, 0xFFFF),
+                0x29 => {
+                    let char_addr =
+                        |digit: u32| (FONT_ADDR + FONT_CHAR_LENGTH * digit as usize) as u32;
+                    state.i = Interval::new(char_addr(vx.lo), char_addr(vx.hi));
+                }
+                0x30 => {
+                    let char_addr =
+                        |digit: u32| (BIG_FONT_ADDR + BIG_FONT_CHAR_LENGTH * digit as usize) as u32;
+                    state.i = Interval::new(char_addr(vx.lo), char_addr(vx.hi));
+                }
+                0x33 => self.access(pc, opcode, state.i, 3, Access::Write),
+                0x55 | 0x65 => {
+                    let access = if nn == 0x55 {
+                        Access::Write
+                    } else {
+                        Access::Read
+                    };
+                    self.access(pc, opcode, state.i, x as u32 + 1, access);
+                    if nn == 0x65 {
+                        for value in &mut state.v[..=x] {
+                            *value = BYTE;
+                        }
+                    }
+                    // I is left alone or moved past the registers depending on a quirk
+                    let moved = state.i.add(Interval::exact(x as u32 + 1), 0xFFFF);
+                    state.i = state.i.join(moved);
+                }
+                0x85 => {
+                    for value in &mut state.v[..=x] {
+                        *value = BYTE;
+                    }
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+        self.flow(pc + 2, state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Access, BoundsVerifier, FindingKind};
+
+    #[test]
+    fn test_verify_bounds() {
+        // a sprite drawn from the ROM in a loop is fine
+        let rom = [0xA2, 0x06, 0xD0, 0x11, 0x12, 0x02, 0xFF];
+        assert_eq!(BoundsVerifier::verify(&rom), []);
+
+        // I walks up through memory by V0 every frame, so it eventually draws past the end
+        let rom = [0xA2, 0x08, 0x60, 0x10, 0xD0, 0x11, 0xF0, 0x1E, 0x12, 0x04];
+        let findings = BoundsVerifier::verify(&rom);
+        assert!(findings.iter().any(|finding| matches!(
+            finding.kind,
+            FindingKind::OutOfBounds {
+                access: Access::Read,
+                ..
+            }
+        )));
+
+        // reading past the ROM before anything has written there
+        let rom = [0xA3, 0x00, 0xF1, 0x65, 0x12, 0x04];
+        let findings = BoundsVerifier::verify(&rom);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].addr, 0x202);
+        assert_eq!(
+            findings[0].kind,
+            FindingKind::Uninitialized {
+                start: 0x300,
+                end: 0x301
+            }
+        );
+
+        // but not once it's been stored to
+        let rom = [0xA3, 0x00, 0xF1, 0x55, 0xA3, 0x00, 0xF1, 0x65, 0x12, 0x08];
+        assert_eq!(BoundsVerifier::verify(&rom), []);
+
+        // BCD right at the end of memory writes past it
+        let rom = [0xAF, 0xFE, 0xF0, 0x33, 0x12, 0x04];
+        let findings = BoundsVerifier::verify(&rom);
+        assert_eq!(
+            findings[0].kind,
+            FindingKind::OutOfBounds {
+                access: Access::Write,
+                start: 0xFFE,
+                end: 0x1000
+            }
+        );
+    }
+
+    #[test]
+    fn test_verify_through_subroutines() {
+        // the subroutine sets up I, which the caller draws with after it returns
+        let rom = [
+            0x22, 0x06, // 0x200: CALL 0x206
+            0xD0, 0x11, // 0x202: DRW V0, V0, 1
+            0x12, 0x02, // 0x204: JP 0x202
+            0xA2, 0x0A, // 0x206: LD I, 0x20a
+            0x00, 0xEE, // 0x208: RET
+            0x80, // 0x20a: sprite
+        ];
+        assert_eq!(BoundsVerifier::verify(&rom), []);
+
+        let rom = [0xB2, 0x00];
+        let findings = BoundsVerifier::verify(&rom);
+        assert_eq!(findings[0].kind, FindingKind::UnfollowedJump);
+    }
+
+    #[test]
+    fn test_verify_long_index() {
+        let rom = [
+            0xF0, 0x00, 0x02, 0x0C, // 0x200: LD I, LONG 0x20c
+            0x30, 0x00, // 0x204: SE V0, 0
+            0xF0, 0x00, 0x0F, 0xFF, // 0x206: LD I, LONG 0xfff, which is skipped whole
+            0xD0, 0x12, // 0x20A: DRW V0, V1, 2
+            0x12, 0x0A, // 0x20C: JP 0x20A, which is also the sprite
+        ];
+        // the draw sees both values of I, and nothing is run from the address
+        let findings = BoundsVerifier::verify(&rom);
+        assert!(findings.iter().all(|finding| finding.addr == 0x20A));
+        assert!(findings.iter().any(|finding| finding.kind
+            == FindingKind::OutOfBounds {
+                access: Access::Read,
+                start: 0x20C,
+                end: 0x1000
+            }));
+    }
+}
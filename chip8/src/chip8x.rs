@@ -0,0 +1,119 @@
+use crate::{Frame, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+/// The width in pixels of the columns the VP-590 colour board colours the screen in
+pub const COLOUR_ZONE_WIDTH: usize = 8;
+/// The height in pixels of the zones BXY0 colours
+pub const COLOUR_ZONE_HEIGHT: usize = 4;
+
+const ZONE_COLUMNS: usize = SCREEN_WIDTH / COLOUR_ZONE_WIDTH;
+
+/// The foreground colours BXYN picks between, as RGBA: black, red, blue, violet, green, yellow,
+/// aqua and white
+pub const FOREGROUND_COLOURS: [[u8; 4]; 8] = [
+    [0, 0, 0, 255],
+    [255, 0, 0, 255],
+    [0, 0, 255, 255],
+    [255, 0, 255, 255],
+    [0, 255, 0, 255],
+    [255, 255, 0, 255],
+    [0, 255, 255, 255],
+    [255, 255, 255, 255],
+];
+
+/// The background colours 02A0 steps through, as RGBA: blue, black, green and red
+pub const BACKGROUND_COLOURS: [[u8; 4]; 4] = [
+    [0, 0, 128, 255],
+    [0, 0, 0, 255],
+    [0, 128, 0, 255],
+    [128, 0, 0, 255],
+];
+
+/// The colours of CHIP-8X's VP-590 colour board: one background colour for the whole screen, and
+/// a foreground colour for each row of each 8 pixel wide column
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ColourZones {
+    /// The index of the background colour in `BACKGROUND_COLOURS`
+    pub(crate) background: u8,
+    /// The index in `FOREGROUND_COLOURS` of each zone, row by row
+    pub(crate) foreground: [u8; ZONE_COLUMNS * SCREEN_HEIGHT],
+}
+
+impl ColourZones {
+    /// Red on blue, which is how the colour board starts
+    pub fn new() -> Self {
+        Self {
+            background: 0,
+            foreground: [1; ZONE_COLUMNS * SCREEN_HEIGHT],
+        }
+    }
+
+    /// The background colour of the whole screen
+    pub fn background(&self) -> [u8; 4] {
+        BACKGROUND_COLOURS[self.background as usize]
+    }
+
+    /// The colour lit pixels at (`x`, `y`) of `frame` are drawn in
+    /// The colours have a low resolution layout, so high resolution pixels share their zone.
+    pub fn foreground(&self, x: usize, y: usize, frame: &Frame) -> [u8; 4] {
+        let (scale_x, scale_y) = frame.scale();
+        let column = (x / scale_x / COLOUR_ZONE_WIDTH) % ZONE_COLUMNS;
+        let row = (y / scale_y) % SCREEN_HEIGHT;
+        FOREGROUND_COLOURS[self.foreground[row * ZONE_COLUMNS + column] as usize]
+    }
+
+    /// Move on to the next background colour, going back to the first after the last
+    #[cfg(any(feature = "chip8x", test))]
+    pub(crate) fn cycle_background(&mut self) {
+        self.background = (self.background + 1) % BACKGROUND_COLOURS.len() as u8;
+    }
+
+    /// Set the foreground colour of `rows` rows from `row` in `columns` columns from `column`,
+    /// wrapping around the edges of the screen
+    #[cfg(any(feature = "chip8x", test))]
+    pub(crate) fn fill(
+        &mut self,
+        column: usize,
+        columns: usize,
+        row: usize,
+        rows: usize,
+        colour: u8,
+    ) {
+        for row in (row..row + rows).map(|row| row % SCREEN_HEIGHT) {
+            for column in (column..column + columns).map(|column| column % ZONE_COLUMNS) {
+                self.foreground[row * ZONE_COLUMNS + column] = colour & 0x7;
+            }
+        }
+    }
+}
+
+impl Default for ColourZones {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ColourZones, BACKGROUND_COLOURS, FOREGROUND_COLOURS};
+    use crate::Frame;
+
+    #[test]
+    fn test_colour_zones() {
+        let mut colours = ColourZones::new();
+        assert_eq!(colours.background(), BACKGROUND_COLOURS[0]);
+        colours.cycle_background();
+        colours.cycle_background();
+        colours.cycle_background();
+        colours.cycle_background();
+        assert_eq!(colours.background(), BACKGROUND_COLOURS[0]);
+
+        colours.fill(7, 2, 31, 2, 4);
+        let frame = Frame::new();
+        assert_eq!(colours.foreground(56, 31, &frame), FOREGROUND_COLOURS[4]);
+        assert_eq!(colours.foreground(0, 0, &frame), FOREGROUND_COLOURS[4]);
+        assert_eq!(colours.foreground(8, 0, &frame), FOREGROUND_COLOURS[1]);
+        // high resolution pixels share the zone of the low resolution pixel they're part of
+        let frame = Frame::blank(true);
+        assert_eq!(colours.foreground(113, 63, &frame), FOREGROUND_COLOURS[4]);
+    }
+}
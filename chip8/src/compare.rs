@@ -0,0 +1,161 @@
+use std::fmt::Display;
+
+use anyhow::{bail, ensure};
+
+use crate::{Frame, HIRES_HEIGHT, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+/// The width and height in pixels of the regions a comparison's mismatch map is split into
+pub const REGION_SIZE: usize = 8;
+
+/// Write a framebuffer as rows of '#' for set pixels and '.' for unset ones, the format frame
+/// captures are stored in
+pub fn format_frame(fb: &Frame) -> String {
+    fb.iter_rows()
+        .map(|row| {
+            let mut line: String = row
+                .iter()
+                .map(|pixel| if *pixel != 0 { '#' } else { '.' })
+                .collect();
+            line.push('\n');
+            line
+        })
+        .collect()
+}
+
+/// Parse a frame capture written by `format_frame`, in any resolution
+pub fn parse_frame(text: &str) -> anyhow::Result<Frame> {
+    let rows: Vec<&str> = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+    ensure!(
+        rows.len() == SCREEN_HEIGHT || rows.len() == HIRES_HEIGHT,
+        "expected {} or {} rows, got {}",
+        SCREEN_HEIGHT,
+        HIRES_HEIGHT,
+        rows.len()
+    );
+
+    // 64 rows are either SUPER-CHIP's wide screen or the two-page interpreter's square one
+    let mut fb = if rows.len() == SCREEN_HEIGHT {
+        Frame::new()
+    } else if rows[0].chars().count() == SCREEN_WIDTH {
+        Frame::two_page()
+    } else {
+        Frame::blank(true)
+    };
+    for (y, row) in rows.iter().enumerate() {
+        ensure!(
+            row.chars().count() == fb.width(),
+            "row {}: expected {} pixels, got {}",
+            y + 1,
+            fb.width(),
+            row.chars().count()
+        );
+        for (x, c) in row.chars().enumerate() {
+            let pixel = match c {
+                '#' => 1,
+                '.' => 0,
+                _ => bail!("row {}: unexpected '{}', expected '#' or '.'", y + 1, c),
+            };
+            fb.set(x, y, pixel);
+        }
+    }
+    Ok(fb)
+}
+
+/// How two frames differ, overall and in each `REGION_SIZE` square region
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FrameComparison {
+    /// The number of pixels that differ
+    pub differing: usize,
+    /// The number of differing pixels in each region, row by row
+    pub regions: Vec<Vec<usize>>,
+}
+
+impl FrameComparison {
+    /// Compare `a` and `b` pixel by pixel. Frames in different resolutions are compared over the
+    /// larger one, with the smaller treated as unlit outside its bounds.
+    pub fn new(a: &Frame, b: &Frame) -> Self {
+        let width = a.width().max(b.width());
+        let height = a.height().max(b.height());
+        let mut regions = vec![vec![0; width / REGION_SIZE]; height / REGION_SIZE];
+        let mut differing = 0;
+        for y in 0..height {
+            for x in 0..width {
+                if (a.get(x, y) != 0) != (b.get(x, y) != 0) {
+                    regions[y / REGION_SIZE][x / REGION_SIZE] += 1;
+                    differing += 1;
+                }
+            }
+        }
+        Self { differing, regions }
+    }
+
+    pub fn is_match(&self) -> bool {
+        self.differing == 0
+    }
+}
+
+impl Display for FrameComparison {
+    /// Write the number of differing pixels followed by the mismatch map, with a character per
+    /// region that's '.' where the frames match, the count where fewer than 10 pixels differ and
+    /// '#' otherwise
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let pixels = self.regions.len() * self.regions[0].len() * REGION_SIZE * REGION_SIZE;
+        writeln!(f, "{} of {} pixels differ", self.differing, pixels)?;
+        for row in &self.regions {
+            let line: String = row
+                .iter()
+                .map(|count| match count {
+                    0 => '.',
+                    1..=9 => char::from_digit(*count as u32, 10).unwrap(),
+                    _ => '#',
+                })
+                .collect();
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_frame, parse_frame, FrameComparison, REGION_SIZE};
+    use crate::{Frame, HIRES_HEIGHT, HIRES_WIDTH};
+
+    #[test]
+    fn test_compare_frames() {
+        let a = Frame::new();
+        let mut b = a;
+        b.set(0, 0, 1);
+        b.set(63, 31, 1);
+        for x in 16..28 {
+            b.set(x, 9, 1);
+        }
+
+        let text = format_frame(&b);
+        assert_eq!(parse_frame(&text).unwrap(), b);
+        assert!(parse_frame(&text.replacen('#', "x", 1)).is_err());
+        assert!(parse_frame(&text[..text.len() - 66]).is_err());
+
+        let comparison = FrameComparison::new(&a, &b);
+        assert_eq!(comparison.differing, 14);
+        assert_eq!(comparison.is_match(), false);
+        assert_eq!(
+            comparison.to_string(),
+            "14 of 2048 pixels differ\n1.......\n..84....\n........\n.......1\n"
+        );
+        assert_eq!(FrameComparison::new(&b, &b).is_match(), true);
+
+        let mut hires = Frame::blank(true);
+        hires.set(HIRES_WIDTH - 1, HIRES_HEIGHT - 1, 1);
+        let text = format_frame(&hires);
+        assert_eq!(text.lines().count(), HIRES_HEIGHT);
+        assert_eq!(parse_frame(&text).unwrap(), hires);
+        let comparison = FrameComparison::new(&b, &hires);
+        assert_eq!(comparison.differing, 15);
+        assert_eq!(comparison.regions.len(), HIRES_HEIGHT / REGION_SIZE);
+    }
+}
@@ -0,0 +1,136 @@
+use crate::{Chip8, Frame, REGISTER_COUNT};
+
+/// The machine state a custom opcode handler can read and change
+pub struct OpcodeContext<'a> {
+    pub v: &'a mut [u8; REGISTER_COUNT],
+    pub i: &'a mut u16,
+    /// The address of the next instruction, since the handler's own has already been fetched
+    pub pc: &'a mut u16,
+    pub dt: &'a mut u8,
+    pub st: &'a mut u8,
+    pub memory: &'a mut [u8],
+    pub fb: &'a mut Frame,
+}
+
+type Handler = Box<dyn FnMut(&mut OpcodeContext, u16) + Send>;
+
+/// A handler for the opcodes that match `pattern` in the bits set in `mask`
+pub(crate) struct CustomOpcode {
+    mask: u16,
+    pattern: u16,
+    handler: Handler,
+}
+
+impl Chip8 {
+    /// Handle the otherwise invalid opcodes that equal `pattern` in the bits set in `mask`, such
+    /// as `custom_opcode(0xF0FF, 0xF0F1, ..)` for FXF1, by calling `handler` with the opcode.
+    /// Valid opcodes always run as normal, and the first matching handler registered wins.
+    pub fn custom_opcode(
+        mut self,
+        mask: u16,
+        pattern: u16,
+        handler: impl FnMut(&mut OpcodeContext, u16) + Send + 'static,
+    ) -> Self {
+        self.custom_opcodes.push(CustomOpcode {
+            mask,
+            pattern: pattern & mask,
+            handler: Box::new(handler),
+        });
+        self
+    }
+
+    /// Call `handler` for every invalid opcode that no handler registered before it takes, trapping
+    /// them rather than leaving them to the invalid opcode policy
+    pub fn trap_invalid_opcodes(
+        self,
+        handler: impl FnMut(&mut OpcodeContext, u16) + Send + 'static,
+    ) -> Self {
+        self.custom_opcode(0, 0, handler)
+    }
+
+    /// Run the handler registered for `opcode`, returning false if there isn't one
+    pub(crate) fn run_custom_opcode(&mut self, opcode: u16) -> bool {
+        let Some(custom) = self
+            .custom_opcodes
+            .iter_mut()
+            .find(|custom| opcode & custom.mask == custom.pattern)
+        else {
+            return false;
+        };
+
+        let fb = self.display.fb;
+        let mut context = OpcodeContext {
+            v: &mut self.v,
+            i: &mut self.i,
+            pc: &mut self.pc,
+            dt: &mut self.dt,
+            st: &mut self.st,
+            memory: &mut self.memory.data,
+            fb: &mut self.display.fb,
+        };
+        (custom.handler)(&mut context, opcode);
+
+        if self.display.fb != fb {
+            self.display.mark_dirty();
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Chip8, Chip8Error};
+
+    #[test]
+    fn test_custom_opcode() {
+        // a "syscall" that stores the sum of V0 and V1 at I
+        let mut chip8 = Chip8::new()
+            .unwrap()
+            .custom_opcode(0xFFFF, 0x0123, |context, _| {
+                let sum = context.v[0].wrapping_add(context.v[1]);
+                context.memory[*context.i as usize] = sum;
+            })
+            .custom_opcode(0xF0FF, 0xF0F1, |context, opcode| {
+                context.v[(opcode >> 8) as usize & 0xF] = 0xAA;
+            });
+        chip8
+            .load_rom(&[0x60, 0x02, 0x61, 0x03, 0xA3, 0x00, 0x01, 0x23, 0xF5, 0xF1])
+            .unwrap();
+        for _ in 0..5 {
+            chip8.step().unwrap();
+        }
+        assert_eq!(chip8.memory.data[0x300], 5);
+        assert_eq!(chip8.v[5], 0xAA);
+        assert_eq!(chip8.pc, 0x20A);
+    }
+
+    #[test]
+    fn test_trap_invalid_opcodes() {
+        let mut chip8 = Chip8::new()
+            .unwrap()
+            .custom_opcode(0xFFFF, 0x0123, |context, _| context.v[0] = 1)
+            .trap_invalid_opcodes(|context, opcode| context.v[1] = opcode as u8);
+        chip8.load_rom(&[0x01, 0x23, 0x01, 0x24]).unwrap();
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+        assert_eq!(chip8.v[0], 1);
+        assert_eq!(chip8.v[1], 0x24);
+    }
+
+    #[test]
+    fn test_unhandled_opcode() {
+        let mut chip8 = Chip8::new()
+            .unwrap()
+            .custom_opcode(0xFFFF, 0x0123, |_, _| {});
+        chip8.load_rom(&[0x01, 0x24]).unwrap();
+        let error = chip8.step().unwrap_err();
+        assert_eq!(
+            error,
+            Chip8Error::InvalidOpcode {
+                pc: 0x200,
+                opcode: 0x0124
+            }
+        );
+        assert!(error.to_string().contains("machine code is not supported"));
+    }
+}
@@ -0,0 +1,330 @@
+use std::collections::BTreeMap;
+#[cfg(feature = "std")]
+use std::path::Path;
+
+use anyhow::{bail, Context};
+
+use crate::json::Value;
+use crate::{rom_hash, Chip8, Keymap, Quirks, FRAME_RATE};
+
+/// The controls the community CHIP-8 database gives keys for, and the host keys they're bound to
+const CONTROLS: [(&str, &str); 6] = [
+    ("up", "up"),
+    ("down", "down"),
+    ("left", "left"),
+    ("right", "right"),
+    ("a", "space"),
+    ("b", "enter"),
+];
+
+/// The quirks and speed a known ROM was written for
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RomProfile {
+    pub title: String,
+    pub quirks: Quirks,
+    /// The instructions run each frame, if the ROM needs a particular speed
+    pub tickrate: Option<usize>,
+    /// The CHIP-8 keys the ROM's controls are on, keyed by `up`, `down`, `left`, `right`, `a`
+    /// and `b`
+    pub keys: BTreeMap<String, u8>,
+}
+
+impl RomProfile {
+    /// Apply the quirks and speed to `chip8`
+    pub fn configure(&self, chip8: Chip8) -> Chip8 {
+        let chip8 = chip8.quirks(self.quirks);
+        match self.tickrate {
+            Some(tickrate) => {
+                let clock_hz = tickrate.saturating_mul(FRAME_RATE).min(u32::MAX as usize);
+                chip8.clock_hz(clock_hz as u32)
+            }
+            None => chip8,
+        }
+    }
+
+    /// Bind the arrow keys to the ROM's directions, and space and enter to its `a` and `b`
+    /// buttons, for whichever of them it has keys for
+    pub fn bind_keys(&self, keymap: &mut Keymap) {
+        for (control, label) in CONTROLS {
+            if let Some(key) = self.keys.get(control) {
+                keymap.bind(label, *key);
+            }
+        }
+    }
+}
+
+/// Profiles of known ROMs keyed by the SHA-1 hash the community CHIP-8 database identifies them
+/// by. They're read either from the database's own `programs.json`, or from lines of
+/// `HASH PRESET TICKRATE TITLE`, where the preset is a quirks preset name, the tickrate is `-`
+/// when any speed will do, and lines starting with `#` are comments.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RomDatabase {
+    profiles: BTreeMap<String, RomProfile>,
+}
+
+impl RomDatabase {
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let mut profiles = BTreeMap::new();
+        for (n, line) in text
+            .lines()
+            .enumerate()
+            .map(|(n, line)| (n + 1, line.trim()))
+        {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.splitn(4, char::is_whitespace);
+            let (Some(hash), Some(preset), Some(tickrate), Some(title)) =
+                (fields.next(), fields.next(), fields.next(), fields.next())
+            else {
+                bail!("line {}: expected HASH PRESET TICKRATE TITLE", n);
+            };
+            if hash.len() != 40 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                bail!("line {}: invalid rom hash '{}'", n, hash);
+            }
+            let quirks = preset
+                .parse::<Quirks>()
+                .with_context(|| format!("line {}", n))?;
+            let tickrate = match tickrate {
+                "-" => None,
+                tickrate => Some(
+                    tickrate
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|tickrate| *tickrate > 0)
+                        .with_context(|| format!("line {}: invalid tickrate '{}'", n, tickrate))?,
+                ),
+            };
+            let profile = RomProfile {
+                title: title.trim().to_string(),
+                quirks,
+                tickrate,
+                keys: BTreeMap::new(),
+            };
+            profiles.insert(hash.to_ascii_lowercase(), profile);
+        }
+        Ok(Self { profiles })
+    }
+
+    /// Parse the community CHIP-8 database's `programs.json`. ROMs are given the quirks of the
+    /// first platform they list that can be run, along with any quirks they need on top of it,
+    /// and ROMs only made for other platforms are left out.
+    pub fn parse_community(json: &str) -> anyhow::Result<Self> {
+        let programs = Value::parse(json)?;
+        let programs = programs
+            .as_array()
+            .context("expected an array of programs")?;
+        let mut profiles = BTreeMap::new();
+        for (n, program) in programs.iter().enumerate() {
+            let title = program
+                .get("title")
+                .and_then(Value::as_str)
+                .with_context(|| format!("program {} has no title", n))?;
+            let roms = program
+                .get("roms")
+                .and_then(Value::as_object)
+                .with_context(|| format!("{} has no roms", title))?;
+            for (hash, rom) in roms {
+                if hash.len() != 40 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                    bail!("{}: invalid rom hash '{}'", title, hash);
+                }
+                let platforms = rom.get("platforms").and_then(Value::as_array);
+                let Some((platform, mut quirks)) = platforms
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Value::as_str)
+                    .find_map(|platform| Some((platform, platform_quirks(platform)?)))
+                else {
+                    continue;
+                };
+                if let Some(overrides) = rom.get("quirkyPlatforms").and_then(|q| q.get(platform)) {
+                    apply_quirks(&mut quirks, overrides);
+                }
+                let tickrate = rom
+                    .get("tickrate")
+                    .and_then(Value::as_f64)
+                    .filter(|tickrate| *tickrate >= 1.0)
+                    .map(|tickrate| tickrate as usize);
+                let keys = CONTROLS
+                    .iter()
+                    .filter_map(|(control, _)| {
+                        let key = rom.get("keys")?.get(control)?.as_f64()?;
+                        (0.0..=15.0)
+                            .contains(&key)
+                            .then(|| (control.to_string(), key as u8))
+                    })
+                    .collect();
+                let profile = RomProfile {
+                    title: title.to_string(),
+                    quirks,
+                    tickrate,
+                    keys,
+                };
+                profiles.insert(hash.to_ascii_lowercase(), profile);
+            }
+        }
+        Ok(Self { profiles })
+    }
+
+    /// Load a database, reading it as the community database's JSON if it starts like JSON
+    #[cfg(feature = "std")]
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path).context("read rom database")?;
+        let database = if text.trim_start().starts_with('[') {
+            Self::parse_community(&text)
+        } else {
+            Self::parse(&text)
+        };
+        database.with_context(|| format!("parse rom database {}", path.display()))
+    }
+
+    /// The profile of the ROM with the SHA-1 `hash`
+    pub fn get(&self, hash: &str) -> Option<&RomProfile> {
+        self.profiles.get(&hash.to_ascii_lowercase())
+    }
+
+    /// The profile of `rom`, if it's a known ROM, to pick its quirks and speed automatically
+    pub fn auto_configure(&self, rom: &[u8]) -> Option<&RomProfile> {
+        self.get(&rom_hash(rom))
+    }
+
+    pub fn len(&self) -> usize {
+        self.profiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.profiles.is_empty()
+    }
+}
+
+/// The quirks of a platform named in the community database, if it's one that can be run
+fn platform_quirks(platform: &str) -> Option<Quirks> {
+    match platform {
+        "originalChip8" | "hybridVIP" | "chip8x" => Some(Quirks::cosmac_vip()),
+        "modernChip8" => Some(Quirks::default()),
+        "chip48" | "superchip1" | "superchip" => Some(Quirks::schip()),
+        "xochip" => Some(Quirks::xo_chip()),
+        _ => None,
+    }
+}
+
+/// Change the quirks a ROM's `quirkyPlatforms` entry says differ from its platform's
+fn apply_quirks(quirks: &mut Quirks, overrides: &Value) {
+    let quirk = |name| overrides.get(name).and_then(Value::as_bool);
+    if let Some(shift) = quirk("shift") {
+        quirks.legacy_shift = !shift;
+    }
+    if let Some(leave_i) = quirk("memoryLeaveIUnchanged") {
+        quirks.memory_increment_i = !leave_i;
+    }
+    if let Some(wrap) = quirk("wrap") {
+        quirks.wrap_sprites = wrap;
+    }
+    if let Some(jump) = quirk("jump") {
+        quirks.jump_add_offset = jump;
+    }
+    if let Some(vblank) = quirk("vblank") {
+        quirks.display_wait = vblank;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{RomDatabase, RomProfile};
+    use crate::{rom_hash, Chip8, Keymap, Quirks};
+
+    #[test]
+    fn test_rom_database() {
+        let rom = [0x12, 0x00];
+        let text = format!(
+            "# hash preset tickrate title\n{} schip 30 Jump Forever\n\n{} vip - Empty\n",
+            rom_hash(&rom).to_ascii_uppercase(),
+            rom_hash(&[]),
+        );
+        let database = RomDatabase::parse(&text).unwrap();
+        assert_eq!(database.len(), 2);
+
+        let expected = RomProfile {
+            title: "Jump Forever".to_string(),
+            quirks: Quirks::schip(),
+            tickrate: Some(30),
+            keys: BTreeMap::new(),
+        };
+        assert_eq!(database.auto_configure(&rom), Some(&expected));
+        assert_eq!(database.get(&rom_hash(&[])).unwrap().tickrate, None);
+        assert_eq!(database.auto_configure(&[0x00, 0xE0]), None);
+
+        let chip8 = expected.configure(Chip8::new().unwrap());
+        assert_eq!(chip8.config.quirks, Quirks::schip());
+        assert_eq!(chip8.config.clock_hz, 1800);
+
+        assert!(RomDatabase::parse("1234 vip - Short hash").is_err());
+        assert!(RomDatabase::parse(&format!("{} chip48 - Unknown", rom_hash(&rom))).is_err());
+        assert!(RomDatabase::parse(&format!("{} vip 0 Stopped", rom_hash(&rom))).is_err());
+        assert!(RomDatabase::parse(&format!("{} vip", rom_hash(&rom))).is_err());
+
+        let huge = RomProfile {
+            tickrate: Some(usize::MAX),
+            ..expected
+        };
+        assert_eq!(
+            huge.configure(Chip8::new().unwrap()).config.clock_hz,
+            u32::MAX
+        );
+    }
+
+    #[test]
+    fn test_community_database() {
+        let rom = [0x12, 0x00];
+        let json = format!(
+            r#"[
+                {{"title": "Jump Forever", "roms": {{"{}": {{
+                    "platforms": ["megachip8", "superchip", "xochip"],
+                    "quirkyPlatforms": {{"superchip": {{"shift": true, "jump": false}}}},
+                    "tickrate": 30,
+                    "keys": {{"up": 5, "a": 6, "player2Up": 8, "b": 99}}
+                }}}}}},
+                {{"title": "Empty", "roms": {{"{}": {{"platforms": ["originalChip8"]}}}}}},
+                {{"title": "Huge", "roms": {{"{}": {{"platforms": ["megachip8"]}}}}}}
+            ]"#,
+            rom_hash(&rom).to_ascii_uppercase(),
+            rom_hash(&[]),
+            rom_hash(&[0x00, 0xE0]),
+        );
+        let database = RomDatabase::parse_community(&json).unwrap();
+        assert_eq!(database.len(), 2);
+
+        let profile = database.auto_configure(&rom).unwrap();
+        assert_eq!(profile.title, "Jump Forever");
+        assert_eq!(
+            profile.quirks,
+            Quirks {
+                legacy_shift: false,
+                jump_add_offset: false,
+                ..Quirks::schip()
+            }
+        );
+        assert_eq!(profile.tickrate, Some(30));
+        let keys = BTreeMap::from([("a".to_string(), 6), ("up".to_string(), 5)]);
+        assert_eq!(profile.keys, keys);
+
+        let mut keymap = Keymap::empty();
+        profile.bind_keys(&mut keymap);
+        assert_eq!(keymap.key("up").value(), Some(5));
+        assert_eq!(keymap.key("space").value(), Some(6));
+        assert!(!keymap.is_bound("enter"));
+
+        let empty = database.get(&rom_hash(&[])).unwrap();
+        assert_eq!(empty.quirks, Quirks::cosmac_vip());
+        assert_eq!(empty.tickrate, None);
+        assert!(empty.keys.is_empty());
+
+        assert!(RomDatabase::parse_community("{}").is_err());
+        assert!(RomDatabase::parse_community(r#"[{"title": "No roms"}]"#).is_err());
+        assert!(
+            RomDatabase::parse_community(r#"[{"title": "Bad", "roms": {"1234": {}}}]"#).is_err()
+        );
+    }
+}
@@ -0,0 +1,236 @@
+use std::fmt::Display;
+
+use crate::MachineState;
+
+/// A register whose value differs between two states
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RegisterChange {
+    pub name: String,
+    pub before: u16,
+    pub after: u16,
+}
+
+/// A run of consecutive memory bytes that differ between two states
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemoryChange {
+    pub addr: usize,
+    pub before: Vec<u8>,
+    pub after: Vec<u8>,
+}
+
+/// The differences between two machine states
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StateDiff {
+    pub registers: Vec<RegisterChange>,
+    pub memory: Vec<MemoryChange>,
+    /// The coordinates of every pixel that was toggled, row by row
+    pub pixels: Vec<(usize, usize)>,
+}
+
+impl StateDiff {
+    /// Diff two savestates produced by `Chip8::save_state`
+    pub fn between(before: &[u8], after: &[u8]) -> anyhow::Result<Self> {
+        let before = MachineState::decode(before)?;
+        let after = MachineState::decode(after)?;
+        Ok(Self::between_states(&before, &after))
+    }
+
+    /// Diff two machine states produced by `Chip8::dump_state`
+    pub fn between_states(before: &MachineState, after: &MachineState) -> Self {
+        let mut registers = Vec::new();
+        let mut register = |name: String, before: u16, after: u16| {
+            if before != after {
+                registers.push(RegisterChange {
+                    name,
+                    before,
+                    after,
+                });
+            }
+        };
+        register("PC".to_string(), before.pc, after.pc);
+        register("I".to_string(), before.i, after.i);
+        register("SP".to_string(), before.sp as u16, after.sp as u16);
+        register("DT".to_string(), before.dt as u16, after.dt as u16);
+        register("ST".to_string(), before.st as u16, after.st as u16);
+        for (n, (a, b)) in before.v.iter().zip(after.v).enumerate() {
+            register(format!("V{:X}", n), *a as u16, b as u16);
+        }
+        for (n, (a, b)) in before.stack.iter().zip(after.stack).enumerate() {
+            register(format!("stack[{:X}]", n), *a, b);
+        }
+        register("pitch".to_string(), before.pitch as u16, after.pitch as u16);
+
+        let mut memory: Vec<MemoryChange> = Vec::new();
+        for (addr, (a, b)) in before.memory.iter().zip(&after.memory).enumerate() {
+            if a == b {
+                continue;
+            }
+            match memory.last_mut() {
+                Some(change) if change.addr + change.before.len() == addr => {
+                    change.before.push(*a);
+                    change.after.push(*b);
+                }
+                _ => memory.push(MemoryChange {
+                    addr,
+                    before: vec![*a],
+                    after: vec![*b],
+                }),
+            }
+        }
+
+        // a change of resolution compares the pixels at the same coordinates
+        let width = before.fb.width().max(after.fb.width());
+        let height = before.fb.height().max(after.fb.height());
+        let pixels = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .filter(|(x, y)| before.fb.get(*x, *y) != after.fb.get(*x, *y))
+            .collect();
+
+        Self {
+            registers,
+            memory,
+            pixels,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.registers.is_empty() && self.memory.is_empty() && self.pixels.is_empty()
+    }
+}
+
+impl Display for StateDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "no changes");
+        }
+
+        if !self.registers.is_empty() {
+            writeln!(f, "registers:")?;
+            for change in &self.registers {
+                writeln!(
+                    f,
+                    "  {:<8} {:#06x} -> {:#06x}",
+                    change.name, change.before, change.after
+                )?;
+            }
+        }
+
+        if !self.memory.is_empty() {
+            let bytes: usize = self.memory.iter().map(|change| change.before.len()).sum();
+            writeln!(f, "memory: {} bytes in {} ranges", bytes, self.memory.len())?;
+            for change in &self.memory {
+                let hex = |bytes: &[u8]| {
+                    bytes
+                        .iter()
+                        .map(|byte| format!("{:02X}", byte))
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                };
+                writeln!(
+                    f,
+                    "  {:#06x}..{:#06x}  {} -> {}",
+                    change.addr,
+                    change.addr + change.before.len(),
+                    hex(&change.before),
+                    hex(&change.after)
+                )?;
+            }
+        }
+
+        if !self.pixels.is_empty() {
+            let min_x = self
+                .pixels
+                .iter()
+                .map(|(x, _)| *x)
+                .min()
+                .unwrap_or_default();
+            let max_x = self
+                .pixels
+                .iter()
+                .map(|(x, _)| *x)
+                .max()
+                .unwrap_or_default();
+            let min_y = self
+                .pixels
+                .iter()
+                .map(|(_, y)| *y)
+                .min()
+                .unwrap_or_default();
+            let max_y = self
+                .pixels
+                .iter()
+                .map(|(_, y)| *y)
+                .max()
+                .unwrap_or_default();
+            writeln!(
+                f,
+                "display: {} pixels toggled within ({}, {})..=({}, {})",
+                self.pixels.len(),
+                min_x,
+                min_y,
+                max_x,
+                max_y
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MemoryChange, RegisterChange, StateDiff};
+    use crate::Chip8;
+
+    #[test]
+    fn test_state_diff() {
+        let mut chip8 = Chip8::new().unwrap();
+        chip8
+            .load_rom(&[0x60, 0x2A, 0xA3, 0x00, 0xF0, 0x33, 0xD0, 0x01, 0x12, 0x08])
+            .unwrap();
+        let before = chip8.save_state();
+        for _ in 0..4 {
+            chip8.step().unwrap();
+        }
+        let diff = StateDiff::between(&before, &chip8.save_state()).unwrap();
+
+        assert_eq!(
+            diff.registers[..3],
+            [
+                RegisterChange {
+                    name: "PC".to_string(),
+                    before: 0x200,
+                    after: 0x208
+                },
+                RegisterChange {
+                    name: "I".to_string(),
+                    before: 0x000,
+                    after: 0x300
+                },
+                RegisterChange {
+                    name: "V0".to_string(),
+                    before: 0x00,
+                    after: 0x2A
+                },
+            ]
+        );
+        // 42 is stored as 0, 4, 2
+        assert_eq!(
+            diff.memory,
+            [MemoryChange {
+                addr: 0x301,
+                before: vec![0, 0],
+                after: vec![4, 2]
+            }]
+        );
+        // the top row of the sprite at 0x300 is 0b00000000
+        assert_eq!(diff.pixels, []);
+
+        let report = diff.to_string();
+        assert!(report.contains("V0"));
+        assert!(report.contains("0x0301..0x0303  00 00 -> 04 02"));
+        assert_eq!(
+            StateDiff::between(&before, &before).unwrap().to_string(),
+            "no changes\n"
+        );
+    }
+}
@@ -0,0 +1,109 @@
+/// Return the assembly mnemonic for `opcode` in the notation of Cowgod's CHIP-8 reference, or
+/// `None` if it isn't a valid instruction
+pub fn disassemble(opcode: u16) -> Option<String> {
+    let x = (opcode >> 8) & 0xF;
+    let y = (opcode >> 4) & 0xF;
+    let n = opcode & 0xF;
+    let nn = opcode & 0xFF;
+    let nnn = opcode & 0xFFF;
+
+    let text = match opcode >> 12 {
+        0x0 => match nnn {
+            0x0E0 => "CLS".to_string(),
+            0x0EE => "RET".to_string(),
+            0x2A0 => "BGND".to_string(),
+            0x0C0..=0x0CF => format!("SCD {}", n),
+            0x0D0..=0x0DF => format!("SCU {}", n),
+            0x0FB => "SCR".to_string(),
+            0x0FC => "SCL".to_string(),
+            0x0FD => "EXIT".to_string(),
+            0x0FE => "LOW".to_string(),
+            0x0FF => "HIGH".to_string(),
+            _ => return None,
+        },
+        0x1 => format!("JP {:#05x}", nnn),
+        0x2 => format!("CALL {:#05x}", nnn),
+        0x3 => format!("SE V{:X}, {:#04x}", x, nn),
+        0x4 => format!("SNE V{:X}, {:#04x}", x, nn),
+        0x5 => match n {
+            0x2 => format!("LD [I], V{:X}, V{:X}", x, y),
+            0x3 => format!("LD V{:X}, V{:X}, [I]", x, y),
+            _ => format!("SE V{:X}, V{:X}", x, y),
+        },
+        0x6 => format!("LD V{:X}, {:#04x}", x, nn),
+        0x7 => format!("ADD V{:X}, {:#04x}", x, nn),
+        0x8 => {
+            let mnemonic = match n {
+                0x0 => "LD",
+                0x1 => "OR",
+                0x2 => "AND",
+                0x3 => "XOR",
+                0x4 => "ADD",
+                0x5 => "SUB",
+                0x6 => "SHR",
+                0x7 => "SUBN",
+                0xE => "SHL",
+                _ => return None,
+            };
+            format!("{} V{:X}, V{:X}", mnemonic, x, y)
+        }
+        0x9 => format!("SNE V{:X}, V{:X}", x, y),
+        0xA => format!("LD I, {:#05x}", nnn),
+        0xB => format!("JP V0, {:#05x}", nnn),
+        0xC => format!("RND V{:X}, {:#04x}", x, nn),
+        0xD => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+        0xE => match nn {
+            0x9E => format!("SKP V{:X}", x),
+            0xA1 => format!("SKNP V{:X}", x),
+            0xF2 => format!("SKP2 V{:X}", x),
+            0xF5 => format!("SKNP2 V{:X}", x),
+            _ => return None,
+        },
+        0xF => match nn {
+            0x00 if x == 0 => "LD I, LONG".to_string(),
+            0x01 => format!("PLANE {}", x),
+            0x02 if x == 0 => "AUDIO".to_string(),
+            0x07 => format!("LD V{:X}, DT", x),
+            0x0A => format!("LD V{:X}, K", x),
+            0x15 => format!("LD DT, V{:X}", x),
+            0x18 => format!("LD ST, V{:X}", x),
+            0x1E => format!("ADD I, V{:X}", x),
+            0x29 => format!("LD F, V{:X}", x),
+            0x30 => format!("LD HF, V{:X}", x),
+            0x33 => format!("LD B, V{:X}", x),
+            0x3A => format!("PITCH V{:X}", x),
+            0x55 => format!("LD [I], V{:X}", x),
+            0x65 => format!("LD V{:X}, [I]", x),
+            0x75 => format!("LD R, V{:X}", x),
+            0x85 => format!("LD V{:X}, R", x),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::disassemble;
+
+    #[test]
+    fn test_disassemble() {
+        assert_eq!(disassemble(0x00E0).as_deref(), Some("CLS"));
+        assert_eq!(disassemble(0x00FF).as_deref(), Some("HIGH"));
+        assert_eq!(disassemble(0x00C4).as_deref(), Some("SCD 4"));
+        assert_eq!(disassemble(0x00D1).as_deref(), Some("SCU 1"));
+        assert_eq!(disassemble(0x1228).as_deref(), Some("JP 0x228"));
+        assert_eq!(disassemble(0x6A2F).as_deref(), Some("LD VA, 0x2f"));
+        assert_eq!(disassemble(0x8AB4).as_deref(), Some("ADD VA, VB"));
+        assert_eq!(disassemble(0xD015).as_deref(), Some("DRW V0, V1, 5"));
+        assert_eq!(disassemble(0xF355).as_deref(), Some("LD [I], V3"));
+        assert_eq!(disassemble(0xF000).as_deref(), Some("LD I, LONG"));
+        assert_eq!(disassemble(0x5312).as_deref(), Some("LD [I], V3, V1"));
+        assert_eq!(disassemble(0x02A0).as_deref(), Some("BGND"));
+        assert_eq!(disassemble(0xE4F2).as_deref(), Some("SKP2 V4"));
+        assert_eq!(disassemble(0xF100), None);
+        assert_eq!(disassemble(0x0123), None);
+        assert_eq!(disassemble(0x8008), None);
+    }
+}
@@ -1,59 +1,279 @@
 use std::fmt::Display as FmtDisplay;
 
-use crate::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::{HIRES_HEIGHT, HIRES_WIDTH, SCREEN_HEIGHT, SCREEN_WIDTH};
 
-pub type FrameBuffer = [[u8; SCREEN_WIDTH]; SCREEN_HEIGHT];
+/// A copy of what's on screen. Each pixel is a 2-bit colour index, with bit 0 set when it's lit in
+/// the first plane and bit 1 when it's lit in XO-CHIP's second plane, so it's 0 when unlit. The
+/// size and layout in memory aren't part of the API, so read it through the accessors rather than
+/// assuming 64x32.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Frame {
+    /// The pixels row by row, `width` to a row, with the rest unused in low resolution
+    pub(crate) pixels: [u8; HIRES_WIDTH * HIRES_HEIGHT],
+    /// Whether the frame is in SUPER-CHIP's 128x64 high resolution mode
+    pub(crate) hires: bool,
+    /// Whether the frame is in the 64x64 mode of the two-page hires CHIP-8 interpreter
+    pub(crate) two_page: bool,
+}
+
+impl Frame {
+    /// A low resolution frame with every pixel unlit
+    pub const fn new() -> Self {
+        Self::blank(false)
+    }
+
+    /// A frame with every pixel unlit, in high or low resolution
+    pub const fn blank(hires: bool) -> Self {
+        Self {
+            pixels: [0; HIRES_WIDTH * HIRES_HEIGHT],
+            hires,
+            two_page: false,
+        }
+    }
+
+    /// A 64x64 frame with every pixel unlit, the screen of the two-page hires CHIP-8 interpreter
+    pub const fn two_page() -> Self {
+        Self {
+            two_page: true,
+            ..Self::blank(false)
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        if self.hires {
+            HIRES_WIDTH
+        } else {
+            SCREEN_WIDTH
+        }
+    }
+
+    pub fn height(&self) -> usize {
+        if self.hires || self.two_page {
+            HIRES_HEIGHT
+        } else {
+            SCREEN_HEIGHT
+        }
+    }
+
+    pub fn is_hires(&self) -> bool {
+        self.hires
+    }
+
+    pub fn is_two_page(&self) -> bool {
+        self.two_page
+    }
+
+    /// The number of the frame's pixels across and down that take up the space of a low
+    /// resolution pixel, which isn't the same both ways in the 64x64 mode
+    pub fn scale(&self) -> (usize, usize) {
+        (self.width() / SCREEN_WIDTH, self.height() / SCREEN_HEIGHT)
+    }
+
+    /// Return the index of the pixel at the coordinates, which have to be within the frame
+    fn index(&self, x: usize, y: usize) -> usize {
+        y * self.width() + x
+    }
+
+    /// The colour index of the pixel at the coordinates, or 0 if they're outside the frame
+    pub fn get(&self, x: usize, y: usize) -> u8 {
+        if x >= self.width() || y >= self.height() {
+            return 0;
+        }
+        self.pixels[self.index(x, y)]
+    }
+
+    /// Set the pixel at the coordinates, ignoring coordinates outside the frame
+    pub fn set(&mut self, x: usize, y: usize, value: u8) {
+        if x < self.width() && y < self.height() {
+            let idx = self.index(x, y);
+            self.pixels[idx] = value;
+        }
+    }
+
+    /// Iterate over the rows of the frame, top to bottom, each `width` pixels long
+    pub fn iter_rows(&self) -> impl Iterator<Item = &[u8]> + '_ {
+        self.pixels[..self.width() * self.height()].chunks_exact(self.width())
+    }
+
+    /// Iterate over the coordinates of the lit pixels, row by row
+    pub fn iter_set_pixels(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.iter_rows().enumerate().flat_map(|(y, row)| {
+            row.iter()
+                .enumerate()
+                .filter(|(_, pixel)| **pixel != 0)
+                .map(move |(x, _)| (x, y))
+        })
+    }
+}
+
+impl Default for Frame {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The bits of the planes there are to draw in
+#[cfg(any(feature = "xochip", test))]
+pub const ALL_PLANES: u8 = 0b11;
 
 pub struct Display {
-    pub(crate) fb: FrameBuffer,
-    pub(crate) dirty: bool,
+    pub(crate) fb: Frame,
+    pub(crate) dirty_rows: [bool; HIRES_HEIGHT],
+    /// The planes drawing, clearing and scrolling affect, one bit per plane
+    pub(crate) planes: u8,
 }
 
 impl Display {
     pub fn new() -> Self {
         Self {
-            fb: [[0; SCREEN_WIDTH]; SCREEN_HEIGHT],
-            dirty: false,
+            fb: Frame::new(),
+            dirty_rows: [false; HIRES_HEIGHT],
+            planes: 1,
         }
     }
 
-    pub fn fb(&mut self) -> FrameBuffer {
-        self.dirty = false;
+    /// Return a copy of the framebuffer and mark every row as clean
+    pub fn fb(&mut self) -> Frame {
+        self.dirty_rows = [false; HIRES_HEIGHT];
         self.fb
     }
 
-    /// Toggle the pixel at the coordinates and return true if it was already on
-    /// This function marks the display as dirty, causing it to be re-rendered on the next update
-    pub fn toggle(&mut self, x: usize, y: usize) -> bool {
-        if x >= SCREEN_WIDTH || y >= SCREEN_HEIGHT {
+    /// Mark every row as dirty, causing the whole display to be re-rendered on the next update
+    pub fn mark_dirty(&mut self) {
+        self.dirty_rows = [true; HIRES_HEIGHT];
+    }
+
+    /// Return true if any row has changed since the framebuffer was last read
+    pub fn is_dirty(&self) -> bool {
+        self.dirty_rows.contains(&true)
+    }
+
+    /// Iterate over the indices of the rows that changed since the framebuffer was last read
+    pub fn dirty_rows(&self) -> impl Iterator<Item = usize> + '_ {
+        self.dirty_rows
+            .iter()
+            .enumerate()
+            .take(self.fb.height())
+            .filter_map(|(y, dirty)| dirty.then_some(y))
+    }
+
+    /// Toggle the pixel at the coordinates in `plane`, given as its bit, and return true if it was
+    /// already on in that plane
+    /// This function marks the row as dirty, causing it to be re-rendered on the next update
+    pub fn toggle(&mut self, x: usize, y: usize, plane: u8) -> bool {
+        if x >= self.fb.width() || y >= self.fb.height() {
             return false;
         }
-        self.dirty = true;
-        let prev = self.fb[y][x];
-        self.fb[y][x] ^= 1;
-        prev == 1
+        self.dirty_rows[y] = true;
+        let idx = self.fb.index(x, y);
+        let prev = self.fb.pixels[idx];
+        self.fb.pixels[idx] ^= plane;
+        prev & plane != 0
+    }
+
+    /// Toggle the pixel at the coordinates in `plane` like `toggle`, wrapping coordinates past
+    /// the edges of the screen around to the other side
+    pub fn toggle_wrapping(&mut self, x: usize, y: usize, plane: u8) -> bool {
+        self.toggle(x % self.fb.width(), y % self.fb.height(), plane)
     }
 
-    /// Clear the display contents by zeroing out the framebuffer
-    /// This function marks the display as dirty, causing it to be re-rendered on the next update
+    /// Clear the selected planes, leaving the others as they are
+    /// This function marks every row as dirty, causing the display to be re-rendered on the next update
     pub fn clear(&mut self) {
-        self.dirty = true;
-        for i in 0..SCREEN_HEIGHT {
-            self.fb[i].fill(0);
+        self.mark_dirty();
+        for pixel in self.fb.pixels.iter_mut() {
+            *pixel &= !self.planes;
         }
     }
 
+    /// Switch between the 64x32 and 128x64 resolutions, clearing the display
+    #[cfg(any(feature = "schip", test))]
+    pub fn set_hires(&mut self, hires: bool) {
+        self.mark_dirty();
+        self.fb = Frame::blank(hires);
+    }
+
+    /// Switch to the 64x64 screen of the two-page hires CHIP-8 interpreter, clearing the display
+    #[cfg(any(feature = "two_page", test))]
+    pub fn set_two_page(&mut self) {
+        self.mark_dirty();
+        self.fb = Frame::two_page();
+    }
+
+    /// Move the picture in the selected planes down `n` rows, dropping the rows that go off the
+    /// bottom and leaving blank ones at the top
+    #[cfg(any(feature = "schip", test))]
+    pub fn scroll_down(&mut self, n: usize) {
+        self.shift_planes(|pixels, width| {
+            let shift = (n * width).min(pixels.len());
+            pixels.copy_within(..pixels.len() - shift, shift);
+            pixels[..shift].fill(0);
+        });
+    }
+
+    /// Move the picture in the selected planes up `n` rows, dropping the rows that go off the top
+    /// and leaving blank ones at the bottom
+    #[cfg(any(feature = "xochip", test))]
+    pub fn scroll_up(&mut self, n: usize) {
+        self.shift_planes(|pixels, width| {
+            let shift = (n * width).min(pixels.len());
+            pixels.copy_within(shift.., 0);
+            let len = pixels.len();
+            pixels[len - shift..].fill(0);
+        });
+    }
+
+    /// Move the picture in the selected planes right `n` columns, dropping the columns that go off
+    /// the right edge
+    #[cfg(any(feature = "schip", test))]
+    pub fn scroll_right(&mut self, n: usize) {
+        self.shift_planes(|pixels, width| {
+            let n = n.min(width);
+            for row in pixels.chunks_exact_mut(width) {
+                row.copy_within(..width - n, n);
+                row[..n].fill(0);
+            }
+        });
+    }
+
+    /// Move the picture in the selected planes left `n` columns, dropping the columns that go off
+    /// the left edge
+    #[cfg(any(feature = "schip", test))]
+    pub fn scroll_left(&mut self, n: usize) {
+        self.shift_planes(|pixels, width| {
+            let n = n.min(width);
+            for row in pixels.chunks_exact_mut(width) {
+                row.copy_within(n.., 0);
+                row[width - n..].fill(0);
+            }
+        });
+    }
+
+    /// Apply `shift` to a copy of the visible pixels, given with the width of a row, and keep the
+    /// result in the selected planes only
+    #[cfg(any(feature = "schip", feature = "xochip", test))]
+    fn shift_planes(&mut self, shift: impl FnOnce(&mut [u8], usize)) {
+        self.mark_dirty();
+        let (width, height) = (self.fb.width(), self.fb.height());
+        let mut shifted = self.fb.pixels;
+        shift(&mut shifted[..width * height], width);
+        for (pixel, shifted) in self.fb.pixels.iter_mut().zip(shifted) {
+            *pixel = (*pixel & !self.planes) | (shifted & self.planes);
+        }
+    }
+
+    /// Whether the pixel at the coordinates is lit in the first plane
     #[cfg(test)]
     pub fn is_set(&self, x: usize, y: usize) -> bool {
-        self.fb[y][x] == 1
+        self.fb.get(x, y) & 1 == 1
     }
 }
 
 impl FmtDisplay for Display {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for y in 0..SCREEN_HEIGHT {
-            for x in 0..SCREEN_WIDTH {
-                write!(f, "{}", self.fb[y][x])?;
+        for row in self.fb.iter_rows() {
+            for pixel in row {
+                write!(f, "{}", pixel)?;
             }
             writeln!(f)?;
         }
@@ -63,19 +283,198 @@ impl FmtDisplay for Display {
 
 #[cfg(test)]
 mod tests {
-    use super::{Display, SCREEN_HEIGHT, SCREEN_WIDTH};
+    use super::{
+        Display, Frame, ALL_PLANES, HIRES_HEIGHT, HIRES_WIDTH, SCREEN_HEIGHT, SCREEN_WIDTH,
+    };
 
     #[test]
     fn test_toggle() {
         let mut display = Display::new();
-        assert_eq!(display.toggle(SCREEN_WIDTH - 1, SCREEN_HEIGHT - 1), false);
-        assert_eq!(display.fb[SCREEN_HEIGHT - 1][SCREEN_WIDTH - 1], 1);
-        assert_eq!(display.toggle(SCREEN_WIDTH - 1, SCREEN_HEIGHT - 1), true);
-        assert_eq!(display.fb[SCREEN_HEIGHT - 1][SCREEN_WIDTH - 1], 0);
-        assert_eq!(display.toggle(0, 0), false);
-        assert_eq!(display.fb[0][0], 1);
-        assert_eq!(display.toggle(0, 0), true);
-        assert_eq!(display.fb[0][0], 0);
-        assert_eq!(display.toggle(SCREEN_WIDTH, SCREEN_HEIGHT), false);
+        assert_eq!(
+            display.toggle(SCREEN_WIDTH - 1, SCREEN_HEIGHT - 1, 1),
+            false
+        );
+        assert_eq!(display.fb.get(SCREEN_WIDTH - 1, SCREEN_HEIGHT - 1), 1);
+        assert_eq!(display.toggle(SCREEN_WIDTH - 1, SCREEN_HEIGHT - 1, 1), true);
+        assert_eq!(display.fb.get(SCREEN_WIDTH - 1, SCREEN_HEIGHT - 1), 0);
+        assert_eq!(display.toggle(0, 0, 1), false);
+        assert_eq!(display.fb.get(0, 0), 1);
+        assert_eq!(display.toggle(0, 0, 1), true);
+        assert_eq!(display.fb.get(0, 0), 0);
+        assert_eq!(display.toggle(SCREEN_WIDTH, SCREEN_HEIGHT, 1), false);
+    }
+
+    #[test]
+    fn test_toggle_wrapping() {
+        let mut display = Display::new();
+        assert_eq!(
+            display.toggle_wrapping(SCREEN_WIDTH + 1, SCREEN_HEIGHT * 2 + 3, 1),
+            false
+        );
+        assert_eq!(display.fb.get(1, 3), 1);
+        assert_eq!(display.toggle_wrapping(1, 3, 1), true);
+        assert_eq!(display.fb.get(1, 3), 0);
+    }
+
+    #[test]
+    fn test_iter_rows() {
+        let mut display = Display::new();
+        display.toggle(2, 1, 1);
+        let rows: Vec<&[u8]> = display.fb.iter_rows().collect();
+        assert_eq!(rows.len(), SCREEN_HEIGHT);
+        assert_eq!(rows[1].len(), SCREEN_WIDTH);
+        assert_eq!(rows[1][2], 1);
+        assert_eq!(rows[0].contains(&1), false);
+    }
+
+    #[test]
+    fn test_frame() {
+        let mut frame = Frame::new();
+        assert_eq!(
+            (frame.width(), frame.height()),
+            (SCREEN_WIDTH, SCREEN_HEIGHT)
+        );
+        frame.set(3, 1, 1);
+        frame.set(0, 2, 1);
+        frame.set(SCREEN_WIDTH, 0, 1);
+        assert_eq!(frame.get(3, 1), 1);
+        assert_eq!(frame.get(4, 1), 0);
+        assert_eq!(frame.get(SCREEN_WIDTH, 0), 0);
+        assert_eq!(
+            frame.iter_set_pixels().collect::<Vec<_>>(),
+            [(3, 1), (0, 2)]
+        );
+    }
+
+    #[test]
+    fn test_hires() {
+        let mut display = Display::new();
+        display.toggle(1, 1, 1);
+        display.fb();
+        display.set_hires(true);
+        assert_eq!(display.fb.get(1, 1), 0);
+        assert_eq!(display.dirty_rows().count(), HIRES_HEIGHT);
+
+        assert_eq!(display.toggle(HIRES_WIDTH - 1, HIRES_HEIGHT - 1, 1), false);
+        assert_eq!(display.toggle(HIRES_WIDTH, 0, 1), false);
+        let fb = display.fb();
+        assert_eq!((fb.width(), fb.height()), (HIRES_WIDTH, HIRES_HEIGHT));
+        assert_eq!(fb.iter_rows().count(), HIRES_HEIGHT);
+        assert_eq!(
+            fb.iter_set_pixels().collect::<Vec<_>>(),
+            [(HIRES_WIDTH - 1, HIRES_HEIGHT - 1)]
+        );
+
+        display.set_hires(false);
+        assert_eq!(display.dirty_rows().count(), SCREEN_HEIGHT);
+        assert_eq!(display.fb().iter_rows().count(), SCREEN_HEIGHT);
+    }
+
+    #[test]
+    fn test_two_page() {
+        let mut display = Display::new();
+        display.toggle(1, 1, 1);
+        display.set_two_page();
+        assert_eq!(display.fb.get(1, 1), 0);
+        assert_eq!(display.dirty_rows().count(), HIRES_HEIGHT);
+
+        assert_eq!(display.toggle(SCREEN_WIDTH - 1, HIRES_HEIGHT - 1, 1), false);
+        assert_eq!(display.toggle(SCREEN_WIDTH, 0, 1), false);
+        let fb = display.fb();
+        assert_eq!((fb.width(), fb.height()), (SCREEN_WIDTH, HIRES_HEIGHT));
+        assert_eq!(fb.scale(), (1, 2));
+        assert_eq!(fb.is_hires(), false);
+        assert_eq!(
+            fb.iter_set_pixels().collect::<Vec<_>>(),
+            [(SCREEN_WIDTH - 1, HIRES_HEIGHT - 1)]
+        );
+    }
+
+    #[test]
+    fn test_scroll() {
+        let mut display = Display::new();
+        display.toggle(0, 0, 1);
+        display.toggle(SCREEN_WIDTH - 1, SCREEN_HEIGHT - 1, 1);
+        display.fb();
+
+        // the bottom right pixel goes off the screen rather than wrapping to the top
+        display.scroll_down(2);
+        assert_eq!(display.dirty_rows().count(), SCREEN_HEIGHT);
+        assert_eq!(display.fb().iter_set_pixels().collect::<Vec<_>>(), [(0, 2)]);
+        display.scroll_up(1);
+        assert_eq!(display.fb().iter_set_pixels().collect::<Vec<_>>(), [(0, 1)]);
+        display.scroll_up(2);
+        assert_eq!(display.fb().iter_set_pixels().count(), 0);
+        display.toggle(0, 2, 1);
+
+        display.scroll_right(4);
+        assert_eq!(display.fb().iter_set_pixels().collect::<Vec<_>>(), [(4, 2)]);
+        display.scroll_left(4);
+        assert_eq!(display.fb().iter_set_pixels().collect::<Vec<_>>(), [(0, 2)]);
+        display.scroll_left(4);
+        assert_eq!(display.fb().iter_set_pixels().count(), 0);
+
+        // scrolling further than the screen blanks it
+        display.toggle(1, 1, 1);
+        display.scroll_down(SCREEN_HEIGHT + 1);
+        assert_eq!(display.fb().iter_set_pixels().count(), 0);
+        display.toggle(1, 1, 1);
+        display.scroll_up(SCREEN_HEIGHT + 1);
+        assert_eq!(display.fb().iter_set_pixels().count(), 0);
+        display.toggle(1, 1, 1);
+        display.scroll_right(SCREEN_WIDTH);
+        assert_eq!(display.fb().iter_set_pixels().count(), 0);
+
+        // high resolution rows are twice as long
+        display.set_hires(true);
+        display.toggle(HIRES_WIDTH - 5, HIRES_HEIGHT - 1, 1);
+        display.scroll_right(4);
+        assert_eq!(
+            display.fb().iter_set_pixels().collect::<Vec<_>>(),
+            [(HIRES_WIDTH - 1, HIRES_HEIGHT - 1)]
+        );
+    }
+
+    #[test]
+    fn test_planes() {
+        let mut display = Display::new();
+        assert_eq!(display.toggle(1, 0, 1), false);
+        assert_eq!(display.toggle(1, 0, 2), false);
+        assert_eq!(display.toggle(2, 0, 2), false);
+        assert_eq!(display.fb.get(1, 0), 3);
+        assert_eq!(display.fb.get(2, 0), 2);
+        assert_eq!(display.toggle(2, 0, 1), false);
+        assert_eq!(display.toggle(2, 0, 2), true);
+        assert_eq!(display.fb.get(2, 0), 1);
+
+        // only the selected planes are scrolled and cleared
+        display.planes = 2;
+        display.scroll_down(1);
+        assert_eq!((display.fb.get(1, 0), display.fb.get(1, 1)), (1, 2));
+        display.clear();
+        assert_eq!((display.fb.get(1, 0), display.fb.get(1, 1)), (1, 0));
+        display.planes = ALL_PLANES;
+        display.clear();
+        assert_eq!(display.fb().iter_set_pixels().count(), 0);
+    }
+
+    #[test]
+    fn test_dirty_rows() {
+        let mut display = Display::new();
+        assert_eq!(display.is_dirty(), false);
+
+        display.toggle(0, 3, 1);
+        display.toggle(5, 3, 1);
+        display.toggle(0, SCREEN_HEIGHT - 1, 1);
+        assert_eq!(
+            display.dirty_rows().collect::<Vec<_>>(),
+            [3, SCREEN_HEIGHT - 1]
+        );
+
+        display.fb();
+        assert_eq!(display.is_dirty(), false);
+
+        display.clear();
+        assert_eq!(display.dirty_rows().count(), SCREEN_HEIGHT);
     }
 }
@@ -0,0 +1,88 @@
+use crate::{Chip8, Frame, FrameReport, Key, Player};
+
+/// What frontends need from an emulator, so they can be written once and drive any core
+pub trait EmulatorCore {
+    /// Load a program and start running it
+    fn load(&mut self, rom: &[u8]) -> anyhow::Result<()>;
+
+    /// Run a single 60 Hz frame, reporting what happened during it
+    fn run_frame(&mut self) -> anyhow::Result<FrameReport>;
+
+    /// The current contents of the screen
+    fn framebuffer(&mut self) -> Frame;
+
+    /// Press or release a key on a player's keypad
+    fn key_event(&mut self, player: Player, key: Key, down: bool) -> anyhow::Result<()>;
+
+    /// Fill `out` with the mono audio samples at `sample_rate` for the frame that was just run
+    fn audio(&mut self, out: &mut [f32], sample_rate: u32);
+
+    /// Serialize the machine state
+    fn save_state(&self) -> Vec<u8>;
+
+    /// Restore a state produced by `save_state`
+    fn load_state(&mut self, state: &[u8]) -> anyhow::Result<()>;
+}
+
+impl EmulatorCore for Chip8 {
+    fn load(&mut self, rom: &[u8]) -> anyhow::Result<()> {
+        self.load_rom(rom)
+    }
+
+    fn run_frame(&mut self) -> anyhow::Result<FrameReport> {
+        Ok(Chip8::run_frame(self)?)
+    }
+
+    fn framebuffer(&mut self) -> Frame {
+        self.fb()
+    }
+
+    fn key_event(&mut self, player: Player, key: Key, down: bool) -> anyhow::Result<()> {
+        if down {
+            self.player_keydown(player, key)
+        } else {
+            self.player_keyup(player, key)
+        }
+    }
+
+    fn audio(&mut self, out: &mut [f32], sample_rate: u32) {
+        self.render_audio(out, sample_rate);
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        Chip8::save_state(self)
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> anyhow::Result<()> {
+        Chip8::load_state(self, state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EmulatorCore;
+    use crate::{Chip8, Key, Player};
+
+    #[test]
+    fn test_emulator_core() {
+        let mut core: Box<dyn EmulatorCore> = Box::new(Chip8::new().unwrap());
+        // wait for a key, then draw the font sprite for it
+        core.load(&[0xF0, 0x0A, 0xF0, 0x29, 0xD0, 0x05, 0x12, 0x06])
+            .unwrap();
+        let state = core.save_state();
+
+        core.key_event(Player::One, Key::from_value(0x0), true)
+            .unwrap();
+        core.run_frame().unwrap();
+        core.key_event(Player::One, Key::from_value(0x0), false)
+            .unwrap();
+        core.run_frame().unwrap();
+        assert_eq!(core.framebuffer().get(0, 0), 1);
+
+        core.load_state(&state).unwrap();
+        assert_eq!(core.framebuffer().get(0, 0), 0);
+        let mut out = [1.0; 4];
+        core.audio(&mut out, 44100);
+        assert_eq!(out, [0.0; 4]);
+    }
+}
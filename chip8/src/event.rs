@@ -0,0 +1,21 @@
+use crate::HaltReason;
+
+/// Something noteworthy that happened while the interpreter was running
+/// Events are collected per frame and can be read with `Chip8::events` after calling `cycle`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// The sound timer became non-zero, so the buzzer started playing
+    SoundStarted,
+    /// The sound timer reached zero, so the buzzer stopped playing
+    SoundStopped,
+    /// FX0A captured `key` into register `register`
+    KeyCaptured { key: u8, register: u8 },
+    /// FX0A has waited for the configured number of frames without any key being pressed
+    WaitingForKey,
+    /// The instruction at `pc` wrote to `addr`, which instructions had already been fetched from
+    CodeModified { pc: u16, addr: u16 },
+    /// Execution stopped, and won't continue until `Chip8::resume` is called
+    Halted(HaltReason),
+    /// FX75 couldn't save the flags to the flag store, for the reason given
+    FlagSaveFailed(String),
+}
@@ -0,0 +1,367 @@
+use std::fmt::Display;
+
+use crate::disasm::disassemble;
+use crate::{Chip8, Chip8Error, REGISTER_COUNT};
+
+/// The registers an explained step shows the effect on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Registers {
+    pub pc: u16,
+    pub i: u16,
+    pub sp: u8,
+    pub dt: u8,
+    pub st: u8,
+    pub v: [u8; REGISTER_COUNT],
+}
+
+/// The fields an opcode is split into, named the way CHIP-8 references name them
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Fields {
+    pub x: u8,
+    pub y: u8,
+    pub n: u8,
+    pub nn: u8,
+    pub nnn: u16,
+}
+
+/// Everything about a single step, for showing how an interpreter works
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Explanation {
+    /// The address the instruction was fetched from
+    pub addr: u16,
+    /// The two bytes fetched
+    pub bytes: [u8; 2],
+    pub fields: Fields,
+    pub mnemonic: String,
+    /// What the instruction does, in plain English
+    pub description: String,
+    pub before: Registers,
+    pub after: Registers,
+}
+
+impl Explanation {
+    /// Describe every register the step changed, such as `V0: 0x00 -> 0x2a`
+    pub fn changes(&self) -> Vec<String> {
+        let (before, after) = (&self.before, &self.after);
+        let mut changes = Vec::new();
+        for (x, (before, after)) in before.v.iter().zip(after.v).enumerate() {
+            if *before != after {
+                changes.push(format!("V{:X}: {:#04x} -> {:#04x}", x, before, after));
+            }
+        }
+        let wide = [("PC", before.pc, after.pc), ("I", before.i, after.i)];
+        for (name, before, after) in wide {
+            if before != after {
+                changes.push(format!("{}: {:#06x} -> {:#06x}", name, before, after));
+            }
+        }
+        let narrow = [
+            ("SP", before.sp, after.sp),
+            ("DT", before.dt, after.dt),
+            ("ST", before.st, after.st),
+        ];
+        for (name, before, after) in narrow {
+            if before != after {
+                changes.push(format!("{}: {} -> {}", name, before, after));
+            }
+        }
+        changes
+    }
+}
+
+impl Display for Explanation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Fields { x, y, n, nn, nnn } = self.fields;
+        writeln!(
+            f,
+            "{:#06x}: {:02x} {:02x}  {}",
+            self.addr, self.bytes[0], self.bytes[1], self.mnemonic
+        )?;
+        writeln!(
+            f,
+            "  x={:X} y={:X} n={:X} nn={:02X} nnn={:03X}",
+            x, y, n, nn, nnn
+        )?;
+        writeln!(f, "  {}", self.description)?;
+        for change in self.changes() {
+            writeln!(f, "  {}", change)?;
+        }
+        Ok(())
+    }
+}
+
+impl Chip8 {
+    fn registers_snapshot(&self) -> Registers {
+        Registers {
+            pc: self.pc,
+            i: self.i,
+            sp: self.sp,
+            dt: self.dt,
+            st: self.st,
+            v: self.v,
+        }
+    }
+
+    /// Run one instruction like `step`, returning an explanation of what it did, or `None` if
+    /// execution has halted
+    pub fn step_explained(&mut self) -> Result<Option<Explanation>, Chip8Error> {
+        if self.halted.is_some() {
+            return Ok(None);
+        }
+
+        let before = self.registers_snapshot();
+        let opcode = self.next_opcode();
+        let fields = Fields {
+            x: ((opcode >> 8) & 0xF) as u8,
+            y: ((opcode >> 4) & 0xF) as u8,
+            n: (opcode & 0xF) as u8,
+            nn: (opcode & 0xFF) as u8,
+            nnn: opcode & 0xFFF,
+        };
+        let mnemonic = disassemble(opcode).unwrap_or_else(|| format!("DW {:#06x}", opcode));
+        let description = self.describe(opcode, &fields);
+        self.step()?;
+
+        Ok(Some(Explanation {
+            addr: before.pc,
+            bytes: opcode.to_be_bytes(),
+            fields,
+            mnemonic,
+            description,
+            before,
+            after: self.registers_snapshot(),
+        }))
+    }
+
+    /// Describe what `opcode` does under the current quirks
+    fn describe(&self, opcode: u16, fields: &Fields) -> String {
+        let Fields { x, y, n, nn, nnn } = *fields;
+        match (opcode >> 12, n, nn) {
+            (0x0, _, 0xE0) if nnn == 0x0E0 => "Clear the screen".to_string(),
+            #[cfg(feature = "two_page")]
+            (0x0, _, 0x30) if nnn == 0x230 && self.config.two_page => {
+                "Clear the screen".to_string()
+            }
+            (0x0, _, 0xEE) if nnn == 0x0EE => {
+                "Return from the current subroutine to the address on top of the stack".to_string()
+            }
+            (0x0, _, 0xA0) if nnn == 0x2A0 && self.config.chip8x => {
+                "Step the background colour on to the next of blue, black, green and red"
+                    .to_string()
+            }
+            (0x0, _, _) if nnn & 0xFF0 == 0x0C0 => {
+                format!("Scroll the screen down {} rows", n)
+            }
+            (0x0, _, _) if nnn & 0xFF0 == 0x0D0 => {
+                format!("Scroll the screen up {} rows", n)
+            }
+            (0x0, _, 0xFB) if nnn == 0x0FB => "Scroll the screen right 4 columns".to_string(),
+            (0x0, _, 0xFC) if nnn == 0x0FC => "Scroll the screen left 4 columns".to_string(),
+            (0x0, _, 0xFD) if nnn == 0x0FD => {
+                "Exit the program, halting the interpreter".to_string()
+            }
+            (0x0, _, 0xFE) if nnn == 0x0FE => {
+                "Switch to the 64x32 low resolution screen, clearing it".to_string()
+            }
+            (0x0, _, 0xFF) if nnn == 0x0FF => {
+                "Switch to the 128x64 high resolution screen, clearing it".to_string()
+            }
+            #[cfg(feature = "two_page")]
+            (0x1, _, _) if nnn == 0x260 && self.pc == 0x200 && self.config.two_page => format!(
+                "Switch to the 64x64 screen and run the program from {:#05x}",
+                crate::TWO_PAGE_ENTRY
+            ),
+            (0x1, _, _) => format!("Jump to {:#05x}", nnn),
+            (0x2, _, _) => format!(
+                "Call the subroutine at {:#05x}, pushing the address of the next instruction",
+                nnn
+            ),
+            (0x3, _, _) => format!("Skip the next instruction if V{:X} equals {:#04x}", x, nn),
+            (0x4, _, _) => format!(
+                "Skip the next instruction if V{:X} doesn't equal {:#04x}",
+                x, nn
+            ),
+            (0x5, 0x2, _) => format!(
+                "Store V{:X} through V{:X} in memory starting at I, leaving I unchanged",
+                x, y
+            ),
+            (0x5, 0x3, _) => format!(
+                "Load V{:X} through V{:X} from memory starting at I, leaving I unchanged",
+                x, y
+            ),
+            (0x5, 0x1, _) if self.config.chip8x => format!(
+                "Add each nibble of V{:X} to the same nibble of V{:X} as three bit numbers",
+                y, x
+            ),
+            (0x5, _, _) => format!("Skip the next instruction if V{:X} equals V{:X}", x, y),
+            (0x6, _, _) => format!("Set V{:X} to {:#04x}", x, nn),
+            (0x7, _, _) => format!(
+                "Add {:#04x} to V{:X}, wrapping around without touching VF",
+                nn, x
+            ),
+            (0x8, 0x0, _) => format!("Set V{:X} to V{:X}", x, y),
+            (0x8, 0x1, _) => format!("Set V{:X} to V{:X} OR V{:X}", x, x, y),
+            (0x8, 0x2, _) => format!("Set V{:X} to V{:X} AND V{:X}", x, x, y),
+            (0x8, 0x3, _) => format!("Set V{:X} to V{:X} XOR V{:X}", x, x, y),
+            (0x8, 0x4, _) => format!(
+                "Add V{:X} to V{:X}, setting VF to 1 if the sum overflowed and 0 otherwise",
+                y, x
+            ),
+            (0x8, 0x5, _) => format!(
+                "Subtract V{:X} from V{:X}, setting VF to 0 if it borrowed and 1 otherwise",
+                y, x
+            ),
+            (0x8, 0x7, _) => format!(
+                "Set V{:X} to V{:X} minus V{:X}, setting VF to 0 if it borrowed and 1 otherwise",
+                x, y, x
+            ),
+            (0x8, 0x6 | 0xE, _) => {
+                let direction = if n == 0x6 { "right" } else { "left" };
+                if self.config.quirks.legacy_shift {
+                    format!(
+                        "Set V{:X} to V{:X} shifted {} by one, setting VF to the bit shifted out",
+                        x, y, direction
+                    )
+                } else {
+                    format!(
+                        "Shift V{:X} {} by one, setting VF to the bit shifted out",
+                        x, direction
+                    )
+                }
+            }
+            (0x9, _, _) => format!(
+                "Skip the next instruction if V{:X} doesn't equal V{:X}",
+                x, y
+            ),
+            (0xA, _, _) => format!("Set I to {:#05x}", nnn),
+            (0xB, 0, _) if self.config.chip8x => format!(
+                "Set the colour of the 8x4 zones given by V{:X} and V{:X} to the colour in V{:X}",
+                x,
+                (x + 1) & 0xF,
+                y
+            ),
+            (0xB, _, _) if self.config.chip8x => format!(
+                "Set the colour of {} rows of the column at V{:X} and V{:X} to the colour in V{:X}",
+                n,
+                x,
+                y,
+                (x + 1) & 0xF
+            ),
+            (0xB, _, _) if self.config.quirks.jump_add_offset => {
+                format!("Jump to {:#05x} plus V{:X}", nnn, x)
+            }
+            (0xB, _, _) => format!("Jump to {:#05x} plus V0", nnn),
+            (0xC, _, _) => format!("Set V{:X} to a random number ANDed with {:#04x}", x, nn),
+            (0xD, 0, _) if cfg!(feature = "schip") && self.is_hires() => format!(
+                "Draw the 16x16 sprite at I to the coordinates in V{:X} and V{:X}, setting VF to \
+                 1 if any pixel was turned off and 0 otherwise",
+                x, y
+            ),
+            (0xD, _, _) => format!(
+                "Draw the {} byte sprite at I to the coordinates in V{:X} and V{:X}, setting VF \
+                 to 1 if any pixel was turned off and 0 otherwise",
+                n, x, y
+            ),
+            (0xE, _, 0x9E) => format!("Skip the next instruction if the key in V{:X} is down", x),
+            (0xE, _, 0xA1) => format!("Skip the next instruction if the key in V{:X} is up", x),
+            (0xE, _, 0xF2) if self.config.chip8x => format!(
+                "Skip the next instruction if the key in V{:X} is down on the second keypad",
+                x
+            ),
+            (0xE, _, 0xF5) if self.config.chip8x => format!(
+                "Skip the next instruction if the key in V{:X} is up on the second keypad",
+                x
+            ),
+            (0xF, _, 0x00) if opcode == 0xF000 => {
+                let pc = self.pc as usize + 2;
+                let nnnn = u16::from_be_bytes([
+                    self.memory.data[pc % self.memory.size()],
+                    self.memory.data[(pc + 1) % self.memory.size()],
+                ]);
+                format!(
+                    "Set I to {:#06x}, the address in the word after the instruction",
+                    nnnn
+                )
+            }
+            (0xF, _, 0x01) => format!(
+                "Select the display planes in the mask {:#04b} for drawing, clearing and scrolling",
+                x & 0b11
+            ),
+            (0xF, _, 0x02) if x == 0 => {
+                "Load the 16 byte audio pattern at I into the pattern buffer".to_string()
+            }
+            (0xF, _, 0x07) => format!("Set V{:X} to the delay timer", x),
+            (0xF, _, 0x0A) => format!(
+                "Wait for a key to be pressed and released, then store it in V{:X}",
+                x
+            ),
+            (0xF, _, 0x15) => format!("Set the delay timer to V{:X}", x),
+            (0xF, _, 0x18) => format!("Set the sound timer to V{:X}", x),
+            (0xF, _, 0x1E) => format!("Add V{:X} to I", x),
+            (0xF, _, 0x29) => format!("Point I at the font sprite for the hex digit in V{:X}", x),
+            (0xF, _, 0x30) => format!(
+                "Point I at the big font sprite for the hex digit in V{:X}",
+                x
+            ),
+            (0xF, _, 0x33) => format!(
+                "Store the hundreds, tens and ones digits of V{:X} at I, I+1 and I+2",
+                x
+            ),
+            (0xF, _, 0x3A) => format!("Set the audio pitch to V{:X}", x),
+            (0xF, _, 0x55) => format!(
+                "Store V0 through V{:X} in memory starting at I{}",
+                x,
+                self.increment_note()
+            ),
+            (0xF, _, 0x65) => format!(
+                "Load V0 through V{:X} from memory starting at I{}",
+                x,
+                self.increment_note()
+            ),
+            (0xF, _, 0x75) => format!("Save V0 through V{:X} in the RPL user flags", x),
+            (0xF, _, 0x85) => format!("Load V0 through V{:X} from the RPL user flags", x),
+            _ => "Not a valid instruction".to_string(),
+        }
+    }
+
+    fn increment_note(&self) -> &'static str {
+        if self.config.quirks.memory_increment_i {
+            ", leaving I just past the last one"
+        } else {
+            ", leaving I unchanged"
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Chip8;
+
+    #[test]
+    fn test_step_explained() {
+        let mut chip8 = Chip8::new().unwrap();
+        chip8.load_rom(&[0x60, 0x2A, 0x80, 0x06]).unwrap();
+
+        let explanation = chip8.step_explained().unwrap().unwrap();
+        assert_eq!(explanation.addr, 0x200);
+        assert_eq!(explanation.bytes, [0x60, 0x2A]);
+        assert_eq!(explanation.fields.nn, 0x2A);
+        assert_eq!(explanation.mnemonic, "LD V0, 0x2a");
+        assert_eq!(explanation.description, "Set V0 to 0x2a");
+        assert_eq!(
+            explanation.changes(),
+            ["V0: 0x00 -> 0x2a", "PC: 0x0200 -> 0x0202"]
+        );
+
+        let explanation = chip8.step_explained().unwrap().unwrap();
+        assert_eq!(
+            explanation.description,
+            "Shift V0 right by one, setting VF to the bit shifted out"
+        );
+        assert_eq!(
+            explanation.to_string(),
+            "0x0202: 80 06  SHR V0, V0\n  x=0 y=0 n=6 nn=06 nnn=006\n  Shift V0 right by one, \
+             setting VF to the bit shifted out\n  V0: 0x2a -> 0x15\n  PC: 0x0202 -> 0x0204\n"
+        );
+    }
+}
@@ -0,0 +1,83 @@
+#[cfg(feature = "std")]
+use std::path::PathBuf;
+
+#[cfg(feature = "std")]
+use anyhow::{ensure, Context};
+
+#[cfg(feature = "std")]
+use crate::config_dir;
+
+/// The number of RPL user flags FX75 and FX85 save and load. SUPER-CHIP had 8 and XO-CHIP has 16.
+pub const FLAG_COUNT: usize = 16;
+
+/// Keeps the RPL user flags between runs of a ROM, the way the HP-48 kept them in its memory
+pub trait FlagStore {
+    /// Return the flags saved last time, or `None` if none have been saved
+    fn load(&self) -> anyhow::Result<Option<[u8; FLAG_COUNT]>>;
+    /// Save `flags`, replacing whatever was saved before
+    fn save(&mut self, flags: &[u8; FLAG_COUNT]) -> anyhow::Result<()>;
+}
+
+#[cfg(feature = "std")]
+/// Keeps the flags in a file, which is created along with its directory on the first save
+pub struct FsFlagStore {
+    path: PathBuf,
+}
+
+#[cfg(feature = "std")]
+impl FsFlagStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// The store for the ROM with the SHA-1 `hash`, in the directory every frontend shares
+    pub fn for_rom(hash: &str) -> Option<Self> {
+        config_dir().map(|dir| Self::new(dir.join("flags").join(format!("{}.rpl", hash))))
+    }
+}
+
+#[cfg(feature = "std")]
+impl FlagStore for FsFlagStore {
+    fn load(&self) -> anyhow::Result<Option<[u8; FLAG_COUNT]>> {
+        if !self.path.exists() {
+            return Ok(None);
+        }
+        let bytes = std::fs::read(&self.path).context("read flags file")?;
+        ensure!(
+            bytes.len() == FLAG_COUNT,
+            "flags file is {} bytes (expected {})",
+            bytes.len(),
+            FLAG_COUNT
+        );
+        Ok(Some(bytes.try_into().unwrap()))
+    }
+
+    fn save(&mut self, flags: &[u8; FLAG_COUNT]) -> anyhow::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir).context("create flags directory")?;
+        }
+        std::fs::write(&self.path, flags).context("write flags file")
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{FlagStore, FsFlagStore, FLAG_COUNT};
+
+    #[test]
+    fn test_fs_flag_store() {
+        let dir = std::env::temp_dir().join(format!("chipper-flags-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut store = FsFlagStore::new(dir.join("rom.rpl"));
+        assert_eq!(store.load().unwrap(), None);
+
+        let mut flags = [0; FLAG_COUNT];
+        flags[..3].copy_from_slice(&[1, 2, 3]);
+        store.save(&flags).unwrap();
+        assert_eq!(store.load().unwrap(), Some(flags));
+
+        std::fs::write(dir.join("rom.rpl"), [1, 2]).unwrap();
+        assert!(store.load().is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
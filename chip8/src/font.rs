@@ -0,0 +1,146 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use anyhow::bail;
+
+use crate::{FONT_CHAR_LENGTH, FONT_DATA};
+
+/// The hex digit glyphs of the COSMAC VIP interpreter, which drew its 7 without a diagonal
+#[rustfmt::skip]
+const VIP_FONT: [u8; FONT_CHAR_LENGTH * 0x10] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x60, 0x20, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0xA0, 0xA0, 0xF0, 0x20, 0x20, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x10, 0x10, 0x10, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xF0, 0x50, 0x70, 0x50, 0xF0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xF0, 0x50, 0x50, 0x50, 0xF0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+/// The three pixel wide hex digit glyphs of the DREAM 6800's CHIPOS
+#[rustfmt::skip]
+const DREAM_6800_FONT: [u8; FONT_CHAR_LENGTH * 0x10] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+    0x40, 0x40, 0x40, 0x40, 0x40, // 1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+    0x80, 0xA0, 0xA0, 0xE0, 0x20, // 4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+    0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0xC0, 0xA0, 0xE0, 0xA0, 0xC0, // B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+    0xC0, 0xA0, 0xA0, 0xA0, 0xC0, // D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+];
+
+/// The three pixel wide hex digit glyphs of the ETI-660, with lowercase b and d
+#[rustfmt::skip]
+const ETI_660_FONT: [u8; FONT_CHAR_LENGTH * 0x10] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+    0x20, 0x20, 0x20, 0x20, 0x20, // 1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+    0xA0, 0xA0, 0xE0, 0x20, 0x20, // 4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+    0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0x80, 0x80, 0xE0, 0xA0, 0xE0, // B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+    0x20, 0x20, 0xE0, 0xA0, 0xE0, // D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+];
+
+/// The small fonts of the historical interpreters, which programs that read the font directly or
+/// line digits up against their own sprites may depend on
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FontSet {
+    Vip,
+    Dream6800,
+    Eti660,
+    /// SUPER-CHIP's font, which most modern interpreters use and is the default
+    #[default]
+    Schip,
+}
+
+impl FontSet {
+    /// The glyphs of the hex digits 0-F
+    pub fn data(&self) -> &'static [u8; FONT_CHAR_LENGTH * 0x10] {
+        match self {
+            FontSet::Vip => &VIP_FONT,
+            FontSet::Dream6800 => &DREAM_6800_FONT,
+            FontSet::Eti660 => &ETI_660_FONT,
+            FontSet::Schip => &FONT_DATA,
+        }
+    }
+}
+
+impl FromStr for FontSet {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "vip" => Ok(FontSet::Vip),
+            "dream6800" => Ok(FontSet::Dream6800),
+            "eti660" => Ok(FontSet::Eti660),
+            "schip" => Ok(FontSet::Schip),
+            _ => bail!(
+                "unknown font set '{}' (expected vip, dream6800, eti660 or schip)",
+                s
+            ),
+        }
+    }
+}
+
+impl Display for FontSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            FontSet::Vip => "vip",
+            FontSet::Dream6800 => "dream6800",
+            FontSet::Eti660 => "eti660",
+            FontSet::Schip => "schip",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FontSet;
+    use crate::{Chip8, FONT_CHAR_LENGTH};
+
+    #[test]
+    fn test_font_set() {
+        for set in [
+            FontSet::Vip,
+            FontSet::Dream6800,
+            FontSet::Eti660,
+            FontSet::Schip,
+        ] {
+            assert_eq!(set.to_string().parse::<FontSet>().unwrap(), set);
+        }
+        assert!("chip48".parse::<FontSet>().is_err());
+
+        let chip8 = Chip8::new().unwrap().font_set(FontSet::Dream6800);
+        let one = chip8.font_range().start + FONT_CHAR_LENGTH;
+        assert_eq!(chip8.memory.data[one..one + FONT_CHAR_LENGTH], [0x40; 5]);
+    }
+}
@@ -0,0 +1,252 @@
+use anyhow::{bail, ensure, Context};
+
+/// The largest code the LZW compression in GIFs uses, which is 12 bits
+const MAX_CODES: usize = 1 << 12;
+
+/// Decode the frames of a GIF into the colour index of each of their pixels, row by row. The
+/// colours themselves are left out, as only the indices carry the data hidden in Octo cartridges.
+pub(crate) fn decode_frames(bytes: &[u8]) -> anyhow::Result<Vec<Vec<u8>>> {
+    let mut reader = Reader { bytes, offset: 0 };
+    let magic = reader.take(6).context("read gif header")?;
+    ensure!(magic == b"GIF87a" || magic == b"GIF89a", "not a gif");
+    reader.take(4).context("read gif screen size")?;
+    let flags = reader.byte()?;
+    reader.take(2)?;
+    skip_colour_table(&mut reader, flags)?;
+
+    let mut frames = Vec::new();
+    loop {
+        match reader.byte().context("gif ends without a trailer")? {
+            // an extension, such as the frame delays, which have nothing to decode
+            0x21 => {
+                reader.byte()?;
+                read_sub_blocks(&mut reader)?;
+            }
+            0x2C => {
+                reader.take(4)?;
+                let width = reader.word()? as usize;
+                let height = reader.word()? as usize;
+                let flags = reader.byte()?;
+                skip_colour_table(&mut reader, flags)?;
+                let min_code_size = reader.byte()?;
+                let data = read_sub_blocks(&mut reader)?;
+                let pixels = decode_lzw(min_code_size, &data, width * height)
+                    .with_context(|| format!("decode gif frame {}", frames.len()))?;
+                let interlaced = flags & 0x40 != 0;
+                frames.push(if interlaced {
+                    deinterlace(&pixels, width, height)
+                } else {
+                    pixels
+                });
+            }
+            0x3B => return Ok(frames),
+            block => bail!("unknown gif block {:#04x}", block),
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> anyhow::Result<&'a [u8]> {
+        let bytes = self
+            .bytes
+            .get(self.offset..self.offset + len)
+            .context("gif is truncated")?;
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> anyhow::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn word(&mut self) -> anyhow::Result<u16> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+}
+
+/// Skip the colour table the packed `flags` of a screen or image descriptor say follows
+fn skip_colour_table(reader: &mut Reader, flags: u8) -> anyhow::Result<()> {
+    if flags & 0x80 != 0 {
+        reader.take(3 << ((flags & 0x07) + 1))?;
+    }
+    Ok(())
+}
+
+/// Join up the chain of length-prefixed sub-blocks that ends with an empty one
+fn read_sub_blocks(reader: &mut Reader) -> anyhow::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    loop {
+        let len = reader.byte()? as usize;
+        if len == 0 {
+            return Ok(data);
+        }
+        data.extend_from_slice(reader.take(len)?);
+    }
+}
+
+/// Decompress the `len` colour indices of a frame
+fn decode_lzw(min_code_size: u8, data: &[u8], len: usize) -> anyhow::Result<Vec<u8>> {
+    ensure!(
+        (1..=8).contains(&min_code_size),
+        "invalid lzw code size {}",
+        min_code_size
+    );
+    let clear = 1usize << min_code_size;
+    let end = clear + 1;
+    let reset = |table: &mut Vec<Vec<u8>>| {
+        table.clear();
+        table.extend((0..clear).map(|index| vec![index as u8]));
+        // the clear and end codes take up two entries
+        table.extend([Vec::new(), Vec::new()]);
+    };
+    let mut table = Vec::with_capacity(MAX_CODES);
+    reset(&mut table);
+    let mut code_size = min_code_size as usize + 1;
+    let mut previous: Option<usize> = None;
+    let mut pixels = Vec::with_capacity(len);
+    let mut bit = 0;
+
+    while pixels.len() < len && bit + code_size <= data.len() * 8 {
+        // codes are packed starting from the least significant bit
+        let code = (0..code_size).fold(0, |code, n| {
+            let at = bit + n;
+            code | (((data[at / 8] >> (at % 8)) & 1) as usize) << n
+        });
+        bit += code_size;
+
+        if code == clear {
+            reset(&mut table);
+            code_size = min_code_size as usize + 1;
+            previous = None;
+            continue;
+        }
+        if code == end {
+            break;
+        }
+        let entry = match (table.get(code), previous) {
+            (Some(entry), _) => entry.clone(),
+            // the one code that can be used before it's added is the previous one extended by
+            // its own first index
+            (None, Some(previous)) if code == table.len() => {
+                let mut entry = table[previous].clone();
+                entry.push(entry[0]);
+                entry
+            }
+            _ => bail!("invalid lzw code {}", code),
+        };
+        pixels.extend_from_slice(&entry);
+        if let Some(previous) = previous {
+            if table.len() < MAX_CODES {
+                let mut added = table[previous].clone();
+                added.push(entry[0]);
+                table.push(added);
+                if table.len() == 1 << code_size && code_size < 12 {
+                    code_size += 1;
+                }
+            }
+        }
+        previous = Some(code);
+    }
+    ensure!(pixels.len() >= len, "frame is missing pixels");
+    pixels.truncate(len);
+    Ok(pixels)
+}
+
+/// Put the rows of an interlaced frame back in order. They're stored every eighth row from the
+/// first, every eighth from the fifth, every fourth from the third, then every other from the
+/// second.
+fn deinterlace(pixels: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let rows = (0..height)
+        .step_by(8)
+        .chain((4..height).step_by(8))
+        .chain((2..height).step_by(4))
+        .chain((1..height).step_by(2));
+    let mut ordered = vec![0; pixels.len()];
+    for (stored, row) in rows.enumerate() {
+        ordered[row * width..(row + 1) * width]
+            .copy_from_slice(&pixels[stored * width..(stored + 1) * width]);
+    }
+    ordered
+}
+
+/// Encode frames of colour indices as a GIF, as simply as possible, for the tests to build
+/// cartridges with
+#[cfg(test)]
+pub(crate) fn encode_frames(width: u16, height: u16, frames: &[Vec<u8>]) -> Vec<u8> {
+    let mut gif = b"GIF89a".to_vec();
+    gif.extend_from_slice(&width.to_le_bytes());
+    gif.extend_from_slice(&height.to_le_bytes());
+    // a global colour table of 256 greys
+    gif.extend_from_slice(&[0x87, 0, 0]);
+    gif.extend((0..=255).flat_map(|n| [n; 3]));
+    for frame in frames {
+        gif.push(0x2C);
+        gif.extend_from_slice(&[0, 0, 0, 0]);
+        gif.extend_from_slice(&width.to_le_bytes());
+        gif.extend_from_slice(&height.to_le_bytes());
+        gif.push(0);
+        gif.push(8);
+
+        // every index is written as it is, with a clear code often enough that the table never
+        // grows past 9 bit codes
+        let mut codes = vec![256];
+        for chunk in frame.chunks(250) {
+            codes.extend(chunk.iter().map(|index| *index as u32));
+            codes.push(256);
+        }
+        codes.push(257);
+        let mut data = vec![0u8; (codes.len() * 9).div_ceil(8)];
+        for (n, code) in codes.iter().enumerate() {
+            for bit in 0..9 {
+                let at = n * 9 + bit;
+                data[at / 8] |= (((code >> bit) & 1) as u8) << (at % 8);
+            }
+        }
+        for block in data.chunks(255) {
+            gif.push(block.len() as u8);
+            gif.extend_from_slice(block);
+        }
+        gif.push(0);
+    }
+    gif.push(0x3B);
+    gif
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_frames, decode_lzw, deinterlace, encode_frames};
+
+    #[test]
+    fn test_decode_frames() {
+        let frames = [(0..=255).collect(), vec![7; 256], (0..=255).rev().collect()];
+        let gif = encode_frames(32, 8, &frames);
+        assert_eq!(decode_frames(&gif).unwrap(), frames);
+
+        assert!(decode_frames(b"GIF89a").is_err());
+        assert!(decode_frames(&gif[..gif.len() - 1]).is_err());
+        assert!(decode_frames(b"\x89PNG\r\n").is_err());
+    }
+
+    #[test]
+    fn test_decode_lzw() {
+        // 1 then the code for the entry about to be added, 1 1, which is the tricky case
+        assert_eq!(
+            decode_lzw(2, &[0x8C, 0x25, 0x05], 5).unwrap(),
+            [1, 1, 1, 2, 2]
+        );
+        assert!(decode_lzw(2, &[0x3C], 1).is_err());
+
+        let rows: Vec<u8> = [0, 4, 2, 6, 1, 3, 5, 7]
+            .iter()
+            .flat_map(|row| [*row; 2])
+            .collect();
+        let ordered: Vec<u8> = (0..8).flat_map(|row| [row; 2]).collect();
+        assert_eq!(deinterlace(&rows, 2, 8), ordered);
+    }
+}
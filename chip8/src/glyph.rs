@@ -0,0 +1,44 @@
+use crate::{FONT_CHAR_LENGTH, FONT_DATA};
+
+/// The rows of the 4x5 glyph for `c`, in the high nibble of each byte like the CHIP-8 font, which
+/// the hex digits are taken from. Lowercase letters are drawn as uppercase, and characters without
+/// a glyph are left blank.
+pub fn glyph(c: char) -> [u8; FONT_CHAR_LENGTH] {
+    let c = c.to_ascii_uppercase();
+    if let Some(digit) = c.to_digit(16) {
+        let start = digit as usize * FONT_CHAR_LENGTH;
+        return FONT_DATA[start..start + FONT_CHAR_LENGTH]
+            .try_into()
+            .unwrap();
+    }
+    match c {
+        'G' => [0xF0, 0x80, 0xB0, 0x90, 0xF0],
+        'H' => [0x90, 0x90, 0xF0, 0x90, 0x90],
+        'I' => [0x70, 0x20, 0x20, 0x20, 0x70],
+        'J' => [0x10, 0x10, 0x10, 0x90, 0x60],
+        'K' => [0x90, 0xA0, 0xC0, 0xA0, 0x90],
+        'L' => [0x80, 0x80, 0x80, 0x80, 0xF0],
+        'M' => [0x90, 0xF0, 0xF0, 0x90, 0x90],
+        'N' => [0x90, 0xD0, 0xB0, 0x90, 0x90],
+        'O' => [0x60, 0x90, 0x90, 0x90, 0x60],
+        'P' => [0xE0, 0x90, 0xE0, 0x80, 0x80],
+        'Q' => [0x60, 0x90, 0x90, 0xB0, 0x70],
+        'R' => [0xE0, 0x90, 0xE0, 0xA0, 0x90],
+        'S' => [0x70, 0x80, 0x60, 0x10, 0xE0],
+        'T' => [0xF0, 0x40, 0x40, 0x40, 0x40],
+        'U' => [0x90, 0x90, 0x90, 0x90, 0x60],
+        'V' => [0x90, 0x90, 0x90, 0x60, 0x60],
+        'W' => [0x90, 0x90, 0xF0, 0xF0, 0x90],
+        'X' => [0x90, 0x90, 0x60, 0x90, 0x90],
+        'Y' => [0x90, 0x90, 0x60, 0x40, 0x40],
+        'Z' => [0xF0, 0x10, 0x60, 0x80, 0xF0],
+        ':' => [0x00, 0x40, 0x00, 0x40, 0x00],
+        ',' => [0x00, 0x00, 0x00, 0x40, 0x80],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x40],
+        '[' => [0x60, 0x40, 0x40, 0x40, 0x60],
+        ']' => [0x60, 0x20, 0x20, 0x20, 0x60],
+        '-' => [0x00, 0x00, 0xF0, 0x00, 0x00],
+        '>' => [0x80, 0x40, 0x20, 0x40, 0x80],
+        _ => [0; FONT_CHAR_LENGTH],
+    }
+}
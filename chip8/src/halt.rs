@@ -0,0 +1,273 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use anyhow::bail;
+
+/// Why the interpreter stopped executing instructions
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HaltReason {
+    /// The instruction at `pc` tried to write to `addr`, which is in the protected region below the
+    /// ROM address
+    ProtectedWrite { pc: u16, addr: u16 },
+    /// The jump at `pc` closes a loop that can never make progress, either by jumping to itself or
+    /// by going around without any I/O or change of state
+    IdleLoop { pc: u16 },
+    /// The program ended itself with the SUPER-CHIP exit instruction 00FD at `pc`
+    Exited { pc: u16 },
+    /// The call at `pc` went deeper than the stack depth
+    StackOverflow { pc: u16 },
+    /// The return at `pc` ran with no subroutine to return from
+    StackUnderflow { pc: u16 },
+    /// `opcode` at `pc` isn't an instruction and no custom handler took it
+    InvalidOpcode { pc: u16, opcode: u16 },
+    /// The instruction at `pc` fetched or accessed memory from `addr` past the end of memory
+    AddressOverflow { pc: u16, addr: u16 },
+}
+
+impl Display for HaltReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HaltReason::ProtectedWrite { pc, addr } => write!(
+                f,
+                "write to protected address {:#06x} by the instruction at {:#06x}",
+                addr, pc
+            ),
+            HaltReason::IdleLoop { pc } => write!(f, "idle loop at {:#06x}", pc),
+            HaltReason::Exited { pc } => write!(f, "program exited at {:#06x}", pc),
+            HaltReason::StackOverflow { pc } => write!(f, "call stack overflow at {:#06x}", pc),
+            HaltReason::StackUnderflow { pc } => {
+                write!(f, "return with an empty call stack at {:#06x}", pc)
+            }
+            HaltReason::InvalidOpcode { pc, opcode } => {
+                write!(f, "invalid opcode {:04X} at {:#06x}", opcode, pc)
+            }
+            HaltReason::AddressOverflow { pc, addr } => write!(
+                f,
+                "access past the end of memory from {:#06x} by the instruction at {:#06x}",
+                addr, pc
+            ),
+        }
+    }
+}
+
+/// Whether execution can carry on after `Chip8::step` or `Chip8::cycle`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StepOutcome {
+    Running,
+    /// Execution has halted, and carries on from the instruction that halted it after `resume`
+    Halted(HaltReason),
+}
+
+/// A fault in the program that `Chip8::step` and `Chip8::cycle` return rather than halting on,
+/// leaving the program counter on the instruction that caused it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Chip8Error {
+    /// `opcode` at `pc` isn't an instruction and no custom handler took it
+    InvalidOpcode { pc: u16, opcode: u16 },
+    /// The instruction at `pc` fetched or accessed memory from `addr` past the end of memory
+    AddressOverflow { pc: u16, addr: u16 },
+}
+
+impl Display for Chip8Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Chip8Error::InvalidOpcode { pc, opcode } => write!(
+                f,
+                "invalid opcode '{:04X}' encountered at {:#04x}{}",
+                opcode,
+                pc,
+                // 0NNN called a machine code routine on the original interpreters
+                if opcode >> 12 == 0 {
+                    " (machine code is not supported)"
+                } else {
+                    ""
+                }
+            ),
+            Chip8Error::AddressOverflow { pc, addr } => write!(
+                f,
+                "access past the end of memory from {:#06x} by the instruction at {:#06x}",
+                addr, pc
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Chip8Error {}
+
+/// What happens when the program runs an opcode that isn't an instruction, once any custom
+/// opcode handlers have had their chance to take it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InvalidOpcodePolicy {
+    /// Return `Chip8Error::InvalidOpcode` from `step`, which suits development where a bad
+    /// opcode is a bug
+    #[default]
+    Error,
+    /// Halt with `HaltReason::InvalidOpcode`, leaving the program counter on the opcode
+    Halt,
+    /// Treat it as a no-op and carry on with the next instruction
+    Skip,
+}
+
+impl FromStr for InvalidOpcodePolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "error" => Ok(InvalidOpcodePolicy::Error),
+            "halt" => Ok(InvalidOpcodePolicy::Halt),
+            "skip" => Ok(InvalidOpcodePolicy::Skip),
+            _ => bail!(
+                "unknown invalid opcode policy '{}' (expected error, halt or skip)",
+                s
+            ),
+        }
+    }
+}
+
+/// What happens when the program counter or an access through I runs past the end of memory
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AddressOverflowPolicy {
+    /// Return `Chip8Error::AddressOverflow` from `step`, which suits development where running
+    /// off the end is a bug
+    #[default]
+    Error,
+    /// Halt with `HaltReason::AddressOverflow`, leaving the program counter on the instruction
+    Halt,
+    /// Wrap around to 0x000 at the end of memory
+    Wrap,
+    /// Keep addresses to 12 bits, as the COSMAC VIP did, wrapping at 0x1000 however much memory
+    /// there is
+    Mask,
+}
+
+impl AddressOverflowPolicy {
+    /// Whether addresses past the end carry on from the start rather than faulting
+    pub fn wraps(&self) -> bool {
+        matches!(
+            self,
+            AddressOverflowPolicy::Wrap | AddressOverflowPolicy::Mask
+        )
+    }
+}
+
+impl FromStr for AddressOverflowPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "error" => Ok(AddressOverflowPolicy::Error),
+            "halt" => Ok(AddressOverflowPolicy::Halt),
+            "wrap" => Ok(AddressOverflowPolicy::Wrap),
+            "mask" => Ok(AddressOverflowPolicy::Mask),
+            _ => bail!(
+                "unknown address overflow policy '{}' (expected error, halt, wrap or mask)",
+                s
+            ),
+        }
+    }
+}
+
+/// The parts of the machine state a loop has to change to be making progress
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) struct LoopState {
+    pub(crate) pc: u16,
+    pub(crate) v: [u8; 16],
+    pub(crate) i: u16,
+    pub(crate) sp: u8,
+}
+
+/// Spots loops that will spin forever, by checking whether anything observable changed between
+/// two passes over the backward jump that closes them
+#[derive(Default)]
+pub(crate) struct IdleDetector {
+    /// The state at the most recent backward jump
+    last: Option<LoopState>,
+    /// Whether an instruction that does I/O or writes memory has run since then
+    io: bool,
+}
+
+impl IdleDetector {
+    /// Observe `opcode` as it's about to run at `state.pc`, returning true if it closes an idle loop
+    pub(crate) fn observe(&mut self, opcode: u16, state: LoopState) -> bool {
+        let nnn = opcode & 0x0FFF;
+        match opcode >> 12 {
+            0x1 if nnn <= state.pc => {
+                let idle = nnn == state.pc || (!self.io && self.last == Some(state));
+                self.last = Some(state);
+                self.io = false;
+                return idle;
+            }
+            // clears, scrolls, resolution switches and the CHIP-8X background colour
+            0x0 if matches!(nnn, 0x0E0 | 0x0FB | 0x0FC | 0x0FE | 0x0FF | 0x230 | 0x2A0)
+                || matches!(nnn >> 4, 0x0C | 0x0D) =>
+            {
+                self.io = true
+            }
+            // CHIP-8X colours the screen with BXYN, and a loop that jumps with BNNN is too rare to
+            // tell apart from it
+            0xB => self.io = true,
+            // register range stores
+            0x5 if opcode & 0xF == 0x2 => self.io = true,
+            0xC..=0xE => self.io = true,
+            // timer reads, key waits, sound, memory writes and flag saves
+            0xF if matches!(opcode & 0xFF, 0x07 | 0x0A | 0x18 | 0x33 | 0x55 | 0x75) => {
+                self.io = true
+            }
+            _ => {}
+        }
+        false
+    }
+
+    pub(crate) fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IdleDetector, LoopState};
+
+    #[test]
+    fn test_idle_detector() {
+        let state = LoopState {
+            pc: 0x204,
+            v: [0; 16],
+            i: 0,
+            sp: 0,
+        };
+        let mut detector = IdleDetector::default();
+        assert_eq!(detector.observe(0x1204, state), true);
+
+        // a loop needs to go around once unchanged before it's known to be idle
+        detector.reset();
+        assert_eq!(detector.observe(0x1200, state), false);
+        assert_eq!(detector.observe(0x1200, state), true);
+
+        // going around with I/O is progress even if the registers stay the same
+        detector.reset();
+        assert_eq!(detector.observe(0x1200, state), false);
+        assert_eq!(detector.observe(0xF007, state), false);
+        assert_eq!(detector.observe(0x1200, state), false);
+
+        let mut changed = state;
+        changed.v[0] = 1;
+        assert_eq!(detector.observe(0x1200, changed), false);
+
+        // scrolling and switching resolution change the screen too
+        for opcode in [
+            0x00C4, 0x00D2, 0x00FB, 0x00FC, 0x00FE, 0x00FF, 0x02A0, 0xB123,
+        ] {
+            detector.reset();
+            assert_eq!(detector.observe(0x1200, state), false);
+            assert_eq!(detector.observe(opcode, state), false);
+            assert_eq!(detector.observe(0x1200, state), false);
+        }
+
+        // forward jumps don't close loops
+        assert_eq!(detector.observe(0x1300, state), false);
+        assert_eq!(detector.observe(0x1300, state), false);
+    }
+}
@@ -0,0 +1,181 @@
+#[cfg(feature = "std")]
+use std::path::Path;
+
+use anyhow::{bail, ensure, Context};
+
+use crate::{rom_hash, ROM_ADDR};
+
+/// A blob loaded into memory at `addr`
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Segment {
+    pub addr: u16,
+    pub data: Vec<u8>,
+}
+
+/// A program made up of several segments loaded at different addresses, and the address
+/// execution starts at
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProgramImage {
+    pub segments: Vec<Segment>,
+    pub entry: u16,
+}
+
+impl ProgramImage {
+    /// An image holding a plain ROM, loaded and started at the ROM address
+    pub fn from_rom(rom: &[u8]) -> Self {
+        Self::from_rom_at(rom, ROM_ADDR as u16)
+    }
+
+    /// An image holding a plain ROM loaded and started at `addr`, such as 0x600 for ETI-660
+    /// programs
+    pub fn from_rom_at(rom: &[u8], addr: u16) -> Self {
+        Self {
+            segments: vec![Segment {
+                addr,
+                data: rom.to_vec(),
+            }],
+            entry: addr,
+        }
+    }
+
+    #[cfg(feature = "std")]
+    /// Read the manifest at `path`, loading the files it lists relative to its directory
+    pub fn from_manifest_file(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path).context("read image manifest")?;
+        let dir = path.parent().unwrap_or(Path::new("."));
+        Self::parse_manifest(&text, |file| {
+            std::fs::read(dir.join(file)).with_context(|| format!("read segment {}", file))
+        })
+        .with_context(|| format!("parse image manifest {}", path.display()))
+    }
+
+    /// Parse a manifest, with one `load ADDR FILE` line per segment and an optional `entry ADDR`
+    /// line, which defaults to the ROM address. Addresses are in hex and `#` starts a comment.
+    /// `read` is called with each file name to get the segment's contents.
+    pub fn parse_manifest(
+        text: &str,
+        mut read: impl FnMut(&str) -> anyhow::Result<Vec<u8>>,
+    ) -> anyhow::Result<Self> {
+        let mut segments: Vec<Segment> = Vec::new();
+        let mut entry = None;
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let context = || format!("line {}", number + 1);
+            let words: Vec<_> = line.split_whitespace().collect();
+            match words[..] {
+                ["entry", addr] => {
+                    ensure!(entry.is_none(), "{}: entry given twice", context());
+                    entry = Some(parse_addr(addr).with_context(context)?);
+                }
+                ["load", addr, file] => {
+                    let addr = parse_addr(addr).with_context(context)?;
+                    let data = read(file).with_context(context)?;
+                    let segment = Segment { addr, data };
+                    if let Some(other) = segments.iter().find(|other| other.overlaps(&segment)) {
+                        bail!(
+                            "{}: segment at {:#06x} overlaps the one at {:#06x}",
+                            context(),
+                            segment.addr,
+                            other.addr
+                        );
+                    }
+                    segments.push(segment);
+                }
+                _ => bail!(
+                    "{}: expected 'load ADDR FILE' or 'entry ADDR', got '{}'",
+                    context(),
+                    line
+                ),
+            }
+        }
+
+        ensure!(!segments.is_empty(), "image has no segments");
+        Ok(Self {
+            segments,
+            entry: entry.unwrap_or(ROM_ADDR as u16),
+        })
+    }
+
+    /// The lowercase hex SHA-1 digest identifying the image, which for an image holding a plain
+    /// ROM is the digest of the ROM
+    pub fn hash(&self) -> String {
+        if self.segments.len() == 1
+            && self.segments[0].addr == ROM_ADDR as u16
+            && self.entry == ROM_ADDR as u16
+        {
+            return rom_hash(&self.segments[0].data);
+        }
+
+        let mut data = self.entry.to_be_bytes().to_vec();
+        for segment in &self.segments {
+            data.extend_from_slice(&segment.addr.to_be_bytes());
+            data.extend_from_slice(&(segment.data.len() as u32).to_be_bytes());
+            data.extend_from_slice(&segment.data);
+        }
+        rom_hash(&data)
+    }
+}
+
+impl Segment {
+    fn end(&self) -> usize {
+        self.addr as usize + self.data.len()
+    }
+
+    fn overlaps(&self, other: &Segment) -> bool {
+        (self.addr as usize) < other.end() && (other.addr as usize) < self.end()
+    }
+}
+
+fn parse_addr(s: &str) -> anyhow::Result<u16> {
+    let digits = s.strip_prefix("0x").unwrap_or(s);
+    u16::from_str_radix(digits, 16).with_context(|| format!("invalid address '{}'", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ProgramImage, Segment};
+    use crate::rom_hash;
+
+    #[test]
+    fn test_parse_manifest() {
+        let text = "# two banks\nentry 0x300\nload 200 main.ch8\nload 0x1000 bank.bin # data\n";
+        let image =
+            ProgramImage::parse_manifest(text, |file| Ok(file.as_bytes().to_vec())).unwrap();
+        assert_eq!(image.entry, 0x300);
+        assert_eq!(
+            image.segments,
+            [
+                Segment {
+                    addr: 0x200,
+                    data: b"main.ch8".to_vec()
+                },
+                Segment {
+                    addr: 0x1000,
+                    data: b"bank.bin".to_vec()
+                },
+            ]
+        );
+
+        let read = |_: &str| Ok(vec![0; 4]);
+        assert!(ProgramImage::parse_manifest("load 200 a\nload 202 b", read).is_err());
+        assert!(ProgramImage::parse_manifest("entry 200", read).is_err());
+        assert!(ProgramImage::parse_manifest("load 200", read).is_err());
+        assert!(ProgramImage::parse_manifest("load xyz a", read).is_err());
+    }
+
+    #[test]
+    fn test_hash() {
+        let rom = [0x12, 0x00];
+        assert_eq!(ProgramImage::from_rom(&rom).hash(), rom_hash(&rom));
+
+        let mut image = ProgramImage::from_rom(&rom);
+        image.entry = 0x202;
+        assert_ne!(image.hash(), rom_hash(&rom));
+    }
+}
@@ -0,0 +1,209 @@
+use std::str::FromStr;
+
+use anyhow::{bail, Context};
+
+use crate::keypad::Keypad;
+
+/// A single step of an input macro
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MacroStep {
+    /// Hold `key` down for `frames` frames, then release it
+    Press { key: u8, frames: u32 },
+    /// Leave the keypad alone for `frames` frames
+    Wait { frames: u32 },
+}
+
+impl MacroStep {
+    fn frames(&self) -> u32 {
+        match self {
+            MacroStep::Press { frames, .. } | MacroStep::Wait { frames } => *frames,
+        }
+    }
+}
+
+/// A scripted sequence of key presses, e.g. "press 5 for 3 frames, wait 10, press A"
+/// Steps are separated by commas or newlines and `#` starts a comment. A press lasts one frame
+/// unless a duration is given, and a wait is always a number of frames. Pressing the same key
+/// twice in a row needs a wait in between for the program to see it released.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InputMacro {
+    pub(crate) steps: Vec<MacroStep>,
+}
+
+impl InputMacro {
+    pub fn steps(&self) -> &[MacroStep] {
+        &self.steps
+    }
+
+    /// The number of frames the macro takes to play
+    pub fn len_frames(&self) -> u32 {
+        self.steps.iter().map(MacroStep::frames).sum()
+    }
+}
+
+impl FromStr for InputMacro {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut steps = Vec::new();
+        for line in s.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            for step in line
+                .split(',')
+                .map(str::trim)
+                .filter(|step| !step.is_empty())
+            {
+                steps.push(parse_step(step).with_context(|| format!("parse step '{}'", step))?);
+            }
+        }
+        Ok(Self { steps })
+    }
+}
+
+fn parse_step(step: &str) -> anyhow::Result<MacroStep> {
+    let lower = step.to_ascii_lowercase();
+    let words: Vec<&str> = lower.split_whitespace().collect();
+    match words.as_slice() {
+        ["press", key] => Ok(MacroStep::Press {
+            key: parse_key(key)?,
+            frames: 1,
+        }),
+        ["press", key, "for", frames] | ["press", key, "for", frames, "frame" | "frames"] => {
+            let frames = parse_frames(frames)?;
+            if frames == 0 {
+                bail!("a press must last at least one frame");
+            }
+            Ok(MacroStep::Press {
+                key: parse_key(key)?,
+                frames,
+            })
+        }
+        ["wait", frames] | ["wait", frames, "frame" | "frames"] => Ok(MacroStep::Wait {
+            frames: parse_frames(frames)?,
+        }),
+        _ => bail!("expected 'press KEY [for N frames]' or 'wait N [frames]'"),
+    }
+}
+
+fn parse_key(key: &str) -> anyhow::Result<u8> {
+    u8::from_str_radix(key, 16)
+        .ok()
+        .filter(|key| *key <= 0xF)
+        .with_context(|| format!("invalid key '{}', expected a hex digit", key))
+}
+
+fn parse_frames(frames: &str) -> anyhow::Result<u32> {
+    frames
+        .parse::<u32>()
+        .with_context(|| format!("invalid frame count '{}'", frames))
+}
+
+/// Drives the keypad through the steps of an input macro, one frame at a time
+pub(crate) struct MacroPlayer {
+    input_macro: InputMacro,
+    /// The index of the next step to start
+    next: usize,
+    /// The step currently being played
+    current: Option<MacroStep>,
+    /// The number of frames left in the current step
+    frames_left: u32,
+}
+
+impl MacroPlayer {
+    pub fn new(input_macro: InputMacro) -> Self {
+        Self {
+            input_macro,
+            next: 0,
+            current: None,
+            frames_left: 0,
+        }
+    }
+
+    /// Apply the macro's key states for the next frame
+    pub fn tick(&mut self, keypad: &mut Keypad) {
+        while self.frames_left == 0 {
+            if let Some(MacroStep::Press { key, .. }) = self.current.take() {
+                keypad.set_key(key, false);
+            }
+            let Some(step) = self.input_macro.steps.get(self.next).copied() else {
+                return;
+            };
+            self.next += 1;
+            if let MacroStep::Press { key, .. } = step {
+                keypad.set_key(key, true);
+            }
+            self.frames_left = step.frames();
+            self.current = Some(step);
+        }
+        self.frames_left -= 1;
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.current.is_none() && self.next >= self.input_macro.steps.len()
+    }
+
+    /// Release any key the macro is holding down
+    pub fn stop(&mut self, keypad: &mut Keypad) {
+        if let Some(MacroStep::Press { key, .. }) = self.current.take() {
+            keypad.set_key(key, false);
+        }
+        self.next = self.input_macro.steps.len();
+        self.frames_left = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InputMacro, MacroPlayer, MacroStep};
+    use crate::keypad::Keypad;
+
+    #[test]
+    fn test_parse() {
+        let input_macro: InputMacro =
+            "press 5 for 3 frames, wait 10\nPress A # confirm\n\nwait 1 frame"
+                .parse()
+                .unwrap();
+        assert_eq!(
+            input_macro.steps(),
+            [
+                MacroStep::Press {
+                    key: 0x5,
+                    frames: 3
+                },
+                MacroStep::Wait { frames: 10 },
+                MacroStep::Press {
+                    key: 0xA,
+                    frames: 1
+                },
+                MacroStep::Wait { frames: 1 },
+            ]
+        );
+        assert_eq!(input_macro.len_frames(), 15);
+
+        assert!("press G".parse::<InputMacro>().is_err());
+        assert!("press 1 for 0 frames".parse::<InputMacro>().is_err());
+        assert!("jump".parse::<InputMacro>().is_err());
+    }
+
+    #[test]
+    fn test_player() {
+        let mut keypad = Keypad::new();
+        let mut player = MacroPlayer::new("press 5 for 2 frames, wait 1, press 6".parse().unwrap());
+
+        let mut frames = Vec::new();
+        while !player.is_finished() {
+            player.tick(&mut keypad);
+            frames.push((keypad.is_key_down(0x5), keypad.is_key_down(0x6)));
+        }
+        assert_eq!(
+            frames,
+            [
+                (true, false),
+                (true, false),
+                (false, false),
+                (false, true),
+                (false, false)
+            ]
+        );
+    }
+}
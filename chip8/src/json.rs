@@ -0,0 +1,226 @@
+use std::collections::BTreeMap;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use anyhow::{bail, ensure, Context};
+
+/// How deeply arrays and objects can nest, which keeps malformed input from overflowing the stack
+const MAX_DEPTH: usize = 64;
+
+/// A JSON value, as read from Octo's options and cartridges and the community CHIP-8 database
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(BTreeMap<String, Value>),
+}
+
+impl Value {
+    pub(crate) fn parse(json: &str) -> anyhow::Result<Self> {
+        let mut chars = json.chars().peekable();
+        let value = parse_value(&mut chars, 0)?;
+        skip_whitespace(&mut chars);
+        ensure!(
+            chars.next().is_none(),
+            "unexpected characters after the value"
+        );
+        Ok(value)
+    }
+
+    pub(crate) fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_object(&self) -> Option<&BTreeMap<String, Value>> {
+        match self {
+            Value::Object(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// The value of `key`, if this is an object that has it
+    pub(crate) fn get(&self, key: &str) -> Option<&Value> {
+        self.as_object().and_then(|values| values.get(key))
+    }
+}
+
+fn parse_value(chars: &mut Peekable<Chars>, depth: usize) -> anyhow::Result<Value> {
+    ensure!(depth < MAX_DEPTH, "nested too deeply");
+    skip_whitespace(chars);
+    match chars.peek() {
+        Some('"') => {
+            chars.next();
+            Ok(Value::String(parse_string(chars)?))
+        }
+        Some('{') => {
+            chars.next();
+            parse_object(chars, depth)
+        }
+        Some('[') => {
+            chars.next();
+            parse_array(chars, depth)
+        }
+        Some(c) if c.is_ascii_alphanumeric() || *c == '-' => {
+            let mut word = String::new();
+            while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || ".+-".contains(*c)) {
+                word.push(c);
+            }
+            match word.as_str() {
+                "true" => Ok(Value::Bool(true)),
+                "false" => Ok(Value::Bool(false)),
+                "null" => Ok(Value::Null),
+                _ => word
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|n| n.is_finite())
+                    .map(Value::Number)
+                    .with_context(|| format!("invalid value '{}'", word)),
+            }
+        }
+        Some(c) => bail!("unexpected '{}'", c),
+        None => bail!("expected a value"),
+    }
+}
+
+/// Parse the rest of an object whose opening brace has been read
+fn parse_object(chars: &mut Peekable<Chars>, depth: usize) -> anyhow::Result<Value> {
+    let mut values = BTreeMap::new();
+    loop {
+        skip_whitespace(chars);
+        match chars.next() {
+            Some('}') if values.is_empty() => break,
+            Some('"') => {}
+            _ => bail!("expected a key"),
+        }
+        let key = parse_string(chars)?;
+        skip_whitespace(chars);
+        ensure!(chars.next() == Some(':'), "expected ':' after '{}'", key);
+        let value = parse_value(chars, depth + 1).with_context(|| format!("in '{}'", key))?;
+        values.insert(key, value);
+
+        skip_whitespace(chars);
+        match chars.next() {
+            Some(',') => {}
+            Some('}') => break,
+            _ => bail!("expected ',' or '}}'"),
+        }
+    }
+    Ok(Value::Object(values))
+}
+
+/// Parse the rest of an array whose opening bracket has been read
+fn parse_array(chars: &mut Peekable<Chars>, depth: usize) -> anyhow::Result<Value> {
+    let mut values = Vec::new();
+    skip_whitespace(chars);
+    if chars.next_if_eq(&']').is_some() {
+        return Ok(Value::Array(values));
+    }
+    loop {
+        values.push(parse_value(chars, depth + 1)?);
+        skip_whitespace(chars);
+        match chars.next() {
+            Some(',') => {}
+            Some(']') => break,
+            _ => bail!("expected ',' or ']'"),
+        }
+    }
+    Ok(Value::Array(values))
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+/// Parse the rest of a string whose opening quote has been read
+fn parse_string(chars: &mut Peekable<Chars>) -> anyhow::Result<String> {
+    let mut string = String::new();
+    loop {
+        match chars.next().context("unterminated string")? {
+            '"' => return Ok(string),
+            '\\' => match chars.next().context("unterminated string")? {
+                'n' => string.push('\n'),
+                't' => string.push('\t'),
+                'r' => string.push('\r'),
+                'b' => string.push('\u{8}'),
+                'f' => string.push('\u{c}'),
+                'u' => {
+                    let unit = parse_unit(chars)?;
+                    // characters outside the basic plane are escaped as a surrogate pair
+                    let c = if (0xD800..0xDC00).contains(&unit) {
+                        ensure!(
+                            chars.next() == Some('\\') && chars.next() == Some('u'),
+                            "unpaired surrogate in string"
+                        );
+                        let low = parse_unit(chars)?;
+                        char::decode_utf16([unit, low]).next().and_then(Result::ok)
+                    } else {
+                        char::from_u32(unit as u32)
+                    };
+                    string.push(c.context("invalid escape in string")?);
+                }
+                c => string.push(c),
+            },
+            c => string.push(c),
+        }
+    }
+}
+
+/// Parse the four hex digits of a `\u` escape
+fn parse_unit(chars: &mut Peekable<Chars>) -> anyhow::Result<u16> {
+    let digits: String = chars.by_ref().take(4).collect();
+    u16::from_str_radix(&digits, 16).with_context(|| format!("invalid escape '\\u{}'", digits))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Value;
+
+    #[test]
+    fn test_parse_json() {
+        let value = Value::parse(
+            r#"{"title": "Pong \u00e9\ud83d\ude00", "roms": [{"tickrate": 15, "platforms": ["a"]}],
+                "empty": {}, "none": [], "flag": false, "missing": null}"#,
+        )
+        .unwrap();
+        assert_eq!(value.get("title").and_then(Value::as_str), Some("Pong é😀"));
+        let Some(Value::Array(roms)) = value.get("roms") else {
+            panic!("roms isn't an array");
+        };
+        assert_eq!(roms[0].get("tickrate"), Some(&Value::Number(15.0)));
+        assert_eq!(value.get("flag"), Some(&Value::Bool(false)));
+        assert_eq!(value.get("missing"), Some(&Value::Null));
+        assert_eq!(value.get("none"), Some(&Value::Array(Vec::new())));
+
+        assert!(Value::parse(r#"{"a": 1"#).is_err());
+        assert!(Value::parse("[1, 2] 3").is_err());
+        assert!(Value::parse("1e999").is_err());
+        assert!(Value::parse(&"[".repeat(100)).is_err());
+    }
+}
@@ -1,6 +1,32 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use anyhow::{bail, Context};
+
+/// The CHIP-8 keys in the 4x4 cluster, row by row
+const CLUSTER_KEYS: [u8; 0x10] = [
+    0x1, 0x2, 0x3, 0xC, //
+    0x4, 0x5, 0x6, 0xD, //
+    0x7, 0x8, 0x9, 0xE, //
+    0xA, 0x0, 0xB, 0xF, //
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Key(Option<usize>);
 
 impl Key {
+    /// The keypad key numbered `value`, which must be in the range 0x0 through 0xF
+    pub fn from_value(value: u8) -> Self {
+        Self((value <= 0xF).then_some(value as usize))
+    }
+
+    /// The number of the keypad key, if the host key maps onto one
+    pub fn value(&self) -> Option<u8> {
+        self.0.map(|key| key as u8)
+    }
+
     pub fn from_scancode(value: u32) -> Self {
         match value {
             18 => Self(Some(0x1)), // 1 -> 1
@@ -46,46 +72,587 @@ impl Key {
     }
 }
 
+/// Host keyboard layouts, used to map key labels from the same physical 4x4 cluster onto the
+/// CHIP-8 keypad regardless of what is printed on the keys
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Layout {
+    Qwerty,
+    Azerty,
+    Qwertz,
+    Dvorak,
+    Colemak,
+    /// The numeric keypad rather than the main cluster, with the digits mapped onto themselves
+    /// and the operator keys onto A-F
+    Numpad,
+}
+
+impl Layout {
+    /// The labels of the keys in the physical 1-4/Z-V cluster, row by row
+    fn cluster_labels(&self) -> [&'static str; 0x10] {
+        match self {
+            Layout::Numpad => [
+                "1", "2", "3", "+", "4", "5", "6", "-", "7", "8", "9", "*", ".", "0", "enter", "/",
+            ],
+            Layout::Qwerty => [
+                "1", "2", "3", "4", "q", "w", "e", "r", "a", "s", "d", "f", "z", "x", "c", "v",
+            ],
+            Layout::Azerty => [
+                "&", "é", "\"", "'", "a", "z", "e", "r", "q", "s", "d", "f", "w", "x", "c", "v",
+            ],
+            Layout::Qwertz => [
+                "1", "2", "3", "4", "q", "w", "e", "r", "a", "s", "d", "f", "y", "x", "c", "v",
+            ],
+            Layout::Dvorak => [
+                "1", "2", "3", "4", "'", ",", ".", "p", "a", "o", "e", "u", ";", "q", "j", "k",
+            ],
+            Layout::Colemak => [
+                "1", "2", "3", "4", "q", "w", "f", "p", "a", "r", "s", "t", "z", "x", "c", "d",
+            ],
+        }
+    }
+
+    #[cfg(feature = "std")]
+    /// Guess the host layout from the environment, falling back to QWERTY
+    /// XKB settings are checked first, then the locale is used as a rough hint
+    pub fn detect() -> Self {
+        let var = |name: &str| std::env::var(name).unwrap_or_default().to_ascii_lowercase();
+
+        let variant = var("XKB_DEFAULT_VARIANT");
+        if variant.contains("dvorak") {
+            return Layout::Dvorak;
+        }
+        if variant.contains("colemak") {
+            return Layout::Colemak;
+        }
+
+        let mut region = var("XKB_DEFAULT_LAYOUT");
+        if region.is_empty() {
+            region = ["LC_ALL", "LC_MESSAGES", "LANG"]
+                .into_iter()
+                .map(var)
+                .find(|value| !value.is_empty())
+                .unwrap_or_default();
+        }
+
+        let language = region.split(['_', '.', '-', ',']).next().unwrap_or("");
+        match language {
+            "fr" | "be" => Layout::Azerty,
+            "de" | "at" | "ch" | "cs" | "cz" | "sk" | "hu" | "sl" | "hr" => Layout::Qwertz,
+            _ => Layout::Qwerty,
+        }
+    }
+}
+
+impl FromStr for Layout {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "qwerty" => Ok(Layout::Qwerty),
+            "azerty" => Ok(Layout::Azerty),
+            "qwertz" => Ok(Layout::Qwertz),
+            "dvorak" => Ok(Layout::Dvorak),
+            "colemak" => Ok(Layout::Colemak),
+            "numpad" => Ok(Layout::Numpad),
+            _ => bail!(
+                "unknown keyboard layout '{}' (expected qwerty, azerty, qwertz, dvorak, colemak or numpad)",
+                s
+            ),
+        }
+    }
+}
+
+impl Display for Layout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Layout::Qwerty => "qwerty",
+            Layout::Azerty => "azerty",
+            Layout::Qwertz => "qwertz",
+            Layout::Dvorak => "dvorak",
+            Layout::Colemak => "colemak",
+            Layout::Numpad => "numpad",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Maps host key labels onto the CHIP-8 keypad
+pub struct Keymap {
+    bindings: HashMap<String, u8>,
+}
+
+impl Keymap {
+    /// Create a keymap without any bindings
+    pub fn empty() -> Self {
+        Self {
+            bindings: HashMap::new(),
+        }
+    }
+
+    /// Create a keymap binding the 4x4 key cluster of `layout` to the CHIP-8 keypad
+    pub fn from_layout(layout: Layout) -> Self {
+        let mut keymap = Self::empty();
+        for (label, key) in layout.cluster_labels().into_iter().zip(CLUSTER_KEYS) {
+            keymap.bind(label, key);
+        }
+        if layout == Layout::Numpad {
+            // the labels are shared with the main keyboard, so also bind the physical key names
+            // for frontends that can tell the numeric keypad apart
+            for key in 0x0..=0x9 {
+                keymap.bind(&format!("numpad{}", key), key);
+            }
+            keymap.bind("numpaddecimal", 0xA);
+            keymap.bind("numpadenter", 0xB);
+            keymap.bind("numpadadd", 0xC);
+            keymap.bind("numpadsubtract", 0xD);
+            keymap.bind("numpadmultiply", 0xE);
+            keymap.bind("numpaddivide", 0xF);
+        }
+        keymap
+    }
+
+    /// Bind the host key with `label` to the CHIP-8 `key`, replacing any existing binding
+    pub fn bind(&mut self, label: &str, key: u8) {
+        self.bindings.insert(label.to_lowercase(), key & 0xF);
+    }
+
+    pub fn unbind(&mut self, label: &str) {
+        self.bindings.remove(&label.to_lowercase());
+    }
+
+    /// Look up the CHIP-8 key bound to the host key with `label`
+    pub fn key(&self, label: &str) -> Key {
+        Key(self
+            .bindings
+            .get(&label.to_lowercase())
+            .map(|key| *key as usize))
+    }
+
+    /// Parse a keymap from lines of `label = key`, with the key given as a hex digit
+    /// Blank lines and lines starting with `#` are ignored
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let mut keymap = Self::empty();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            // split on the last `=` so the `=` key itself can be bound
+            let (label, key) = line
+                .rsplit_once('=')
+                .with_context(|| format!("line {}: expected 'label = key'", n + 1))?;
+            let key = u8::from_str_radix(key.trim(), 16)
+                .ok()
+                .filter(|key| *key <= 0xF)
+                .with_context(|| format!("line {}: invalid key '{}'", n + 1, key.trim()))?;
+            keymap.bind(label.trim(), key);
+        }
+        Ok(keymap)
+    }
+
+    /// Return true if the host key with `label` is bound to a CHIP-8 key
+    pub fn is_bound(&self, label: &str) -> bool {
+        self.bindings.contains_key(&label.to_lowercase())
+    }
+
+    /// Look up the CHIP-8 key bound to the first of `labels` that has a binding
+    /// Frontends can pass several names for the same host key, e.g. its physical and logical names
+    pub fn key_for_any<'a>(&self, labels: impl IntoIterator<Item = &'a str>) -> Key {
+        labels
+            .into_iter()
+            .map(|label| self.key(label))
+            .find(|key| key.0.is_some())
+            .unwrap_or(Key(None))
+    }
+}
+
+impl Display for Keymap {
+    /// Write the keymap in the format read by `Keymap::parse`, sorted by key then label
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut bindings: Vec<_> = self.bindings.iter().collect();
+        bindings.sort_by_key(|(label, key)| (**key, label.as_str()));
+        for (label, key) in bindings {
+            writeln!(f, "{} = {:X}", label, key)?;
+        }
+        Ok(())
+    }
+}
+
+/// Selects which of the two keypads an input event is for
+/// Plain CHIP-8 programs only read the first keypad, the second is used by CHIP-8X and by
+/// two-player homebrew conventions
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Player {
+    One,
+    Two,
+}
+
+/// How FX0A chooses a key when several are held while it waits
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum KeyWaitPolicy {
+    /// The lowest numbered key held is captured once it's released
+    #[default]
+    Lowest,
+    /// The most recently pressed key is captured once it's released
+    MostRecent,
+    /// Whichever key held during the wait is released first is captured
+    FirstReleased,
+}
+
+impl FromStr for KeyWaitPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "lowest" => Ok(KeyWaitPolicy::Lowest),
+            "most-recent" => Ok(KeyWaitPolicy::MostRecent),
+            "first-released" => Ok(KeyWaitPolicy::FirstReleased),
+            _ => bail!(
+                "unknown key wait policy '{}' (expected lowest, most-recent or first-released)",
+                s
+            ),
+        }
+    }
+}
+
+/// The default number of autofire presses per second
+pub const DEFAULT_AUTOFIRE_RATE: u32 = 10;
+
 pub struct Keypad {
+    /// The key states seen by the program
     pub(crate) keys: [u8; 0x10],
-    pub(crate) awaiting_release: Option<u8>,
+    /// The key states as reported by the host, which differ from `keys` while autofire is active
+    pub(crate) held: [bool; 0x10],
+    /// The keys which are repeatedly pressed and released while held
+    pub(crate) autofire: [bool; 0x10],
+    /// The number of autofire presses per second
+    pub(crate) autofire_rate: u32,
+    /// The number of frames since the keypad was created, used to phase autofire presses
+    pub(crate) frame: u32,
+    /// The order keys were last pressed in, as a counter value per key
+    pub(crate) press_order: [u32; 0x10],
+    /// The number of key presses seen so far
+    pub(crate) press_count: u32,
+    /// A mask of the keys FX0A will capture once they're released
+    pub(crate) wait_candidates: u16,
+    /// The number of frames left before each key pressed with `press_for` is released
+    pub(crate) release_in: [Option<u32>; 0x10],
 }
 
 impl Keypad {
     pub fn new() -> Self {
         Self {
             keys: [0; 0x10],
-            awaiting_release: None,
+            held: [false; 0x10],
+            autofire: [false; 0x10],
+            autofire_rate: DEFAULT_AUTOFIRE_RATE,
+            frame: 0,
+            press_order: [0; 0x10],
+            press_count: 0,
+            wait_candidates: 0,
+            release_in: [None; 0x10],
         }
     }
 
     pub fn keydown(&mut self, key: Key) -> anyhow::Result<()> {
         if let Some(key) = key.0 {
-            self.keys[key] = 1;
+            self.set_key(key as u8, true);
         }
         Ok(())
     }
 
     pub fn keyup(&mut self, key: Key) -> anyhow::Result<()> {
         if let Some(key) = key.0 {
-            self.keys[key] = 0;
+            self.set_key(key as u8, false);
         }
         Ok(())
     }
 
-    pub fn await_release(&mut self, key: u8) {
-        self.awaiting_release = Some(key);
+    /// Press or release the key numbered `key` as if it was done on the host
+    pub(crate) fn set_key(&mut self, key: u8, down: bool) {
+        let key = (key & 0xF) as usize;
+        if down && !self.held[key] {
+            self.press_count = self.press_count.wrapping_add(1);
+            self.press_order[key] = self.press_count;
+        }
+        self.held[key] = down;
+        self.keys[key] = down as u8;
+        self.release_in[key] = None;
+    }
+
+    /// Press `key` for the next `frames` frames, releasing it automatically afterwards
+    pub fn press_for(&mut self, key: Key, frames: u32) {
+        if let Some(key) = key.0 {
+            self.set_key(key as u8, true);
+            self.release_in[key] = Some(frames);
+        }
+    }
+
+    /// Advance the autofire phase by one 60 Hz frame, pressing or releasing held autofire keys
+    pub fn tick(&mut self) {
+        self.frame = self.frame.wrapping_add(1);
+        for key in 0..0x10 {
+            match self.release_in[key] {
+                Some(0) => self.set_key(key as u8, false),
+                Some(frames) => self.release_in[key] = Some(frames - 1),
+                None => {}
+            }
+        }
+        // a press and a release both last half a period, and always at least one frame
+        let half_period = (30 / self.autofire_rate.max(1)).max(1);
+        let pressed = (self.frame / half_period) & 1 == 0;
+        for key in 0..0x10 {
+            if self.autofire[key] && self.held[key] {
+                self.keys[key] = pressed as u8;
+            }
+        }
+    }
+
+    pub fn set_autofire(&mut self, key: u8, enabled: bool) {
+        let key = (key & 0xF) as usize;
+        self.autofire[key] = enabled;
+        if !enabled {
+            self.keys[key] = self.held[key] as u8;
+        }
+    }
+
+    pub fn is_autofire(&self, key: u8) -> bool {
+        self.autofire[(key & 0xF) as usize]
+    }
+
+    /// Return the keys held down on the host as a bitmask, with bit N set while key N is held
+    pub fn mask(&self) -> u16 {
+        self.held_keys().fold(0, |mask, key| mask | 1 << key)
+    }
+
+    /// Press and release keys so that exactly the keys set in `mask` are held
+    pub fn set_mask(&mut self, mask: u16) {
+        for key in 0..0x10u8 {
+            let down = mask & (1 << key) != 0;
+            if down != self.held[key as usize] {
+                self.set_key(key, down);
+            }
+        }
     }
 
-    pub fn process_release(&mut self) {
-        self.awaiting_release = None;
+    /// Iterate over the keys currently held down on the host
+    pub fn held_keys(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..0x10u8).filter(|key| self.held[*key as usize])
     }
 
+    /// Poll for a key press and release on behalf of FX0A, returning the captured key if there is one
+    pub fn poll_key_wait(&mut self, policy: KeyWaitPolicy) -> Option<u8> {
+        if let Some(key) =
+            (0..0x10u8).find(|key| self.wait_candidates & (1 << key) != 0 && self.is_key_up(*key))
+        {
+            self.wait_candidates = 0;
+            return Some(key);
+        }
+
+        let keys = self.keys;
+        let mut down = (0..0x10u8).filter(|key| keys[*key as usize] == 1);
+        match policy {
+            KeyWaitPolicy::Lowest => {
+                if self.wait_candidates == 0 {
+                    if let Some(key) = down.next() {
+                        self.wait_candidates = 1 << key;
+                    }
+                }
+            }
+            KeyWaitPolicy::MostRecent => {
+                if let Some(key) =
+                    down.min_by_key(|key| std::cmp::Reverse(self.press_order[*key as usize]))
+                {
+                    self.wait_candidates = 1 << key;
+                }
+            }
+            KeyWaitPolicy::FirstReleased => {
+                for key in down {
+                    self.wait_candidates |= 1 << key;
+                }
+            }
+        }
+        None
+    }
+
+    /// Whether `key` is down, using only its low nibble as the COSMAC VIP did
     pub fn is_key_down(&self, key: u8) -> bool {
-        self.keys[key as usize] == 1
+        self.keys[(key & 0x0F) as usize] == 1
     }
 
+    /// Whether `key` is up, using only its low nibble as the COSMAC VIP did
     pub fn is_key_up(&self, key: u8) -> bool {
-        self.keys[key as usize] == 0
+        self.keys[(key & 0x0F) as usize] == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Key, KeyWaitPolicy, Keymap, Keypad, Layout};
+
+    #[test]
+    fn test_autofire() {
+        let mut keypad = Keypad::new();
+        keypad.autofire_rate = 15; // two frames pressed, two frames released
+        keypad.set_autofire(0x5, true);
+        keypad.keydown(Key(Some(0x5))).unwrap();
+        keypad.keydown(Key(Some(0x6))).unwrap();
+
+        let mut states = Vec::new();
+        for _ in 0..6 {
+            keypad.tick();
+            states.push(keypad.is_key_down(0x5));
+            assert_eq!(keypad.is_key_down(0x6), true);
+        }
+        assert_eq!(states, [true, false, false, true, true, false]);
+        assert_eq!(keypad.held_keys().collect::<Vec<_>>(), [0x5, 0x6]);
+
+        keypad.set_autofire(0x5, false);
+        assert_eq!(keypad.is_key_down(0x5), true);
+        keypad.keyup(Key(Some(0x5))).unwrap();
+        keypad.tick();
+        assert_eq!(keypad.is_key_down(0x5), false);
+    }
+
+    #[test]
+    fn test_keymap_from_layout() {
+        let keymap = Keymap::from_layout(Layout::Qwerty);
+        assert_eq!(keymap.key("1").0, Some(0x1));
+        assert_eq!(keymap.key("Q").0, Some(0x4));
+        assert_eq!(keymap.key("v").0, Some(0xF));
+        assert_eq!(keymap.key("y").0, None);
+
+        let keymap = Keymap::from_layout(Layout::Azerty);
+        assert_eq!(keymap.key("a").0, Some(0x4));
+        assert_eq!(keymap.key("q").0, Some(0x7));
+        assert_eq!(keymap.key("w").0, Some(0xA));
+
+        let keymap = Keymap::from_layout(Layout::Dvorak);
+        assert_eq!(keymap.key("'").0, Some(0x4));
+        assert_eq!(keymap.key("k").0, Some(0xF));
+    }
+
+    #[test]
+    fn test_keymap_numpad() {
+        let keymap = Keymap::from_layout(Layout::Numpad);
+        assert_eq!(keymap.key("7").0, Some(0x7));
+        assert_eq!(keymap.key("enter").0, Some(0xB));
+        assert_eq!(keymap.key("Numpad0").0, Some(0x0));
+        assert_eq!(keymap.key("NumpadDivide").0, Some(0xF));
+        assert_eq!(keymap.key_for_any(["Home", "numpad7"]).0, Some(0x7));
+        assert_eq!(keymap.key_for_any(["Home", "End"]).0, None);
+    }
+
+    #[test]
+    fn test_keymap_bind() {
+        let mut keymap = Keymap::from_layout(Layout::Qwertz);
+        keymap.bind("Up", 0x2);
+        assert_eq!(keymap.key("up").0, Some(0x2));
+        keymap.unbind("y");
+        assert_eq!(keymap.key("y").0, None);
+    }
+
+    #[test]
+    fn test_keymap_parse() {
+        let keymap = Keymap::parse("# pong\nup = 1\n\n= = C\ndown=4\n").unwrap();
+        assert_eq!(keymap.key("up").0, Some(0x1));
+        assert_eq!(keymap.key("=").0, Some(0xC));
+        assert_eq!(keymap.key("down").0, Some(0x4));
+        assert_eq!(keymap.to_string(), "up = 1\ndown = 4\n= = C\n");
+        assert_eq!(
+            Keymap::parse(&keymap.to_string()).unwrap().to_string(),
+            keymap.to_string()
+        );
+
+        assert!(Keymap::parse("up").is_err());
+        assert!(Keymap::parse("up = 10").is_err());
+    }
+
+    #[test]
+    fn test_layout_from_str() {
+        assert_eq!("Colemak".parse::<Layout>().unwrap(), Layout::Colemak);
+        assert!("hcesar".parse::<Layout>().is_err());
+    }
+
+    #[test]
+    fn test_key_wait_policy() {
+        let press = |keypad: &mut Keypad, key: usize| keypad.keydown(Key(Some(key))).unwrap();
+        let release = |keypad: &mut Keypad, key: usize| keypad.keyup(Key(Some(key))).unwrap();
+
+        // the lowest key is latched even when a higher key is released first
+        let mut keypad = Keypad::new();
+        press(&mut keypad, 0x8);
+        press(&mut keypad, 0x3);
+        assert_eq!(keypad.poll_key_wait(KeyWaitPolicy::Lowest), None);
+        release(&mut keypad, 0x8);
+        assert_eq!(keypad.poll_key_wait(KeyWaitPolicy::Lowest), None);
+        release(&mut keypad, 0x3);
+        assert_eq!(keypad.poll_key_wait(KeyWaitPolicy::Lowest), Some(0x3));
+
+        let mut keypad = Keypad::new();
+        press(&mut keypad, 0x3);
+        assert_eq!(keypad.poll_key_wait(KeyWaitPolicy::MostRecent), None);
+        press(&mut keypad, 0x8);
+        assert_eq!(keypad.poll_key_wait(KeyWaitPolicy::MostRecent), None);
+        release(&mut keypad, 0x3);
+        assert_eq!(keypad.poll_key_wait(KeyWaitPolicy::MostRecent), None);
+        release(&mut keypad, 0x8);
+        assert_eq!(keypad.poll_key_wait(KeyWaitPolicy::MostRecent), Some(0x8));
+
+        let mut keypad = Keypad::new();
+        press(&mut keypad, 0x3);
+        press(&mut keypad, 0x8);
+        assert_eq!(keypad.poll_key_wait(KeyWaitPolicy::FirstReleased), None);
+        release(&mut keypad, 0x8);
+        assert_eq!(
+            keypad.poll_key_wait(KeyWaitPolicy::FirstReleased),
+            Some(0x8)
+        );
+        // the key still held isn't captured straight away by the next wait
+        assert_eq!(keypad.poll_key_wait(KeyWaitPolicy::FirstReleased), None);
+
+        assert_eq!(
+            "most-recent".parse::<KeyWaitPolicy>().unwrap(),
+            KeyWaitPolicy::MostRecent
+        );
+        assert!("random".parse::<KeyWaitPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_press_for() {
+        let mut keypad = Keypad::new();
+        keypad.press_for(Key::from_value(0x3), 2);
+        assert_eq!(keypad.is_key_down(0x3), true);
+
+        let mut states = Vec::new();
+        for _ in 0..3 {
+            keypad.tick();
+            states.push(keypad.is_key_down(0x3));
+        }
+        assert_eq!(states, [true, true, false]);
+
+        // a host release cancels the pending release
+        keypad.press_for(Key::from_value(0x3), 1);
+        keypad.keyup(Key::from_value(0x3)).unwrap();
+        keypad.keydown(Key::from_value(0x3)).unwrap();
+        keypad.tick();
+        keypad.tick();
+        assert_eq!(keypad.is_key_down(0x3), true);
+    }
+
+    #[test]
+    fn test_mask() {
+        let mut keypad = Keypad::new();
+        keypad.keydown(Key::from_value(0x1)).unwrap();
+        keypad.keydown(Key::from_value(0xF)).unwrap();
+        assert_eq!(keypad.mask(), 0x8002);
+
+        keypad.set_mask(0x0011);
+        assert_eq!(keypad.held_keys().collect::<Vec<_>>(), [0x0, 0x4]);
+        assert_eq!(keypad.is_key_down(0xF), false);
+        assert_eq!(Key::from_label("v").value(), Some(0xF));
+        assert_eq!(Key::from_label("?").value(), None);
     }
 }
@@ -0,0 +1,189 @@
+use std::fmt::Display;
+#[cfg(feature = "std")]
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use anyhow::{bail, Context};
+
+#[cfg(feature = "std")]
+/// Return the directory every chipper frontend stores its configuration in, if one can be
+/// determined
+pub fn config_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        return Some(PathBuf::from(dir).join("chipper"));
+    }
+    if let Some(dir) = std::env::var_os("APPDATA").filter(|dir| !dir.is_empty()) {
+        return Some(PathBuf::from(dir).join("chipper"));
+    }
+    std::env::var_os("HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(|dir| PathBuf::from(dir).join(".config").join("chipper"))
+}
+
+/// The position and size of a window, in the frontend's window coordinates
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WindowGeometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Where a frontend's windows were when it last closed, so the next launch can put them back
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WindowLayout {
+    /// The game window
+    pub main: Option<WindowGeometry>,
+    pub fullscreen: bool,
+    /// The names of the debug panels that were open, and where their windows were
+    pub panels: Vec<(String, Option<WindowGeometry>)>,
+}
+
+impl WindowLayout {
+    #[cfg(feature = "std")]
+    fn path(frontend: &str) -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join("layout").join(format!("{}.layout", frontend)))
+    }
+
+    #[cfg(feature = "std")]
+    /// Load the layout `frontend` saved, if it has saved one
+    pub fn load(frontend: &str) -> anyhow::Result<Option<Self>> {
+        let Some(path) = Self::path(frontend) else {
+            return Ok(None);
+        };
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let text = std::fs::read_to_string(&path).context("read window layout")?;
+        let layout = Self::parse(&text)
+            .with_context(|| format!("parse window layout {}", path.display()))?;
+        Ok(Some(layout))
+    }
+
+    #[cfg(feature = "std")]
+    /// Save the layout for `frontend` to load on its next launch
+    pub fn save(&self, frontend: &str) -> anyhow::Result<()> {
+        let path = Self::path(frontend).context("no configuration directory available")?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).context("create window layout directory")?;
+        }
+        std::fs::write(&path, self.to_string()).context("write window layout")
+    }
+
+    /// Parse a layout, with a `window X Y WIDTH HEIGHT` line for the game window, a `fullscreen`
+    /// line if it was fullscreen, and a `panel NAME [X Y WIDTH HEIGHT]` line per open debug panel
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let mut layout = Self::default();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let words: Vec<_> = line.split_whitespace().collect();
+            let geometry =
+                |words: &[&str]| parse_geometry(words).with_context(|| format!("line {}", n + 1));
+            match words[..] {
+                ["window", ref rest @ ..] => layout.main = Some(geometry(rest)?),
+                ["fullscreen"] => layout.fullscreen = true,
+                ["panel", name] => layout.panels.push((name.to_string(), None)),
+                ["panel", name, ref rest @ ..] => layout
+                    .panels
+                    .push((name.to_string(), Some(geometry(rest)?))),
+                _ => bail!(
+                    "line {}: expected 'window', 'fullscreen' or 'panel', got '{}'",
+                    n + 1,
+                    line
+                ),
+            }
+        }
+        Ok(layout)
+    }
+}
+
+fn parse_geometry(words: &[&str]) -> anyhow::Result<WindowGeometry> {
+    let [x, y, width, height] = words else {
+        bail!("expected X Y WIDTH HEIGHT");
+    };
+    Ok(WindowGeometry {
+        x: parse_number("x", x)?,
+        y: parse_number("y", y)?,
+        width: parse_number("width", width)?,
+        height: parse_number("height", height)?,
+    })
+}
+
+fn parse_number<T: FromStr>(name: &str, value: &str) -> anyhow::Result<T> {
+    value
+        .parse()
+        .ok()
+        .with_context(|| format!("invalid {} '{}'", name, value))
+}
+
+impl Display for WindowGeometry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} {} {}", self.x, self.y, self.width, self.height)
+    }
+}
+
+impl Display for WindowLayout {
+    /// Write the layout in the format read by `WindowLayout::parse`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(main) = &self.main {
+            writeln!(f, "window {}", main)?;
+        }
+        if self.fullscreen {
+            writeln!(f, "fullscreen")?;
+        }
+        for (name, geometry) in &self.panels {
+            match geometry {
+                Some(geometry) => writeln!(f, "panel {} {}", name, geometry)?,
+                None => writeln!(f, "panel {}", name)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{WindowGeometry, WindowLayout};
+
+    #[test]
+    fn test_parse_window_layout() {
+        let layout = WindowLayout {
+            main: Some(WindowGeometry {
+                x: -20,
+                y: 40,
+                width: 640,
+                height: 320,
+            }),
+            fullscreen: true,
+            panels: vec![
+                (
+                    "memory".to_string(),
+                    Some(WindowGeometry {
+                        x: 700,
+                        y: 0,
+                        width: 456,
+                        height: 342,
+                    }),
+                ),
+                ("registers".to_string(), None),
+            ],
+        };
+        let text = layout.to_string();
+        assert_eq!(
+            text,
+            "window -20 40 640 320\nfullscreen\npanel memory 700 0 456 342\npanel registers\n"
+        );
+        assert_eq!(WindowLayout::parse(&text).unwrap(), layout);
+
+        assert!(WindowLayout::parse("window 1 2 3").is_err());
+        assert!(WindowLayout::parse("window 1 2 3 -4").is_err());
+        assert!(WindowLayout::parse("maximised").is_err());
+    }
+}
@@ -1,18 +1,150 @@
+#[cfg(feature = "debugger")]
+mod analyze;
+#[cfg(feature = "debugger")]
+mod asm;
+mod audio;
+#[cfg(feature = "debugger")]
+mod bounds;
+mod chip8x;
+#[cfg(feature = "debugger")]
+mod compare;
+mod custom;
+mod database;
+mod diff;
+#[cfg(feature = "debugger")]
+mod disasm;
 mod display;
+mod emulator;
+mod event;
+#[cfg(feature = "debugger")]
+mod explain;
+mod flags;
+mod font;
+mod gif;
+mod glyph;
+mod halt;
+mod hash;
+mod image;
+mod input_macro;
+mod json;
 mod keypad;
+mod layout;
+mod lockstep;
 mod memory;
-
+mod menu;
+mod metrics;
+mod movie;
+mod octo;
+mod palette;
+#[cfg(feature = "debugger")]
+mod pipeline;
+mod quirks;
+mod random;
+mod rewind;
+mod run;
+mod savestate;
+mod splash;
+mod thumbnail;
+mod timing;
+#[cfg(feature = "debugger")]
+mod watch;
+mod wav;
+
+#[cfg(feature = "std")]
 use std::path::PathBuf;
 
-use anyhow::{bail, Context};
-use rand::Rng;
+use anyhow::{bail, ensure, Context};
 
+use crate::audio::Buzzer;
+use crate::custom::CustomOpcode;
 use crate::display::Display;
+#[cfg(feature = "xochip")]
+use crate::display::ALL_PLANES;
+use crate::halt::{IdleDetector, LoopState};
+use crate::input_macro::MacroPlayer;
 use crate::keypad::Keypad;
 use crate::memory::Memory;
-
-pub use display::FrameBuffer;
-pub use keypad::Key;
+#[cfg(feature = "debugger")]
+use crate::pipeline::Pipeline;
+use crate::random::Random;
+use crate::rewind::RewindBuffer;
+
+#[cfg(feature = "debugger")]
+pub use analyze::{Analysis, Region, RegionKind, Sprite, Variant};
+#[cfg(feature = "debugger")]
+pub use asm::{
+    assemble, assemble_instruction, disassemble_rom, verify_round_trip, RoundTripMismatch,
+};
+pub use audio::{
+    Waveform, AUDIO_PATTERN_LENGTH, BUZZER_AMPLITUDE, BUZZER_FREQUENCY, DEFAULT_ENVELOPE_MS,
+    DEFAULT_PITCH,
+};
+#[cfg(feature = "debugger")]
+pub use bounds::{Access, BoundsVerifier, Finding, FindingKind};
+pub use chip8x::{
+    ColourZones, BACKGROUND_COLOURS, COLOUR_ZONE_HEIGHT, COLOUR_ZONE_WIDTH, FOREGROUND_COLOURS,
+};
+#[cfg(feature = "debugger")]
+pub use compare::{format_frame, parse_frame, FrameComparison, REGION_SIZE};
+pub use custom::OpcodeContext;
+pub use database::{RomDatabase, RomProfile};
+pub use diff::{MemoryChange, RegisterChange, StateDiff};
+#[cfg(feature = "debugger")]
+pub use disasm::disassemble;
+pub use display::Frame;
+pub use emulator::EmulatorCore;
+pub use event::Event;
+#[cfg(feature = "debugger")]
+pub use explain::{Explanation, Fields, Registers};
+#[cfg(feature = "std")]
+pub use flags::FsFlagStore;
+pub use flags::{FlagStore, FLAG_COUNT};
+pub use font::FontSet;
+pub use glyph::glyph;
+pub use halt::{AddressOverflowPolicy, Chip8Error, HaltReason, InvalidOpcodePolicy, StepOutcome};
+pub use hash::rom_hash;
+pub use image::{ProgramImage, Segment};
+pub use input_macro::{InputMacro, MacroStep};
+pub use keypad::{Key, KeyWaitPolicy, Keymap, Layout, Player};
+#[cfg(feature = "std")]
+pub use layout::config_dir;
+pub use layout::{WindowGeometry, WindowLayout};
+pub use lockstep::{Divergence, Lockstep};
+pub use menu::{RomMenu, MAX_MENU_ENTRIES};
+pub use metrics::Metrics;
+#[cfg(feature = "std")]
+pub use metrics::MetricsServer;
+pub use movie::{Desync, Movie, MoviePlayer, MovieQuirks, CHECKPOINT_INTERVAL, EMULATOR_VERSION};
+pub use octo::{ensure_not_cartridge, is_octo_cartridge, OctoCartridge, OctoOptions};
+pub use palette::{Magnifier, Palette};
+#[cfg(feature = "debugger")]
+pub use pipeline::PipelineEvent;
+pub use quirks::Quirks;
+pub use rewind::REWIND_INTERVAL;
+pub use run::{FrameReport, HaltCondition, RunOutcome, StopReason};
+#[cfg(feature = "std")]
+pub use savestate::FsStateStore;
+pub use savestate::{MachineState, MemoryStateStore, StateStore};
+pub use splash::SPLASH_ROM;
+#[cfg(feature = "std")]
+pub use thumbnail::ThumbnailCache;
+pub use thumbnail::{render_thumbnail, THUMBNAIL_FRAMES, THUMBNAIL_SEED};
+pub use timing::{vip_cycles, VIP_CYCLES_PER_FRAME};
+#[cfg(all(feature = "debugger", feature = "std"))]
+pub use watch::WatchExporter;
+#[cfg(feature = "debugger")]
+pub use watch::{format_watches, Watch, WatchExpr, WatchFormat};
+pub use wav::WavWriter;
+
+/// Print an executed instruction when `print_operations` is on. Without the `trace` feature this
+/// compiles to nothing.
+macro_rules! trace_op {
+    ($chip8:expr, $($arg:tt)*) => {
+        if cfg!(feature = "trace") && $chip8.config.print_operations {
+            println!($($arg)*);
+        }
+    };
+}
 
 pub const FONT_CHAR_LENGTH: usize = 5;
 
@@ -35,15 +167,86 @@ pub const FONT_DATA: [u8; FONT_CHAR_LENGTH * 0x10] = [
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
 
+/// The default address of the font, which can be moved with `font_address`
 pub const FONT_ADDR: usize = 0x050;
 
+pub const BIG_FONT_CHAR_LENGTH: usize = 10;
+
+/// SUPER-CHIP's 8x10 font that FX30 points into, with the hex digits Octo adds after 0-9
+#[rustfmt::skip]
+pub const BIG_FONT_DATA: [u8; BIG_FONT_CHAR_LENGTH * 0x10] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
+
+/// The address of the big font, straight after the small font when that's in its default place.
+/// It stays here when the small font is moved, unless the small font is moved over it.
+pub const BIG_FONT_ADDR: usize = FONT_ADDR + FONT_DATA.len();
+
+/// Binary-coded decimal digits (hundreds, tens, ones) for every byte value, used by FX33
+const BCD_TABLE: [[u8; 3]; 256] = {
+    let mut table = [[0; 3]; 256];
+    let mut n = 0;
+    while n < 256 {
+        table[n] = [(n / 100) as u8, (n / 10 % 10) as u8, (n % 10) as u8];
+        n += 1;
+    }
+    table
+};
+
+/// The default memory size, which is what the original CHIP-8 had
 pub const MEM_SIZE: usize = 0x1000;
+/// The memory size of XO-CHIP, the largest `memory_size` accepts
+pub const XO_CHIP_MEM_SIZE: usize = 0x10000;
 pub const ROM_ADDR: usize = 0x200;
+/// Where ETI-660 programs are loaded, above its larger interpreter
+pub const ETI_660_ROM_ADDR: usize = 0x600;
 pub const STACK_SIZE: usize = 0x10;
 pub const REGISTER_COUNT: usize = 0x10;
 
+/// The default number of frames FX0A waits without any key activity before reporting it
+pub const DEFAULT_KEY_WAIT_TIMEOUT: u32 = 300;
+
+/// The size of the screen in the standard low resolution mode. SUPER-CHIP programs can switch to
+/// high resolution, so use `Chip8::screen_width` and `Chip8::screen_height` for the current size.
 pub const SCREEN_WIDTH: usize = 64;
 pub const SCREEN_HEIGHT: usize = 32;
+/// The size of the screen in SUPER-CHIP's high resolution mode
+pub const HIRES_WIDTH: usize = 128;
+pub const HIRES_HEIGHT: usize = 64;
+
+/// Where the two-page hires CHIP-8 interpreter starts running programs, after the 1260 jump they
+/// begin with
+pub const TWO_PAGE_ENTRY: u16 = 0x2C0;
+
+/// The number of frames `cycle` is meant to be called for each second
+pub const FRAME_RATE: usize = 60;
+
+/// The default clock speed in instructions per second, eleven a frame
+pub const DEFAULT_CLOCK_HZ: u32 = 660;
+
+/// The number of bytes in the instruction starting with `opcode`. Every instruction is one word
+/// except XO-CHIP's F000 NNNN, which carries a 16-bit address in the word after it.
+pub fn instruction_length(opcode: u16) -> usize {
+    match opcode {
+        0xF000 => 4,
+        _ => 2,
+    }
+}
 
 struct Opcode {
     c: u8,
@@ -61,21 +264,51 @@ impl std::fmt::Display for Opcode {
 }
 
 struct Chip8Config {
-    legacy_shift: bool,
-    jump_add_offset: bool,
-    memory_increment_i: bool,
+    quirks: Quirks,
     print_operations: bool,
-    ops_per_cycle: usize,
+    clock_hz: u32,
+    vip_timing: bool,
+    key_wait_policy: KeyWaitPolicy,
+    key_wait_timeout: u32,
+    write_protect: bool,
+    stack_depth: usize,
+    invalid_opcode_policy: InvalidOpcodePolicy,
+    address_overflow_policy: AddressOverflowPolicy,
+    detect_idle_loops: bool,
+    manual_timers: bool,
+    font_addr: usize,
+    font: [u8; FONT_DATA.len()],
+    big_font: [u8; BIG_FONT_DATA.len()],
+    rom_addr: usize,
+    start_pc: Option<u16>,
+    chip8x: bool,
+    #[cfg(feature = "two_page")]
+    two_page: bool,
 }
 
 impl Chip8Config {
     pub fn new() -> Self {
         Self {
-            legacy_shift: false,
-            jump_add_offset: false,
-            memory_increment_i: false,
+            quirks: Quirks::default(),
             print_operations: false,
-            ops_per_cycle: 11,
+            clock_hz: DEFAULT_CLOCK_HZ,
+            vip_timing: false,
+            key_wait_policy: KeyWaitPolicy::Lowest,
+            key_wait_timeout: DEFAULT_KEY_WAIT_TIMEOUT,
+            write_protect: false,
+            stack_depth: STACK_SIZE,
+            invalid_opcode_policy: InvalidOpcodePolicy::Error,
+            address_overflow_policy: AddressOverflowPolicy::Error,
+            detect_idle_loops: false,
+            manual_timers: false,
+            font_addr: FONT_ADDR,
+            font: FONT_DATA,
+            big_font: BIG_FONT_DATA,
+            rom_addr: ROM_ADDR,
+            start_pc: None,
+            chip8x: false,
+            #[cfg(feature = "two_page")]
+            two_page: false,
         }
     }
 }
@@ -88,6 +321,13 @@ pub struct Chip8 {
     display: Display,
     /// A hexadecimal keypad containing 16 key states labelled 0 through F
     keypad: Keypad,
+    /// The keypad for the second player, which has the same layout as the first, and which
+    /// CHIP-8X's EXF2 and EXF5 read
+    second_keypad: Keypad,
+    /// Plays back a scripted key sequence on the first keypad
+    macro_player: Option<MacroPlayer>,
+    /// The keys each keypad holds from the start of the next frame, if they've been latched
+    latched_input: [Option<u16>; 2],
     /// A stack for 16-bit addresses, which is used to call subroutines/functions and return from them
     stack: [u16; STACK_SIZE],
     /// A pointer to the current stack address in use
@@ -102,20 +342,79 @@ pub struct Chip8 {
     dt: u8,
     /// The sound timer is decremented at a rate of 60 Hz until it reaches 0, and plays a tone as long as it's not 0
     st: u8,
+    /// Generates the tone that plays while the sound timer is active
+    buzzer: Buzzer,
+    /// The colours CHIP-8X programs set with 02A0 and BXYN
+    colours: ColourZones,
+    /// The source of CXNN's random numbers, which can be seeded to make runs reproducible
+    rng: Random,
+    /// Periodic snapshots to rewind to, if rewinding is enabled
+    rewind: Option<RewindBuffer>,
+    /// The number of frames run since the newest rewind snapshot was taken
+    frames_since_snapshot: u32,
+    /// The number of instructions of the current frame that have run, when `run_until` stopped
+    /// partway through it
+    frame_ops: usize,
+    /// The number of instructions the current frame runs, its share of the clock speed
+    frame_budget: usize,
+    /// The fraction of an instruction left over from the clock speed not being a multiple of the
+    /// frame rate, in instructions per second, which is carried into the next frame's budget
+    clock_carry: u32,
+    /// The machine cycles the instructions of the current frame have taken with VIP timing,
+    /// including any the last instruction of the previous frame ran over by
+    frame_cycles: u32,
+    /// Whether FX0A found no key to capture during the current frame
+    key_wait_spun: bool,
+    /// Whether 00E0 or DXYN ran during the current frame
+    frame_drew: bool,
+    /// Whether DXYN ran with display wait on, which ends the current frame
+    vblank_wait: bool,
+    /// The number of consecutive frames FX0A has waited without any key being down
+    key_wait_frames: u32,
+    /// Watches for loops that can't make progress, when idle loops halt execution
+    idle: IdleDetector,
+    /// Why execution stopped, if it has
+    halted: Option<HaltReason>,
+    /// The fault the instruction being executed hit, which `step` returns as an error
+    fault: Option<Chip8Error>,
+    /// The address of the instruction being executed, which the program counter has already
+    /// moved past and may have wrapped around from
+    op_pc: u16,
+    /// The number of bytes written to addresses that had already been executed
+    code_modifications: u64,
+    /// The number of instructions executed since the interpreter was created
+    instructions_run: u64,
+    /// Events emitted since the start of the current frame
+    events: Vec<Event>,
+    /// Where the stages of each instruction are reported, while anything is subscribed
+    #[cfg(feature = "debugger")]
+    pipeline: Pipeline,
+    /// Handlers for opcodes the interpreter doesn't implement
+    custom_opcodes: Vec<CustomOpcode>,
+    /// The RPL user flags FX75 saves V0 to VX into and FX85 loads them back from
+    flags: [u8; FLAG_COUNT],
+    /// Where the flags are kept between runs, if anywhere
+    flag_store: Option<Box<dyn FlagStore + Send>>,
 }
 
 impl Chip8 {
     pub fn new() -> anyhow::Result<Self> {
-        let mut memory = Memory::new();
+        let mut memory = Memory::new(MEM_SIZE);
         memory
             .write(FONT_ADDR, &FONT_DATA)
             .context("write font into memory")?;
+        memory
+            .write(BIG_FONT_ADDR, &BIG_FONT_DATA)
+            .context("write big font into memory")?;
 
         Ok(Chip8 {
             config: Chip8Config::new(),
             memory,
             display: Display::new(),
             keypad: Keypad::new(),
+            second_keypad: Keypad::new(),
+            macro_player: None,
+            latched_input: [None; 2],
             stack: [0; STACK_SIZE],
             sp: 0,
             v: [0; REGISTER_COUNT],
@@ -123,22 +422,74 @@ impl Chip8 {
             i: 0,
             dt: 0,
             st: 0,
+            buzzer: Buzzer::new(),
+            colours: ColourZones::new(),
+            rng: Random::new(),
+            rewind: None,
+            frames_since_snapshot: 0,
+            frame_ops: 0,
+            frame_budget: 0,
+            clock_carry: 0,
+            frame_cycles: 0,
+            key_wait_spun: false,
+            frame_drew: false,
+            vblank_wait: false,
+            key_wait_frames: 0,
+            idle: IdleDetector::default(),
+            halted: None,
+            fault: None,
+            op_pc: 0,
+            code_modifications: 0,
+            instructions_run: 0,
+            events: Vec::new(),
+            #[cfg(feature = "debugger")]
+            pipeline: Pipeline::default(),
+            custom_opcodes: Vec::new(),
+            flags: [0; FLAG_COUNT],
+            flag_store: None,
         })
     }
 
     /* Config builder functions */
+    /// Set every quirk at once, e.g. to one of the presets
+    pub fn quirks(mut self, quirks: Quirks) -> Self {
+        self.config.quirks = quirks;
+        self
+    }
+
     pub fn legacy_shift(mut self, value: bool) -> Self {
-        self.config.legacy_shift = value;
+        self.config.quirks.legacy_shift = value;
         self
     }
 
     pub fn jump_add_offset(mut self, value: bool) -> Self {
-        self.config.jump_add_offset = value;
+        self.config.quirks.jump_add_offset = value;
         self
     }
 
     pub fn memory_increment_i(mut self, value: bool) -> Self {
-        self.config.memory_increment_i = value;
+        self.config.quirks.memory_increment_i = value;
+        self
+    }
+
+    /// End the frame after DXYN, as the COSMAC VIP did by waiting for the vertical blank before
+    /// drawing, so at most one sprite is drawn per frame
+    pub fn display_wait(mut self, value: bool) -> Self {
+        self.config.quirks.display_wait = value;
+        self
+    }
+
+    /// Wrap the parts of sprites that go off the edges of the screen around to the other side,
+    /// rather than clipping them
+    pub fn wrap_sprites(mut self, value: bool) -> Self {
+        self.config.quirks.wrap_sprites = value;
+        self
+    }
+
+    /// Point FX29 at the glyph for the whole of VX rather than just its low nibble, reading past
+    /// the end of the font for values above 0xF like the COSMAC VIP
+    pub fn unmasked_font(mut self, value: bool) -> Self {
+        self.config.quirks.unmasked_font = value;
         self
     }
 
@@ -147,479 +498,2389 @@ impl Chip8 {
         self
     }
 
-    pub fn ops_per_cycle(mut self, value: usize) -> Self {
-        self.config.ops_per_cycle = value;
-        self
+    #[deprecated(note = "set the speed in instructions per second with `clock_hz`")]
+    pub fn ops_per_cycle(self, value: usize) -> Self {
+        self.clock_hz((value * FRAME_RATE) as u32)
     }
 
-    pub fn load_rom(&mut self, rom: &[u8]) -> anyhow::Result<()> {
-        self.memory
-            .write(ROM_ADDR, rom)
-            .context("write rom into memory")?;
-        self.pc = ROM_ADDR as u16;
-        Ok(())
+    /// Run `hz` instructions each second. A speed that isn't a multiple of the frame rate is
+    /// spread over the frames, so e.g. 90 Hz alternates between one and two instructions a frame.
+    pub fn clock_hz(mut self, hz: u32) -> Self {
+        self.set_clock_hz(hz);
+        self
     }
 
-    pub fn load_rom_from_file(&mut self, path: PathBuf) -> anyhow::Result<()> {
-        let buf = std::fs::read(path).context("read rom file")?;
-        self.load_rom(&buf).context("load rom from file")?;
-        Ok(())
+    /// Run as many instructions each frame as fit in the COSMAC VIP's machine cycles for a frame,
+    /// with each taking as long as it did on the VIP, rather than following the clock speed
+    pub fn vip_timing(mut self, value: bool) -> Self {
+        self.config.vip_timing = value;
+        self
     }
 
-    pub fn is_fb_dirty(&self) -> bool {
-        self.display.dirty
+    /// Set the speed in instructions per second rather than per frame
+    pub fn instructions_per_second(mut self, value: usize) -> Self {
+        self.set_instructions_per_second(value);
+        self
     }
 
-    pub fn is_sound_playing(&self) -> bool {
-        self.st > 0
+    /// Seed the random number generator used by CXNN, so the same inputs always produce the same run
+    pub fn rng_seed(mut self, seed: u64) -> Self {
+        self.rng = Random::from_seed(seed);
+        self
     }
 
-    pub fn fb(&mut self) -> crate::display::FrameBuffer {
-        self.display.fb()
+    /// Keep a rewind snapshot every second for the last `seconds` seconds, 0 disables rewinding
+    pub fn rewind_seconds(mut self, seconds: usize) -> Self {
+        self.rewind = (seconds > 0).then(|| RewindBuffer::new(seconds));
+        self.frames_since_snapshot = 0;
+        self
     }
 
-    pub fn keydown(&mut self, key: Key) -> anyhow::Result<()> {
-        self.keypad.keydown(key)
+    /// Halt instead of writing to memory below the ROM address, which holds the font and the
+    /// interpreter. `poke` refuses such writes with an error.
+    pub fn write_protect(mut self, value: bool) -> Self {
+        self.config.write_protect = value;
+        self
     }
 
-    pub fn keyup(&mut self, key: Key) -> anyhow::Result<()> {
-        self.keypad.keyup(key)
+    /// Set what happens when the program runs an opcode that isn't an instruction and that no
+    /// custom opcode handler takes
+    pub fn invalid_opcode_policy(mut self, value: InvalidOpcodePolicy) -> Self {
+        self.config.invalid_opcode_policy = value;
+        self
     }
 
-    pub fn cycle(&mut self) {
-        self.dt = self.dt.saturating_sub(1);
-        self.st = self.st.saturating_sub(1);
-        for _ in 0..self.config.ops_per_cycle {
-            self.step();
-        }
+    /// Set what happens when the program counter or I runs past the end of memory
+    pub fn address_overflow_policy(mut self, value: AddressOverflowPolicy) -> Self {
+        self.config.address_overflow_policy = value;
+        self
     }
 
-    pub fn step(&mut self) {
-        let opcode = self.fetch();
-        let opcode = self.decode(opcode);
-        self.execute(opcode);
+    /// Halt with `HaltReason::IdleLoop` once the program settles into a loop it can never leave,
+    /// which is how most programs finish
+    pub fn detect_idle_loops(mut self, value: bool) -> Self {
+        self.config.detect_idle_loops = value;
+        self
     }
 
-    fn fetch(&mut self) -> u16 {
-        if self.config.print_operations {
-            print!("{:#02x} ", self.pc);
-        }
+    /// Stop `cycle` from ticking the timers, so they only change through `set_delay_timer`,
+    /// `set_sound_timer` and `tick_timers`, freezing time while single-stepping
+    pub fn manual_timers(mut self, value: bool) -> Self {
+        self.config.manual_timers = value;
+        self
+    }
 
-        let pc = self.pc as usize;
-        let b1 = self.memory.data[pc] as u16;
-        let b2 = self.memory.data[pc + 1] as u16;
+    /// Run CHIP-8X programs: 02A0 and BXYN set the colours of the VP-590 colour board, replacing
+    /// the BNNN jump, 5XY1 adds the nibbles of two registers and EXF2 and EXF5 read the second
+    /// keypad
+    #[cfg(feature = "chip8x")]
+    pub fn chip8x(mut self, value: bool) -> Self {
+        self.config.chip8x = value;
+        self
+    }
 
-        self.pc += 2;
+    /// Run programs for the two-page hires CHIP-8 interpreter: the 1260 jump they start with
+    /// switches to a 64x64 screen and runs the program from `TWO_PAGE_ENTRY`, and 0230 clears the
+    /// screen
+    #[cfg(feature = "two_page")]
+    pub fn two_page_hires(mut self, value: bool) -> Self {
+        self.config.two_page = value;
+        self
+    }
 
-        b1 << 8 | b2
+    /// Set how FX0A chooses between several keys held while it waits
+    pub fn key_wait_policy(mut self, value: KeyWaitPolicy) -> Self {
+        self.config.key_wait_policy = value;
+        self
     }
 
-    fn decode(&mut self, opcode: u16) -> Opcode {
-        Opcode {
-            c: ((opcode & 0xF000) >> 12) as u8,
-            x: ((opcode & 0x0F00) >> 8) as u8,
-            y: ((opcode & 0x00F0) >> 4) as u8,
-            n: (opcode & 0x000F) as u8,
-            nn: (opcode & 0x00FF) as u8,
-            nnn: opcode & 0x0FFF,
-        }
+    /// Set the number of frames FX0A waits without any key activity before emitting
+    /// `Event::WaitingForKey`, 0 never reports it
+    pub fn key_wait_timeout(mut self, frames: u32) -> Self {
+        self.config.key_wait_timeout = frames;
+        self
     }
 
-    fn execute(&mut self, opcode: Opcode) {
-        match opcode.c {
-            0x0 => match (opcode.x, opcode.y, opcode.n) {
-                (0, 0xE, 0) => self.op_cls(),
-                (0, 0xE, 0xE) => self.op_sub_return(),
-                _ => self.bail_invalid_op(opcode, true).unwrap(),
-            },
-            0x1 => self.op_jump(opcode.nnn),
-            0x2 => self.op_sub_call(opcode.nnn),
-            0x3 => self.op_skip_eq(opcode.x, opcode.nn),
-            0x4 => self.op_skip_ne(opcode.x, opcode.nn),
-            0x5 => self.op_skip_reg_eq(opcode.x, opcode.y),
-            0x6 => self.op_set(opcode.x, opcode.nn),
-            0x7 => self.op_add(opcode.x, opcode.nn),
-            0x8 => match opcode.n {
-                0x0 => self.op_reg_set(opcode.x, opcode.y),
-                0x1 => self.op_reg_or(opcode.x, opcode.y),
-                0x2 => self.op_reg_and(opcode.x, opcode.y),
-                0x3 => self.op_reg_xor(opcode.x, opcode.y),
-                0x4 => self.op_reg_add(opcode.x, opcode.y),
-                0x5 => self.op_reg_sub_right(opcode.x, opcode.y),
-                0x6 => self.op_reg_shift_right(opcode.x, opcode.y),
-                0x7 => self.op_reg_sub_left(opcode.x, opcode.y),
-                0xE => self.op_reg_shift_left(opcode.x, opcode.y),
-                _ => self.bail_invalid_op(opcode, false).unwrap(),
-            },
-            0x9 => self.op_skip_reg_ne(opcode.x, opcode.y),
-            0xA => self.op_set_index(opcode.nnn),
-            0xB => self.op_jump_with_offset(opcode.nnn, opcode.x),
-            0xC => self.op_random(opcode.x, opcode.nn),
-            0xD => self.op_display(opcode.x, opcode.y, opcode.n),
-            0xE => match opcode.nn {
-                0x9E => self.op_skip_if_key_down(opcode.x),
-                0xA1 => self.op_skip_if_key_up(opcode.x),
-                _ => self.bail_invalid_op(opcode, false).unwrap(),
-            },
-            0xF => match opcode.nn {
-                0x07 => self.op_dt_get(opcode.x),
-                0x0A => self.op_get_key(opcode.x),
-                0x15 => self.op_dt_set(opcode.x),
-                0x18 => self.op_st_set(opcode.x),
-                0x1E => self.op_add_to_index(opcode.x),
-                0x29 => self.op_font_character(opcode.x),
-                0x33 => self.op_convert_to_decimal(opcode.x),
-                0x55 => self.op_memory_store(opcode.x),
-                0x65 => self.op_memory_load(opcode.x),
-                _ => self.bail_invalid_op(opcode, false).unwrap(),
-            },
-            _ => self.bail_invalid_op(opcode, false).unwrap(),
-        }
+    /// Set the number of presses per second generated while an autofire key is held
+    pub fn autofire_rate(mut self, value: u32) -> Self {
+        self.keypad.autofire_rate = value.max(1);
+        self
     }
 
-    fn bail_invalid_op(&self, opcode: Opcode, machine_code: bool) -> anyhow::Result<()> {
-        bail!(
-            "invalid opcode '{}' encountered at {:#04x}{}",
-            opcode,
-            self.pc - 2,
-            if machine_code {
-                " (machine code is not supported)"
-            } else {
-                ""
-            }
-        );
+    pub fn buzzer_frequency(mut self, value: f32) -> Self {
+        self.buzzer.frequency = value;
+        self
     }
 
-    fn print_op(&self, s: String) {
-        if self.config.print_operations {
-            println!("{}", s);
-        }
+    pub fn buzzer_waveform(mut self, value: Waveform) -> Self {
+        self.buzzer.waveform = value;
+        self
     }
 
-    /* Operations */
+    /// Set the length of the attack/release ramp applied when the buzzer starts and stops
+    pub fn buzzer_envelope_ms(mut self, value: f32) -> Self {
+        self.buzzer.envelope_ms = value.max(0.0);
+        self
+    }
 
-    /// 0x00E0
-    fn op_cls(&mut self) {
-        self.print_op(format!("op_cls(00E0)"));
-        self.display.clear();
+    /// Limit how deep subroutine calls can nest, between 1 and `STACK_SIZE`, such as the 12 levels
+    /// of the COSMAC VIP. A call past the limit halts with `HaltReason::StackOverflow`.
+    pub fn stack_depth(mut self, depth: usize) -> Self {
+        self.config.stack_depth = depth.clamp(1, STACK_SIZE);
+        self
     }
 
-    /// 0x00EE
-    fn op_sub_return(&mut self) {
-        self.print_op(format!("op_sub_return(00EE)"));
-        assert!(self.sp > 0, "sub return with empty stack");
-        self.sp -= 1;
-        self.pc = self.stack[self.sp as usize];
+    /// Set the size of memory in bytes, clamped between the smallest size that can hold the font
+    /// and a one instruction ROM and `XO_CHIP_MEM_SIZE`, clearing anything loaded so far
+    pub fn memory_size(mut self, size: usize) -> Self {
+        self.memory = Memory::new(size.clamp(ROM_ADDR + 2, XO_CHIP_MEM_SIZE));
+        self.write_fonts();
+        self
     }
 
-    /// 0x1NNN
-    fn op_jump(&mut self, nnn: u16) {
-        self.print_op(format!("op_jump(1NNN) {:#04x}", nnn));
-        self.pc = nnn;
+    /// Move the font to `addr`, which is where FX29 points into, clamped so the whole font stays
+    /// below the ROM address. Some interpreters kept it at 0x000 and a few programs read it directly.
+    pub fn font_address(mut self, addr: usize) -> Self {
+        let old = self.font_range();
+        self.memory.data[old].fill(0);
+        self.config.font_addr = addr.min(self.config.rom_addr.saturating_sub(FONT_DATA.len()));
+        self.write_fonts();
+        self
     }
 
-    /// 0x2NNN
-    fn op_sub_call(&mut self, nnn: u16) {
-        self.print_op(format!("op_sub_call(2NNN) {:#04x}", nnn));
-        assert!(self.sp < 12, "call stack overflow");
-        self.stack[self.sp as usize] = self.pc;
-        self.sp += 1;
-        self.pc = nnn;
+    /// Load ROMs at `addr` rather than `ROM_ADDR`, such as `ETI_660_ROM_ADDR` for ETI-660
+    /// programs, and start running them there
+    pub fn rom_address(mut self, addr: usize) -> Self {
+        self.config.rom_addr = addr;
+        self.pc = addr as u16;
+        self
     }
 
-    /// 0x3XNN
-    fn op_skip_eq(&mut self, x: u8, nn: u8) {
-        self.print_op(format!("op_jump_eq(3XNN) {:#02x} {:#02x}", x, nn));
-        if self.v[x as usize] == nn {
-            self.pc += 2;
-        }
+    /// Start running loaded ROMs at `addr` rather than the address they're loaded at, e.g. to
+    /// load data in front of the code
+    pub fn start_pc(mut self, addr: u16) -> Self {
+        self.config.start_pc = Some(addr);
+        self.pc = addr;
+        self
     }
 
-    /// 0x4XNN
-    fn op_skip_ne(&mut self, x: u8, nn: u8) {
-        self.print_op(format!("op_skip_ne(0x4XNN) {:#02x} {:#02x}", x, nn));
-        if self.v[x as usize] != nn {
-            self.pc += 2;
-        }
+    /// Use the font of a historical interpreter, which is written wherever the font is
+    pub fn font_set(mut self, set: FontSet) -> Self {
+        self.config.font = *set.data();
+        self.write_fonts();
+        self
     }
 
-    /// 0x5XY0
-    fn op_skip_reg_eq(&mut self, x: u8, y: u8) {
-        self.print_op(format!("op_skip_reg_eq(5XY0) {:#02x} {:#02x}", x, y));
-        if self.v[x as usize] == self.v[y as usize] {
-            self.pc += 2;
-        }
+    /// Replace the built-in font FX29 points into with `font`, such as the glyphs of a particular
+    /// historical interpreter. It's kept when the font is moved or memory is resized.
+    pub fn set_font(&mut self, font: &[u8; FONT_CHAR_LENGTH * 0x10]) -> anyhow::Result<()> {
+        let range = self.font_range();
+        ensure!(
+            range.end <= self.config.rom_addr,
+            "font at {:#05x}..{:#05x} overlaps the rom at {:#05x}",
+            range.start,
+            range.end,
+            self.config.rom_addr
+        );
+        self.config.font = *font;
+        self.write_fonts();
+        Ok(())
     }
 
-    /// 0x6XNN
-    fn op_set(&mut self, x: u8, nn: u8) {
-        self.print_op(format!("op_set(6XNN) {:#02x} {:#02x}", x, nn));
-        self.v[x as usize] = nn;
+    /// Replace the built-in big font FX30 points into with `font`
+    pub fn set_big_font(&mut self, font: &[u8; BIG_FONT_CHAR_LENGTH * 0x10]) -> anyhow::Result<()> {
+        let range = self.big_font_range();
+        ensure!(
+            range.end <= self.config.rom_addr,
+            "big font at {:#05x}..{:#05x} overlaps the rom at {:#05x}",
+            range.start,
+            range.end,
+            self.config.rom_addr
+        );
+        self.config.big_font = *font;
+        self.write_fonts();
+        Ok(())
     }
 
-    /// 0x7XNN
-    fn op_add(&mut self, x: u8, nn: u8) {
-        self.print_op(format!("op_add(7XNN) {:#02x} {:#02x}", x, nn));
-        self.v[x as usize] = self.v[x as usize].wrapping_add(nn);
+    /// Write both fonts into memory, the small one last so it wins if it's been moved over the
+    /// big one
+    fn write_fonts(&mut self) {
+        self.memory
+            .write(BIG_FONT_ADDR, &self.config.big_font)
+            .expect("big font fits below the rom address");
+        self.memory
+            .write(self.config.font_addr, &self.config.font)
+            .expect("font fits below the rom address");
     }
 
-    /// 0x8XY0
-    fn op_reg_set(&mut self, x: u8, y: u8) {
-        self.print_op(format!("op_reg_set(8XY0) {:#02x} {:#02x}", x, y));
-        self.v[x as usize] = self.v[y as usize];
+    /// The addresses the font occupies
+    pub fn font_range(&self) -> std::ops::Range<usize> {
+        self.config.font_addr..self.config.font_addr + FONT_DATA.len()
     }
 
-    /// 0x8XY1
-    fn op_reg_or(&mut self, x: u8, y: u8) {
-        self.print_op(format!("op_reg_or(8XY1) {:#02x} {:#02x}", x, y));
-        self.v[x as usize] |= self.v[y as usize];
+    /// The addresses the big font FX30 points into occupies
+    pub fn big_font_range(&self) -> std::ops::Range<usize> {
+        BIG_FONT_ADDR..BIG_FONT_ADDR + BIG_FONT_DATA.len()
     }
 
-    /// 0x8XY2
-    fn op_reg_and(&mut self, x: u8, y: u8) {
-        self.print_op(format!("op_reg_and(8XY2) {:#02x} {:#02x}", x, y));
-        self.v[x as usize] &= self.v[y as usize];
+    /// Keep the RPL user flags in `store`, loading the ones saved there and saving them again
+    /// whenever FX75 runs. They aren't part of savestates, so loading a state leaves them as they
+    /// are, like the flags of the calculator outliving the program.
+    pub fn set_flag_store(&mut self, store: Box<dyn FlagStore + Send>) -> anyhow::Result<()> {
+        if let Some(flags) = store.load().context("load flags")? {
+            self.flags = flags;
+        }
+        self.flag_store = Some(store);
+        Ok(())
     }
 
-    /// 0x8XY3
-    fn op_reg_xor(&mut self, x: u8, y: u8) {
-        self.print_op(format!("op_reg_xor(8XY3) {:#02x} {:#02x}", x, y));
-        self.v[x as usize] ^= self.v[y as usize];
+    /// The RPL user flags
+    pub fn flags(&self) -> &[u8; FLAG_COUNT] {
+        &self.flags
     }
 
-    /// 0x8XY4
-    fn op_reg_add(&mut self, x: u8, y: u8) {
-        self.print_op(format!("op_reg_add(8XY4) {:#02x} {:#02x}", x, y));
-        let (sum, overflow) = self.v[x as usize].overflowing_add(self.v[y as usize]);
-        self.v[x as usize] = sum;
-        self.v[0xF] = overflow as u8;
+    pub fn memory_size_bytes(&self) -> usize {
+        self.memory.size()
     }
 
-    /// 0x8XY5
-    fn op_reg_sub_right(&mut self, x: u8, y: u8) {
-        self.print_op(format!("op_reg_sub_right(8XY5) {:#02x} {:#02x}", x, y));
-        let (sum, overflow) = self.v[x as usize].overflowing_sub(self.v[y as usize]);
-        self.v[x as usize] = sum;
-        self.v[0xF] = !overflow as u8;
+    pub fn volume(mut self, value: f32) -> Self {
+        self.buzzer.volume = value.clamp(0.0, 1.0);
+        self
     }
 
-    /// 0x8XY6
-    fn op_reg_shift_right(&mut self, x: u8, y: u8) {
-        self.print_op(format!("op_reg_shift_right(8XY6) {:#02} {:#02}", x, y));
-        if self.config.legacy_shift {
-            self.v[x as usize] = self.v[y as usize];
+    /// Load `rom` at the ROM address, `ROM_ADDR` unless it's been changed with `rom_address`, and
+    /// start running it there, or at the address given to `start_pc`
+    pub fn load_rom(&mut self, rom: &[u8]) -> anyhow::Result<()> {
+        self.load_rom_at(self.config.rom_addr, rom)?;
+        if let Some(pc) = self.config.start_pc {
+            self.pc = pc;
         }
-        let flag = self.v[x as usize] & 0x1;
-        self.v[x as usize] >>= 1;
-        self.v[0xF] = flag;
+        Ok(())
     }
 
-    /// 0x8XY7
-    fn op_reg_sub_left(&mut self, x: u8, y: u8) {
-        self.print_op(format!("op_reg_sub_left(8XY7) {:#02x} {:#02x}", x, y));
-        let (sum, overflow) = self.v[y as usize].overflowing_sub(self.v[x as usize]);
-        self.v[x as usize] = sum;
-        self.v[0xF] = !overflow as u8;
+    /// Load `rom` at `addr` and start running it there
+    pub fn load_rom_at(&mut self, addr: usize, rom: &[u8]) -> anyhow::Result<()> {
+        ensure!(
+            addr + rom.len() <= self.memory.size(),
+            "rom is {} bytes, more than fit above {:#05x} in {} bytes of memory",
+            rom.len(),
+            addr,
+            self.memory.size()
+        );
+        self.memory
+            .write(addr, rom)
+            .context("write rom into memory")?;
+        self.start_program(addr as u16);
+        Ok(())
     }
 
-    /// 0x8XYE
-    fn op_reg_shift_left(&mut self, x: u8, y: u8) {
-        self.print_op(format!("op_reg_shift_left(8XYE) {:#02} {:#02}", x, y));
-        if self.config.legacy_shift {
-            self.v[x as usize] = self.v[y as usize];
+    /// Load every segment of `image` and start executing at its entry point
+    /// Nothing is written if any segment doesn't fit in memory
+    pub fn load_image(&mut self, image: &ProgramImage) -> anyhow::Result<()> {
+        for segment in &image.segments {
+            ensure!(
+                segment.addr as usize + segment.data.len() <= self.memory.size(),
+                "segment at {:#06x} doesn't fit in {} bytes of memory",
+                segment.addr,
+                self.memory.size()
+            );
         }
-        let flag = self.v[x as usize] >> 7 & 0x1;
-        self.v[x as usize] <<= 1;
-        self.v[0xF] = flag;
-    }
+        ensure!(
+            (image.entry as usize) + 2 <= self.memory.size(),
+            "entry point {:#06x} is outside memory",
+            image.entry
+        );
 
-    /// 0x9XY0
-    fn op_skip_reg_ne(&mut self, x: u8, y: u8) {
-        self.print_op(format!("op_skip_reg_ne(9XY0) {:#02x} {:#02x}", x, y));
-        if self.v[x as usize] != self.v[y as usize] {
-            self.pc += 2;
+        for segment in &image.segments {
+            self.memory
+                .write(segment.addr as usize, &segment.data)
+                .context("write segment into memory")?;
         }
+        self.start_program(image.entry);
+        Ok(())
     }
 
-    /// 0xANNN
-    fn op_set_index(&mut self, nnn: u16) {
-        self.print_op(format!("op_set_index(ANNN) {:#04x}", nnn));
-        self.i = nnn;
+    /// Forget everything observed about the previous program and start executing at `entry`
+    fn start_program(&mut self, entry: u16) {
+        self.memory.clear_executed();
+        self.code_modifications = 0;
+        self.idle.reset();
+        self.pc = entry;
     }
 
-    /// 0xBNNN
-    fn op_jump_with_offset(&mut self, nnn: u16, x: u8) {
-        self.print_op(format!("op_jump_with_offset(BNNN) {:#04x}", nnn));
-        let idx = if self.config.jump_add_offset {
-            x as usize
-        } else {
-            0
-        };
-        self.pc = nnn + self.v[idx] as u16;
+    #[cfg(feature = "std")]
+    pub fn load_rom_from_file(&mut self, path: PathBuf) -> anyhow::Result<()> {
+        let buf = std::fs::read(path).context("read rom file")?;
+        self.load_rom(&buf).context("load rom from file")?;
+        Ok(())
     }
 
-    /// 0xCNNN
-    fn op_random(&mut self, x: u8, nn: u8) {
-        self.print_op(format!("op_random(CXNN) {:#02x} {:#02x}", x, nn));
-        self.v[x as usize] = nn & rand::rng().random::<u8>();
+    pub fn is_fb_dirty(&self) -> bool {
+        self.display.is_dirty()
     }
 
-    /// 0xDXYN
-    fn op_display(&mut self, x: u8, y: u8, n: u8) {
-        self.print_op(format!("op_display(DXYN) {:#02x} {:#02x} {:#02x}", x, y, n));
-        let vx = self.v[x as usize] as usize % SCREEN_WIDTH;
-        let vy = self.v[y as usize] as usize % SCREEN_HEIGHT;
-        self.v[0xF] = 0;
-
-        for row in 0..n as usize {
-            let y = vy + row;
-            if y >= SCREEN_HEIGHT {
-                break;
-            }
+    /// Iterate over the framebuffer rows that changed since `fb` was last called
+    pub fn dirty_rows(&self) -> impl Iterator<Item = usize> + '_ {
+        self.display.dirty_rows()
+    }
 
-            let byte = self.memory.data[self.i as usize + row];
-            for col in 0..8 {
-                let x = vx + col;
-                if x >= SCREEN_WIDTH {
+    pub fn is_sound_playing(&self) -> bool {
+        self.st > 0
+    }
+
+    /// Synthesize `out.len()` mono samples of buzzer output at `sample_rate`
+    /// The tone is gated per emulated frame, so requesting `sample_rate / 60` samples after each
+    /// `cycle` keeps onsets and offsets sample-accurate relative to the sound timer
+    pub fn render_audio(&mut self, out: &mut [f32], sample_rate: u32) {
+        self.buzzer
+            .render_frames(out, sample_rate, self.is_sound_playing());
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.buzzer.muted
+    }
+
+    pub fn set_muted(&mut self, muted: bool) {
+        self.buzzer.muted = muted;
+    }
+
+    pub fn toggle_mute(&mut self) {
+        self.buzzer.muted = !self.buzzer.muted;
+    }
+
+    pub fn fb(&mut self) -> Frame {
+        self.display.fb()
+    }
+
+    /// The width of the screen in its current resolution
+    pub fn screen_width(&self) -> usize {
+        self.display.fb.width()
+    }
+
+    /// The height of the screen in its current resolution
+    pub fn screen_height(&self) -> usize {
+        self.display.fb.height()
+    }
+
+    /// Whether the program has switched to SUPER-CHIP's 128x64 high resolution mode
+    pub fn is_hires(&self) -> bool {
+        self.display.fb.is_hires()
+    }
+
+    /// The colours of the CHIP-8X colour board, which the screen is drawn in instead of the
+    /// palette when running CHIP-8X programs
+    pub fn chip8x_colours(&self) -> Option<&ColourZones> {
+        self.config.chip8x.then_some(&self.colours)
+    }
+
+    pub fn keydown(&mut self, key: Key) -> anyhow::Result<()> {
+        self.keypad.keydown(key)
+    }
+
+    pub fn keyup(&mut self, key: Key) -> anyhow::Result<()> {
+        self.keypad.keyup(key)
+    }
+
+    /// Press or release `key` on the first keypad directly, bypassing any host key translation
+    /// Injected events behave exactly like host events, so they're useful for deterministic tests
+    pub fn inject_key_event(&mut self, key: Key, pressed: bool) -> anyhow::Result<()> {
+        if pressed {
+            self.keypad.keydown(key)
+        } else {
+            self.keypad.keyup(key)
+        }
+    }
+
+    /// Press `key` on the first keypad for the next `frames` frames, then release it
+    pub fn press_key_for(&mut self, key: Key, frames: u32) {
+        self.keypad.press_for(key, frames);
+    }
+
+    /// The number of instructions run each second
+    pub fn ips(&self) -> usize {
+        self.config.clock_hz as usize
+    }
+
+    /// Change the clock speed while running, in instructions per second
+    pub fn set_clock_hz(&mut self, hz: u32) {
+        self.config.clock_hz = hz;
+        self.clock_carry = 0;
+    }
+
+    /// Change the speed while running, never going below one instruction per second
+    pub fn set_instructions_per_second(&mut self, value: usize) {
+        self.set_clock_hz(value.clamp(1, u32::MAX as usize) as u32);
+    }
+
+    /// Speed up by `steps` steps of about a tenth each, or slow down if `steps` is negative,
+    /// never going below one instruction per frame
+    pub fn adjust_speed(&mut self, steps: i32) {
+        let min = FRAME_RATE as u32;
+        for _ in 0..steps.unsigned_abs() {
+            let hz = self.config.clock_hz;
+            let step = (hz / 10).max(min);
+            self.config.clock_hz = if steps > 0 {
+                hz.saturating_add(step)
+            } else {
+                hz.saturating_sub(step).max(min)
+            };
+        }
+    }
+
+    /// Run `frames` frames back to back with `cycle`, stopping at the first fault
+    pub fn run_frames(&mut self, frames: u32) -> Result<StepOutcome, Chip8Error> {
+        for _ in 0..frames {
+            self.cycle()?;
+        }
+        Ok(self.outcome())
+    }
+
+    pub fn player_keydown(&mut self, player: Player, key: Key) -> anyhow::Result<()> {
+        self.keypad_mut(player).keydown(key)
+    }
+
+    pub fn player_keyup(&mut self, player: Player, key: Key) -> anyhow::Result<()> {
+        self.keypad_mut(player).keyup(key)
+    }
+
+    /// Return the keys held on a keypad as a bitmask, with bit N set while key N is held
+    pub fn keypad_mask(&self, player: Player) -> u16 {
+        match player {
+            Player::One => self.keypad.mask(),
+            Player::Two => self.second_keypad.mask(),
+        }
+    }
+
+    /// Hold exactly the keys set in `mask` on a keypad, e.g. to apply input received from a peer
+    pub fn set_keypad_mask(&mut self, player: Player, mask: u16) {
+        self.keypad_mut(player).set_mask(mask);
+    }
+
+    /// Hold exactly the keys in `mask` on `player`'s keypad from the start of the next frame rather
+    /// than straight away, so the input for a frame can be composed while emulation is paused
+    pub fn latch_input(&mut self, player: Player, mask: u16) {
+        self.latched_input[player as usize] = Some(mask);
+    }
+
+    /// The keys `player`'s keypad will hold at the start of the next frame
+    pub fn next_frame_input(&self, player: Player) -> u16 {
+        self.latched_input[player as usize].unwrap_or_else(|| self.keypad_mask(player))
+    }
+
+    /// Toggle whether `key` is held in the input latched for the next frame. Only its low nibble
+    /// picks the key.
+    pub fn toggle_latched_key(&mut self, player: Player, key: u8) {
+        let mask = self.next_frame_input(player) ^ (1 << (key & 0xF));
+        self.latch_input(player, mask);
+    }
+
+    fn keypad_mut(&mut self, player: Player) -> &mut Keypad {
+        match player {
+            Player::One => &mut self.keypad,
+            Player::Two => &mut self.second_keypad,
+        }
+    }
+
+    /// Make `key` repeatedly press and release itself while it's held down
+    pub fn set_autofire(&mut self, key: u8, enabled: bool) {
+        self.keypad.set_autofire(key, enabled);
+    }
+
+    pub fn toggle_autofire(&mut self, key: u8) {
+        let enabled = self.keypad.is_autofire(key);
+        self.keypad.set_autofire(key, !enabled);
+    }
+
+    pub fn is_autofire(&self, key: u8) -> bool {
+        self.keypad.is_autofire(key)
+    }
+
+    /// Start playing `input_macro` on the first keypad from the next frame, replacing any macro
+    /// already playing
+    pub fn play_macro(&mut self, input_macro: InputMacro) {
+        self.stop_macro();
+        self.macro_player = Some(MacroPlayer::new(input_macro));
+    }
+
+    /// Stop the playing macro, releasing any key it's holding down
+    pub fn stop_macro(&mut self) {
+        if let Some(mut player) = self.macro_player.take() {
+            player.stop(&mut self.keypad);
+        }
+    }
+
+    pub fn is_macro_playing(&self) -> bool {
+        self.macro_player.is_some()
+    }
+
+    /// Iterate over the keys currently held down on the host
+    pub fn held_keys(&self) -> impl Iterator<Item = u8> + '_ {
+        self.keypad.held_keys()
+    }
+
+    /// Run a single 60 Hz frame: execute the frame's share of the clock speed's instructions, then
+    /// tick the timers once
+    /// If `run_until` stopped partway through a frame, only the rest of that frame is run, and a
+    /// fault stops the frame at the instruction that caused it
+    pub fn cycle(&mut self) -> Result<StepOutcome, Chip8Error> {
+        if self.frame_ops == 0 {
+            self.begin_frame();
+        }
+        while !self.is_frame_over() {
+            self.step_in_frame()?;
+        }
+        self.end_frame();
+        self.frame_ops = 0;
+        Ok(self.outcome())
+    }
+
+    /// Run the next instruction as part of the current frame, counting it against the frame's
+    /// instructions or, with VIP timing, its machine cycles
+    pub(crate) fn step_in_frame(&mut self) -> Result<StepOutcome, Chip8Error> {
+        if self.config.vip_timing {
+            self.frame_cycles += vip_cycles(self.next_opcode());
+        }
+        let outcome = self.step()?;
+        self.frame_ops += 1;
+        Ok(outcome)
+    }
+
+    /// Whether the current frame has run all the instructions it has time for
+    pub(crate) fn is_frame_over(&self) -> bool {
+        if self.vblank_wait {
+            return true;
+        }
+        if self.config.vip_timing {
+            self.frame_cycles >= VIP_CYCLES_PER_FRAME
+        } else {
+            self.frame_ops >= self.frame_budget
+        }
+    }
+
+    /// Apply the input for a new frame, before any of its instructions run
+    pub(crate) fn begin_frame(&mut self) {
+        self.events.clear();
+        self.frame_drew = false;
+        self.vblank_wait = false;
+        // saturating, as the carry would overflow the fastest clock
+        let hz = self.config.clock_hz.saturating_add(self.clock_carry);
+        self.frame_budget = (hz / FRAME_RATE as u32) as usize;
+        self.clock_carry = hz % FRAME_RATE as u32;
+        // the first snapshot is taken once the program is loaded and about to start
+        if self.rewind.as_ref().is_some_and(RewindBuffer::is_empty) {
+            let state = self.save_state();
+            if let Some(rewind) = self.rewind.as_mut() {
+                rewind.push(state);
+            }
+        }
+        for player in [Player::One, Player::Two] {
+            if let Some(mask) = self.latched_input[player as usize].take() {
+                self.set_keypad_mask(player, mask);
+            }
+        }
+        if let Some(player) = self.macro_player.as_mut() {
+            player.tick(&mut self.keypad);
+            if player.is_finished() {
+                self.macro_player = None;
+            }
+        }
+        self.keypad.tick();
+        self.second_keypad.tick();
+    }
+
+    /// Finish a frame once its instructions have run
+    pub(crate) fn end_frame(&mut self) {
+        // the tone sounds for every frame that ends with a non-zero sound timer, so it lasts
+        // exactly as many frames as the value the program loaded into ST
+        self.buzzer.push_frame(self.is_sound_playing());
+        // an instruction that ran past the end of the frame takes its time from the next one,
+        // while waiting for the vertical blank uses up the rest of this one
+        self.frame_cycles = self.frame_cycles.saturating_sub(VIP_CYCLES_PER_FRAME);
+        if !self.config.manual_timers {
+            self.tick_timers();
+        }
+
+        let key_down = (0..0x10).any(|key| self.keypad.is_key_down(key));
+        if self.key_wait_spun && !key_down {
+            self.key_wait_frames += 1;
+            if self.key_wait_frames == self.config.key_wait_timeout {
+                self.events.push(Event::WaitingForKey);
+            }
+        } else {
+            self.key_wait_frames = 0;
+        }
+        self.key_wait_spun = false;
+
+        if self.rewind.is_some() {
+            self.frames_since_snapshot += 1;
+            if self.frames_since_snapshot == REWIND_INTERVAL {
+                let state = self.save_state();
+                if let Some(rewind) = self.rewind.as_mut() {
+                    rewind.push(state);
+                }
+                self.frames_since_snapshot = 0;
+            }
+        }
+    }
+
+    /// Return true once FX0A has waited for the configured timeout without any key activity,
+    /// until a key is pressed or the program moves on
+    pub fn is_waiting_for_key(&self) -> bool {
+        self.config.key_wait_timeout != 0 && self.key_wait_frames >= self.config.key_wait_timeout
+    }
+
+    /// The number of seconds that can currently be rewound
+    pub fn rewind_available(&self) -> usize {
+        let snapshots = self.rewind.as_ref().map_or(0, RewindBuffer::len);
+        if self.frames_since_snapshot == 0 {
+            snapshots.saturating_sub(1)
+        } else {
+            snapshots
+        }
+    }
+
+    /// Go back `seconds` seconds by restoring an earlier rewind snapshot, returning the number of
+    /// seconds actually rewound, which is less than requested once the oldest snapshot is reached
+    pub fn rewind(&mut self, seconds: usize) -> anyhow::Result<usize> {
+        let Some(rewind) = self.rewind.as_mut() else {
+            bail!("rewinding is disabled");
+        };
+        if seconds == 0 {
+            return Ok(0);
+        }
+
+        // returning to the newest snapshot counts as a second unless it was only just taken
+        let at_snapshot = self.frames_since_snapshot == 0;
+        let steps = if at_snapshot { seconds } else { seconds - 1 };
+        let (state, stepped) = rewind.step_back(steps)?;
+        self.load_state(&state).context("restore rewind snapshot")?;
+        self.frames_since_snapshot = 0;
+        Ok(if at_snapshot { stepped } else { stepped + 1 })
+    }
+
+    /// Decrement the delay and sound timers by one 60 Hz tick
+    /// Timers are only ticked at frame boundaries so the instruction loop carries no timer overhead
+    pub fn tick_timers(&mut self) {
+        self.dt = self.dt.saturating_sub(1);
+        self.set_sound_timer(self.st.saturating_sub(1));
+    }
+
+    /// The events emitted during the last frame, in the order they happened
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    pub fn set_delay_timer(&mut self, value: u8) {
+        self.dt = value;
+    }
+
+    /// Update the sound timer, emitting an event when the buzzer starts or stops
+    pub fn set_sound_timer(&mut self, value: u8) {
+        let was_playing = self.is_sound_playing();
+        self.st = value;
+        match (was_playing, self.is_sound_playing()) {
+            (false, true) => self.events.push(Event::SoundStarted),
+            (true, false) => self.events.push(Event::SoundStopped),
+            _ => {}
+        }
+    }
+
+    /// The opcode at the program counter, which is the next one `step` will execute
+    pub fn next_opcode(&self) -> u16 {
+        let pc = self.pc as usize;
+        let size = self.memory.size();
+        let b1 = self.memory.data[pc % size] as u16;
+        let b2 = self.memory.data[(pc + 1) % size] as u16;
+        b1 << 8 | b2
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    /// The variable registers V0 through VF
+    pub fn registers(&self) -> &[u8; REGISTER_COUNT] {
+        &self.v
+    }
+
+    pub fn index(&self) -> u16 {
+        self.i
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.dt
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.st
+    }
+
+    /// The return addresses of the subroutines currently being run, outermost first
+    pub fn call_stack(&self) -> &[u16] {
+        &self.stack[..self.sp as usize]
+    }
+
+    /// How many subroutines deep execution is, out of the `stack_depth` calls can nest
+    pub fn call_depth(&self) -> (usize, usize) {
+        (self.sp as usize, self.config.stack_depth)
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        &self.memory.data
+    }
+
+    /// Write `bytes` straight into memory at `addr`, as a debugger or cheat would, refusing writes
+    /// below the ROM address when write protection is on
+    pub fn poke(&mut self, addr: u16, bytes: &[u8]) -> anyhow::Result<()> {
+        let addr = addr as usize;
+        ensure!(
+            addr + bytes.len() <= self.memory.size(),
+            "poke at {:#05x} runs past the end of memory",
+            addr
+        );
+        ensure!(
+            !self.config.write_protect || addr >= self.config.rom_addr,
+            "poke at {:#05x} is below the write protected rom address {:#05x}",
+            addr,
+            self.config.rom_addr
+        );
+        self.memory.data[addr..addr + bytes.len()].copy_from_slice(bytes);
+        Ok(())
+    }
+
+    /// Why execution stopped, if it has
+    pub fn halt_reason(&self) -> Option<&HaltReason> {
+        self.halted.as_ref()
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted.is_some()
+    }
+
+    /// Continue executing after a halt, retrying the instruction that caused it
+    pub fn resume(&mut self) {
+        self.halted = None;
+    }
+
+    /// Stop executing, leaving the program counter on the instruction that caused the halt
+    fn halt(&mut self, reason: HaltReason) {
+        self.pc = self.op_pc;
+        self.events.push(Event::Halted(reason.clone()));
+        self.halted = Some(reason);
+    }
+
+    /// Abandon the instruction being executed for `step` to return `error`, leaving the program
+    /// counter on it
+    fn fault(&mut self, error: Chip8Error) {
+        self.pc = self.op_pc;
+        self.fault = Some(error);
+    }
+
+    /// The size of the memory addresses wrap around in, which is only 0x1000 when they're masked
+    /// to 12 bits
+    fn address_space(&self) -> usize {
+        match self.config.address_overflow_policy {
+            AddressOverflowPolicy::Mask => self.memory.size().min(0x1000),
+            _ => self.memory.size(),
+        }
+    }
+
+    /// Where `addr` ends up once it's wrapped around the address space
+    fn wrap_address(&self, addr: usize) -> usize {
+        addr % self.address_space()
+    }
+
+    /// Check that the `len` bytes from `addr` are in memory, faulting or halting if they aren't
+    /// and the address overflow policy doesn't wrap them. Returns false if the instruction stops.
+    fn check_address(&mut self, addr: usize, len: usize) -> bool {
+        if addr + len <= self.address_space() || self.config.address_overflow_policy.wraps() {
+            return true;
+        }
+        self.address_overflow(addr as u16);
+        false
+    }
+
+    /// Fault or halt on an access past the end of memory from `addr`, whichever the address
+    /// overflow policy asks for
+    fn address_overflow(&mut self, addr: u16) {
+        let pc = self.op_pc;
+        match self.config.address_overflow_policy {
+            AddressOverflowPolicy::Error => {
+                self.fault(Chip8Error::AddressOverflow { pc, addr });
+            }
+            _ => self.halt(HaltReason::AddressOverflow { pc, addr }),
+        }
+    }
+
+    /// Move the program counter `len` bytes forward, wrapping it around the address space if the
+    /// policy wraps. The program counter can't hold the address past the top of a 64K memory, so
+    /// without wrapping the last word there overflows. Returns false if the instruction stops.
+    fn advance_pc(&mut self, len: usize) -> bool {
+        let pc = self.pc as usize + len;
+        if self.config.address_overflow_policy.wraps() {
+            self.pc = self.wrap_address(pc) as u16;
+            return true;
+        }
+        match u16::try_from(pc) {
+            Ok(pc) => {
+                self.pc = pc;
+                true
+            }
+            Err(_) => {
+                self.address_overflow(self.pc);
+                false
+            }
+        }
+    }
+
+    /// The byte at `addr`, wrapped around the address space
+    fn read_byte(&self, addr: usize) -> u8 {
+        self.memory.data[self.wrap_address(addr)]
+    }
+
+    /// Write `bytes` to memory starting at `addr`, returning false if the write halted instead
+    fn write_memory(&mut self, addr: usize, bytes: &[u8]) -> bool {
+        if addr + bytes.len() > self.address_space() {
+            // only writes the policy wraps get here, and they're written a byte at a time
+            for (offset, byte) in bytes.iter().enumerate() {
+                if !self.write_memory(self.wrap_address(addr + offset), &[*byte]) {
+                    return false;
+                }
+            }
+            return true;
+        }
+        if self.config.write_protect && addr < self.config.rom_addr {
+            self.halt(HaltReason::ProtectedWrite {
+                pc: self.op_pc,
+                addr: addr as u16,
+            });
+            return false;
+        }
+
+        let modified = (addr..addr + bytes.len()).filter(|addr| self.memory.is_executed(*addr));
+        let mut first = None;
+        for modified in modified {
+            first.get_or_insert(modified);
+            self.code_modifications += 1;
+        }
+        if let Some(first) = first {
+            self.events.push(Event::CodeModified {
+                pc: self.op_pc,
+                addr: first as u16,
+            });
+        }
+
+        #[cfg(feature = "debugger")]
+        if self.pipeline.is_active() {
+            let before = &self.memory.data[addr..addr + bytes.len()];
+            self.pipeline.record_write(addr, before, bytes);
+        }
+        self.memory.data[addr..addr + bytes.len()].copy_from_slice(bytes);
+        true
+    }
+
+    /// The number of bytes written to addresses that instructions had already been fetched from
+    /// Each write also emits `Event::CodeModified`
+    pub fn code_modifications(&self) -> u64 {
+        self.code_modifications
+    }
+
+    /// The number of instructions executed since the interpreter was created
+    pub fn instructions_run(&self) -> u64 {
+        self.instructions_run
+    }
+
+    /// Return true if an instruction has been fetched from `addr` since the ROM was loaded
+    pub fn is_executed(&self, addr: u16) -> bool {
+        self.memory.is_executed(addr as usize)
+    }
+
+    /// Run the next instruction, unless execution has halted
+    pub fn step(&mut self) -> Result<StepOutcome, Chip8Error> {
+        if self.halted.is_some() {
+            return Ok(self.outcome());
+        }
+        self.instructions_run += 1;
+        #[cfg(feature = "debugger")]
+        if self.pipeline.is_active() {
+            self.step_traced();
+        } else {
+            self.execute_next();
+        }
+        #[cfg(not(feature = "debugger"))]
+        self.execute_next();
+        match self.fault.take() {
+            Some(error) => Err(error),
+            None => Ok(self.outcome()),
+        }
+    }
+
+    /// Whether execution can carry on
+    fn outcome(&self) -> StepOutcome {
+        match &self.halted {
+            Some(reason) => StepOutcome::Halted(reason.clone()),
+            None => StepOutcome::Running,
+        }
+    }
+
+    /// Fetch, decode and execute the next instruction
+    fn execute_next(&mut self) {
+        self.op_pc = self.pc;
+        let Some(opcode) = self.fetch() else {
+            return;
+        };
+        if self.config.detect_idle_loops {
+            let state = LoopState {
+                pc: self.op_pc,
+                v: self.v,
+                i: self.i,
+                sp: self.sp,
+            };
+            if self.idle.observe(opcode, state) {
+                self.halt(HaltReason::IdleLoop { pc: state.pc });
+                return;
+            }
+        }
+        let opcode = self.decode(opcode);
+        self.execute(opcode);
+    }
+
+    fn fetch(&mut self) -> Option<u16> {
+        if cfg!(feature = "trace") && self.config.print_operations {
+            print!("{:#02x} ", self.pc);
+        }
+
+        self.fetch_word()
+    }
+
+    /// Read the word at the program counter and move past it, returning None if the instruction
+    /// stops because the word is past the end of memory
+    fn fetch_word(&mut self) -> Option<u16> {
+        let pc = self.pc as usize;
+        if !self.check_address(pc, 2) {
+            return None;
+        }
+        let (addr1, addr2) = (self.wrap_address(pc), self.wrap_address(pc + 1));
+        self.memory.mark_executed(addr1);
+        self.memory.mark_executed(addr2);
+        let b1 = self.memory.data[addr1] as u16;
+        let b2 = self.memory.data[addr2] as u16;
+
+        if !self.advance_pc(2) {
+            return None;
+        }
+        Some(b1 << 8 | b2)
+    }
+
+    /// Move past the next instruction, which is two words long if it's F000 NNNN
+    fn skip_instruction(&mut self) {
+        self.advance_pc(instruction_length(self.next_opcode()));
+    }
+
+    fn decode(&mut self, opcode: u16) -> Opcode {
+        Opcode {
+            c: ((opcode & 0xF000) >> 12) as u8,
+            x: ((opcode & 0x0F00) >> 8) as u8,
+            y: ((opcode & 0x00F0) >> 4) as u8,
+            n: (opcode & 0x000F) as u8,
+            nn: (opcode & 0x00FF) as u8,
+            nnn: opcode & 0x0FFF,
+        }
+    }
+
+    fn execute(&mut self, opcode: Opcode) {
+        match opcode.c {
+            0x0 => match (opcode.x, opcode.y, opcode.n) {
+                (0, 0xE, 0) => self.op_cls(),
+                (0, 0xE, 0xE) => self.op_sub_return(),
+                #[cfg(feature = "chip8x")]
+                (2, 0xA, 0) if self.config.chip8x => self.op_cycle_background(),
+                #[cfg(feature = "two_page")]
+                (2, 3, 0) if self.config.two_page => self.op_two_page_cls(),
+                #[cfg(feature = "schip")]
+                (0, 0xC, n) => self.op_scroll_down(n),
+                #[cfg(feature = "xochip")]
+                (0, 0xD, n) => self.op_scroll_up(n),
+                #[cfg(feature = "schip")]
+                (0, 0xF, 0xB) => self.op_scroll_right(),
+                #[cfg(feature = "schip")]
+                (0, 0xF, 0xC) => self.op_scroll_left(),
+                #[cfg(feature = "schip")]
+                (0, 0xF, 0xD) => self.op_exit(),
+                #[cfg(feature = "schip")]
+                (0, 0xF, 0xE) => self.op_lores(),
+                #[cfg(feature = "schip")]
+                (0, 0xF, 0xF) => self.op_hires(),
+                _ => self.invalid_op(opcode),
+            },
+            0x1 => self.op_jump(opcode.nnn),
+            0x2 => self.op_sub_call(opcode.nnn),
+            0x3 => self.op_skip_eq(opcode.x, opcode.nn),
+            0x4 => self.op_skip_ne(opcode.x, opcode.nn),
+            0x5 => match opcode.n {
+                #[cfg(feature = "xochip")]
+                0x2 => self.op_range_store(opcode.x, opcode.y),
+                #[cfg(feature = "xochip")]
+                0x3 => self.op_range_load(opcode.x, opcode.y),
+                #[cfg(feature = "chip8x")]
+                0x1 if self.config.chip8x => self.op_add_nibbles(opcode.x, opcode.y),
+                _ => self.op_skip_reg_eq(opcode.x, opcode.y),
+            },
+            0x6 => self.op_set(opcode.x, opcode.nn),
+            0x7 => self.op_add(opcode.x, opcode.nn),
+            0x8 => match opcode.n {
+                0x0 => self.op_reg_set(opcode.x, opcode.y),
+                0x1 => self.op_reg_or(opcode.x, opcode.y),
+                0x2 => self.op_reg_and(opcode.x, opcode.y),
+                0x3 => self.op_reg_xor(opcode.x, opcode.y),
+                0x4 => self.op_reg_add(opcode.x, opcode.y),
+                0x5 => self.op_reg_sub_right(opcode.x, opcode.y),
+                0x6 => self.op_reg_shift_right(opcode.x, opcode.y),
+                0x7 => self.op_reg_sub_left(opcode.x, opcode.y),
+                0xE => self.op_reg_shift_left(opcode.x, opcode.y),
+                _ => self.invalid_op(opcode),
+            },
+            0x9 => self.op_skip_reg_ne(opcode.x, opcode.y),
+            0xA => self.op_set_index(opcode.nnn),
+            #[cfg(feature = "chip8x")]
+            0xB if self.config.chip8x => self.op_colour(opcode.x, opcode.y, opcode.n),
+            0xB => self.op_jump_with_offset(opcode.nnn, opcode.x),
+            0xC => self.op_random(opcode.x, opcode.nn),
+            0xD => self.op_display(opcode.x, opcode.y, opcode.n),
+            0xE => match opcode.nn {
+                0x9E => self.op_skip_if_key_down(opcode.x),
+                0xA1 => self.op_skip_if_key_up(opcode.x),
+                #[cfg(feature = "chip8x")]
+                0xF2 if self.config.chip8x => self.op_skip_if_second_key_down(opcode.x),
+                #[cfg(feature = "chip8x")]
+                0xF5 if self.config.chip8x => self.op_skip_if_second_key_up(opcode.x),
+                _ => self.invalid_op(opcode),
+            },
+            0xF => match opcode.nn {
+                #[cfg(feature = "xochip")]
+                0x00 if opcode.x == 0 => self.op_long_index(),
+                #[cfg(feature = "xochip")]
+                0x01 => self.op_plane_select(opcode.x),
+                #[cfg(feature = "xochip")]
+                0x02 if opcode.x == 0 => self.op_audio_pattern(),
+                0x07 => self.op_dt_get(opcode.x),
+                0x0A => self.op_get_key(opcode.x),
+                0x15 => self.op_dt_set(opcode.x),
+                0x18 => self.op_st_set(opcode.x),
+                0x1E => self.op_add_to_index(opcode.x),
+                0x29 => self.op_font_character(opcode.x),
+                #[cfg(feature = "schip")]
+                0x30 => self.op_big_font_character(opcode.x),
+                0x33 => self.op_convert_to_decimal(opcode.x),
+                #[cfg(feature = "xochip")]
+                0x3A => self.op_pitch_set(opcode.x),
+                0x55 => self.op_memory_store(opcode.x),
+                0x65 => self.op_memory_load(opcode.x),
+                #[cfg(feature = "schip")]
+                0x75 => self.op_flags_store(opcode.x),
+                #[cfg(feature = "schip")]
+                0x85 => self.op_flags_load(opcode.x),
+                _ => self.invalid_op(opcode),
+            },
+            _ => self.invalid_op(opcode),
+        }
+    }
+
+    /// Run the custom handler for an otherwise invalid opcode, falling back to the invalid opcode
+    /// policy if there isn't one
+    fn invalid_op(&mut self, opcode: Opcode) {
+        let raw = (opcode.c as u16) << 12 | opcode.nnn;
+        if self.run_custom_opcode(raw) {
+            return;
+        }
+        match self.config.invalid_opcode_policy {
+            InvalidOpcodePolicy::Error => self.fault(Chip8Error::InvalidOpcode {
+                pc: self.op_pc,
+                opcode: raw,
+            }),
+            InvalidOpcodePolicy::Halt => self.halt(HaltReason::InvalidOpcode {
+                pc: self.op_pc,
+                opcode: raw,
+            }),
+            InvalidOpcodePolicy::Skip => {}
+        }
+    }
+
+    /* Operations */
+
+    /// 0x00E0
+    fn op_cls(&mut self) {
+        trace_op!(self, "op_cls(00E0)");
+        self.display.clear();
+        self.frame_drew = true;
+    }
+
+    /// 0x00EE
+    fn op_sub_return(&mut self) {
+        trace_op!(self, "op_sub_return(00EE)");
+        if self.sp == 0 {
+            self.halt(HaltReason::StackUnderflow { pc: self.op_pc });
+            return;
+        }
+        self.sp -= 1;
+        self.pc = self.stack[self.sp as usize];
+    }
+
+    /// 0x0230
+    #[cfg(feature = "two_page")]
+    fn op_two_page_cls(&mut self) {
+        trace_op!(self, "op_two_page_cls(0230)");
+        self.display.clear();
+        self.frame_drew = true;
+    }
+
+    /// 0x02A0
+    #[cfg(feature = "chip8x")]
+    fn op_cycle_background(&mut self) {
+        trace_op!(self, "op_cycle_background(02A0)");
+        self.colours.cycle_background();
+        self.display.mark_dirty();
+    }
+
+    /// 0x00CN
+    #[cfg(feature = "schip")]
+    fn op_scroll_down(&mut self, n: u8) {
+        trace_op!(self, "op_scroll_down(00CN) {}", n);
+        self.display.scroll_down(n as usize);
+        self.frame_drew = true;
+    }
+
+    /// 0x00DN
+    #[cfg(feature = "xochip")]
+    fn op_scroll_up(&mut self, n: u8) {
+        trace_op!(self, "op_scroll_up(00DN) {}", n);
+        self.display.scroll_up(n as usize);
+        self.frame_drew = true;
+    }
+
+    /// 0x00FB
+    #[cfg(feature = "schip")]
+    fn op_scroll_right(&mut self) {
+        trace_op!(self, "op_scroll_right(00FB)");
+        self.display.scroll_right(4);
+        self.frame_drew = true;
+    }
+
+    /// 0x00FC
+    #[cfg(feature = "schip")]
+    fn op_scroll_left(&mut self) {
+        trace_op!(self, "op_scroll_left(00FC)");
+        self.display.scroll_left(4);
+        self.frame_drew = true;
+    }
+
+    /// 0x00FD
+    #[cfg(feature = "schip")]
+    fn op_exit(&mut self) {
+        trace_op!(self, "op_exit(00FD)");
+        self.halt(HaltReason::Exited { pc: self.op_pc });
+    }
+
+    /// 0x00FE
+    #[cfg(feature = "schip")]
+    fn op_lores(&mut self) {
+        trace_op!(self, "op_lores(00FE)");
+        self.display.set_hires(false);
+        self.frame_drew = true;
+    }
+
+    /// 0x00FF
+    #[cfg(feature = "schip")]
+    fn op_hires(&mut self) {
+        trace_op!(self, "op_hires(00FF)");
+        self.display.set_hires(true);
+        self.frame_drew = true;
+    }
+
+    /// 0x1NNN
+    fn op_jump(&mut self, nnn: u16) {
+        trace_op!(self, "op_jump(1NNN) {:#04x}", nnn);
+        // the jump at the start of the program is into the two-page interpreter, which takes the
+        // place of the usual one below 0x2C0
+        #[cfg(feature = "two_page")]
+        if self.config.two_page && nnn == 0x260 && self.pc == ROM_ADDR as u16 + 2 {
+            self.display.set_two_page();
+            self.frame_drew = true;
+            self.pc = TWO_PAGE_ENTRY;
+            return;
+        }
+        self.pc = nnn;
+    }
+
+    /// 0x2NNN
+    fn op_sub_call(&mut self, nnn: u16) {
+        trace_op!(self, "op_sub_call(2NNN) {:#04x}", nnn);
+        if self.sp as usize >= self.config.stack_depth {
+            self.halt(HaltReason::StackOverflow { pc: self.op_pc });
+            return;
+        }
+        self.stack[self.sp as usize] = self.pc;
+        self.sp += 1;
+        self.pc = nnn;
+    }
+
+    /// 0x3XNN
+    fn op_skip_eq(&mut self, x: u8, nn: u8) {
+        trace_op!(self, "op_jump_eq(3XNN) {:#02x} {:#02x}", x, nn);
+        if self.v[x as usize] == nn {
+            self.skip_instruction();
+        }
+    }
+
+    /// 0x4XNN
+    fn op_skip_ne(&mut self, x: u8, nn: u8) {
+        trace_op!(self, "op_skip_ne(0x4XNN) {:#02x} {:#02x}", x, nn);
+        if self.v[x as usize] != nn {
+            self.skip_instruction();
+        }
+    }
+
+    /// 0x5XY0
+    fn op_skip_reg_eq(&mut self, x: u8, y: u8) {
+        trace_op!(self, "op_skip_reg_eq(5XY0) {:#02x} {:#02x}", x, y);
+        if self.v[x as usize] == self.v[y as usize] {
+            self.skip_instruction();
+        }
+    }
+
+    /// The registers from VX to VY in order, counting down if Y is below X
+    #[cfg(feature = "xochip")]
+    fn register_range(x: u8, y: u8) -> impl Iterator<Item = usize> {
+        let (x, y) = (x as usize, y as usize);
+        (0..=x.abs_diff(y)).map(move |n| if x <= y { x + n } else { x - n })
+    }
+
+    /// 0x5XY2
+    #[cfg(feature = "xochip")]
+    fn op_range_store(&mut self, x: u8, y: u8) {
+        trace_op!(self, "op_range_store(5XY2) {:#02x} {:#02x}", x, y);
+        let values: Vec<u8> = Self::register_range(x, y).map(|r| self.v[r]).collect();
+        if self.check_address(self.i as usize, values.len()) {
+            self.write_memory(self.i as usize, &values);
+        }
+    }
+
+    /// 0x5XY3
+    #[cfg(feature = "xochip")]
+    fn op_range_load(&mut self, x: u8, y: u8) {
+        trace_op!(self, "op_range_load(5XY3) {:#02x} {:#02x}", x, y);
+        let start = self.i as usize;
+        let len = x.abs_diff(y) as usize + 1;
+        if !self.check_address(start, len) {
+            return;
+        }
+        for (offset, r) in Self::register_range(x, y).enumerate() {
+            self.v[r] = self.read_byte(start + offset);
+        }
+    }
+
+    /// 0x5XY1
+    #[cfg(feature = "chip8x")]
+    fn op_add_nibbles(&mut self, x: u8, y: u8) {
+        trace_op!(self, "op_add_nibbles(5XY1) {:#02x} {:#02x}", x, y);
+        // each nibble is added on its own as a three bit number, carrying into nothing
+        let (vx, vy) = (self.v[x as usize], self.v[y as usize]);
+        let high = ((vx >> 4) + (vy >> 4)) & 0x7;
+        let low = ((vx & 0xF) + (vy & 0xF)) & 0x7;
+        self.v[x as usize] = high << 4 | low;
+    }
+
+    /// 0x6XNN
+    fn op_set(&mut self, x: u8, nn: u8) {
+        trace_op!(self, "op_set(6XNN) {:#02x} {:#02x}", x, nn);
+        self.v[x as usize] = nn;
+    }
+
+    /// 0x7XNN
+    fn op_add(&mut self, x: u8, nn: u8) {
+        trace_op!(self, "op_add(7XNN) {:#02x} {:#02x}", x, nn);
+        self.v[x as usize] = self.v[x as usize].wrapping_add(nn);
+    }
+
+    /// 0x8XY0
+    fn op_reg_set(&mut self, x: u8, y: u8) {
+        trace_op!(self, "op_reg_set(8XY0) {:#02x} {:#02x}", x, y);
+        self.v[x as usize] = self.v[y as usize];
+    }
+
+    /// 0x8XY1
+    fn op_reg_or(&mut self, x: u8, y: u8) {
+        trace_op!(self, "op_reg_or(8XY1) {:#02x} {:#02x}", x, y);
+        self.v[x as usize] |= self.v[y as usize];
+    }
+
+    /// 0x8XY2
+    fn op_reg_and(&mut self, x: u8, y: u8) {
+        trace_op!(self, "op_reg_and(8XY2) {:#02x} {:#02x}", x, y);
+        self.v[x as usize] &= self.v[y as usize];
+    }
+
+    /// 0x8XY3
+    fn op_reg_xor(&mut self, x: u8, y: u8) {
+        trace_op!(self, "op_reg_xor(8XY3) {:#02x} {:#02x}", x, y);
+        self.v[x as usize] ^= self.v[y as usize];
+    }
+
+    /// 0x8XY4
+    fn op_reg_add(&mut self, x: u8, y: u8) {
+        trace_op!(self, "op_reg_add(8XY4) {:#02x} {:#02x}", x, y);
+        let (sum, overflow) = self.v[x as usize].overflowing_add(self.v[y as usize]);
+        self.v[x as usize] = sum;
+        self.v[0xF] = overflow as u8;
+    }
+
+    /// 0x8XY5
+    fn op_reg_sub_right(&mut self, x: u8, y: u8) {
+        trace_op!(self, "op_reg_sub_right(8XY5) {:#02x} {:#02x}", x, y);
+        let (sum, overflow) = self.v[x as usize].overflowing_sub(self.v[y as usize]);
+        self.v[x as usize] = sum;
+        self.v[0xF] = !overflow as u8;
+    }
+
+    /// 0x8XY6
+    fn op_reg_shift_right(&mut self, x: u8, y: u8) {
+        trace_op!(self, "op_reg_shift_right(8XY6) {:#02} {:#02}", x, y);
+        if self.config.quirks.legacy_shift {
+            self.v[x as usize] = self.v[y as usize];
+        }
+        let flag = self.v[x as usize] & 0x1;
+        self.v[x as usize] >>= 1;
+        self.v[0xF] = flag;
+    }
+
+    /// 0x8XY7
+    fn op_reg_sub_left(&mut self, x: u8, y: u8) {
+        trace_op!(self, "op_reg_sub_left(8XY7) {:#02x} {:#02x}", x, y);
+        let (sum, overflow) = self.v[y as usize].overflowing_sub(self.v[x as usize]);
+        self.v[x as usize] = sum;
+        self.v[0xF] = !overflow as u8;
+    }
+
+    /// 0x8XYE
+    fn op_reg_shift_left(&mut self, x: u8, y: u8) {
+        trace_op!(self, "op_reg_shift_left(8XYE) {:#02} {:#02}", x, y);
+        if self.config.quirks.legacy_shift {
+            self.v[x as usize] = self.v[y as usize];
+        }
+        let flag = self.v[x as usize] >> 7 & 0x1;
+        self.v[x as usize] <<= 1;
+        self.v[0xF] = flag;
+    }
+
+    /// 0x9XY0
+    fn op_skip_reg_ne(&mut self, x: u8, y: u8) {
+        trace_op!(self, "op_skip_reg_ne(9XY0) {:#02x} {:#02x}", x, y);
+        if self.v[x as usize] != self.v[y as usize] {
+            self.skip_instruction();
+        }
+    }
+
+    /// 0xANNN
+    fn op_set_index(&mut self, nnn: u16) {
+        trace_op!(self, "op_set_index(ANNN) {:#04x}", nnn);
+        self.i = nnn;
+    }
+
+    /// 0xBNNN
+    fn op_jump_with_offset(&mut self, nnn: u16, x: u8) {
+        trace_op!(self, "op_jump_with_offset(BNNN) {:#04x}", nnn);
+        let idx = if self.config.quirks.jump_add_offset {
+            x as usize
+        } else {
+            0
+        };
+        self.pc = nnn + self.v[idx] as u16;
+    }
+
+    /// 0xBXYN
+    #[cfg(feature = "chip8x")]
+    fn op_colour(&mut self, x: u8, y: u8, n: u8) {
+        trace_op!(self, "op_colour(BXYN) {:#02x} {:#02x} {:#02x}", x, y, n);
+        let next = self.v[(x as usize + 1) % REGISTER_COUNT];
+        let vx = self.v[x as usize] as usize;
+        if n == 0 {
+            // VX and VX+1 hold the first zone in the low nibble and the number of zones after it
+            // in the high nibble, across in 8x4 zones and down in the same way
+            let (column, columns) = (vx & 0xF, (vx >> 4) + 1);
+            let (row, rows) = ((next & 0xF) as usize, (next >> 4) as usize + 1);
+            self.colours.fill(
+                column,
+                columns,
+                row * COLOUR_ZONE_HEIGHT,
+                rows * COLOUR_ZONE_HEIGHT,
+                self.v[y as usize],
+            );
+        } else {
+            // the 8 pixel wide column at VX, N rows down from VY, in the colour in VX+1
+            let vy = self.v[y as usize] as usize;
+            self.colours
+                .fill(vx / COLOUR_ZONE_WIDTH, 1, vy, n as usize, next);
+        }
+        self.display.mark_dirty();
+    }
+
+    /// 0xCNNN
+    fn op_random(&mut self, x: u8, nn: u8) {
+        trace_op!(self, "op_random(CXNN) {:#02x} {:#02x}", x, nn);
+        self.v[x as usize] = nn & self.rng.byte();
+    }
+
+    /// 0xDXYN
+    fn op_display(&mut self, x: u8, y: u8, n: u8) {
+        trace_op!(self, "op_display(DXYN) {:#02x} {:#02x} {:#02x}", x, y, n);
+        let (width, height) = (self.screen_width(), self.screen_height());
+        let vx = self.v[x as usize] as usize % width;
+        let vy = self.v[y as usize] as usize % height;
+        self.v[0xF] = 0;
+        self.frame_drew = true;
+        self.vblank_wait = self.config.quirks.display_wait;
+
+        // SUPER-CHIP draws a 16x16 sprite of two bytes a row for N=0 in its high resolution
+        // mode, while plain CHIP-8 programs get the zero rows they expect
+        let (rows, row_bytes) = match n {
+            #[cfg(feature = "schip")]
+            0 if self.is_hires() => (16, 2),
+            n => (n as usize, 1),
+        };
+        let sprite_width = row_bytes * 8;
+        let wrap = self.config.quirks.wrap_sprites;
+        // each selected plane is drawn from its own copy of the sprite, the first plane's first
+        let planes = self.display.planes;
+        let mut sprite = self.i as usize;
+        for plane in [1, 2].into_iter().filter(|plane| planes & plane != 0) {
+            if !self.check_address(sprite, rows * row_bytes) {
+                return;
+            }
+            for row in 0..rows {
+                let y = vy + row;
+                if y >= height && !wrap {
                     break;
                 }
 
-                if (byte >> (7 - col)) & 0x1 == 1 {
-                    if self.display.toggle(x, y) {
+                let start = sprite + row * row_bytes;
+                let bits = (start..start + row_bytes)
+                    .fold(0u16, |bits, addr| bits << 8 | self.read_byte(addr) as u16);
+                for col in 0..sprite_width {
+                    let x = vx + col;
+                    if x >= width && !wrap {
+                        break;
+                    }
+
+                    if (bits >> (sprite_width - 1 - col)) & 0x1 == 1
+                        && self.display.toggle_wrapping(x, y, plane)
+                    {
                         self.v[0xF] = 1;
                     }
                 }
             }
+            sprite += rows * row_bytes;
         }
     }
 
     /// 0xEX9E
     fn op_skip_if_key_down(&mut self, x: u8) {
-        self.print_op(format!("op_skip_if_key_down(EX9E) {:#02x}", x));
+        trace_op!(self, "op_skip_if_key_down(EX9E) {:#02x}", x);
         if self.keypad.is_key_down(self.v[x as usize]) {
-            self.pc += 2;
+            self.skip_instruction();
         }
     }
 
     /// 0xEXA1
     fn op_skip_if_key_up(&mut self, x: u8) {
-        self.print_op(format!("op_skip_if_key_up(EXA1) {:#02x}", x));
+        trace_op!(self, "op_skip_if_key_up(EXA1) {:#02x}", x);
         if self.keypad.is_key_up(self.v[x as usize]) {
-            self.pc += 2;
+            self.skip_instruction();
+        }
+    }
+
+    /// 0xEXF2
+    #[cfg(feature = "chip8x")]
+    fn op_skip_if_second_key_down(&mut self, x: u8) {
+        trace_op!(self, "op_skip_if_second_key_down(EXF2) {:#02x}", x);
+        if self.second_keypad.is_key_down(self.v[x as usize]) {
+            self.skip_instruction();
+        }
+    }
+
+    /// 0xEXF5
+    #[cfg(feature = "chip8x")]
+    fn op_skip_if_second_key_up(&mut self, x: u8) {
+        trace_op!(self, "op_skip_if_second_key_up(EXF5) {:#02x}", x);
+        if self.second_keypad.is_key_up(self.v[x as usize]) {
+            self.skip_instruction();
+        }
+    }
+
+    /// 0xF000 NNNN
+    #[cfg(feature = "xochip")]
+    fn op_long_index(&mut self) {
+        let Some(nnnn) = self.fetch_word() else {
+            return;
+        };
+        trace_op!(self, "op_long_index(F000) {:#06x}", nnnn);
+        self.i = nnnn;
+    }
+
+    /// 0xFN01
+    #[cfg(feature = "xochip")]
+    fn op_plane_select(&mut self, n: u8) {
+        trace_op!(self, "op_plane_select(FN01) {:#02x}", n);
+        self.display.planes = n & ALL_PLANES;
+    }
+
+    /// 0xF002
+    #[cfg(feature = "xochip")]
+    fn op_audio_pattern(&mut self) {
+        trace_op!(self, "op_audio_pattern(F002)");
+        let start = self.i as usize;
+        if !self.check_address(start, AUDIO_PATTERN_LENGTH) {
+            return;
         }
+        let mut pattern = [0; AUDIO_PATTERN_LENGTH];
+        for (offset, byte) in pattern.iter_mut().enumerate() {
+            *byte = self.read_byte(start + offset);
+        }
+        self.buzzer.pattern = Some(pattern);
     }
 
     /// 0xFX07
     fn op_dt_get(&mut self, x: u8) {
-        self.print_op(format!("op_dt_get(FX07) {:#02x}", x));
+        trace_op!(self, "op_dt_get(FX07) {:#02x}", x);
         self.v[x as usize] = self.dt;
     }
 
     /// 0xFX0A
     fn op_get_key(&mut self, x: u8) {
-        self.print_op(format!("op_get_key(FX0A) {:#02x}", x));
-        if let Some(key) = self.keypad.awaiting_release {
-            if self.keypad.is_key_up(key) {
+        trace_op!(self, "op_get_key(FX0A) {:#02x}", x);
+        match self.keypad.poll_key_wait(self.config.key_wait_policy) {
+            Some(key) => {
                 self.v[x as usize] = key;
-                self.keypad.process_release();
-                return;
+                self.events.push(Event::KeyCaptured { key, register: x });
             }
-        }
-        for key in 0..0xF + 1 {
-            if self.keypad.is_key_down(key) {
-                self.keypad.await_release(key);
-                break;
+            None => {
+                self.key_wait_spun = true;
+                self.pc = self.op_pc;
             }
         }
-        self.pc -= 2;
     }
 
     /// 0xFX15
     fn op_dt_set(&mut self, x: u8) {
-        self.print_op(format!("op_dt_set(FX15) {:#02x}", x));
+        trace_op!(self, "op_dt_set(FX15) {:#02x}", x);
         self.dt = self.v[x as usize];
     }
 
-    /// 0xFX18
-    fn op_st_set(&mut self, x: u8) {
-        self.print_op(format!("op_st_set(FX18) {:#02x}", x));
-        self.st = self.v[x as usize];
+    /// 0xFX18
+    fn op_st_set(&mut self, x: u8) {
+        trace_op!(self, "op_st_set(FX18) {:#02x}", x);
+        self.set_sound_timer(self.v[x as usize]);
+    }
+
+    /// 0xFX1E
+    fn op_add_to_index(&mut self, x: u8) {
+        trace_op!(self, "op_add_to_index(FX1E) {:#02x}", x);
+        self.add_to_index(self.v[x as usize] as u16);
+    }
+
+    /// Move I forward by `n`, wrapping it around at the top of the 16-bit address space or at 12
+    /// bits when addresses are masked
+    fn add_to_index(&mut self, n: u16) {
+        self.i = self.i.wrapping_add(n);
+        if self.config.address_overflow_policy == AddressOverflowPolicy::Mask {
+            self.i &= 0xFFF;
+        }
+    }
+
+    /// 0xFX29
+    fn op_font_character(&mut self, x: u8) {
+        trace_op!(self, "op_font_character(FX29) {:#02x}", x);
+        let mask = if self.config.quirks.unmasked_font {
+            0xFF
+        } else {
+            0x0F
+        };
+        self.i = (self.config.font_addr + FONT_CHAR_LENGTH * (self.v[x as usize] & mask) as usize)
+            as u16;
+    }
+
+    /// 0xFX30
+    #[cfg(feature = "schip")]
+    fn op_big_font_character(&mut self, x: u8) {
+        trace_op!(self, "op_big_font_character(FX30) {:#02x}", x);
+        // the big font only has the 16 hex digits, so only the low nibble picks one, as FX29
+        // does without its quirk
+        self.i =
+            (BIG_FONT_ADDR + BIG_FONT_CHAR_LENGTH * (self.v[x as usize] & 0x0F) as usize) as u16;
+    }
+
+    /// 0xFX33
+    fn op_convert_to_decimal(&mut self, x: u8) {
+        trace_op!(self, "op_convert_to_decimal(FX33) {:#02x}", x);
+        let digits = BCD_TABLE[self.v[x as usize] as usize];
+        if self.check_address(self.i as usize, digits.len()) {
+            self.write_memory(self.i as usize, &digits);
+        }
+    }
+
+    /// 0xFX3A
+    #[cfg(feature = "xochip")]
+    fn op_pitch_set(&mut self, x: u8) {
+        trace_op!(self, "op_pitch_set(FX3A) {:#02x}", x);
+        self.buzzer.pitch = self.v[x as usize];
+    }
+
+    /// 0xFX55
+    fn op_memory_store(&mut self, x: u8) {
+        trace_op!(self, "op_memory_store(FX55) {:#02x}", x);
+        let count = x as usize + 1;
+        if !self.check_address(self.i as usize, count) {
+            return;
+        }
+        let registers = self.v;
+        if self.write_memory(self.i as usize, &registers[..count])
+            && self.config.quirks.memory_increment_i
+        {
+            self.add_to_index(count as u16);
+        }
+    }
+
+    /// 0xFX65
+    fn op_memory_load(&mut self, x: u8) {
+        trace_op!(self, "op_memory_load(FX65) {:#02x}", x);
+        let start = self.i as usize;
+        if !self.check_address(start, x as usize + 1) {
+            return;
+        }
+        for i in 0..(x as usize) + 1 {
+            self.v[i] = self.read_byte(start + i);
+            if self.config.quirks.memory_increment_i {
+                self.add_to_index(1);
+            }
+        }
+    }
+
+    /// 0xFX75
+    #[cfg(feature = "schip")]
+    fn op_flags_store(&mut self, x: u8) {
+        trace_op!(self, "op_flags_store(FX75) {:#02x}", x);
+        let len = x as usize + 1;
+        self.flags[..len].copy_from_slice(&self.v[..len]);
+        if let Some(store) = self.flag_store.as_mut() {
+            if let Err(e) = store.save(&self.flags) {
+                self.events.push(Event::FlagSaveFailed(format!("{:?}", e)));
+            }
+        }
+    }
+
+    /// 0xFX85
+    #[cfg(feature = "schip")]
+    fn op_flags_load(&mut self, x: u8) {
+        trace_op!(self, "op_flags_load(FX85) {:#02x}", x);
+        let len = x as usize + 1;
+        self.v[..len].copy_from_slice(&self.flags[..len]);
+    }
+}
+
+impl std::fmt::Display for Chip8 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "=== Memory ===\n{}", self.memory)?;
+        write!(f, "=== Display ===\n{}", self.display)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "xochip")]
+    use super::AUDIO_PATTERN_LENGTH;
+    #[cfg(feature = "two_page")]
+    use super::TWO_PAGE_ENTRY;
+    use super::{
+        AddressOverflowPolicy, Chip8, Chip8Error, Event, HaltReason, InvalidOpcodePolicy, Key,
+        Player, ProgramImage, Segment, StepOutcome, ETI_660_ROM_ADDR, FONT_ADDR, FONT_CHAR_LENGTH,
+        FONT_DATA, SCREEN_HEIGHT, SCREEN_WIDTH, STACK_SIZE, XO_CHIP_MEM_SIZE,
+    };
+    #[cfg(feature = "schip")]
+    use super::{
+        FlagStore, BIG_FONT_CHAR_LENGTH, BIG_FONT_DATA, FLAG_COUNT, HIRES_HEIGHT, HIRES_WIDTH,
+    };
+    #[cfg(feature = "chip8x")]
+    use super::{Frame, BACKGROUND_COLOURS, FOREGROUND_COLOURS};
+
+    #[test]
+    fn test_cycle_ticks_timers_once() {
+        let mut chip8 = Chip8::new().unwrap().clock_hz(240);
+        #[rustfmt::skip]
+        chip8.load_rom(&[
+            0x60, 0x05, // v0 = 5
+            0xF0, 0x15, // dt = v0
+            0xF0, 0x18, // st = v0
+            0x12, 0x06, // jump to self
+        ]).unwrap();
+
+        // the timers are set during the frame and only ticked once it has finished
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.dt, 4);
+        assert_eq!(chip8.st, 4);
+
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.dt, 3);
+        assert_eq!(chip8.st, 3);
+    }
+
+    #[test]
+    fn test_cycle_fault() {
+        let mut chip8 = Chip8::new().unwrap().clock_hz(240);
+        #[rustfmt::skip]
+        chip8.load_rom(&[
+            0x70, 0x01, // v0 += 1
+            0x70, 0x01, // v0 += 1
+            0x01, 0x24, // invalid
+            0x12, 0x06, // jump to self
+        ]).unwrap();
+        chip8.dt = 5;
+
+        // a fault stops the frame partway through, before the timers tick
+        let error = chip8.cycle().unwrap_err();
+        assert_eq!(
+            error,
+            Chip8Error::InvalidOpcode {
+                pc: 0x204,
+                opcode: 0x0124
+            }
+        );
+        assert_eq!(chip8.v[0], 2);
+        assert_eq!(chip8.pc, 0x204);
+        assert_eq!(chip8.dt, 5);
+
+        // the next cycle carries on with the rest of the frame from the faulting instruction
+        chip8.poke(0x204, &[0x70, 0x01]).unwrap();
+        assert_eq!(chip8.cycle(), Ok(StepOutcome::Running));
+        assert_eq!(chip8.v[0], 3);
+        assert_eq!(chip8.pc, 0x206);
+        assert_eq!(chip8.dt, 4);
+
+        // and running several frames stops at the first fault
+        let mut chip8 = Chip8::new().unwrap();
+        chip8.load_rom(&[0x70, 0x01, 0x01, 0x24]).unwrap();
+        chip8.dt = 10;
+        assert!(chip8.run_frames(3).is_err());
+        assert_eq!(chip8.v[0], 1);
+        assert_eq!(chip8.dt, 10);
+        assert!(chip8.run_frames(3).is_err());
+        assert_eq!(chip8.v[0], 1);
+    }
+
+    #[test]
+    fn test_cycle_display_wait() {
+        #[rustfmt::skip]
+        let rom = [
+            0x70, 0x01, // v0 += 1
+            0xD1, 0x15, // draw
+            0x70, 0x01, // v0 += 1
+            0xD1, 0x15, // draw
+            0x12, 0x08, // jump to self
+        ];
+        let mut chip8 = Chip8::new().unwrap().clock_hz(600).display_wait(true);
+        chip8.load_rom(&rom).unwrap();
+
+        // the draw uses up the rest of the frame, so the next instruction waits for the next one
+        chip8.cycle().unwrap();
+        assert_eq!((chip8.v[0], chip8.pc), (1, 0x204));
+        chip8.cycle().unwrap();
+        assert_eq!((chip8.v[0], chip8.pc), (2, 0x208));
+
+        let mut chip8 = Chip8::new().unwrap().clock_hz(600);
+        chip8.load_rom(&rom).unwrap();
+        chip8.cycle().unwrap();
+        assert_eq!((chip8.v[0], chip8.pc), (2, 0x208));
+    }
+
+    #[test]
+    fn test_sound_events() {
+        let mut chip8 = Chip8::new().unwrap().clock_hz(60);
+        #[rustfmt::skip]
+        chip8.load_rom(&[
+            0x60, 0x02, // v0 = 2
+            0xF0, 0x18, // st = v0
+            0x12, 0x04, // jump to self
+        ]).unwrap();
+
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.events(), []);
+
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.events(), [Event::SoundStarted]);
+
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.events(), [Event::SoundStopped]);
+
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.events(), []);
+    }
+
+    #[test]
+    fn test_player_keypads() {
+        let mut chip8 = Chip8::new().unwrap();
+        chip8
+            .player_keydown(Player::Two, Key::from_label("q"))
+            .unwrap();
+        assert_eq!(chip8.second_keypad.is_key_down(0x4), true);
+        assert_eq!(chip8.keypad.is_key_down(0x4), false);
+
+        chip8
+            .player_keydown(Player::One, Key::from_label("w"))
+            .unwrap();
+        assert_eq!(chip8.keypad.is_key_down(0x5), true);
+        assert_eq!(chip8.second_keypad.is_key_down(0x5), false);
+
+        chip8
+            .player_keyup(Player::Two, Key::from_label("q"))
+            .unwrap();
+        assert_eq!(chip8.second_keypad.is_key_down(0x4), false);
+    }
+
+    #[test]
+    fn test_play_macro() {
+        let mut chip8 = Chip8::new().unwrap().clock_hz(0);
+        chip8.play_macro("press 5, wait 1".parse().unwrap());
+        assert_eq!(chip8.is_macro_playing(), true);
+
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.keypad.is_key_down(0x5), true);
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.keypad.is_key_down(0x5), false);
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.is_macro_playing(), false);
+
+        chip8.play_macro("press 7 for 10 frames".parse().unwrap());
+        chip8.cycle().unwrap();
+        chip8.stop_macro();
+        assert_eq!(chip8.keypad.is_key_down(0x7), false);
+    }
+
+    #[test]
+    fn test_inject_key_event() {
+        let mut chip8 = Chip8::new().unwrap();
+        // wait for a key, then loop forever
+        chip8.load_rom(&[0xF1, 0x0A, 0x12, 0x02]).unwrap();
+
+        chip8.inject_key_event(Key::from_value(0xB), true).unwrap();
+        chip8.run_frames(2).unwrap();
+        assert_eq!(chip8.pc, 0x200);
+        chip8.inject_key_event(Key::from_value(0xB), false).unwrap();
+        chip8.run_frames(1).unwrap();
+        assert_eq!(chip8.v[1], 0xB);
+
+        chip8.load_rom(&[0xF2, 0x0A, 0x12, 0x02]).unwrap();
+        chip8.pc = 0x200;
+        chip8.press_key_for(Key::from_value(0x4), 3);
+        chip8.run_frames(3).unwrap();
+        assert_eq!(chip8.pc, 0x200);
+        chip8.run_frames(1).unwrap();
+        assert_eq!(chip8.v[2], 0x4);
+    }
+
+    #[test]
+    fn test_rewind() {
+        let mut chip8 = Chip8::new().unwrap().clock_hz(60).rewind_seconds(2);
+        // count up in V0 forever
+        chip8.load_rom(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+        assert!(Chip8::new().unwrap().rewind(1).is_err());
+
+        // two frames per increment, so V0 is 30 after each second
+        chip8.run_frames(130).unwrap();
+        assert_eq!(chip8.v[0], 65);
+        assert_eq!(chip8.rewind_available(), 2);
+        assert_eq!(chip8.rewind(1).unwrap(), 1);
+        assert_eq!(chip8.v[0], 60);
+        assert_eq!(chip8.rewind(1).unwrap(), 1);
+        assert_eq!(chip8.v[0], 30);
+        // only two seconds of snapshots are kept
+        assert_eq!(chip8.rewind(5).unwrap(), 0);
+        assert_eq!(chip8.v[0], 30);
+    }
+
+    #[test]
+    fn test_latch_input() {
+        let mut chip8 = Chip8::new().unwrap();
+        chip8.load_rom(&[0x12, 0x00]).unwrap();
+
+        chip8.toggle_latched_key(Player::One, 0x5);
+        chip8.toggle_latched_key(Player::One, 0xA);
+        chip8.toggle_latched_key(Player::One, 0x5);
+        assert_eq!(chip8.next_frame_input(Player::One), 1 << 0xA);
+        // nothing changes until the next frame starts
+        assert_eq!(chip8.keypad_mask(Player::One), 0);
+
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.keypad_mask(Player::One), 1 << 0xA);
+        assert_eq!(chip8.next_frame_input(Player::One), 1 << 0xA);
+
+        chip8.latch_input(Player::Two, 0b11);
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.keypad_mask(Player::Two), 0b11);
+        assert_eq!(chip8.keypad_mask(Player::One), 1 << 0xA);
+
+        // keys past 0xF wrap around rather than overflowing the shift
+        chip8.toggle_latched_key(Player::One, 0x1A);
+        assert_eq!(chip8.next_frame_input(Player::One), 0);
+        chip8.toggle_latched_key(Player::One, 0xFF);
+        assert_eq!(chip8.next_frame_input(Player::One), 1 << 0xF);
+    }
+
+    #[test]
+    fn test_adjust_speed() {
+        let mut chip8 = Chip8::new().unwrap().instructions_per_second(700);
+        assert_eq!(chip8.ips(), 700);
+
+        chip8.adjust_speed(2);
+        assert_eq!(chip8.ips(), 847);
+        chip8.adjust_speed(-1);
+        assert_eq!(chip8.ips(), 763);
+        chip8.adjust_speed(-100);
+        assert_eq!(chip8.ips(), 60);
+
+        chip8.set_instructions_per_second(0);
+        assert_eq!(chip8.ips(), 1);
+    }
+
+    #[test]
+    fn test_clock_hz() {
+        let mut chip8 = Chip8::new().unwrap().clock_hz(90);
+        chip8.load_rom(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+
+        // a frame and a half's worth of instructions a frame alternates between one and two
+        let mut ran = Vec::new();
+        for _ in 0..4 {
+            let before = chip8.instructions_run;
+            chip8.cycle().unwrap();
+            ran.push(chip8.instructions_run - before);
+        }
+        assert_eq!(ran, [1, 2, 1, 2]);
+
+        chip8.set_clock_hz(1_800_000);
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.frame_budget, 30_000);
+
+        // the fastest clock doesn't overflow when the carry from the last frame is added
+        chip8.set_clock_hz(u32::MAX);
+        for _ in 0..2 {
+            chip8.begin_frame();
+            assert_eq!(chip8.frame_budget, (u32::MAX / 60) as usize);
+        }
+        chip8.adjust_speed(1);
+        assert_eq!(chip8.config.clock_hz, u32::MAX);
+        chip8.set_instructions_per_second(usize::MAX);
+        assert_eq!(chip8.config.clock_hz, u32::MAX);
+    }
+
+    #[test]
+    fn test_rng_seed() {
+        let run = || {
+            let mut chip8 = Chip8::new().unwrap().rng_seed(42).clock_hz(960);
+            chip8.load_rom(&[0xC0, 0xFF].repeat(16)).unwrap();
+            chip8.cycle().unwrap();
+            chip8.state_hash()
+        };
+        assert_eq!(run(), run());
+    }
+
+    #[test]
+    fn test_memory_size() {
+        let mut chip8 = Chip8::new().unwrap().memory_size(XO_CHIP_MEM_SIZE);
+        assert_eq!(chip8.memory_size_bytes(), 0x10000);
+        assert_eq!(
+            chip8.memory.data[FONT_ADDR..FONT_ADDR + FONT_DATA.len()],
+            FONT_DATA
+        );
+        chip8.load_rom(&[0x00; 0x8000]).unwrap();
+
+        let mut chip8 = Chip8::new().unwrap().memory_size(0x800);
+        assert!(chip8.load_rom(&[0x00; 0x800]).is_err());
+        assert_eq!(
+            Chip8::new().unwrap().memory_size(0).memory_size_bytes(),
+            0x202
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "xochip")]
+    fn test_memory_size_large_rom() {
+        // run past 0x1000 and draw a sprite from high in memory with F000 NNNN
+        let mut rom = [0x60, 0x00].repeat(0x800);
+        rom.extend_from_slice(&[0xF0, 0x00, 0x90, 0x00, 0xD0, 0x01]);
+        let mut chip8 = Chip8::new().unwrap().memory_size(XO_CHIP_MEM_SIZE);
+        chip8.load_rom(&rom).unwrap();
+        chip8.memory.data[0x9000] = 0x80;
+        for _ in 0..0x802 {
+            chip8.step().unwrap();
+        }
+        assert_eq!(chip8.pc, 0x1206);
+        assert_eq!(chip8.display.is_set(0, 0), true);
+
+        let mut chip8 = Chip8::new().unwrap();
+        let error = chip8.load_rom(&rom).unwrap_err();
+        assert!(error.to_string().contains("4096 bytes of memory"));
+    }
+
+    #[test]
+    fn test_memory_size_sprite_bounds() {
+        let mut chip8 = Chip8::new().unwrap().memory_size(0x800);
+        chip8.load_rom(&[0xD0, 0x02]).unwrap();
+        chip8.i = 0x7FF;
+        let error = chip8.step().unwrap_err();
+        assert_eq!(
+            error,
+            Chip8Error::AddressOverflow {
+                pc: 0x200,
+                addr: 0x7FF
+            }
+        );
+        assert_eq!(chip8.pc, 0x200);
+    }
+
+    #[test]
+    fn test_memory_size_bounds() {
+        let mut chip8 = Chip8::new().unwrap().memory_size(0x800);
+        chip8.load_rom(&[0xF1, 0x55]).unwrap();
+        chip8.i = 0x7FF;
+        let error = chip8.step().unwrap_err();
+        assert_eq!(
+            error,
+            Chip8Error::AddressOverflow {
+                pc: 0x200,
+                addr: 0x7FF
+            }
+        );
+    }
+
+    #[test]
+    fn test_write_protect() {
+        let mut chip8 = Chip8::new().unwrap().write_protect(true);
+        chip8
+            .load_rom(&[0xA0, 0x50, 0xF0, 0x55, 0xA3, 0x00])
+            .unwrap();
+        chip8.cycle().unwrap();
+
+        let reason = HaltReason::ProtectedWrite {
+            pc: 0x202,
+            addr: 0x050,
+        };
+        assert_eq!(chip8.halt_reason(), Some(&reason));
+        assert_eq!(chip8.events(), [Event::Halted(reason)]);
+        assert_eq!(chip8.pc, 0x202);
+        assert_eq!(chip8.memory.data[FONT_ADDR], FONT_DATA[0]);
+
+        // without write protection the write goes through
+        let mut chip8 = Chip8::new().unwrap();
+        chip8.load_rom(&[0xA0, 0x50, 0xF0, 0x55]).unwrap();
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+        assert_eq!(chip8.is_halted(), false);
+        assert_eq!(chip8.memory.data[FONT_ADDR], 0);
+    }
+
+    #[test]
+    fn test_poke() {
+        let mut chip8 = Chip8::new().unwrap();
+        chip8.poke(FONT_ADDR as u16, &[0xAA]).unwrap();
+        assert_eq!(chip8.memory.data[FONT_ADDR], 0xAA);
+        assert!(chip8.poke(0xFFF, &[0, 0]).is_err());
+
+        let mut chip8 = Chip8::new().unwrap().write_protect(true);
+        assert!(chip8.poke(FONT_ADDR as u16, &[0xAA]).is_err());
+        assert_eq!(chip8.memory.data[FONT_ADDR], FONT_DATA[0]);
+        chip8.poke(0x200, &[0x12, 0x34]).unwrap();
+        assert_eq!(chip8.memory.data[0x200..0x202], [0x12, 0x34]);
+        assert_eq!(chip8.is_halted(), false);
+    }
+
+    #[test]
+    fn test_code_modifications() {
+        let mut chip8 = Chip8::new().unwrap();
+        // store V0 over the first instruction, then V0 and V1 straddling the end of the last one
+        chip8
+            .load_rom(&[0xA2, 0x00, 0xF0, 0x55, 0xA2, 0x07, 0xF1, 0x55])
+            .unwrap();
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+        assert_eq!(chip8.code_modifications(), 1);
+        assert_eq!(
+            chip8.events(),
+            [Event::CodeModified {
+                pc: 0x202,
+                addr: 0x200
+            }]
+        );
+
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+        assert_eq!(chip8.is_executed(0x207), true);
+        assert_eq!(chip8.is_executed(0x208), false);
+        assert_eq!(chip8.code_modifications(), 2);
+        assert_eq!(chip8.events().len(), 2);
+    }
+
+    #[test]
+    fn test_detect_idle_loops() {
+        let mut chip8 = Chip8::new().unwrap().detect_idle_loops(true);
+        chip8
+            .load_rom(&[0x60, 0x05, 0x40, 0x05, 0x12, 0x02])
+            .unwrap();
+        chip8.cycle().unwrap();
+        let reason = HaltReason::IdleLoop { pc: 0x204 };
+        assert_eq!(chip8.halt_reason(), Some(&reason));
+        assert_eq!(chip8.events(), [Event::Halted(reason)]);
+        assert_eq!(chip8.pc, 0x204);
+
+        // a loop counting through V0 is making progress
+        let mut chip8 = Chip8::new().unwrap().detect_idle_loops(true);
+        chip8.load_rom(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+        chip8.run_frames(10).unwrap();
+        assert_eq!(chip8.is_halted(), false);
+
+        // so is a loop that only scrolls the screen
+        #[cfg(feature = "schip")]
+        {
+            let mut chip8 = Chip8::new().unwrap().detect_idle_loops(true);
+            chip8.load_rom(&[0x00, 0xFB, 0x12, 0x00]).unwrap();
+            chip8.run_frames(10).unwrap();
+            assert_eq!(chip8.is_halted(), false);
+        }
+
+        // jumping to itself is idle, but only halts when detection is on
+        let mut chip8 = Chip8::new().unwrap();
+        chip8.load_rom(&[0x12, 0x00]).unwrap();
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.is_halted(), false);
+    }
+
+    #[test]
+    fn test_manual_timers() {
+        let mut chip8 = Chip8::new().unwrap().manual_timers(true);
+        chip8.load_rom(&[0x12, 0x00]).unwrap();
+        chip8.set_delay_timer(3);
+        chip8.set_sound_timer(2);
+        chip8.run_frames(5).unwrap();
+        assert_eq!(chip8.delay_timer(), 3);
+        assert_eq!(chip8.sound_timer(), 2);
+
+        chip8.tick_timers();
+        chip8.tick_timers();
+        assert_eq!(chip8.delay_timer(), 1);
+        assert_eq!(chip8.sound_timer(), 0);
+        assert_eq!(chip8.events(), [Event::SoundStopped]);
+    }
+
+    #[test]
+    fn test_load_image() {
+        let mut chip8 = Chip8::new().unwrap();
+        let image = ProgramImage {
+            segments: vec![
+                Segment {
+                    addr: 0x200,
+                    data: vec![0x12, 0x00],
+                },
+                Segment {
+                    addr: 0xE00,
+                    data: vec![0xAB, 0xCD],
+                },
+            ],
+            entry: 0xE00,
+        };
+        chip8.load_image(&image).unwrap();
+        assert_eq!(chip8.pc, 0xE00);
+        assert_eq!(chip8.memory.data[0x200], 0x12);
+        assert_eq!(chip8.memory.data[0xE01], 0xCD);
+
+        let mut chip8 = Chip8::new().unwrap();
+        let image = ProgramImage {
+            segments: vec![Segment {
+                addr: 0xFFF,
+                data: vec![1, 2],
+            }],
+            entry: 0x200,
+        };
+        assert!(chip8.load_image(&image).is_err());
+        assert_eq!(chip8.memory.data[0xFFF], 0);
     }
 
-    /// 0xFX1E
-    fn op_add_to_index(&mut self, x: u8) {
-        self.print_op(format!("op_add_to_index(FX1E) {:#02x}", x));
-        self.i = self.i.wrapping_add(self.v[x as usize] as u16);
+    #[test]
+    fn test_rom_address() {
+        let mut chip8 = Chip8::new().unwrap().rom_address(ETI_660_ROM_ADDR);
+        assert_eq!(chip8.pc, 0x600);
+        chip8.load_rom(&[0x60, 0x2A, 0x16, 0x02]).unwrap();
+        assert_eq!(chip8.memory.data[0x600..0x604], [0x60, 0x2A, 0x16, 0x02]);
+        assert_eq!(chip8.memory.data[0x200], 0);
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+        assert_eq!((chip8.v[0], chip8.pc), (0x2A, 0x602));
+
+        chip8.load_rom_at(0x300, &[0x13, 0x00]).unwrap();
+        assert_eq!(chip8.pc, 0x300);
+        let error = chip8.load_rom_at(0xFFF, &[0x13, 0x00]).unwrap_err();
+        assert!(error.to_string().contains("above 0xfff"));
     }
 
-    /// 0xFX29
-    fn op_font_character(&mut self, x: u8) {
-        self.print_op(format!("op_font_character(FX29) {:#02x}", x));
-        self.i = (FONT_ADDR + FONT_CHAR_LENGTH * (self.v[x as usize] & 0xFF) as usize) as u16;
+    #[test]
+    fn test_start_pc() {
+        let mut chip8 = Chip8::new().unwrap().start_pc(0x204);
+        assert_eq!(chip8.pc, 0x204);
+        // the data in front of the code is loaded but never run
+        chip8
+            .load_rom(&[0xAB, 0xCD, 0xEF, 0x01, 0x60, 0x2A])
+            .unwrap();
+        assert_eq!(chip8.pc, 0x204);
+        assert_eq!(chip8.memory.data[0x200], 0xAB);
+        chip8.step().unwrap();
+        assert_eq!((chip8.v[0], chip8.pc), (0x2A, 0x206));
+
+        // loading at an explicit address starts there
+        chip8.load_rom_at(0x300, &[0x13, 0x00]).unwrap();
+        assert_eq!(chip8.pc, 0x300);
     }
 
-    /// 0xFX33
-    fn op_convert_to_decimal(&mut self, x: u8) {
-        self.print_op(format!("op_convert_to_decimal(FX33) {:#02x}", x));
-        let n = self.v[x as usize];
-        assert!(self.i as usize + 2 < MEM_SIZE, "memory write overflow");
-        self.memory.data[self.i as usize + 0] = n / 100 % 10;
-        self.memory.data[self.i as usize + 1] = n / 10 % 10;
-        self.memory.data[self.i as usize + 2] = n / 1 % 10;
+    #[test]
+    fn test_op_cls() {
+        let mut chip8 = Chip8::new().unwrap();
+        chip8.load_rom(&[0x00, 0xE0]).unwrap();
+        assert_eq!(chip8.display.toggle(0, 0, 1), false);
+        chip8.step().unwrap();
+        assert_eq!(chip8.display.is_set(0, 0), false);
     }
 
-    /// 0xFX55
-    fn op_memory_store(&mut self, x: u8) {
-        self.print_op(format!("op_memory_store(FX55) {:#02x}", x));
-        assert!(
-            self.i as usize + (x as usize) < MEM_SIZE,
-            "memory write overflow"
-        );
-        let start = self.i as usize;
-        for i in 0..(x as usize) + 1 {
-            self.memory.data[start + i] = self.v[i];
-            if self.config.memory_increment_i {
-                self.i += 1;
-            }
+    #[test]
+    #[cfg(feature = "two_page")]
+    fn test_two_page_hires() {
+        let mut rom = vec![0x12, 0x60];
+        rom.resize(TWO_PAGE_ENTRY as usize - 0x200, 0);
+        // draw the font's 0 at (0, 59), which only fits on the 64x64 screen, then clear it
+        rom.extend_from_slice(&[0x61, 0x3B, 0xF0, 0x29, 0xD0, 0x15, 0x02, 0x30]);
+        let mut chip8 = Chip8::new().unwrap().two_page_hires(true);
+        chip8.load_rom(&rom).unwrap();
+        chip8.step().unwrap();
+        assert_eq!(chip8.pc, TWO_PAGE_ENTRY);
+        assert_eq!((chip8.screen_width(), chip8.screen_height()), (64, 64));
+        assert_eq!(chip8.is_hires(), false);
+
+        for _ in 0..3 {
+            chip8.step().unwrap();
         }
+        assert_eq!(chip8.display.is_set(0, 63), true);
+        chip8.step().unwrap();
+        assert_eq!(chip8.display.is_set(0, 63), false);
+
+        // a later jump to 0x260 is only a jump
+        let mut chip8 = Chip8::new().unwrap().two_page_hires(true);
+        chip8.load_rom(&[0x00, 0xE0, 0x12, 0x60]).unwrap();
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+        assert_eq!(chip8.pc, 0x260);
+        assert_eq!(chip8.screen_height(), SCREEN_HEIGHT);
+
+        // and without the flag the program jumps into the interpreter's space as usual
+        let mut chip8 = Chip8::new().unwrap();
+        chip8.load_rom(&rom).unwrap();
+        chip8.step().unwrap();
+        assert_eq!(chip8.pc, 0x260);
     }
 
-    /// 0xFX65
-    fn op_memory_load(&mut self, x: u8) {
-        self.print_op(format!("op_memory_load(FX65) {:#02x}", x));
-        assert!(
-            self.i as usize + (x as usize) < MEM_SIZE,
-            "memory read out of bounds"
+    #[test]
+    #[cfg(feature = "schip")]
+    fn test_op_hires() {
+        let mut chip8 = Chip8::new().unwrap();
+        // HIGH, then draw the font's 0 at (120, 60), which only fits in high resolution
+        chip8
+            .load_rom(&[
+                0x00, 0xFF, 0x60, 0x78, 0x61, 0x3C, 0xF2, 0x29, 0xD0, 0x15, 0x00, 0xFE,
+            ])
+            .unwrap();
+        chip8.step().unwrap();
+        assert_eq!(chip8.is_hires(), true);
+        assert_eq!(
+            (chip8.screen_width(), chip8.screen_height()),
+            (HIRES_WIDTH, HIRES_HEIGHT)
         );
-        let start = self.i as usize;
-        for i in 0..(x as usize) + 1 {
-            self.v[i] = self.memory.data[start + i];
-            if self.config.memory_increment_i {
-                self.i += 1;
-            }
+        for _ in 0..4 {
+            chip8.step().unwrap();
         }
-    }
-}
+        assert_eq!(chip8.display.is_set(120, 60), true);
+        assert_eq!(chip8.display.is_set(120, 63), true);
+        assert_eq!(chip8.v[0xF], 0);
 
-impl std::fmt::Display for Chip8 {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "=== Memory ===\n{}", self.memory)?;
-        write!(f, "=== Display ===\n{}", self.display)?;
-        Ok(())
+        // LOW clears the screen along with switching back
+        chip8.step().unwrap();
+        assert_eq!(chip8.is_hires(), false);
+        assert_eq!(
+            (chip8.screen_width(), chip8.screen_height()),
+            (SCREEN_WIDTH, SCREEN_HEIGHT)
+        );
+        assert_eq!(chip8.fb().iter_set_pixels().count(), 0);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::{Chip8, FONT_CHAR_LENGTH, FONT_DATA, SCREEN_HEIGHT, SCREEN_WIDTH};
+    #[test]
+    #[cfg(feature = "schip")]
+    fn test_op_exit() {
+        let mut chip8 = Chip8::new().unwrap();
+        chip8
+            .load_rom(&[0x60, 0x01, 0x00, 0xFD, 0x60, 0x02])
+            .unwrap();
+        chip8.cycle().unwrap();
+        let reason = HaltReason::Exited { pc: 0x202 };
+        assert_eq!(chip8.halt_reason(), Some(&reason));
+        assert_eq!(chip8.events(), [Event::Halted(reason)]);
+        assert_eq!(chip8.v[0], 1);
+
+        // it stays exited, since resuming runs the exit again
+        chip8.resume();
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.is_halted(), true);
+        assert_eq!(chip8.v[0], 1);
+    }
 
     #[test]
-    fn test_op_cls() {
+    #[cfg(feature = "schip")]
+    fn test_op_scroll() {
         let mut chip8 = Chip8::new().unwrap();
-        chip8.load_rom(&[0x00, 0xE0]).unwrap();
-        assert_eq!(chip8.display.toggle(0, 0), false);
-        chip8.step();
+        // draw the font's 0 at (0, 0), then SCD 3, SCR and SCL
+        chip8
+            .load_rom(&[0xF0, 0x29, 0xD0, 0x15, 0x00, 0xC3, 0x00, 0xFB, 0x00, 0xFC])
+            .unwrap();
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+        assert_eq!(chip8.display.is_set(0, 0), true);
+        chip8.step().unwrap();
         assert_eq!(chip8.display.is_set(0, 0), false);
+        assert_eq!(chip8.display.is_set(0, 3), true);
+        chip8.step().unwrap();
+        assert_eq!(chip8.display.is_set(0, 3), false);
+        assert_eq!(chip8.display.is_set(4, 3), true);
+        chip8.step().unwrap();
+        assert_eq!(chip8.display.is_set(0, 3), true);
+        assert_eq!(chip8.display.is_set(4, 3), false);
+    }
+
+    #[test]
+    #[cfg(feature = "xochip")]
+    fn test_op_scroll_up() {
+        let mut chip8 = Chip8::new().unwrap();
+        // draw the font's 0 at (0, 2), then SCU 2
+        chip8
+            .load_rom(&[0xF0, 0x29, 0x61, 0x02, 0xD0, 0x15, 0x00, 0xD2])
+            .unwrap();
+        for _ in 0..3 {
+            chip8.step().unwrap();
+        }
+        assert_eq!(chip8.display.is_set(0, 2), true);
+        chip8.step().unwrap();
+        assert_eq!(chip8.display.is_set(0, 0), true);
+        assert_eq!(chip8.display.is_set(0, 5), false);
     }
 
     #[test]
@@ -629,7 +2890,7 @@ mod tests {
         chip8.pc += 2;
         chip8.stack[0] = 0x200;
         chip8.sp += 1;
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.pc, 0x200);
     }
 
@@ -637,7 +2898,7 @@ mod tests {
     fn test_op_jump() {
         let mut chip8 = Chip8::new().unwrap();
         chip8.load_rom(&[0x11, 0x2C]).unwrap();
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.pc, 300);
     }
 
@@ -646,23 +2907,236 @@ mod tests {
         let mut chip8 = Chip8::new().unwrap();
         chip8.load_rom(&[0x00, 0x00, 0x22, 0x00]).unwrap();
         chip8.pc += 2;
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.pc, 0x200);
         assert_eq!(chip8.stack[0], 0x204);
         assert_eq!(chip8.sp, 1);
     }
 
+    #[test]
+    fn test_stack_depth() {
+        // call the next instruction, which calls itself
+        let mut chip8 = Chip8::new().unwrap().stack_depth(12);
+        chip8.load_rom(&[0x22, 0x02, 0x22, 0x02]).unwrap();
+        for _ in 0..12 {
+            chip8.step().unwrap();
+        }
+        assert_eq!(chip8.call_depth(), (12, 12));
+        assert!(!chip8.is_halted());
+        chip8.step().unwrap();
+        assert_eq!(
+            chip8.halt_reason(),
+            Some(&HaltReason::StackOverflow { pc: 0x202 })
+        );
+
+        let mut chip8 = Chip8::new().unwrap();
+        chip8.load_rom(&[0x00, 0xEE]).unwrap();
+        assert_eq!(chip8.call_depth(), (0, STACK_SIZE));
+        chip8.step().unwrap();
+        assert_eq!(
+            chip8.halt_reason(),
+            Some(&HaltReason::StackUnderflow { pc: 0x200 })
+        );
+    }
+
+    #[test]
+    fn test_invalid_opcode_policy() {
+        let mut chip8 = Chip8::new()
+            .unwrap()
+            .invalid_opcode_policy(InvalidOpcodePolicy::Halt);
+        chip8.load_rom(&[0x01, 0x24]).unwrap();
+        chip8.step().unwrap();
+        let reason = HaltReason::InvalidOpcode {
+            pc: 0x200,
+            opcode: 0x0124,
+        };
+        assert_eq!(chip8.halt_reason(), Some(&reason));
+        assert_eq!(chip8.pc, 0x200);
+
+        let mut chip8 = Chip8::new()
+            .unwrap()
+            .invalid_opcode_policy(InvalidOpcodePolicy::Skip);
+        chip8.load_rom(&[0x01, 0x24, 0x60, 0x2A]).unwrap();
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+        assert!(!chip8.is_halted());
+        assert_eq!(chip8.v[0], 0x2A);
+
+        assert_eq!(
+            "SKIP".parse::<InvalidOpcodePolicy>().unwrap(),
+            InvalidOpcodePolicy::Skip
+        );
+        assert!("ignore".parse::<InvalidOpcodePolicy>().is_err());
+    }
+
+    #[test]
+    fn test_address_overflow_policy() {
+        // the last instruction in memory carries on from the start
+        let mut chip8 = Chip8::new()
+            .unwrap()
+            .address_overflow_policy(AddressOverflowPolicy::Wrap);
+        chip8.memory.data[0xFFE..].copy_from_slice(&[0x60, 0x2A]);
+        chip8.pc = 0xFFE;
+        chip8.step().unwrap();
+        assert_eq!(chip8.v[0], 0x2A);
+        assert_eq!(chip8.pc, 0x000);
+
+        // and a store straddling the end wraps around too
+        chip8.memory.data[..2].copy_from_slice(&[0xF1, 0x55]);
+        chip8.i = 0xFFF;
+        chip8.v[1] = 0x2B;
+        chip8.step().unwrap();
+        assert_eq!(chip8.memory.data[0xFFF], 0x2A);
+        assert_eq!(chip8.memory.data[0x000], 0x2B);
+
+        // an instruction split across the end of memory halts
+        let mut chip8 = Chip8::new()
+            .unwrap()
+            .address_overflow_policy(AddressOverflowPolicy::Halt);
+        chip8.pc = 0xFFF;
+        chip8.step().unwrap();
+        let reason = HaltReason::AddressOverflow {
+            pc: 0xFFF,
+            addr: 0xFFF,
+        };
+        assert_eq!(chip8.halt_reason(), Some(&reason));
+        assert_eq!(chip8.pc, 0xFFF);
+
+        // masking keeps I to 12 bits however much memory there is
+        let mut chip8 = Chip8::new()
+            .unwrap()
+            .memory_size(XO_CHIP_MEM_SIZE)
+            .address_overflow_policy(AddressOverflowPolicy::Mask);
+        chip8.load_rom(&[0xF0, 0x1E]).unwrap();
+        chip8.i = 0xFFF;
+        chip8.v[0] = 2;
+        chip8.step().unwrap();
+        assert_eq!(chip8.i, 0x001);
+    }
+
+    #[test]
+    fn test_address_overflow_top_of_memory() {
+        for policy in [
+            AddressOverflowPolicy::Error,
+            AddressOverflowPolicy::Halt,
+            AddressOverflowPolicy::Wrap,
+            AddressOverflowPolicy::Mask,
+        ] {
+            let new_chip8 = |opcode: [u8; 2]| {
+                let mut chip8 = Chip8::new().unwrap().address_overflow_policy(policy);
+                chip8.memory.data[0xFFE..].copy_from_slice(&opcode);
+                chip8.pc = 0xFFE;
+                chip8
+            };
+
+            // instructions that stay put leave the program counter on the last word
+            let mut chip8 = new_chip8([0xF0, 0x0A]);
+            chip8.step().unwrap();
+            assert_eq!(chip8.pc, 0xFFE);
+
+            let mut chip8 = new_chip8([0x01, 0x24]);
+            let error = chip8.step().unwrap_err();
+            assert_eq!(
+                error,
+                Chip8Error::InvalidOpcode {
+                    pc: 0xFFE,
+                    opcode: 0x0124
+                }
+            );
+            assert_eq!(chip8.pc, 0xFFE);
+
+            #[cfg(feature = "schip")]
+            {
+                let mut chip8 = new_chip8([0x00, 0xFD]);
+                chip8.step().unwrap();
+                let reason = HaltReason::Exited { pc: 0xFFE };
+                assert_eq!(chip8.halt_reason(), Some(&reason));
+                assert_eq!(chip8.pc, 0xFFE);
+            }
+
+            // the program counter can't move past the top of a 64K memory without wrapping
+            let mut chip8 = Chip8::new()
+                .unwrap()
+                .memory_size(XO_CHIP_MEM_SIZE)
+                .address_overflow_policy(policy);
+            // masking reads the same code 12 bits down
+            chip8.memory.data[0xFFFE..].copy_from_slice(&[0x60, 0x2A]);
+            chip8.memory.data[0xFFE..0x1000].copy_from_slice(&[0x60, 0x2A]);
+            chip8.pc = 0xFFFE;
+            let result = chip8.step();
+            let overflow = HaltReason::AddressOverflow {
+                pc: 0xFFFE,
+                addr: 0xFFFE,
+            };
+            match policy {
+                AddressOverflowPolicy::Error => {
+                    assert_eq!(
+                        result,
+                        Err(Chip8Error::AddressOverflow {
+                            pc: 0xFFFE,
+                            addr: 0xFFFE
+                        })
+                    );
+                    assert_eq!(chip8.pc, 0xFFFE);
+                }
+                AddressOverflowPolicy::Halt => {
+                    assert_eq!(chip8.halt_reason(), Some(&overflow));
+                    assert_eq!(chip8.pc, 0xFFFE);
+                }
+                _ => {
+                    assert_eq!(chip8.v[0], 0x2A);
+                    assert_eq!(chip8.pc, 0x000);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_skip_at_top_of_memory() {
+        // skipping the last word of a 64K memory goes past what the program counter can hold
+        let mut chip8 = Chip8::new().unwrap().memory_size(XO_CHIP_MEM_SIZE);
+        chip8.memory.data[0xFFFC..].copy_from_slice(&[0x30, 0x00, 0x60, 0x2A]);
+        chip8.pc = 0xFFFC;
+        assert_eq!(
+            chip8.step(),
+            Err(Chip8Error::AddressOverflow {
+                pc: 0xFFFC,
+                addr: 0xFFFE
+            })
+        );
+        assert_eq!(chip8.pc, 0xFFFC);
+
+        // unless it wraps around to the start
+        let mut chip8 = Chip8::new()
+            .unwrap()
+            .memory_size(XO_CHIP_MEM_SIZE)
+            .address_overflow_policy(AddressOverflowPolicy::Wrap);
+        chip8.memory.data[0xFFFC..].copy_from_slice(&[0x30, 0x00, 0x60, 0x2A]);
+        chip8.pc = 0xFFFC;
+        chip8.step().unwrap();
+        assert_eq!(chip8.pc, 0x000);
+
+        // and masking wraps the skip within 12 bits
+        let mut chip8 = Chip8::new()
+            .unwrap()
+            .address_overflow_policy(AddressOverflowPolicy::Mask);
+        chip8.memory.data[0xFFC..0x1000].copy_from_slice(&[0x30, 0x00, 0x60, 0x2A]);
+        chip8.pc = 0xFFC;
+        chip8.step().unwrap();
+        assert_eq!(chip8.pc, 0x000);
+    }
+
     #[test]
     fn test_op_skip_eq() {
         let mut chip8 = Chip8::new().unwrap();
         chip8.load_rom(&[0x30, 0x10]).unwrap();
 
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.pc, 0x202);
 
         chip8.pc = 0x200;
         chip8.v[0] = 0x10;
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.pc, 0x204);
     }
 
@@ -671,12 +3145,12 @@ mod tests {
         let mut chip8 = Chip8::new().unwrap();
         chip8.load_rom(&[0x40, 0x10]).unwrap();
 
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.pc, 0x204);
 
         chip8.pc = 0x200;
         chip8.v[0] = 0x10;
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.pc, 0x202);
     }
 
@@ -685,12 +3159,12 @@ mod tests {
         let mut chip8 = Chip8::new().unwrap();
         chip8.load_rom(&[0x50, 0x10]).unwrap();
 
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.pc, 0x204);
 
         chip8.pc = 0x200;
         chip8.v[1] = 0x10;
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.pc, 0x202);
     }
 
@@ -698,7 +3172,7 @@ mod tests {
     fn test_op_set() {
         let mut chip8 = Chip8::new().unwrap();
         chip8.load_rom(&[0x60, 0xAA]).unwrap();
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.v[0], 0xAA);
     }
 
@@ -708,12 +3182,12 @@ mod tests {
         chip8.load_rom(&[0x70, 0x20]).unwrap();
 
         chip8.v[0] = 16;
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.v[0], 48);
 
         chip8.pc = 0x200;
         chip8.v[0] = 0xFE;
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.v[0], 30);
     }
 
@@ -723,7 +3197,7 @@ mod tests {
         chip8.load_rom(&[0x80, 0x10]).unwrap();
         chip8.v[0] = 10;
         chip8.v[1] = 20;
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.v[0], 20);
     }
 
@@ -733,7 +3207,7 @@ mod tests {
         chip8.load_rom(&[0x80, 0x11]).unwrap();
         chip8.v[0] = 0b10010000;
         chip8.v[1] = 0b11000001;
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.v[0], 0b11010001);
     }
 
@@ -743,7 +3217,7 @@ mod tests {
         chip8.load_rom(&[0x80, 0x12]).unwrap();
         chip8.v[0] = 0b10010001;
         chip8.v[1] = 0b11000001;
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.v[0], 0b10000001);
     }
 
@@ -753,7 +3227,7 @@ mod tests {
         chip8.load_rom(&[0x80, 0x13]).unwrap();
         chip8.v[0] = 0b10010001;
         chip8.v[1] = 0b11000001;
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.v[0], 0b01010000);
     }
 
@@ -764,14 +3238,14 @@ mod tests {
 
         chip8.v[0] = 200;
         chip8.v[1] = 100;
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.v[0], 44);
         assert_eq!(chip8.v[0xF], 1);
 
         chip8.pc = 0x200;
         chip8.v[0] = 10;
         chip8.v[1] = 20;
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.v[0], 30);
         assert_eq!(chip8.v[0xF], 0);
     }
@@ -783,14 +3257,14 @@ mod tests {
 
         chip8.v[0] = 100;
         chip8.v[1] = 25;
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.v[0], 75);
         assert_eq!(chip8.v[0xF], 1);
 
         chip8.pc = 0x200;
         chip8.v[0] = 25;
         chip8.v[1] = 100;
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.v[0], 181);
         assert_eq!(chip8.v[0xF], 0);
     }
@@ -801,7 +3275,7 @@ mod tests {
         chip8.load_rom(&[0x80, 0x16]).unwrap();
 
         chip8.v[0] = 0b00000100;
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.v[0], 0b00000010);
         assert_eq!(chip8.v[0xF], 0);
 
@@ -809,7 +3283,7 @@ mod tests {
         chip8.pc = 0x200;
         chip8.v[0] = 0b0;
         chip8.v[1] = 0b00000101;
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.v[0], 0b00000010);
         assert_eq!(chip8.v[0xF], 1);
     }
@@ -821,14 +3295,14 @@ mod tests {
 
         chip8.v[0] = 25;
         chip8.v[1] = 100;
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.v[0], 75);
         assert_eq!(chip8.v[0xF], 1);
 
         chip8.pc = 0x200;
         chip8.v[0] = 100;
         chip8.v[1] = 25;
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.v[0], u8::MIN);
         assert_eq!(chip8.v[0xF], 0);
     }
@@ -839,7 +3313,7 @@ mod tests {
         chip8.load_rom(&[0x80, 0x1E]).unwrap();
 
         chip8.v[0] = 0b00100000;
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.v[0], 0b01000000);
         assert_eq!(chip8.v[0xF], 0);
 
@@ -847,7 +3321,7 @@ mod tests {
         chip8.pc = 0x200;
         chip8.v[0] = 0b0;
         chip8.v[1] = 0b10100000;
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.v[0], 0b01000000);
         assert_eq!(chip8.v[0xF], 1);
     }
@@ -857,12 +3331,12 @@ mod tests {
         let mut chip8 = Chip8::new().unwrap();
         chip8.load_rom(&[0x90, 0x10]).unwrap();
 
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.pc, 0x202);
 
         chip8.pc = 0x200;
         chip8.v[1] = 0x10;
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.pc, 0x204);
     }
 
@@ -870,7 +3344,7 @@ mod tests {
     fn test_op_set_index() {
         let mut chip8 = Chip8::new().unwrap();
         chip8.load_rom(&[0xA2, 0x22]).unwrap();
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.i, 0x222);
     }
 
@@ -879,13 +3353,13 @@ mod tests {
         let mut chip8 = Chip8::new().unwrap();
         chip8.load_rom(&[0xB3, 0x00]).unwrap();
 
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.pc, 0x300);
 
         chip8 = chip8.jump_add_offset(true);
         chip8.pc = 0x200;
         chip8.v[3] = 0x10;
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.pc, 0x300 + 0x10);
     }
 
@@ -893,7 +3367,7 @@ mod tests {
     fn test_op_random() {
         let mut chip8 = Chip8::new().unwrap();
         chip8.load_rom(&[0xC0, 0x10]).unwrap();
-        chip8.step();
+        chip8.step().unwrap();
         // can't easily test random operation, so we just make sure the operation doesn't panic
     }
 
@@ -913,7 +3387,7 @@ mod tests {
         chip8.v[0] = sx as u8;
         chip8.v[1] = sy as u8;
         chip8.i = 0x202;
-        chip8.step();
+        chip8.step().unwrap();
 
         assert_eq!(chip8.display.is_set(sx + 6, sy), true);
         assert_eq!(chip8.display.is_set(sx + 7, sy), false);
@@ -921,18 +3395,101 @@ mod tests {
         assert_eq!(chip8.display.is_set(sx + 7, sy + 1), true);
     }
 
+    #[test]
+    fn test_op_display_wrap_sprites() {
+        #[rustfmt::skip]
+        let rom = [
+            0xD0, 0x12, // display
+            0b10000001, // sprite
+            0b10000001,
+        ];
+        let (sx, sy) = (SCREEN_WIDTH - 4, SCREEN_HEIGHT - 1);
+
+        let mut chip8 = Chip8::new().unwrap().wrap_sprites(true);
+        chip8.load_rom(&rom).unwrap();
+        chip8.v[0] = sx as u8;
+        chip8.v[1] = sy as u8;
+        chip8.i = 0x202;
+        chip8.step().unwrap();
+        // the right column wraps to the left edge, and the bottom row to the top
+        assert_eq!(chip8.display.is_set(sx, sy), true);
+        assert_eq!(chip8.display.is_set(3, sy), true);
+        assert_eq!(chip8.display.is_set(sx, 0), true);
+        assert_eq!(chip8.display.is_set(3, 0), true);
+        assert_eq!(chip8.fb().iter_set_pixels().count(), 4);
+
+        // clipped, only the top left pixel is drawn
+        let mut chip8 = Chip8::new().unwrap();
+        chip8.load_rom(&rom).unwrap();
+        chip8.v[0] = sx as u8;
+        chip8.v[1] = sy as u8;
+        chip8.i = 0x202;
+        chip8.step().unwrap();
+        assert_eq!(chip8.display.is_set(sx, sy), true);
+        assert_eq!(chip8.fb().iter_set_pixels().count(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "schip")]
+    fn test_op_display_large_sprite() {
+        let mut chip8 = Chip8::new().unwrap();
+        // HIGH, then a 16x16 sprite with only its corners lit
+        let mut rom = vec![0x00, 0xFF, 0xD0, 0x10];
+        rom.extend([0x80, 0x01]);
+        rom.extend([0x00; 28]);
+        rom.extend([0x80, 0x01]);
+        chip8.load_rom(&rom).unwrap();
+        chip8.i = 0x204;
+
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+        assert_eq!(chip8.display.is_set(0, 0), true);
+        assert_eq!(chip8.display.is_set(15, 0), true);
+        assert_eq!(chip8.display.is_set(0, 15), true);
+        assert_eq!(chip8.display.is_set(15, 15), true);
+        assert_eq!(chip8.display.is_set(1, 0), false);
+        assert_eq!(chip8.display.is_set(16, 0), false);
+        assert_eq!(chip8.fb().iter_set_pixels().count(), 4);
+
+        // only the top left corner is still on screen at the bottom right
+        chip8.v[0] = (HIRES_WIDTH - 8) as u8;
+        chip8.v[1] = (HIRES_HEIGHT - 8) as u8;
+        chip8.pc = 0x202;
+        chip8.step().unwrap();
+        assert_eq!(
+            chip8.display.is_set(HIRES_WIDTH - 8, HIRES_HEIGHT - 8),
+            true
+        );
+        assert_eq!(chip8.fb().iter_set_pixels().count(), 5);
+        assert_eq!(chip8.v[0xF], 0);
+
+        // in low resolution N=0 draws nothing, as it does on CHIP-8
+        let mut chip8 = Chip8::new().unwrap();
+        chip8.load_rom(&rom[2..]).unwrap();
+        chip8.i = 0x202;
+        chip8.step().unwrap();
+        assert_eq!(chip8.fb().iter_set_pixels().count(), 0);
+        assert_eq!(chip8.pc, 0x202);
+    }
+
     #[test]
     fn test_op_skip_if_key_down() {
         let mut chip8 = Chip8::new().unwrap();
         chip8.load_rom(&[0xE1, 0x9E]).unwrap();
         chip8.v[1] = 1;
 
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.pc, 0x202);
 
         chip8.pc = 0x200;
         chip8.keypad.keys[1] = 1;
-        chip8.step();
+        chip8.step().unwrap();
+        assert_eq!(chip8.pc, 0x204);
+
+        // only the low nibble of VX picks the key
+        chip8.pc = 0x200;
+        chip8.v[1] = 0x21;
+        chip8.step().unwrap();
         assert_eq!(chip8.pc, 0x204);
     }
 
@@ -942,12 +3499,12 @@ mod tests {
         chip8.load_rom(&[0xE1, 0xA1]).unwrap();
         chip8.v[1] = 1;
 
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.pc, 0x204);
 
         chip8.pc = 0x200;
         chip8.keypad.keys[1] = 1;
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.pc, 0x202);
     }
 
@@ -956,7 +3513,7 @@ mod tests {
         let mut chip8 = Chip8::new().unwrap();
         chip8.load_rom(&[0xF0, 0x07]).unwrap();
         chip8.dt = 0x10;
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.v[0], 0x10);
     }
 
@@ -965,20 +3522,49 @@ mod tests {
         let mut chip8 = Chip8::new().unwrap();
         chip8.load_rom(&[0xF0, 0x0A]).unwrap();
 
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.v[0], 0);
         assert_eq!(chip8.pc, 0x200);
 
         chip8.keypad.keys[0xF] = 1;
-        chip8.step();
-        chip8.step(); // shouldn't register key press until it's released
+        chip8.step().unwrap();
+        chip8.step().unwrap(); // shouldn't register key press until it's released
         assert_eq!(chip8.v[0], 0);
         assert_eq!(chip8.pc, 0x200);
 
         chip8.keypad.keys[0xF] = 0;
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.v[0], 0xF);
         assert_eq!(chip8.pc, 0x202);
+        assert_eq!(
+            chip8.events(),
+            [Event::KeyCaptured {
+                key: 0xF,
+                register: 0
+            }]
+        );
+    }
+
+    #[test]
+    fn test_key_wait_timeout() {
+        let mut chip8 = Chip8::new().unwrap().key_wait_timeout(3);
+        chip8.load_rom(&[0xF0, 0x0A]).unwrap();
+
+        chip8.cycle().unwrap();
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.is_waiting_for_key(), false);
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.is_waiting_for_key(), true);
+        assert_eq!(chip8.events(), [Event::WaitingForKey]);
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.events(), []);
+
+        // holding a key counts as activity even before FX0A captures it
+        chip8
+            .player_keydown(Player::One, Key::from_value(0x5))
+            .unwrap();
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.is_waiting_for_key(), false);
     }
 
     #[test]
@@ -986,7 +3572,7 @@ mod tests {
         let mut chip8 = Chip8::new().unwrap();
         chip8.load_rom(&[0xF0, 0x15]).unwrap();
         chip8.v[0] = 0x10;
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.dt, 0x10);
     }
 
@@ -995,7 +3581,7 @@ mod tests {
         let mut chip8 = Chip8::new().unwrap();
         chip8.load_rom(&[0xF0, 0x18]).unwrap();
         chip8.v[0] = 0x10;
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.st, 0x10);
     }
 
@@ -1005,17 +3591,79 @@ mod tests {
         chip8.load_rom(&[0xF0, 0x1E]).unwrap();
         chip8.i = 0x10;
         chip8.v[0] = 0x10;
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.i, 0x20);
     }
 
+    #[test]
+    fn test_font_address() {
+        let mut chip8 = Chip8::new().unwrap().font_address(0x000);
+        chip8.load_rom(&[0xF0, 0x29]).unwrap();
+        assert_eq!(chip8.font_range(), 0x000..FONT_DATA.len());
+        assert_eq!(chip8.memory.data[..FONT_DATA.len()], FONT_DATA);
+        assert_eq!(chip8.memory.data[FONT_ADDR + FONT_DATA.len() - 1], 0);
+
+        chip8.v[0] = 0x2;
+        chip8.step().unwrap();
+        assert_eq!(chip8.i as usize, 2 * FONT_CHAR_LENGTH);
+
+        let chip8 = Chip8::new()
+            .unwrap()
+            .font_address(0x1000)
+            .memory_size(0x2000);
+        assert_eq!(chip8.font_range().end, 0x200);
+        assert_eq!(chip8.memory.data[0x200 - FONT_DATA.len()], FONT_DATA[0]);
+
+        // the font is kept below wherever ROMs are loaded
+        let chip8 = Chip8::new()
+            .unwrap()
+            .rom_address(ETI_660_ROM_ADDR)
+            .font_address(0x1000)
+            .memory_size(0x2000);
+        assert_eq!(chip8.font_range().end, ETI_660_ROM_ADDR);
+        assert_eq!(
+            chip8.memory.data[ETI_660_ROM_ADDR - FONT_DATA.len()],
+            FONT_DATA[0]
+        );
+    }
+
+    #[test]
+    fn test_set_font() {
+        let mut font = FONT_DATA;
+        font[..FONT_CHAR_LENGTH].copy_from_slice(&[0xE0, 0xA0, 0xA0, 0xA0, 0xE0]);
+        let mut chip8 = Chip8::new().unwrap();
+        chip8.set_font(&font).unwrap();
+        assert_eq!(chip8.memory.data[chip8.font_range()], font);
+
+        // the custom font moves with the font
+        let chip8 = chip8.font_address(0x000);
+        assert_eq!(chip8.memory.data[..FONT_DATA.len()], font);
+
+        // a font can't be put where the rom goes
+        let mut chip8 = Chip8::new().unwrap().rom_address(0x060);
+        assert!(chip8.set_font(&font).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "schip")]
+    fn test_set_big_font() {
+        let mut big_font = BIG_FONT_DATA;
+        big_font[0] = 0xFF;
+        let mut chip8 = Chip8::new().unwrap().font_address(0x000);
+        chip8.set_big_font(&big_font).unwrap();
+        assert_eq!(chip8.memory.data[chip8.big_font_range()], big_font);
+
+        let mut chip8 = Chip8::new().unwrap().rom_address(0x060);
+        assert!(chip8.set_big_font(&big_font).is_err());
+    }
+
     #[test]
     fn test_op_font_character() {
         let mut chip8 = Chip8::new().unwrap();
         chip8.load_rom(&[0xF0, 0x29]).unwrap();
 
         chip8.v[0] = 0;
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(
             chip8.memory.data[chip8.i as usize..chip8.i as usize + FONT_CHAR_LENGTH],
             FONT_DATA[0..0 + FONT_CHAR_LENGTH]
@@ -1023,11 +3671,89 @@ mod tests {
 
         chip8.pc = 0x200;
         chip8.v[0] = 0xF;
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(
             chip8.memory.data[chip8.i as usize..chip8.i as usize + FONT_CHAR_LENGTH],
             FONT_DATA[0xF * FONT_CHAR_LENGTH..0xF * FONT_CHAR_LENGTH + FONT_CHAR_LENGTH]
         );
+
+        // only the low nibble picks the glyph, unless the quirk is on
+        chip8.pc = 0x200;
+        chip8.v[0] = 0x1A;
+        chip8.step().unwrap();
+        assert_eq!(chip8.i as usize, FONT_ADDR + 0xA * FONT_CHAR_LENGTH);
+
+        let mut chip8 = Chip8::new().unwrap().unmasked_font(true);
+        chip8.load_rom(&[0xF0, 0x29]).unwrap();
+        chip8.v[0] = 0x1A;
+        chip8.step().unwrap();
+        assert_eq!(chip8.i as usize, FONT_ADDR + 0x1A * FONT_CHAR_LENGTH);
+    }
+
+    #[test]
+    #[cfg(feature = "schip")]
+    fn test_op_big_font_character() {
+        let mut chip8 = Chip8::new().unwrap();
+        chip8.load_rom(&[0xF0, 0x30]).unwrap();
+        assert_eq!(chip8.memory.data[chip8.big_font_range()], BIG_FONT_DATA);
+        assert_eq!(chip8.big_font_range().start, chip8.font_range().end);
+
+        chip8.v[0] = 0x9;
+        chip8.step().unwrap();
+        assert_eq!(
+            chip8.memory.data[chip8.i as usize..chip8.i as usize + BIG_FONT_CHAR_LENGTH],
+            BIG_FONT_DATA[9 * BIG_FONT_CHAR_LENGTH..10 * BIG_FONT_CHAR_LENGTH]
+        );
+
+        // only the low nibble picks the glyph, so I stays inside the big font
+        chip8.pc = 0x200;
+        chip8.v[0] = 0xFA;
+        chip8.step().unwrap();
+        assert_eq!(
+            chip8.i as usize,
+            chip8.big_font_range().start + 0xA * BIG_FONT_CHAR_LENGTH
+        );
+
+        // the big font stays put when the small font moves out of the way
+        let chip8 = Chip8::new().unwrap().font_address(0x000);
+        assert_eq!(chip8.memory.data[chip8.big_font_range()], BIG_FONT_DATA);
+    }
+
+    #[test]
+    #[cfg(feature = "schip")]
+    fn test_op_flags() {
+        #[derive(Clone, Default)]
+        struct SharedStore(std::sync::Arc<std::sync::Mutex<Option<[u8; FLAG_COUNT]>>>);
+        impl FlagStore for SharedStore {
+            fn load(&self) -> anyhow::Result<Option<[u8; FLAG_COUNT]>> {
+                Ok(*self.0.lock().unwrap())
+            }
+            fn save(&mut self, flags: &[u8; FLAG_COUNT]) -> anyhow::Result<()> {
+                *self.0.lock().unwrap() = Some(*flags);
+                Ok(())
+            }
+        }
+
+        let store = SharedStore::default();
+        let mut chip8 = Chip8::new().unwrap();
+        chip8.set_flag_store(Box::new(store.clone())).unwrap();
+        // LD R, V2, then clear the registers and LD V1, R
+        chip8
+            .load_rom(&[0xF2, 0x75, 0x60, 0x00, 0x61, 0x00, 0x62, 0x00, 0xF1, 0x85])
+            .unwrap();
+        chip8.v[..3].copy_from_slice(&[7, 8, 9]);
+        chip8.step().unwrap();
+        assert_eq!(chip8.flags()[..4], [7, 8, 9, 0]);
+        assert_eq!(store.0.lock().unwrap().unwrap()[..3], [7, 8, 9]);
+        for _ in 0..4 {
+            chip8.step().unwrap();
+        }
+        assert_eq!(chip8.v[..3], [7, 8, 0]);
+
+        // a new instance picks up the flags the last one saved
+        let mut chip8 = Chip8::new().unwrap();
+        chip8.set_flag_store(Box::new(store)).unwrap();
+        assert_eq!(chip8.flags()[..3], [7, 8, 9]);
     }
 
     #[test]
@@ -1036,11 +3762,214 @@ mod tests {
         chip8.load_rom(&[0xF0, 0x33]).unwrap();
         chip8.v[0] = 156;
         chip8.i = 0x300;
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(
             chip8.memory.data[chip8.i as usize..chip8.i as usize + 3],
             [1, 5, 6]
         );
+
+        chip8.pc = 0x200;
+        chip8.v[0] = 7;
+        chip8.step().unwrap();
+        assert_eq!(
+            chip8.memory.data[chip8.i as usize..chip8.i as usize + 3],
+            [0, 0, 7]
+        );
+
+        chip8.pc = 0x200;
+        chip8.v[0] = 255;
+        chip8.step().unwrap();
+        assert_eq!(
+            chip8.memory.data[chip8.i as usize..chip8.i as usize + 3],
+            [2, 5, 5]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "xochip")]
+    fn test_op_plane_select() {
+        let mut chip8 = Chip8::new().unwrap();
+        // select both planes and draw a two row sprite for each, then clear only the first
+        #[rustfmt::skip]
+        chip8.load_rom(&[
+            0xF3, 0x01, 0xA2, 0x0A, 0xD0, 0x12, 0xF1, 0x01, 0x00, 0xE0,
+            0x80, 0x80, // the first plane's sprite
+            0xC0, 0x00, // the second plane's sprite
+        ]).unwrap();
+        for _ in 0..3 {
+            chip8.step().unwrap();
+        }
+        let fb = chip8.fb();
+        assert_eq!(fb.get(0, 0), 3);
+        assert_eq!(fb.get(1, 0), 2);
+        assert_eq!(fb.get(0, 1), 1);
+        assert_eq!(chip8.v[0xF], 0);
+
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+        let fb = chip8.fb();
+        assert_eq!((fb.get(0, 0), fb.get(1, 0), fb.get(0, 1)), (2, 2, 0));
+    }
+
+    #[test]
+    #[cfg(feature = "xochip")]
+    fn test_op_long_index() {
+        let mut chip8 = Chip8::new().unwrap();
+        // load a 16-bit address, then skip another long load as one instruction
+        #[rustfmt::skip]
+        chip8.load_rom(&[
+            0xF0, 0x00, 0x12, 0x34,
+            0x30, 0x00,
+            0xF0, 0x00, 0x56, 0x78,
+            0x40, 0x00,
+            0xF0, 0x00, 0x9A, 0xBC,
+        ]).unwrap();
+        chip8.step().unwrap();
+        assert_eq!(chip8.i, 0x1234);
+        assert_eq!(chip8.pc, 0x204);
+
+        chip8.step().unwrap();
+        assert_eq!(chip8.pc, 0x20A);
+
+        // a skip that isn't taken runs it
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+        assert_eq!(chip8.i, 0x9ABC);
+        assert_eq!(chip8.pc, 0x210);
+    }
+
+    #[test]
+    #[cfg(feature = "xochip")]
+    fn test_op_range_store() {
+        let mut chip8 = Chip8::new().unwrap();
+        chip8.load_rom(&[0x51, 0x32, 0x53, 0x12]).unwrap();
+        chip8.v[1..4].copy_from_slice(&[0x11, 0x22, 0x33]);
+        chip8.i = 0x300;
+        chip8.step().unwrap();
+        assert_eq!(chip8.memory.data[0x300..0x303], [0x11, 0x22, 0x33]);
+        assert_eq!(chip8.i, 0x300);
+
+        // a descending range stores VX first
+        chip8.i = 0x310;
+        chip8.step().unwrap();
+        assert_eq!(chip8.memory.data[0x310..0x313], [0x33, 0x22, 0x11]);
+        assert_eq!(chip8.i, 0x310);
+    }
+
+    #[test]
+    #[cfg(feature = "xochip")]
+    fn test_op_range_load() {
+        let mut chip8 = Chip8::new().unwrap();
+        chip8
+            .load_rom(&[0x52, 0x43, 0x54, 0x23, 0x55, 0x53])
+            .unwrap();
+        chip8.memory.data[0x300..0x303].copy_from_slice(&[0xAA, 0xBB, 0xCC]);
+        chip8.i = 0x300;
+        chip8.step().unwrap();
+        assert_eq!(chip8.v[2..5], [0xAA, 0xBB, 0xCC]);
+        assert_eq!(chip8.i, 0x300);
+
+        // a descending range loads VX first
+        chip8.step().unwrap();
+        assert_eq!(chip8.v[2..5], [0xCC, 0xBB, 0xAA]);
+
+        // a single register is both ends of the range
+        chip8.v[5] = 0;
+        chip8.step().unwrap();
+        assert_eq!(chip8.v[5], 0xAA);
+        assert_eq!(chip8.v[4], 0xAA);
+    }
+
+    #[test]
+    #[cfg(feature = "xochip")]
+    fn test_op_audio_pattern() {
+        let mut chip8 = Chip8::new().unwrap();
+        chip8.load_rom(&[0xF0, 0x02]).unwrap();
+        chip8.memory.data[0x300..0x300 + AUDIO_PATTERN_LENGTH].fill(0xAA);
+        chip8.i = 0x300;
+        chip8.step().unwrap();
+        assert_eq!(chip8.buzzer.pattern, Some([0xAA; AUDIO_PATTERN_LENGTH]));
+    }
+
+    #[test]
+    #[cfg(feature = "xochip")]
+    fn test_op_pitch_set() {
+        let mut chip8 = Chip8::new().unwrap();
+        chip8.load_rom(&[0xF0, 0x3A]).unwrap();
+        chip8.v[0] = 0x70;
+        chip8.step().unwrap();
+        assert_eq!(chip8.buzzer.pitch, 0x70);
+    }
+
+    #[test]
+    #[cfg(feature = "chip8x")]
+    fn test_chip8x_colours() {
+        let frame = Frame::new();
+        let mut chip8 = Chip8::new().unwrap().chip8x(true);
+        // cycle the background, colour zones 1-2 across and 0 down green, then colour 3 rows of
+        // the column at x=16 yellow
+        #[rustfmt::skip]
+        chip8.load_rom(&[
+            0x02, 0xA0,
+            0xB0, 0x20,
+            0xB2, 0x43,
+        ]).unwrap();
+        chip8.v[0] = 0x11;
+        chip8.v[1] = 0;
+        chip8.v[2] = 4;
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+        let colours = chip8.chip8x_colours().unwrap();
+        assert_eq!(colours.background(), BACKGROUND_COLOURS[1]);
+        assert_eq!(colours.foreground(8, 3, &frame), FOREGROUND_COLOURS[4]);
+        assert_eq!(colours.foreground(23, 0, &frame), FOREGROUND_COLOURS[4]);
+        assert_eq!(colours.foreground(24, 0, &frame), FOREGROUND_COLOURS[1]);
+        assert_eq!(colours.foreground(8, 4, &frame), FOREGROUND_COLOURS[1]);
+
+        chip8.v[2] = 16;
+        chip8.v[3] = 5;
+        chip8.v[4] = 10;
+        chip8.step().unwrap();
+        let colours = chip8.chip8x_colours().unwrap();
+        assert_eq!(colours.foreground(16, 9, &frame), FOREGROUND_COLOURS[1]);
+        assert_eq!(colours.foreground(16, 10, &frame), FOREGROUND_COLOURS[5]);
+        assert_eq!(colours.foreground(16, 12, &frame), FOREGROUND_COLOURS[5]);
+        assert_eq!(colours.foreground(16, 13, &frame), FOREGROUND_COLOURS[1]);
+        assert!(Chip8::new().unwrap().chip8x_colours().is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "chip8x")]
+    fn test_chip8x_opcodes() {
+        let mut chip8 = Chip8::new().unwrap().chip8x(true);
+        #[rustfmt::skip]
+        chip8.load_rom(&[
+            0x50, 0x11,
+            0xE2, 0xF2,
+            0x00, 0x00,
+            0xE2, 0xF5,
+        ]).unwrap();
+        // each nibble adds as a three bit number
+        chip8.v[0] = 0x35;
+        chip8.v[1] = 0x64;
+        chip8.step().unwrap();
+        assert_eq!(chip8.v[0], 0x11);
+
+        // the second keypad is read, not the first
+        chip8.v[2] = 0x7;
+        chip8
+            .player_keydown(Player::Two, Key::from_value(0x7))
+            .unwrap();
+        chip8.step().unwrap();
+        assert_eq!(chip8.pc, 0x206);
+        chip8.step().unwrap();
+        assert_eq!(chip8.pc, 0x208);
+
+        // without CHIP-8X, BNNN is still a jump
+        let mut chip8 = Chip8::new().unwrap();
+        chip8.load_rom(&[0xB3, 0x00]).unwrap();
+        chip8.step().unwrap();
+        assert_eq!(chip8.pc, 0x300);
     }
 
     #[test]
@@ -1050,15 +3979,25 @@ mod tests {
 
         chip8.v[0..3].copy_from_slice(&[0x20, 0x10, 0x30]);
         chip8.i = 0x300;
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.i, 0x300);
         assert_eq!(chip8.memory.data[0x300..0x300 + 3], [0x20, 0x10, 0x30]);
 
         chip8 = chip8.memory_increment_i(true);
         chip8.pc = 0x200;
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.i, 0x300 + 4);
         assert_eq!(chip8.memory.data[0x300..0x300 + 3], [0x20, 0x10, 0x30]);
+
+        // I wraps around past the top of a 64K memory
+        let mut chip8 = Chip8::new()
+            .unwrap()
+            .memory_size(XO_CHIP_MEM_SIZE)
+            .memory_increment_i(true);
+        chip8.load_rom(&[0xF3, 0x55]).unwrap();
+        chip8.i = 0xFFFC;
+        chip8.step().unwrap();
+        assert_eq!(chip8.i, 0x0000);
     }
 
     #[test]
@@ -1068,13 +4007,13 @@ mod tests {
 
         chip8.memory.data[0x300..0x300 + 3].copy_from_slice(&[0x20, 0x10, 0x30]);
         chip8.i = 0x300;
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.i, 0x300);
         assert_eq!(chip8.v[0..3], [0x20, 0x10, 0x30]);
 
         chip8 = chip8.memory_increment_i(true);
         chip8.pc = 0x200;
-        chip8.step();
+        chip8.step().unwrap();
         assert_eq!(chip8.i, 0x300 + 4);
         assert_eq!(chip8.v[0..3], [0x20, 0x10, 0x30]);
     }
@@ -0,0 +1,169 @@
+use std::fmt::Display;
+
+use crate::{Chip8, Chip8Error, StateDiff};
+
+/// The first point at which two instances running in lockstep stopped matching
+pub struct Divergence {
+    /// The number of frames completed before the diverging instruction
+    pub frame: u64,
+    /// The number of instructions executed before the diverging one
+    pub instruction: u64,
+    /// The address of the diverging instruction
+    pub pc: u16,
+    /// The diverging instruction
+    pub opcode: u16,
+    /// How the state of the second instance differs from the first after it
+    pub diff: StateDiff,
+}
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "diverged at instruction {} (frame {}): {:04X} at {:#06x}",
+            self.instruction, self.frame, self.opcode, self.pc
+        )?;
+        write!(f, "{}", self.diff)
+    }
+}
+
+/// Runs two instances with different configurations side by side on the same input, comparing
+/// their state after every instruction to find the first one they disagree on
+/// Both instances should be loaded with the same ROM and seeded with the same RNG seed.
+pub struct Lockstep {
+    pub a: Chip8,
+    pub b: Chip8,
+    frames: u64,
+    instructions: u64,
+}
+
+impl Lockstep {
+    pub fn new(a: Chip8, b: Chip8) -> Self {
+        Self {
+            a,
+            b,
+            frames: 0,
+            instructions: 0,
+        }
+    }
+
+    /// The number of frames run so far
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Run a frame on both instances, stopping at the first divergence
+    /// Each instance runs the frame as `cycle` would, by instruction count or by VIP machine
+    /// cycles, so if one of them runs fewer instructions, or is waiting for the vertical blank,
+    /// it stops stepping early while the other catches up. A fault in either instance stops the
+    /// frame.
+    pub fn run_frame(&mut self) -> Result<Option<Divergence>, Chip8Error> {
+        self.a.begin_frame();
+        self.b.begin_frame();
+
+        while !self.a.is_frame_over() || !self.b.is_frame_over() {
+            let stepping = if self.a.is_frame_over() {
+                &self.b
+            } else {
+                &self.a
+            };
+            let pc = stepping.pc;
+            let opcode = stepping.next_opcode();
+            if !self.a.is_frame_over() {
+                self.a.step_in_frame()?;
+            }
+            if !self.b.is_frame_over() {
+                self.b.step_in_frame()?;
+            }
+            if let Some(divergence) = self.compare(pc, opcode) {
+                return Ok(Some(divergence));
+            }
+            self.instructions += 1;
+        }
+
+        for chip8 in [&mut self.a, &mut self.b] {
+            chip8.end_frame();
+            chip8.frame_ops = 0;
+        }
+        self.frames += 1;
+        Ok(None)
+    }
+
+    /// Run up to `frames` frames, stopping at the first divergence
+    pub fn run(&mut self, frames: u64) -> Result<Option<Divergence>, Chip8Error> {
+        for _ in 0..frames {
+            if let Some(divergence) = self.run_frame()? {
+                return Ok(Some(divergence));
+            }
+        }
+        Ok(None)
+    }
+
+    fn compare(&self, pc: u16, opcode: u16) -> Option<Divergence> {
+        let a = self.a.dump_state();
+        let b = self.b.dump_state();
+        (a != b).then(|| Divergence {
+            frame: self.frames,
+            instruction: self.instructions,
+            pc,
+            opcode,
+            diff: StateDiff::between_states(&a, &b),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Lockstep;
+    use crate::Chip8;
+
+    #[test]
+    fn test_lockstep() {
+        // 8XY6 shifts VY into VX with the legacy quirk, and VX in place otherwise
+        let rom = [0x60, 0x01, 0x61, 0x08, 0x80, 0x16, 0x12, 0x06];
+        let chip8 = |legacy_shift| {
+            let mut chip8 = Chip8::new().unwrap().legacy_shift(legacy_shift);
+            chip8.load_rom(&rom).unwrap();
+            chip8
+        };
+
+        let mut lockstep = Lockstep::new(chip8(false), chip8(false));
+        assert!(lockstep.run(3).unwrap().is_none());
+        assert_eq!(lockstep.frames(), 3);
+
+        let mut lockstep = Lockstep::new(chip8(false), chip8(true));
+        let divergence = lockstep.run(3).unwrap().unwrap();
+        assert_eq!(divergence.instruction, 2);
+        assert_eq!(divergence.pc, 0x204);
+        assert_eq!(divergence.opcode, 0x8016);
+        assert_eq!(divergence.diff.registers[0].name, "V0");
+        assert!(divergence
+            .to_string()
+            .starts_with("diverged at instruction 2"));
+    }
+
+    #[test]
+    fn test_lockstep_vip_timing() {
+        // with VIP timing a frame lasts as many machine cycles as the VIP had, however slow the
+        // clock is set, so both instances reach the shift in the first frame
+        let rom = [0x60, 0x01, 0x61, 0x08, 0x80, 0x16, 0x12, 0x06];
+        let chip8 = |legacy_shift| {
+            let mut chip8 = Chip8::new()
+                .unwrap()
+                .legacy_shift(legacy_shift)
+                .clock_hz(60)
+                .vip_timing(true);
+            chip8.load_rom(&rom).unwrap();
+            chip8
+        };
+
+        let mut lockstep = Lockstep::new(chip8(false), chip8(false));
+        assert!(lockstep.run(2).unwrap().is_none());
+        assert!(lockstep.instructions > 2);
+
+        let mut lockstep = Lockstep::new(chip8(false), chip8(true));
+        let divergence = lockstep.run(3).unwrap().unwrap();
+        assert_eq!(divergence.frame, 0);
+        assert_eq!(divergence.instruction, 2);
+    }
+}
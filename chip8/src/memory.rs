@@ -3,26 +3,48 @@ use std::fmt::Display;
 use anyhow::ensure;
 
 pub struct Memory {
-    pub(crate) data: [u8; crate::MEM_SIZE],
+    pub(crate) data: Vec<u8>,
+    /// A bitset of the addresses that instructions have been fetched from
+    pub(crate) executed: Vec<u64>,
 }
 
 impl Memory {
-    /// Create an empty instance of the Memory struct
-    pub fn new() -> Self {
+    /// Create an empty instance of the Memory struct with `size` bytes
+    pub fn new(size: usize) -> Self {
         Self {
-            data: [0; crate::MEM_SIZE],
+            data: vec![0; size],
+            executed: vec![0; size.div_ceil(64)],
         }
     }
 
-    /// Write `data` into memory starting at `addr` and return the number of bytes written
-    pub fn write(&mut self, addr: usize, data: &[u8]) -> anyhow::Result<u16> {
+    pub fn mark_executed(&mut self, addr: usize) {
+        if addr < self.data.len() {
+            self.executed[addr / 64] |= 1 << (addr % 64);
+        }
+    }
+
+    pub fn is_executed(&self, addr: usize) -> bool {
+        addr < self.data.len() && self.executed[addr / 64] & (1 << (addr % 64)) != 0
+    }
+
+    /// Forget which addresses have been executed
+    pub fn clear_executed(&mut self) {
+        self.executed.fill(0);
+    }
+
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Write `data` into memory starting at `addr` and return the number of bytes available from `addr`
+    pub fn write(&mut self, addr: usize, data: &[u8]) -> anyhow::Result<usize> {
         ensure!(addr < self.data.len(), "memory write address out of bounds");
 
         let available = self.data.len() - addr;
         ensure!(available >= data.len(), "memory write overflow");
 
         self.data[addr..addr + data.len()].copy_from_slice(&data[..data.len()]);
-        Ok(available as u16)
+        Ok(available)
     }
 }
 
@@ -39,3 +61,21 @@ impl Display for Memory {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Memory;
+
+    #[test]
+    fn test_executed() {
+        let mut memory = Memory::new(0x100);
+        memory.mark_executed(0x40);
+        memory.mark_executed(0x1000);
+        assert_eq!(memory.is_executed(0x40), true);
+        assert_eq!(memory.is_executed(0x41), false);
+        assert_eq!(memory.is_executed(0x1000), false);
+
+        memory.clear_executed();
+        assert_eq!(memory.is_executed(0x40), false);
+    }
+}
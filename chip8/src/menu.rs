@@ -0,0 +1,265 @@
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "std")]
+use anyhow::Context;
+
+use crate::{glyph, Chip8, FONT_CHAR_LENGTH, ROM_ADDR};
+
+/// The number of entries on each page of the menu
+const PAGE_ENTRIES: usize = 4;
+/// The number of characters of each title that are shown
+const TITLE_LENGTH: usize = 11;
+/// The number of sprites a title is drawn with, each 8 pixels wide
+const TITLE_SPRITES: usize = 7;
+/// The most ROMs a menu lists, which is as many whole pages as fit in memory after the program
+pub const MAX_MENU_ENTRIES: usize = 24 * PAGE_ENTRIES;
+
+/// Where the program stores the index of the chosen entry
+const CHOSEN_ADDR: usize = 0x29E;
+/// Where the cursor sprite is kept
+const CURSOR_ADDR: usize = 0x29F;
+/// Where the drawn titles start, one after another
+const TITLES_ADDR: usize = 0x2A4;
+/// The value `CHOSEN_ADDR` holds until an entry is chosen
+const NOTHING_CHOSEN: u8 = 0xFF;
+
+/// The menu program, which draws a page of titles with a cursor next to the selected one. 2 and
+/// 8 move the cursor up and down, turning the page once it moves past either end, and 5 stores
+/// the index of the selected entry at `CHOSEN_ADDR` and stops.
+#[rustfmt::skip]
+const PROGRAM: [u16; 79] = [
+    0x6A00, // 0x200: LD VA, 0
+    0x6C00, // 0x202: LD VC, the number of entries
+    0x6405, // 0x204: LD V4, 5
+    0x00E0, // 0x206: CLS, then draw the page the cursor is on
+    0x8BA0, // 0x208: LD VB, VA
+    0x68FC, // 0x20A: LD V8, 0xFC
+    0x8B82, // 0x20C: AND VB, V8, the first entry on the page
+    0xA2A4, // 0x20E: LD I, 0x2A4
+    0x83B0, // 0x210: LD V3, VB
+    0x688C, // 0x212: LD V8, 140
+    0x3300, // 0x214: SE V3, 0
+    0x121A, // 0x216: JP 0x21A
+    0x1220, // 0x218: JP 0x220
+    0xF81E, // 0x21A: ADD I, V8
+    0x73FC, // 0x21C: ADD V3, 0xFC
+    0x1214, // 0x21E: JP 0x214
+    0x6500, // 0x220: LD V5, 0
+    0x6102, // 0x222: LD V1, 2
+    0x86B0, // 0x224: LD V6, VB
+    0x8654, // 0x226: ADD V6, V5
+    0x86C5, // 0x228: SUB V6, VC, which clears VF before the end of the list
+    0x3F00, // 0x22A: SE VF, 0
+    0x1246, // 0x22C: JP 0x246
+    0x6005, // 0x22E: LD V0, 5
+    0x6207, // 0x230: LD V2, 7
+    0xD015, // 0x232: DRW V0, V1, 5
+    0xF41E, // 0x234: ADD I, V4
+    0x7008, // 0x236: ADD V0, 8
+    0x72FF, // 0x238: ADD V2, 0xFF
+    0x3200, // 0x23A: SE V2, 0
+    0x1232, // 0x23C: JP 0x232
+    0x7107, // 0x23E: ADD V1, 7
+    0x7501, // 0x240: ADD V5, 1
+    0x3504, // 0x242: SE V5, 4
+    0x1224, // 0x244: JP 0x224
+    0x2284, // 0x246: CALL 0x284 to draw the cursor
+    0xFD0A, // 0x248: LD VD, K
+    0x4D02, // 0x24A: SNE VD, 2
+    0x1258, // 0x24C: JP 0x258
+    0x4D08, // 0x24E: SNE VD, 8
+    0x1262, // 0x250: JP 0x262
+    0x4D05, // 0x252: SNE VD, 5
+    0x127C, // 0x254: JP 0x27C
+    0x1248, // 0x256: JP 0x248
+    0x4A00, // 0x258: SNE VA, 0
+    0x1248, // 0x25A: JP 0x248
+    0x2284, // 0x25C: CALL 0x284 to erase the cursor
+    0x7AFF, // 0x25E: ADD VA, 0xFF
+    0x126E, // 0x260: JP 0x26E
+    0x86A0, // 0x262: LD V6, VA
+    0x7601, // 0x264: ADD V6, 1
+    0x96C0, // 0x266: SNE V6, VC
+    0x1248, // 0x268: JP 0x248
+    0x2284, // 0x26A: CALL 0x284
+    0x7A01, // 0x26C: ADD VA, 1
+    0x86A0, // 0x26E: LD V6, VA
+    0x68FC, // 0x270: LD V8, 0xFC
+    0x8682, // 0x272: AND V6, V8
+    0x56B0, // 0x274: SE V6, VB
+    0x1206, // 0x276: JP 0x206
+    0x2284, // 0x278: CALL 0x284
+    0x1248, // 0x27A: JP 0x248
+    0x80A0, // 0x27C: LD V0, VA to store the chosen entry
+    0xA29E, // 0x27E: LD I, 0x29E
+    0xF055, // 0x280: LD [I], V0
+    0x1282, // 0x282: JP 0x282
+    0x87A0, // 0x284: LD V7, VA
+    0x6803, // 0x286: LD V8, 3
+    0x8782, // 0x288: AND V7, V8
+    0x8670, // 0x28A: LD V6, V7
+    0x877E, // 0x28C: SHL V7, V7
+    0x877E, // 0x28E: SHL V7, V7
+    0x877E, // 0x290: SHL V7, V7
+    0x8765, // 0x292: SUB V7, V6, seven times the row
+    0x7702, // 0x294: ADD V7, 2
+    0x6600, // 0x296: LD V6, 0
+    0xA29F, // 0x298: LD I, 0x29F
+    0xD675, // 0x29A: DRW V6, V7, 5
+    0x00EE, // 0x29C: RET
+];
+
+/// A menu for picking a ROM on the CHIP-8 screen itself, for frontends without a UI of their own
+/// to list ROMs in. The titles are drawn into the program when it's generated.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RomMenu {
+    entries: Vec<PathBuf>,
+}
+
+impl RomMenu {
+    /// A menu of `entries`, keeping the first `MAX_MENU_ENTRIES` if there are more
+    pub fn new(mut entries: Vec<PathBuf>) -> Self {
+        entries.truncate(MAX_MENU_ENTRIES);
+        Self { entries }
+    }
+
+    #[cfg(feature = "std")]
+    /// A menu of the CHIP-8 ROMs in `dir`, sorted by title
+    pub fn scan(dir: &Path) -> anyhow::Result<Self> {
+        let read_dir =
+            std::fs::read_dir(dir).with_context(|| format!("read directory {}", dir.display()))?;
+        let mut entries = Vec::new();
+        for item in read_dir {
+            let path = item.context("read directory entry")?.path();
+            let extension = path
+                .extension()
+                .and_then(|e| e.to_str())
+                .unwrap_or_default();
+            if matches!(extension.to_ascii_lowercase().as_str(), "ch8" | "c8") {
+                entries.push(path);
+            }
+        }
+        entries.sort_by_key(|path| title(path).to_ascii_lowercase());
+        Ok(Self::new(entries))
+    }
+
+    pub fn entries(&self) -> &[PathBuf] {
+        &self.entries
+    }
+
+    /// The menu program along with the titles it lists
+    pub fn rom(&self) -> Vec<u8> {
+        let mut rom: Vec<u8> = PROGRAM
+            .iter()
+            .enumerate()
+            .flat_map(|(i, opcode)| match i {
+                // LD VC, NN
+                1 => (opcode | self.entries.len() as u16).to_be_bytes(),
+                _ => opcode.to_be_bytes(),
+            })
+            .collect();
+        // the program points I at each of these, so they have to land where it expects
+        debug_assert_eq!(ROM_ADDR + rom.len(), CHOSEN_ADDR);
+        rom.push(NOTHING_CHOSEN);
+        debug_assert_eq!(ROM_ADDR + rom.len(), CURSOR_ADDR);
+        rom.extend_from_slice(&glyph('>'));
+        debug_assert_eq!(ROM_ADDR + rom.len(), TITLES_ADDR);
+        for path in &self.entries {
+            rom.extend_from_slice(&draw_title(&title(path)));
+        }
+        rom
+    }
+
+    /// The entry chosen in the menu running on `chip8`, once one has been
+    pub fn chosen(&self, chip8: &Chip8) -> Option<&Path> {
+        let index = *chip8.memory().get(CHOSEN_ADDR)?;
+        self.entries.get(index as usize).map(PathBuf::as_path)
+    }
+}
+
+/// The title shown for the ROM at `path`, which is its file name without the extension or any
+/// credits in square brackets
+fn title(path: &Path) -> String {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    stem.split('[')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+/// Draw the start of `title` as the `TITLE_SPRITES` sprites the program draws side by side, each
+/// `FONT_CHAR_LENGTH` rows tall
+fn draw_title(title: &str) -> [u8; TITLE_SPRITES * FONT_CHAR_LENGTH] {
+    // each row of the title, with the leftmost pixel in the top bit
+    let mut rows = [0u64; FONT_CHAR_LENGTH];
+    for (i, c) in title.chars().take(TITLE_LENGTH).enumerate() {
+        for (row, bits) in rows.iter_mut().zip(glyph(c)) {
+            *row |= ((bits >> 4) as u64) << (60 - i * 5);
+        }
+    }
+    let mut sprites = [0; TITLE_SPRITES * FONT_CHAR_LENGTH];
+    for (sprite, bytes) in sprites.chunks_exact_mut(FONT_CHAR_LENGTH).enumerate() {
+        for (byte, row) in bytes.iter_mut().zip(rows) {
+            *byte = (row >> (56 - sprite * 8)) as u8;
+        }
+    }
+    sprites
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{RomMenu, MAX_MENU_ENTRIES, PAGE_ENTRIES};
+    use crate::{Chip8, Key};
+
+    #[test]
+    fn test_rom_menu() {
+        let entries: Vec<PathBuf> = ["pong.ch8", "tetris [Fran Dachille, 1991].ch8", "maze.ch8"]
+            .iter()
+            .cycle()
+            .take(PAGE_ENTRIES + 2)
+            .map(PathBuf::from)
+            .collect();
+        let menu = RomMenu::new(entries);
+        let mut chip8 = Chip8::new().unwrap().clock_hz(3000);
+        chip8.load_rom(&menu.rom()).unwrap();
+        chip8.run_frames(5).unwrap();
+        // the cursor's point, and the top left of the P of PONG
+        let fb = chip8.fb();
+        assert_eq!(fb.get(2, 4), 1);
+        assert_eq!(fb.get(5, 2), 1);
+        assert_eq!(menu.chosen(&chip8), None);
+
+        let press = |chip8: &mut Chip8, key: u8| {
+            chip8.press_key_for(Key::from_value(key), 2);
+            chip8.run_frames(5).unwrap();
+        };
+        press(&mut chip8, 0x8);
+        let fb = chip8.fb();
+        assert_eq!(fb.get(2, 4), 0);
+        assert_eq!(fb.get(2, 11), 1);
+
+        // moving past the last entry on the page turns to the next one, and past the end of the
+        // list does nothing
+        for _ in 0..PAGE_ENTRIES + 2 {
+            press(&mut chip8, 0x8);
+        }
+        press(&mut chip8, 0x2);
+        let fb = chip8.fb();
+        assert_eq!(fb.get(2, 4), 1);
+        assert_eq!(fb.get(5, 9), 1);
+        assert_eq!(fb.get(5, 16), 0);
+        press(&mut chip8, 0x5);
+        assert_eq!(
+            menu.chosen(&chip8),
+            Some(PathBuf::from("tetris [Fran Dachille, 1991].ch8").as_path())
+        );
+
+        let entries = vec![PathBuf::from("a.ch8"); MAX_MENU_ENTRIES + 1];
+        let menu = RomMenu::new(entries);
+        assert_eq!(menu.entries().len(), MAX_MENU_ENTRIES);
+        assert!(menu.rom().len() <= 0x1000 - 0x200);
+    }
+}
@@ -0,0 +1,233 @@
+use std::fmt::Display;
+#[cfg(feature = "std")]
+use std::io::{ErrorKind, Read, Write};
+#[cfg(feature = "std")]
+use std::net::{SocketAddr, TcpListener, TcpStream};
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "std")]
+use anyhow::Context;
+
+use crate::{Chip8, Event};
+
+#[cfg(feature = "std")]
+/// How long a scrape has to send its whole request before it's dropped, so a slow client can only
+/// hold up emulation briefly
+const REQUEST_TIMEOUT: Duration = Duration::from_millis(50);
+
+#[cfg(feature = "std")]
+/// The longest request head that's read
+const MAX_REQUEST_LENGTH: usize = 8192;
+
+/// Counters describing a running instance, for monitoring many of them
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Metrics {
+    /// The number of frames emulated
+    pub frames: u64,
+    /// The number of instructions executed
+    pub instructions: u64,
+    /// The speed the instance is set to run at
+    pub ips: usize,
+    /// The number of times execution halted
+    pub halts: u64,
+    /// The number of peers connected, such as a netplay partner
+    pub clients: usize,
+    /// The number of key presses and releases that were thrown away rather than applied
+    pub dropped_inputs: u64,
+}
+
+impl Metrics {
+    /// Count the frame `chip8` just ran
+    pub fn record_frame(&mut self, chip8: &Chip8) {
+        self.frames += 1;
+        self.instructions = chip8.instructions_run();
+        self.ips = chip8.ips();
+        self.halts += chip8
+            .events()
+            .iter()
+            .filter(|event| matches!(event, Event::Halted(_)))
+            .count() as u64;
+    }
+}
+
+impl Display for Metrics {
+    /// Write the metrics in the Prometheus text exposition format
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let metrics = [
+            ("frames_total", "counter", "Frames emulated", self.frames),
+            (
+                "instructions_total",
+                "counter",
+                "Instructions executed",
+                self.instructions,
+            ),
+            (
+                "ips",
+                "gauge",
+                "The speed in instructions per second",
+                self.ips as u64,
+            ),
+            (
+                "halts_total",
+                "counter",
+                "Times execution halted",
+                self.halts,
+            ),
+            ("clients", "gauge", "Peers connected", self.clients as u64),
+            (
+                "dropped_inputs_total",
+                "counter",
+                "Key events thrown away rather than applied",
+                self.dropped_inputs,
+            ),
+        ];
+        for (name, kind, help, value) in metrics {
+            writeln!(f, "# HELP chipper_{} {}", name, help)?;
+            writeln!(f, "# TYPE chipper_{} {}", name, kind)?;
+            writeln!(f, "chipper_{} {}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+/// Serves `Metrics` over HTTP at `/metrics` for Prometheus to scrape. Requests are only answered
+/// from `poll`, so serving them needs no thread and never blocks for long.
+pub struct MetricsServer {
+    listener: TcpListener,
+}
+
+#[cfg(feature = "std")]
+impl MetricsServer {
+    /// Listen on `addr`, e.g. `127.0.0.1:9184`
+    pub fn bind(addr: &str) -> anyhow::Result<Self> {
+        let listener = TcpListener::bind(addr).context("bind metrics address")?;
+        listener
+            .set_nonblocking(true)
+            .context("configure metrics socket")?;
+        Ok(Self { listener })
+    }
+
+    /// The address being listened on, which has the port picked when binding to port 0
+    pub fn local_addr(&self) -> anyhow::Result<SocketAddr> {
+        self.listener
+            .local_addr()
+            .context("read metrics socket address")
+    }
+
+    /// Answer every request waiting with `metrics`. Call this once a frame.
+    pub fn poll(&self, metrics: &Metrics) -> anyhow::Result<()> {
+        loop {
+            match self.listener.accept() {
+                // a client that goes away or sends garbage only affects its own scrape
+                Ok((stream, _)) => {
+                    let _ = respond(stream, metrics);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e) => return Err(e).context("accept metrics connection"),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+/// Read the request on `stream` and reply with the metrics, or a 404 for any path but `/metrics`
+fn respond(mut stream: TcpStream, metrics: &Metrics) -> std::io::Result<()> {
+    stream.set_nonblocking(false)?;
+
+    // the timeout covers the whole request, as a client trickling in a byte at a time would
+    // otherwise reset it with every read
+    let deadline = Instant::now() + REQUEST_TIMEOUT;
+    let mut request = Vec::new();
+    let mut buf = [0; 1024];
+    while !request.windows(4).any(|end| end == b"\r\n\r\n") && request.len() < MAX_REQUEST_LENGTH {
+        let remaining = deadline
+            .checked_duration_since(Instant::now())
+            .filter(|remaining| !remaining.is_zero())
+            .ok_or(ErrorKind::TimedOut)?;
+        stream.set_read_timeout(Some(remaining))?;
+        let n = stream.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let path = request.split_whitespace().nth(1).unwrap_or_default();
+    let (status, body) = match path {
+        "/metrics" => ("200 OK", metrics.to_string()),
+        _ => ("404 Not Found", "not found\n".to_string()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::time::{Duration, Instant};
+
+    use super::{Metrics, MetricsServer};
+    use crate::Chip8;
+
+    #[test]
+    fn test_metrics() {
+        let mut chip8 = Chip8::new().unwrap().clock_hz(240);
+        chip8.load_rom(&[0x60, 0x01, 0x12, 0x00]).unwrap();
+        let mut metrics = Metrics::default();
+        for _ in 0..3 {
+            chip8.cycle().unwrap();
+            metrics.record_frame(&chip8);
+        }
+        assert_eq!(metrics.frames, 3);
+        assert_eq!(metrics.instructions, 12);
+        assert_eq!(metrics.ips, 240);
+        assert!(metrics
+            .to_string()
+            .contains("# TYPE chipper_frames_total counter\nchipper_frames_total 3\n"));
+
+        let server = MetricsServer::bind("127.0.0.1:0").unwrap();
+        let scrape = |path: &str| {
+            let mut stream = TcpStream::connect(server.local_addr().unwrap()).unwrap();
+            write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+            server.poll(&metrics).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+        let response = scrape("/metrics");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with(&metrics.to_string()));
+        assert!(scrape("/").starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+
+    #[test]
+    fn test_metrics_slow_client() {
+        let server = MetricsServer::bind("127.0.0.1:0").unwrap();
+        let mut stream = TcpStream::connect(server.local_addr().unwrap()).unwrap();
+        // a byte at a time, each well inside the timeout, never finishing the request
+        let client = std::thread::spawn(move || {
+            for _ in 0..100 {
+                if stream.write_all(b"G").is_err() {
+                    break;
+                }
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        });
+        std::thread::sleep(Duration::from_millis(10));
+
+        let start = Instant::now();
+        server.poll(&Metrics::default()).unwrap();
+        assert!(start.elapsed() < Duration::from_millis(500));
+        client.join().unwrap();
+    }
+}
@@ -0,0 +1,481 @@
+use std::fmt::Display;
+#[cfg(feature = "std")]
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{bail, ensure, Context};
+
+use crate::{Chip8, Player, FRAME_RATE};
+
+/// The first line of every movie file
+const MAGIC: &str = "c8m";
+
+/// The movie format version, bumped whenever the layout changes
+const VERSION: u32 = 1;
+
+/// The number of frames between each state hash recorded in a movie
+pub const CHECKPOINT_INTERVAL: usize = 60;
+
+/// The version of chipper a movie was recorded with
+pub const EMULATOR_VERSION: &str = concat!("chipper ", env!("CARGO_PKG_VERSION"));
+
+/// The settings that change how a program runs, which playback has to match for the recorded
+/// input to have the same effect
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MovieQuirks {
+    pub legacy_shift: bool,
+    pub jump_add_offset: bool,
+    pub memory_increment_i: bool,
+    pub display_wait: bool,
+    pub wrap_sprites: bool,
+    pub unmasked_font: bool,
+    pub vip_timing: bool,
+    /// The clock speed in instructions per second
+    pub clock_hz: u32,
+}
+
+/// A recording of the input for every frame of a run, along with everything needed to replay it
+/// exactly. Movies are stored as `.c8m` text files, with a header of `key value` lines followed
+/// by an `input` line and then one line per frame holding each player's keypad mask in hex.
+/// Every `CHECKPOINT_INTERVAL` frames a `checkpoint HASH` line records the state hash after the
+/// frame before it, which playback checks to catch desyncs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Movie {
+    /// The SHA-1 digest of the ROM the movie was recorded with
+    pub rom_hash: String,
+    /// The emulator that recorded the movie, as reported by `EMULATOR_VERSION`
+    pub emulator: String,
+    pub quirks: MovieQuirks,
+    /// The seed CXNN's random numbers were generated from
+    pub seed: u64,
+    /// The keypad masks of both players for each frame
+    pub frames: Vec<[u16; 2]>,
+    /// The state hash after each checkpointed frame, along with the number of frames run by then
+    pub checkpoints: Vec<(usize, u64)>,
+}
+
+/// The first checkpoint a movie's playback didn't match
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Desync {
+    /// The number of frames played before the mismatch
+    pub frame: usize,
+    /// The state hash recorded in the movie
+    pub expected: u64,
+    /// The state hash during playback
+    pub actual: u64,
+}
+
+impl Display for Desync {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "desync after frame {}: state hash {:016x}, expected {:016x}",
+            self.frame, self.actual, self.expected
+        )
+    }
+}
+
+impl Movie {
+    /// An empty movie to record a run of `chip8`, which must have been seeded with `seed`
+    pub fn new(rom_hash: &str, chip8: &Chip8, seed: u64) -> Self {
+        Self {
+            rom_hash: rom_hash.to_string(),
+            emulator: EMULATOR_VERSION.to_string(),
+            quirks: chip8.movie_quirks(),
+            seed,
+            frames: Vec::new(),
+            checkpoints: Vec::new(),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path).context("read movie")?;
+        text.parse::<Self>()
+            .with_context(|| format!("parse movie {}", path.display()))
+    }
+
+    #[cfg(feature = "std")]
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        std::fs::write(path, self.to_string()).context("write movie")
+    }
+
+    /// Record the input `chip8` is about to run the next frame with. Call this before each
+    /// `cycle`.
+    pub fn record_frame(&mut self, chip8: &Chip8) {
+        self.frames.push([
+            chip8.next_frame_input(Player::One),
+            chip8.next_frame_input(Player::Two),
+        ]);
+    }
+
+    /// Record the state hash of `chip8` if a checkpoint is due. Call this after each `cycle`.
+    pub fn record_checkpoint(&mut self, chip8: &Chip8) {
+        let frame = self.frames.len();
+        if frame > 0 && frame.is_multiple_of(CHECKPOINT_INTERVAL) {
+            self.checkpoints.push((frame, chip8.state_hash()));
+        }
+    }
+
+    /// Fail unless the movie was recorded with the ROM with the SHA-1 digest `rom_hash`
+    pub fn check_rom(&self, rom_hash: &str) -> anyhow::Result<()> {
+        ensure!(
+            self.rom_hash == rom_hash,
+            "movie was recorded with ROM {} but {} is loaded",
+            self.rom_hash,
+            rom_hash
+        );
+        Ok(())
+    }
+
+    /// Set up `chip8` to replay the movie, with its quirks and its seed
+    pub fn configure(&self, chip8: Chip8) -> Chip8 {
+        chip8.movie_quirks_from(&self.quirks).rng_seed(self.seed)
+    }
+}
+
+impl Chip8 {
+    /// The settings a movie recorded now would need to be played back with
+    pub fn movie_quirks(&self) -> MovieQuirks {
+        MovieQuirks {
+            legacy_shift: self.config.quirks.legacy_shift,
+            jump_add_offset: self.config.quirks.jump_add_offset,
+            memory_increment_i: self.config.quirks.memory_increment_i,
+            display_wait: self.config.quirks.display_wait,
+            wrap_sprites: self.config.quirks.wrap_sprites,
+            unmasked_font: self.config.quirks.unmasked_font,
+            vip_timing: self.config.vip_timing,
+            clock_hz: self.config.clock_hz,
+        }
+    }
+
+    fn movie_quirks_from(self, quirks: &MovieQuirks) -> Self {
+        self.legacy_shift(quirks.legacy_shift)
+            .jump_add_offset(quirks.jump_add_offset)
+            .memory_increment_i(quirks.memory_increment_i)
+            .display_wait(quirks.display_wait)
+            .wrap_sprites(quirks.wrap_sprites)
+            .unmasked_font(quirks.unmasked_font)
+            .vip_timing(quirks.vip_timing)
+            .clock_hz(quirks.clock_hz)
+    }
+}
+
+/// Replays the input of a movie a frame at a time
+pub struct MoviePlayer {
+    movie: Movie,
+    frame: usize,
+    /// The index of the next checkpoint to verify
+    checkpoint: usize,
+}
+
+impl MoviePlayer {
+    pub fn new(movie: Movie) -> Self {
+        Self {
+            movie,
+            frame: 0,
+            checkpoint: 0,
+        }
+    }
+
+    /// The number of frames played so far
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.frame >= self.movie.frames.len()
+    }
+
+    /// Latch the input for the next frame into `chip8`, returning false once every frame has
+    /// been played. Call this before each `cycle`.
+    pub fn next_frame(&mut self, chip8: &mut Chip8) -> bool {
+        let Some([p1, p2]) = self.movie.frames.get(self.frame) else {
+            return false;
+        };
+        chip8.latch_input(Player::One, *p1);
+        chip8.latch_input(Player::Two, *p2);
+        self.frame += 1;
+        true
+    }
+
+    /// Check the state of `chip8` against the movie if there's a checkpoint for the frame just
+    /// played. Call this after each `cycle`.
+    pub fn verify(&mut self, chip8: &Chip8) -> Result<(), Desync> {
+        let Some(&(frame, expected)) = self.movie.checkpoints.get(self.checkpoint) else {
+            return Ok(());
+        };
+        if frame != self.frame {
+            return Ok(());
+        }
+        self.checkpoint += 1;
+
+        let actual = chip8.state_hash();
+        if actual != expected {
+            return Err(Desync {
+                frame,
+                expected,
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    /// The number of checkpoints verified so far
+    pub fn checkpoints_verified(&self) -> usize {
+        self.checkpoint
+    }
+}
+
+const QUIRK_NAMES: [&str; 7] = [
+    "legacy-shift",
+    "jump-add-offset",
+    "memory-increment-i",
+    "display-wait",
+    "wrap-sprites",
+    "unmasked-font",
+    "vip-timing",
+];
+
+impl Display for Movie {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let quirks = &self.quirks;
+        let enabled = [
+            quirks.legacy_shift,
+            quirks.jump_add_offset,
+            quirks.memory_increment_i,
+            quirks.display_wait,
+            quirks.wrap_sprites,
+            quirks.unmasked_font,
+            quirks.vip_timing,
+        ];
+        let names: Vec<&str> = QUIRK_NAMES
+            .iter()
+            .zip(enabled)
+            .filter(|(_, enabled)| *enabled)
+            .map(|(name, _)| *name)
+            .collect();
+
+        writeln!(f, "{} {}", MAGIC, VERSION)?;
+        writeln!(f, "rom {}", self.rom_hash)?;
+        writeln!(f, "emulator {}", self.emulator)?;
+        writeln!(f, "seed {}", self.seed)?;
+        writeln!(f, "quirks {}", names.join(" "))?;
+        writeln!(f, "clock-hz {}", quirks.clock_hz)?;
+        writeln!(f, "input")?;
+        let mut checkpoints = self.checkpoints.iter().peekable();
+        for (frame, [p1, p2]) in self.frames.iter().enumerate() {
+            writeln!(f, "{:04x} {:04x}", p1, p2)?;
+            while let Some((_, hash)) = checkpoints.next_if(|(at, _)| *at == frame + 1) {
+                writeln!(f, "checkpoint {:016x}", hash)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Movie {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s
+            .lines()
+            .enumerate()
+            .map(|(n, line)| (n + 1, line.split('#').next().unwrap_or_default().trim()))
+            .filter(|(_, line)| !line.is_empty());
+
+        let version = match lines.next() {
+            Some((_, line)) => line.strip_prefix(MAGIC).map(str::trim),
+            None => None,
+        };
+        let version: u32 = version
+            .and_then(|version| version.parse().ok())
+            .context("not a movie file")?;
+        ensure!(
+            version == VERSION,
+            "unsupported movie version {} (expected {})",
+            version,
+            VERSION
+        );
+
+        let (mut rom_hash, mut emulator, mut seed, mut clock_hz) = (None, None, None, None);
+        let mut quirks = [false; QUIRK_NAMES.len()];
+        for (n, line) in lines.by_ref() {
+            if line == "input" {
+                break;
+            }
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            let value = value.trim();
+            match key {
+                "rom" => rom_hash = Some(value.to_string()),
+                "emulator" => emulator = Some(value.to_string()),
+                "seed" => {
+                    seed = Some(
+                        value
+                            .parse::<u64>()
+                            .with_context(|| format!("line {}: invalid seed '{}'", n, value))?,
+                    )
+                }
+                "quirks" => {
+                    for name in value.split_whitespace() {
+                        let Some(index) = QUIRK_NAMES.iter().position(|quirk| *quirk == name)
+                        else {
+                            bail!("line {}: unknown quirk '{}'", n, name);
+                        };
+                        quirks[index] = true;
+                    }
+                }
+                "clock-hz" => {
+                    clock_hz =
+                        Some(value.parse::<u32>().with_context(|| {
+                            format!("line {}: invalid clock speed '{}'", n, value)
+                        })?)
+                }
+                // movies recorded before the clock speed was given in Hz
+                "ops-per-cycle" => {
+                    let ops = value.parse::<u32>().with_context(|| {
+                        format!("line {}: invalid ops per cycle '{}'", n, value)
+                    })?;
+                    clock_hz = Some(ops * FRAME_RATE as u32)
+                }
+                _ => bail!("line {}: unknown movie header '{}'", n, key),
+            }
+        }
+
+        let (mut frames, mut checkpoints) = (Vec::new(), Vec::new());
+        for (n, line) in lines {
+            if let Some(hash) = line.strip_prefix("checkpoint ") {
+                let hash = u64::from_str_radix(hash.trim(), 16)
+                    .with_context(|| format!("line {}: invalid checkpoint hash", n))?;
+                checkpoints.push((frames.len(), hash));
+                continue;
+            }
+            let masks: Vec<u16> = line
+                .split_whitespace()
+                .map(|mask| u16::from_str_radix(mask, 16))
+                .collect::<Result<_, _>>()
+                .with_context(|| format!("line {}: invalid keypad mask", n))?;
+            let [p1, p2] = masks[..] else {
+                bail!("line {}: expected a keypad mask for each player", n);
+            };
+            frames.push([p1, p2]);
+        }
+
+        let [legacy_shift, jump_add_offset, memory_increment_i, display_wait, wrap_sprites, unmasked_font, vip_timing] =
+            quirks;
+        Ok(Self {
+            rom_hash: rom_hash.context("movie has no rom hash")?,
+            emulator: emulator.unwrap_or_default(),
+            quirks: MovieQuirks {
+                legacy_shift,
+                jump_add_offset,
+                memory_increment_i,
+                display_wait,
+                wrap_sprites,
+                unmasked_font,
+                vip_timing,
+                clock_hz: clock_hz.context("movie has no clock speed")?,
+            },
+            seed: seed.context("movie has no seed")?,
+            frames,
+            checkpoints,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Desync, Movie, MoviePlayer, CHECKPOINT_INTERVAL};
+    use crate::{rom_hash, Chip8, Key, Player};
+
+    #[test]
+    fn test_movie_round_trip() {
+        let rom = [0x12, 0x00];
+        let mut chip8 = Chip8::new().unwrap().legacy_shift(true).rng_seed(7);
+        chip8.load_rom(&rom).unwrap();
+        let mut movie = Movie::new(&rom_hash(&rom), &chip8, 7);
+        movie.record_frame(&chip8);
+        chip8.keydown(Key::from_value(0x5)).unwrap();
+        movie.record_frame(&chip8);
+
+        let text = movie.to_string();
+        assert!(text.starts_with("c8m 1\nrom "));
+        assert!(text.contains("\nquirks legacy-shift\nclock-hz 660\ninput\n0000 0000\n0020 0000\n"));
+        assert_eq!(text.parse::<Movie>().unwrap(), movie);
+        let old = text.replace("clock-hz 660", "ops-per-cycle 11");
+        assert_eq!(old.parse::<Movie>().unwrap(), movie);
+
+        assert!(movie.check_rom(&rom_hash(&rom)).is_ok());
+        assert!(movie.check_rom(&rom_hash(&[0x00])).is_err());
+        assert!("c8m 2\n".parse::<Movie>().is_err());
+        assert!(text.replace("0020 0000", "0020").parse::<Movie>().is_err());
+    }
+
+    #[test]
+    fn test_movie_player() {
+        let rom = [0x12, 0x00];
+        let mut movie = Movie::new(&rom_hash(&rom), &Chip8::new().unwrap(), 0);
+        movie.frames = vec![[0x1, 0x0], [0x0, 0x8]];
+
+        let mut chip8 = movie.configure(Chip8::new().unwrap());
+        chip8.load_rom(&rom).unwrap();
+        let mut player = MoviePlayer::new(movie);
+        assert_eq!(player.next_frame(&mut chip8), true);
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.keypad_mask(Player::One), 0x1);
+        assert_eq!(player.next_frame(&mut chip8), true);
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.keypad_mask(Player::One), 0x0);
+        assert_eq!(chip8.keypad_mask(Player::Two), 0x8);
+        assert_eq!(player.next_frame(&mut chip8), false);
+        assert_eq!(player.is_finished(), true);
+    }
+
+    /// Record `frames` frames of a ROM that draws a random number every frame
+    fn record_random(frames: usize) -> Movie {
+        let rom = [0xC0, 0xFF, 0x12, 0x00];
+        let mut chip8 = Chip8::new().unwrap().rng_seed(7);
+        chip8.load_rom(&rom).unwrap();
+        let mut movie = Movie::new(&rom_hash(&rom), &chip8, 7);
+        for _ in 0..frames {
+            movie.record_frame(&chip8);
+            chip8.cycle().unwrap();
+            movie.record_checkpoint(&chip8);
+        }
+        movie
+    }
+
+    /// Play `movie` back on `chip8`, returning the first desync and the checkpoints verified
+    fn play(movie: Movie, mut chip8: Chip8) -> (Result<(), Desync>, usize) {
+        chip8.load_rom(&[0xC0, 0xFF, 0x12, 0x00]).unwrap();
+        let mut player = MoviePlayer::new(movie);
+        while player.next_frame(&mut chip8) {
+            chip8.cycle().unwrap();
+            if let Err(desync) = player.verify(&chip8) {
+                return (Err(desync), player.checkpoints_verified());
+            }
+        }
+        (Ok(()), player.checkpoints_verified())
+    }
+
+    #[test]
+    fn test_movie_checkpoints() {
+        let movie = record_random(CHECKPOINT_INTERVAL * 2 + 1);
+        assert_eq!(movie.checkpoints.len(), 2);
+        assert_eq!(movie.checkpoints[1].0, CHECKPOINT_INTERVAL * 2);
+        let text = movie.to_string();
+        assert_eq!(text.matches("\ncheckpoint ").count(), 2);
+        assert_eq!(text.parse::<Movie>().unwrap(), movie);
+
+        let (result, verified) = play(movie.clone(), movie.configure(Chip8::new().unwrap()));
+        assert_eq!(result, Ok(()));
+        assert_eq!(verified, 2);
+
+        // a different seed generates different random numbers, which the first checkpoint catches
+        let chip8 = movie.configure(Chip8::new().unwrap()).rng_seed(8);
+        let (result, _) = play(movie.clone(), chip8);
+        assert_eq!(result.unwrap_err().frame, CHECKPOINT_INTERVAL);
+    }
+}
@@ -0,0 +1,240 @@
+use anyhow::{bail, ensure, Context};
+
+use crate::gif::decode_frames;
+use crate::json::Value;
+use crate::{Chip8, Palette, FRAME_RATE};
+
+/// The signatures GIF files start with, which Octo cartridges are
+const GIF_MAGIC: [&[u8]; 2] = [b"GIF87a", b"GIF89a"];
+
+/// Whether `bytes` look like an Octo cartridge rather than a ROM. Cartridges are GIFs holding the
+/// Octo source of a program rather than its bytecode, so they can't be run without compiling
+/// them in Octo first.
+pub fn is_octo_cartridge(bytes: &[u8]) -> bool {
+    GIF_MAGIC.iter().any(|magic| bytes.starts_with(magic))
+}
+
+/// Fail with an explanation of what to do instead if `rom` is an Octo cartridge
+pub fn ensure_not_cartridge(rom: &[u8]) -> anyhow::Result<()> {
+    ensure!(
+        !is_octo_cartridge(rom),
+        "this is an Octo cartridge, which holds Octo source rather than a ROM; open it in Octo and export it as a .ch8 to run it"
+    );
+    Ok(())
+}
+
+/// The options Octo stores alongside a program, as in the `options` object of a cartridge or an
+/// exported `.json` file. Only the ones chipper has an equivalent for are kept.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OctoOptions {
+    /// The number of instructions run each frame
+    pub tickrate: Option<usize>,
+    /// VX is shifted in place, ignoring VY
+    pub shift_quirks: Option<bool>,
+    /// FX55 and FX65 leave I unchanged
+    pub load_store_quirks: Option<bool>,
+    /// BNNN jumps to NNN plus VX rather than V0
+    pub jump_quirks: Option<bool>,
+    /// DXYN waits for the next frame
+    pub vblank_quirks: Option<bool>,
+    /// DXYN clips sprites at the edges of the screen rather than wrapping them
+    pub clip_quirks: Option<bool>,
+    /// The colour of lit pixels, as `#RRGGBB`
+    pub fill_color: Option<String>,
+    /// The colour of unlit pixels, as `#RRGGBB`
+    pub background_color: Option<String>,
+}
+
+impl OctoOptions {
+    /// Parse Octo's options object, ignoring the options chipper has no equivalent for
+    pub fn parse(json: &str) -> anyhow::Result<Self> {
+        let value = Value::parse(json).context("parse octo options")?;
+        Self::from_value(&value)
+    }
+
+    fn from_value(value: &Value) -> anyhow::Result<Self> {
+        let values = value.as_object().context("octo options aren't an object")?;
+        let number = |key: &str| -> anyhow::Result<Option<usize>> {
+            match values.get(key) {
+                Some(Value::Number(n)) if *n >= 1.0 => Ok(Some(*n as usize)),
+                Some(_) => bail!("invalid octo option '{}' (expected a positive number)", key),
+                None => Ok(None),
+            }
+        };
+        let flag = |key: &str| -> anyhow::Result<Option<bool>> {
+            match values.get(key) {
+                Some(Value::Bool(value)) => Ok(Some(*value)),
+                Some(_) => bail!("invalid octo option '{}' (expected true or false)", key),
+                None => Ok(None),
+            }
+        };
+        let string = |key: &str| -> anyhow::Result<Option<String>> {
+            match values.get(key) {
+                Some(Value::String(value)) => Ok(Some(value.clone())),
+                Some(_) => bail!("invalid octo option '{}' (expected a string)", key),
+                None => Ok(None),
+            }
+        };
+        Ok(Self {
+            tickrate: number("tickrate")?,
+            shift_quirks: flag("shiftQuirks")?,
+            load_store_quirks: flag("loadStoreQuirks")?,
+            jump_quirks: flag("jumpQuirks")?,
+            vblank_quirks: flag("vBlankQuirks")?,
+            clip_quirks: flag("clipQuirks")?,
+            fill_color: string("fillColor")?,
+            background_color: string("backgroundColor")?,
+        })
+    }
+
+    /// Apply the quirks and speed to `chip8`. Octo's quirks are named for the behaviour they
+    /// switch on, so some are the opposite of chipper's.
+    pub fn configure(&self, mut chip8: Chip8) -> Chip8 {
+        if let Some(value) = self.shift_quirks {
+            chip8 = chip8.legacy_shift(!value);
+        }
+        if let Some(value) = self.load_store_quirks {
+            chip8 = chip8.memory_increment_i(!value);
+        }
+        if let Some(value) = self.jump_quirks {
+            chip8 = chip8.jump_add_offset(value);
+        }
+        if let Some(value) = self.vblank_quirks {
+            chip8 = chip8.display_wait(value);
+        }
+        if let Some(value) = self.clip_quirks {
+            chip8 = chip8.wrap_sprites(!value);
+        }
+        if let Some(tickrate) = self.tickrate {
+            let hz = tickrate.saturating_mul(FRAME_RATE).min(u32::MAX as usize);
+            chip8 = chip8.clock_hz(hz as u32);
+        }
+        chip8
+    }
+
+    /// The palette made of the fill and background colours, if both are given
+    pub fn palette(&self) -> anyhow::Result<Option<Palette>> {
+        match (&self.fill_color, &self.background_color) {
+            (Some(fill), Some(background)) => {
+                let palette = format!("{}:{}", fill, background)
+                    .parse::<Palette>()
+                    .context("parse octo colours")?;
+                Ok(Some(palette))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+/// A program shared as an Octo cartridge. The cartridge is a GIF of a label, with the data hidden
+/// in the low nybble of each pixel's colour index, two pixels to a byte with the high nybble first,
+/// running on through every frame. The data is its own length as a 32 bit big-endian number
+/// followed by a JSON object of the program and its options.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OctoCartridge {
+    /// The Octo source of the program, which has to be compiled in Octo to run it
+    pub program: String,
+    pub options: OctoOptions,
+}
+
+impl OctoCartridge {
+    pub fn decode(bytes: &[u8]) -> anyhow::Result<Self> {
+        ensure!(is_octo_cartridge(bytes), "not an octo cartridge");
+        let frames = decode_frames(bytes).context("decode octo cartridge")?;
+        let nybbles: Vec<u8> = frames.iter().flatten().map(|index| index & 0x0F).collect();
+        let data: Vec<u8> = nybbles
+            .chunks_exact(2)
+            .map(|pair| pair[0] << 4 | pair[1])
+            .collect();
+        let len = data
+            .get(..4)
+            .map(|len| u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize)
+            .context("octo cartridge holds no data")?;
+        let payload = data
+            .get(4..4usize.saturating_add(len))
+            .context("octo cartridge data is truncated")?;
+        let json = std::str::from_utf8(payload).context("octo cartridge data isn't text")?;
+        let value = Value::parse(json).context("parse octo cartridge data")?;
+        let program = value
+            .get("program")
+            .and_then(Value::as_str)
+            .context("octo cartridge holds no program")?;
+        let options = match value.get("options") {
+            Some(options) => OctoOptions::from_value(options)?,
+            None => OctoOptions::default(),
+        };
+        Ok(Self {
+            program: program.to_string(),
+            options,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ensure_not_cartridge, is_octo_cartridge, OctoCartridge, OctoOptions};
+    use crate::gif::encode_frames;
+    use crate::{Chip8, Palette};
+
+    #[test]
+    fn test_octo_options() {
+        let json = r##"{"tickrate":20,"fillColor":"#FFCC00","fillColor2":"#FF6600",
+            "backgroundColor":"#996600","shiftQuirks":true,"loadStoreQuirks":false,
+            "vBlankQuirks":true,"clipQuirks":false,"screenRotation":0,"touchInputMode":"none","maxSize":3584}"##;
+        let options = OctoOptions::parse(json).unwrap();
+        assert_eq!(options.tickrate, Some(20));
+        assert_eq!(options.shift_quirks, Some(true));
+        assert_eq!(options.jump_quirks, None);
+        let chip8 = options.configure(Chip8::new().unwrap().legacy_shift(true));
+        assert_eq!(chip8.config.quirks.legacy_shift, false);
+        assert_eq!(chip8.config.quirks.memory_increment_i, true);
+        assert_eq!(chip8.config.quirks.display_wait, true);
+        assert_eq!(chip8.config.quirks.wrap_sprites, true);
+        assert_eq!(chip8.config.clock_hz, 1200);
+        assert_eq!(
+            options.palette().unwrap(),
+            Some("ffcc00:996600".parse::<Palette>().unwrap())
+        );
+
+        assert_eq!(OctoOptions::parse("{}").unwrap(), OctoOptions::default());
+        assert!(OctoOptions::parse(r#"{"tickrate":"fast"}"#).is_err());
+        assert!(OctoOptions::parse(r#"{"tickrate":20"#).is_err());
+        assert!(OctoOptions::parse(r#"{"shiftQuirks":[1]}"#).is_err());
+        assert!(OctoOptions::parse("[]").is_err());
+
+        // a huge tickrate runs as fast as the clock goes rather than overflowing
+        let options = OctoOptions::parse(r#"{"tickrate":1e300}"#).unwrap();
+        let chip8 = options.configure(Chip8::new().unwrap());
+        assert_eq!(chip8.config.clock_hz, u32::MAX);
+
+        assert_eq!(is_octo_cartridge(b"GIF89a\xa0\x00\x80\x00"), true);
+        assert_eq!(is_octo_cartridge(&[0x00, 0xE0, 0x12, 0x00]), false);
+        assert!(ensure_not_cartridge(b"GIF87a").is_err());
+    }
+
+    #[test]
+    fn test_octo_cartridge() {
+        let json = r#"{"key":"","program":": main\n  loop again","options":{"tickrate":500,"shiftQuirks":true}}"#;
+        let mut data = (json.len() as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(json.as_bytes());
+        // the label's colours sit in the high nybble, and the frames are filled out past the data
+        let mut nybbles: Vec<u8> = data
+            .iter()
+            .flat_map(|byte| [0x30 | byte >> 4, 0x50 | byte & 0x0F])
+            .collect();
+        nybbles.resize(2 * 32 * 16, 0x20);
+        let frames: Vec<Vec<u8>> = nybbles.chunks(32 * 16).map(<[u8]>::to_vec).collect();
+        let gif = encode_frames(32, 16, &frames);
+        assert_eq!(is_octo_cartridge(&gif), true);
+
+        let cartridge = OctoCartridge::decode(&gif).unwrap();
+        assert_eq!(cartridge.program, ": main\n  loop again");
+        assert_eq!(cartridge.options.tickrate, Some(500));
+        assert_eq!(cartridge.options.shift_quirks, Some(true));
+        let chip8 = cartridge.options.configure(Chip8::new().unwrap());
+        assert_eq!(chip8.config.clock_hz, 30000);
+
+        assert!(OctoCartridge::decode(&encode_frames(4, 4, &[vec![0; 16]])).is_err());
+        assert!(OctoCartridge::decode(&[0x00, 0xE0]).is_err());
+    }
+}
@@ -0,0 +1,254 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use anyhow::{bail, Context};
+
+use crate::{Frame, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+/// The colours the screen is drawn in, as RGBA
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Palette {
+    /// The colour of lit pixels, or of pixels lit only in the first plane in XO-CHIP programs
+    pub on: [u8; 4],
+    /// The colour of unlit pixels
+    pub off: [u8; 4],
+    /// The colour of the lines between pixels when the pixel grid is shown
+    pub grid: [u8; 4],
+    /// The colour of pixels lit only in XO-CHIP's second plane
+    pub second: [u8; 4],
+    /// The colour of pixels lit in both planes
+    pub both: [u8; 4],
+}
+
+impl Palette {
+    /// White on black, the way chipper has always looked
+    pub const CLASSIC: Palette = Palette {
+        on: [255, 255, 255, 255],
+        off: [0, 0, 0, 255],
+        grid: [48, 48, 48, 255],
+        second: [170, 170, 170, 255],
+        both: [85, 85, 85, 255],
+    };
+    /// Black on white, for users who find light backgrounds easier to read
+    pub const INVERTED: Palette = Palette {
+        on: [0, 0, 0, 255],
+        off: [255, 255, 255, 255],
+        grid: [192, 192, 192, 255],
+        second: [85, 85, 85, 255],
+        both: [170, 170, 170, 255],
+    };
+    /// Yellow on black, the highest contrast pairing for most low-vision users
+    pub const YELLOW: Palette = Palette {
+        on: [255, 255, 0, 255],
+        off: [0, 0, 0, 255],
+        grid: [64, 64, 0, 255],
+        second: [255, 128, 0, 255],
+        both: [128, 64, 0, 255],
+    };
+    /// White on dark blue
+    pub const BLUE: Palette = Palette {
+        on: [255, 255, 255, 255],
+        off: [0, 0, 128, 255],
+        grid: [48, 48, 176, 255],
+        second: [96, 160, 255, 255],
+        both: [32, 96, 192, 255],
+    };
+
+    /// A palette with lit pixels in `on` and unlit ones in `off`, with the grid and the colours
+    /// of the second plane mixed between them
+    pub fn custom(on: [u8; 4], off: [u8; 4]) -> Self {
+        Palette {
+            on,
+            off,
+            grid: mix(on, off, 1),
+            second: mix(on, off, 3),
+            both: mix(on, off, 2),
+        }
+    }
+
+    /// The colour of a pixel with the colour index `index`, as `Frame::get` returns
+    pub fn colour(&self, index: u8) -> [u8; 4] {
+        match index & 0b11 {
+            0 => self.off,
+            1 => self.on,
+            2 => self.second,
+            _ => self.both,
+        }
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::CLASSIC
+    }
+}
+
+impl FromStr for Palette {
+    type Err = anyhow::Error;
+
+    /// Parse a preset name, or a custom `ON:OFF` pair of hex colours such as `ffff00:000000`,
+    /// optionally followed by `:SECOND:BOTH` colours for XO-CHIP's second plane
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "classic" => Ok(Palette::CLASSIC),
+            "inverted" => Ok(Palette::INVERTED),
+            "yellow" => Ok(Palette::YELLOW),
+            "blue" => Ok(Palette::BLUE),
+            _ => {
+                let colours = s.split(':').collect::<Vec<_>>();
+                match colours[..] {
+                    [on, off] => Ok(Palette::custom(parse_colour(on)?, parse_colour(off)?)),
+                    [on, off, second, both] => Ok(Palette {
+                        second: parse_colour(second)?,
+                        both: parse_colour(both)?,
+                        ..Palette::custom(parse_colour(on)?, parse_colour(off)?)
+                    }),
+                    _ => bail!(
+                        "unknown palette '{}' (expected classic, inverted, yellow, blue or ON:OFF hex colours)",
+                        s
+                    ),
+                }
+            }
+        }
+    }
+}
+
+impl Display for Palette {
+    /// Write the preset's name, or the `ON:OFF` pair of hex colours for a custom palette
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let presets = [
+            (Palette::CLASSIC, "classic"),
+            (Palette::INVERTED, "inverted"),
+            (Palette::YELLOW, "yellow"),
+            (Palette::BLUE, "blue"),
+        ];
+        if let Some((_, name)) = presets.iter().find(|(preset, _)| preset == self) {
+            return write!(f, "{}", name);
+        }
+        let mut colours = vec![self.on, self.off];
+        if *self != Palette::custom(self.on, self.off) {
+            colours.extend([self.second, self.both]);
+        }
+        for (n, [r, g, b, _]) in colours.into_iter().enumerate() {
+            let separator = if n > 0 { ":" } else { "" };
+            write!(f, "{}{:02x}{:02x}{:02x}", separator, r, g, b)?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_colour(s: &str) -> anyhow::Result<[u8; 4]> {
+    let digits = s.strip_prefix('#').unwrap_or(s);
+    let value = u32::from_str_radix(digits, 16)
+        .ok()
+        .filter(|_| digits.len() == 6)
+        .with_context(|| format!("invalid colour '{}' (expected RRGGBB)", s))?;
+    let [_, r, g, b] = value.to_be_bytes();
+    Ok([r, g, b, 255])
+}
+
+/// The colour `quarters` quarters of the way from `off` to `on`, used for the colours of custom
+/// palettes that aren't given
+fn mix(on: [u8; 4], off: [u8; 4], quarters: u16) -> [u8; 4] {
+    let mut mixed = off;
+    for (mixed, on) in mixed.iter_mut().zip(on) {
+        *mixed = ((*mixed as u16 * (4 - quarters) + on as u16 * quarters) / 4) as u8;
+    }
+    mixed
+}
+
+/// Shows a zoomed in part of the screen around a focus point, which is kept far enough from the
+/// edges that the view never leaves the screen
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Magnifier {
+    zoom: f32,
+    focus: (f32, f32),
+}
+
+impl Magnifier {
+    /// A magnifier zoomed in by `zoom` times, focused on the middle of the screen
+    pub fn new(zoom: f32) -> Self {
+        Self {
+            zoom: zoom.max(1.0),
+            focus: (SCREEN_WIDTH as f32 / 2.0, SCREEN_HEIGHT as f32 / 2.0),
+        }
+    }
+
+    pub fn zoom(&self) -> f32 {
+        self.zoom
+    }
+
+    /// Move the focus to `(x, y)` in screen pixels
+    pub fn set_focus(&mut self, x: f32, y: f32) {
+        self.focus = (
+            x.clamp(0.0, SCREEN_WIDTH as f32),
+            y.clamp(0.0, SCREEN_HEIGHT as f32),
+        );
+    }
+
+    /// The part of the screen shown, as `(x, y, width, height)` in screen pixels
+    pub fn view(&self) -> (f32, f32, f32, f32) {
+        let width = SCREEN_WIDTH as f32 / self.zoom;
+        let height = SCREEN_HEIGHT as f32 / self.zoom;
+        let x = (self.focus.0 - width / 2.0).clamp(0.0, SCREEN_WIDTH as f32 - width);
+        let y = (self.focus.1 - height / 2.0).clamp(0.0, SCREEN_HEIGHT as f32 - height);
+        (x, y, width, height)
+    }
+
+    /// The pixel of `frame` shown at `(x, y)` of the magnified view, where both run over the
+    /// same range as low resolution screen pixels
+    pub fn source(&self, x: f32, y: f32, frame: &Frame) -> (usize, usize) {
+        let (left, top, _, _) = self.view();
+        let (scale_x, scale_y) = frame.scale();
+        let x = ((left + x / self.zoom) * scale_x as f32) as usize;
+        let y = ((top + y / self.zoom) * scale_y as f32) as usize;
+        (x.min(frame.width() - 1), y.min(frame.height() - 1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Magnifier, Palette};
+    use crate::Frame;
+
+    #[test]
+    fn test_parse_palette() {
+        assert_eq!("Yellow".parse::<Palette>().unwrap(), Palette::YELLOW);
+        let palette = "#ffffff:000080".parse::<Palette>().unwrap();
+        assert_eq!(palette.on, [255, 255, 255, 255]);
+        assert_eq!(palette.off, [0, 0, 128, 255]);
+        assert_eq!(palette.grid, [63, 63, 159, 255]);
+        assert_eq!(palette.to_string(), "ffffff:000080");
+        assert_eq!(palette.colour(0), palette.off);
+        assert_eq!(palette.colour(3), [127, 127, 191, 255]);
+
+        let palette = "ffcc00:996600:ff6600:662200".parse::<Palette>().unwrap();
+        assert_eq!(palette.colour(2), [255, 102, 0, 255]);
+        assert_eq!(palette.to_string(), "ffcc00:996600:ff6600:662200");
+        assert!("ffcc00:996600:ff6600".parse::<Palette>().is_err());
+        assert_eq!(Palette::YELLOW.to_string(), "yellow");
+        assert!("pink".parse::<Palette>().is_err());
+        assert!("fff:000".parse::<Palette>().is_err());
+    }
+
+    #[test]
+    fn test_magnifier() {
+        let frame = Frame::default();
+        let mut magnifier = Magnifier::new(4.0);
+        assert_eq!(magnifier.view(), (24.0, 12.0, 16.0, 8.0));
+        assert_eq!(magnifier.source(0.0, 0.0, &frame), (24, 12));
+        assert_eq!(magnifier.source(63.0, 31.0, &frame), (39, 19));
+
+        // the view stops at the edges of the screen
+        magnifier.set_focus(0.0, 100.0);
+        assert_eq!(magnifier.view(), (0.0, 24.0, 16.0, 8.0));
+        assert_eq!(magnifier.source(63.9, 31.9, &frame), (15, 31));
+
+        // a high resolution frame has twice the pixels under the same view
+        let frame = Frame::blank(true);
+        assert_eq!(magnifier.source(63.9, 31.9, &frame), (31, 63));
+        let frame = Frame::two_page();
+        assert_eq!(magnifier.source(63.9, 31.9, &frame), (15, 63));
+    }
+}
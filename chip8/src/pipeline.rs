@@ -0,0 +1,157 @@
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+use crate::disasm::disassemble;
+use crate::{Chip8, MachineState, MemoryChange, StateDiff};
+
+/// A stage of the fetch/decode/execute cycle of a single instruction
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PipelineEvent {
+    /// `opcode` was fetched from `addr`
+    Fetch { addr: u16, opcode: u16 },
+    /// The instruction at `addr` was decoded into `mnemonic`
+    Decode { addr: u16, mnemonic: String },
+    /// The instruction at `addr` ran, changing the machine state by `changes`
+    Execute { addr: u16, changes: StateDiff },
+}
+
+/// The subscribers to the pipeline events, which are only generated while there are any
+#[derive(Default)]
+pub(crate) struct Pipeline {
+    subscribers: Vec<Sender<PipelineEvent>>,
+    /// The memory written by the instruction currently executing
+    writes: Vec<MemoryChange>,
+}
+
+impl Pipeline {
+    pub(crate) fn is_active(&self) -> bool {
+        !self.subscribers.is_empty()
+    }
+
+    /// Send `event` to every subscriber, dropping the ones that have gone away
+    fn send(&mut self, event: PipelineEvent) {
+        self.subscribers
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+
+    pub(crate) fn record_write(&mut self, addr: usize, before: &[u8], after: &[u8]) {
+        if before != after {
+            self.writes.push(MemoryChange {
+                addr,
+                before: before.to_vec(),
+                after: after.to_vec(),
+            });
+        }
+    }
+}
+
+impl Chip8 {
+    /// Receive an event for every stage of every instruction run from now on, so a frontend can
+    /// animate the CPU cycle. Tracing stops once every receiver has been dropped.
+    pub fn subscribe_pipeline(&mut self) -> Receiver<PipelineEvent> {
+        let (sender, receiver) = channel();
+        self.pipeline.subscribers.push(sender);
+        receiver
+    }
+
+    /// The machine state without memory, which is tracked through the writes instead since
+    /// copying all of it for every instruction would be slow
+    fn traced_state(&self) -> MachineState {
+        MachineState {
+            pc: self.pc,
+            i: self.i,
+            sp: self.sp,
+            dt: self.dt,
+            st: self.st,
+            v: self.v,
+            stack: self.stack,
+            memory: Vec::new(),
+            fb: self.display.fb,
+            planes: self.display.planes,
+            pitch: self.buzzer.pitch,
+            pattern: self.buzzer.pattern,
+            colours: self.colours,
+        }
+    }
+
+    /// Run the next instruction, reporting each stage to the subscribers
+    pub(crate) fn step_traced(&mut self) {
+        let addr = self.pc;
+        let opcode = self.next_opcode();
+        self.pipeline.send(PipelineEvent::Fetch { addr, opcode });
+        let mnemonic = disassemble(opcode).unwrap_or_else(|| format!("DW {:#06x}", opcode));
+        self.pipeline.send(PipelineEvent::Decode { addr, mnemonic });
+
+        let before = self.traced_state();
+        self.execute_next();
+        let mut changes = StateDiff::between_states(&before, &self.traced_state());
+        changes.memory = std::mem::take(&mut self.pipeline.writes);
+        self.pipeline.send(PipelineEvent::Execute { addr, changes });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PipelineEvent;
+    use crate::{Chip8, MemoryChange, RegisterChange, StateDiff};
+
+    #[test]
+    fn test_subscribe_pipeline() {
+        let mut chip8 = Chip8::new().unwrap();
+        chip8
+            .load_rom(&[0x60, 0x07, 0xA3, 0x00, 0xF0, 0x55, 0x12, 0x06])
+            .unwrap();
+        let events = chip8.subscribe_pipeline();
+        chip8.step().unwrap();
+
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            [
+                PipelineEvent::Fetch {
+                    addr: 0x200,
+                    opcode: 0x6007
+                },
+                PipelineEvent::Decode {
+                    addr: 0x200,
+                    mnemonic: "LD V0, 0x07".to_string()
+                },
+                PipelineEvent::Execute {
+                    addr: 0x200,
+                    changes: StateDiff {
+                        registers: vec![
+                            RegisterChange {
+                                name: "PC".to_string(),
+                                before: 0x200,
+                                after: 0x202
+                            },
+                            RegisterChange {
+                                name: "V0".to_string(),
+                                before: 0,
+                                after: 7
+                            },
+                        ],
+                        memory: vec![],
+                        pixels: vec![],
+                    }
+                },
+            ]
+        );
+
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+        let Some(PipelineEvent::Execute { changes, .. }) = events.try_iter().last() else {
+            panic!("expected an execute event");
+        };
+        assert_eq!(
+            changes.memory,
+            [MemoryChange {
+                addr: 0x300,
+                before: vec![0],
+                after: vec![7]
+            }]
+        );
+
+        drop(events);
+        chip8.step().unwrap();
+        assert_eq!(chip8.pipeline.is_active(), false);
+    }
+}
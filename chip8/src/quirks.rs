@@ -0,0 +1,105 @@
+use std::str::FromStr;
+
+use anyhow::bail;
+
+/// The quirks that change how instructions behave, which differ between the interpreters
+/// programs were written for
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Quirks {
+    /// 8XY6 and 8XYE shift VY into VX rather than shifting VX in place
+    pub legacy_shift: bool,
+    /// BNNN jumps to XNN plus VX rather than NNN plus V0
+    pub jump_add_offset: bool,
+    /// FX55 and FX65 leave I pointing past the last register they stored or loaded
+    pub memory_increment_i: bool,
+    /// DXYN ends the frame, as the COSMAC VIP waited for the vertical blank before drawing
+    pub display_wait: bool,
+    /// DXYN wraps sprites around the edges of the screen rather than clipping them
+    pub wrap_sprites: bool,
+    /// FX29 uses the whole of VX rather than its low nibble, so values above 0xF point past the
+    /// font as they did on the COSMAC VIP
+    pub unmasked_font: bool,
+}
+
+impl Quirks {
+    /// The original CHIP-8 interpreter on the COSMAC VIP
+    pub fn cosmac_vip() -> Self {
+        Self {
+            legacy_shift: true,
+            jump_add_offset: false,
+            memory_increment_i: true,
+            display_wait: true,
+            wrap_sprites: false,
+            unmasked_font: true,
+        }
+    }
+
+    /// SUPER-CHIP 1.1 on the HP 48
+    pub fn schip() -> Self {
+        Self {
+            legacy_shift: false,
+            jump_add_offset: true,
+            memory_increment_i: false,
+            display_wait: false,
+            wrap_sprites: false,
+            unmasked_font: false,
+        }
+    }
+
+    /// XO-CHIP as Octo runs it, which went back to the VIP's shifts and loads but draws freely,
+    /// wrapping sprites around the screen
+    pub fn xo_chip() -> Self {
+        Self {
+            legacy_shift: true,
+            jump_add_offset: false,
+            memory_increment_i: true,
+            display_wait: false,
+            wrap_sprites: true,
+            unmasked_font: false,
+        }
+    }
+}
+
+impl FromStr for Quirks {
+    type Err = anyhow::Error;
+
+    /// Parse a preset name
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "vip" => Ok(Quirks::cosmac_vip()),
+            "schip" => Ok(Quirks::schip()),
+            "xochip" => Ok(Quirks::xo_chip()),
+            _ => bail!(
+                "unknown quirks preset '{}' (expected vip, schip or xochip)",
+                s
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Quirks;
+    use crate::Chip8;
+
+    #[test]
+    fn test_quirks_presets() {
+        assert_eq!("VIP".parse::<Quirks>().unwrap(), Quirks::cosmac_vip());
+        assert_eq!("schip".parse::<Quirks>().unwrap(), Quirks::schip());
+        assert_eq!("xochip".parse::<Quirks>().unwrap(), Quirks::xo_chip());
+        assert!("chip48".parse::<Quirks>().is_err());
+        assert_eq!(Quirks::default().legacy_shift, false);
+
+        // the single quirk setters override the preset
+        let chip8 = Chip8::new()
+            .unwrap()
+            .quirks(Quirks::schip())
+            .legacy_shift(true);
+        let expected = Quirks {
+            legacy_shift: true,
+            ..Quirks::schip()
+        };
+        assert_eq!(chip8.config.quirks, expected);
+    }
+}
@@ -0,0 +1,68 @@
+/// The source of CXNN's random numbers. With the `rand` feature it's a `StdRng` seeded by the
+/// operating system, and without it a xorshift generator that starts from the same seed every
+/// time unless it's given another.
+#[cfg(feature = "rand")]
+#[derive(Clone, Debug)]
+pub(crate) struct Random(rand::rngs::StdRng);
+
+#[cfg(feature = "rand")]
+impl Random {
+    pub(crate) fn new() -> Self {
+        use rand::SeedableRng;
+        Self(rand::rngs::StdRng::from_os_rng())
+    }
+
+    pub(crate) fn from_seed(seed: u64) -> Self {
+        use rand::SeedableRng;
+        Self(rand::rngs::StdRng::seed_from_u64(seed))
+    }
+
+    pub(crate) fn byte(&mut self) -> u8 {
+        use rand::Rng;
+        self.0.random()
+    }
+}
+
+/// The seed the built-in generator starts from when it isn't given one
+#[cfg(not(feature = "rand"))]
+const DEFAULT_SEED: u64 = 0x853C_49E6_748F_EA9B;
+
+#[cfg(not(feature = "rand"))]
+#[derive(Clone, Debug)]
+pub(crate) struct Random(u64);
+
+#[cfg(not(feature = "rand"))]
+impl Random {
+    pub(crate) fn new() -> Self {
+        Self::from_seed(DEFAULT_SEED)
+    }
+
+    pub(crate) fn from_seed(seed: u64) -> Self {
+        // xorshift gets stuck at zero, so that seed is swapped for the default
+        Self(if seed == 0 { DEFAULT_SEED } else { seed })
+    }
+
+    /// The top byte of the next xorshift64* output, which has the best distribution
+    pub(crate) fn byte(&mut self) -> u8 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        (self.0.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Random;
+
+    #[test]
+    fn test_random_seed() {
+        let bytes = |seed: u64| {
+            let mut random = Random::from_seed(seed);
+            (0..16).map(|_| random.byte()).collect::<Vec<_>>()
+        };
+        assert_eq!(bytes(7), bytes(7));
+        assert_ne!(bytes(7), bytes(8));
+        assert_ne!(bytes(0), [0; 16]);
+    }
+}
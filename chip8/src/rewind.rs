@@ -0,0 +1,172 @@
+use std::collections::VecDeque;
+
+use anyhow::{bail, Context};
+
+/// The number of frames between rewind snapshots
+pub const REWIND_INTERVAL: u32 = 60;
+
+/// A ring buffer of periodic savestates, compressed as deltas against the snapshot after them
+/// Only the newest snapshot is kept whole. Consecutive states differ in very few bytes, so each
+/// older one is stored as the run-length encoded XOR against its successor.
+pub struct RewindBuffer {
+    /// The newest snapshot
+    latest: Option<Vec<u8>>,
+    /// Deltas that turn each snapshot into the one before it, oldest first
+    deltas: VecDeque<Vec<u8>>,
+    /// The maximum number of snapshots kept, including the newest
+    capacity: usize,
+}
+
+impl RewindBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            latest: None,
+            deltas: VecDeque::new(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Record a new snapshot, dropping the oldest one if the buffer is full
+    pub fn push(&mut self, state: Vec<u8>) {
+        if let Some(latest) = self.latest.take() {
+            if latest.len() == state.len() {
+                self.deltas.push_back(encode_delta(&state, &latest));
+                if self.deltas.len() >= self.capacity {
+                    self.deltas.pop_front();
+                }
+            } else {
+                // a snapshot of a different size can't be diffed, so the history starts over
+                self.deltas.clear();
+            }
+        }
+        self.latest = Some(state);
+    }
+
+    /// The number of snapshots in the buffer
+    pub fn len(&self) -> usize {
+        self.latest.as_ref().map_or(0, |_| self.deltas.len() + 1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.latest.is_none()
+    }
+
+    /// Discard up to `steps` of the newest snapshots and return the snapshot that's then newest,
+    /// along with the number of snapshots actually discarded
+    pub fn step_back(&mut self, steps: usize) -> anyhow::Result<(Vec<u8>, usize)> {
+        let mut latest = self.latest.take().context("no rewind snapshots recorded")?;
+        let mut stepped = 0;
+        while stepped < steps {
+            let Some(delta) = self.deltas.pop_back() else {
+                break;
+            };
+            if let Err(e) = apply_delta(&mut latest, &delta) {
+                self.deltas.clear();
+                self.latest = Some(latest);
+                return Err(e.context("apply rewind delta"));
+            }
+            stepped += 1;
+        }
+        self.latest = Some(latest.clone());
+        Ok((latest, stepped))
+    }
+}
+
+/// Encode the XOR of `a` and `b` as alternating runs: a u16 count of unchanged bytes, a u16
+/// count of changed bytes, then the changed bytes
+fn encode_delta(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+    while pos < a.len() {
+        let zeros = a[pos..]
+            .iter()
+            .zip(&b[pos..])
+            .take(u16::MAX as usize)
+            .take_while(|(a, b)| a == b)
+            .count();
+        pos += zeros;
+        if pos == a.len() {
+            break;
+        }
+        let literals = a[pos..]
+            .iter()
+            .zip(&b[pos..])
+            .take(u16::MAX as usize)
+            .take_while(|(a, b)| a != b)
+            .count();
+        out.extend_from_slice(&(zeros as u16).to_le_bytes());
+        out.extend_from_slice(&(literals as u16).to_le_bytes());
+        out.extend(
+            a[pos..pos + literals]
+                .iter()
+                .zip(&b[pos..pos + literals])
+                .map(|(a, b)| a ^ b),
+        );
+        pos += literals;
+    }
+    out
+}
+
+/// XOR a delta produced by `encode_delta` into `state`
+fn apply_delta(state: &mut [u8], delta: &[u8]) -> anyhow::Result<()> {
+    let mut pos = 0;
+    let mut delta = delta;
+    while !delta.is_empty() {
+        if delta.len() < 4 {
+            bail!("truncated delta");
+        }
+        let zeros = u16::from_le_bytes([delta[0], delta[1]]) as usize;
+        let literals = u16::from_le_bytes([delta[2], delta[3]]) as usize;
+        delta = &delta[4..];
+        pos += zeros;
+        if delta.len() < literals || state.len() < pos + literals {
+            bail!("delta out of range");
+        }
+        for (byte, xor) in state[pos..pos + literals]
+            .iter_mut()
+            .zip(&delta[..literals])
+        {
+            *byte ^= xor;
+        }
+        delta = &delta[literals..];
+        pos += literals;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_delta, encode_delta, RewindBuffer};
+
+    #[test]
+    fn test_delta() {
+        let a = vec![0, 1, 2, 3, 4, 5, 6, 7];
+        let mut b = a.clone();
+        b[2] = 9;
+        b[3] = 9;
+        b[7] = 1;
+
+        let delta = encode_delta(&a, &b);
+        assert_eq!(delta.len(), 2 * 4 + 3);
+        let mut restored = b.clone();
+        apply_delta(&mut restored, &delta).unwrap();
+        assert_eq!(restored, a);
+
+        assert!(encode_delta(&a, &a).is_empty());
+    }
+
+    #[test]
+    fn test_rewind_buffer() {
+        let mut buffer = RewindBuffer::new(3);
+        for value in 0..5 {
+            buffer.push(vec![value; 16]);
+        }
+        assert_eq!(buffer.len(), 3);
+
+        assert_eq!(buffer.step_back(1).unwrap(), (vec![3; 16], 1));
+        // only the three newest snapshots are kept
+        assert_eq!(buffer.step_back(5).unwrap(), (vec![2; 16], 1));
+        assert_eq!(buffer.step_back(0).unwrap(), (vec![2; 16], 0));
+        assert_eq!(buffer.len(), 1);
+    }
+}
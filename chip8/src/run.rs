@@ -0,0 +1,251 @@
+use crate::{Chip8, Chip8Error, HaltReason};
+
+/// A condition that stops `Chip8::run_until`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HaltCondition {
+    /// Stop once this many instructions have run
+    Instructions(u64),
+    /// Stop once this many frames have finished
+    Frames(u64),
+    /// Stop when the program counter reaches the address, before the instruction there runs
+    PcReached(u16),
+    /// Stop when the byte at `addr` holds `value`
+    MemoryEquals { addr: u16, value: u8 },
+    /// Stop as soon as any of the conditions is met
+    Any(Vec<HaltCondition>),
+}
+
+impl HaltCondition {
+    fn is_met(&self, chip8: &Chip8, instructions: u64, frames: u64) -> bool {
+        match self {
+            HaltCondition::Instructions(n) => instructions >= *n,
+            HaltCondition::Frames(n) => frames >= *n,
+            HaltCondition::PcReached(pc) => chip8.pc == *pc,
+            HaltCondition::MemoryEquals { addr, value } => {
+                chip8.memory.data.get(*addr as usize) == Some(value)
+            }
+            HaltCondition::Any(conditions) => conditions
+                .iter()
+                .any(|condition| condition.is_met(chip8, instructions, frames)),
+        }
+    }
+}
+
+/// Why `Chip8::run_until` returned
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    /// The condition was met
+    Condition,
+    /// Execution halted before the condition was met
+    Halted(HaltReason),
+    /// An instruction faulted before the condition was met, leaving the program counter on it
+    Fault(Chip8Error),
+}
+
+/// The result of `Chip8::run_until`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RunOutcome {
+    pub reason: StopReason,
+    /// The number of instructions run
+    pub instructions: u64,
+    /// The number of frames finished
+    pub frames: u64,
+}
+
+/// What happened during a frame run with `Chip8::run_frame`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrameReport {
+    /// Whether 00E0 or DXYN ran, so the screen may have changed
+    pub drew: bool,
+    /// Whether the buzzer is sounding at the end of the frame
+    pub sound: bool,
+    /// Whether execution has halted
+    pub halted: bool,
+}
+
+impl Chip8 {
+    /// Run a single 60 Hz frame with `cycle`, reporting what happened during it. This is the one
+    /// call a frontend needs per display refresh.
+    pub fn run_frame(&mut self) -> Result<FrameReport, Chip8Error> {
+        self.cycle()?;
+        Ok(FrameReport {
+            drew: self.frame_drew,
+            sound: self.is_sound_playing(),
+            halted: self.is_halted(),
+        })
+    }
+
+    /// Run instructions until `condition` is met, execution halts or an instruction faults
+    /// The condition is checked before every instruction, so it can stop partway through a frame,
+    /// in which case the next `run_until` or `cycle` carries on with the rest of it. Conditions
+    /// that may never be met should be combined with a limit using `HaltCondition::Any`.
+    pub fn run_until(&mut self, condition: &HaltCondition) -> RunOutcome {
+        let mut instructions = 0;
+        let mut frames = 0;
+        let reason = loop {
+            let reason = match &self.halted {
+                Some(reason) => StopReason::Halted(reason.clone()),
+                None if condition.is_met(self, instructions, frames) => StopReason::Condition,
+                None => {
+                    match self.step_frame() {
+                        Ok(true) => instructions += 1,
+                        Ok(false) => {}
+                        Err(error) => break StopReason::Fault(error),
+                    }
+                    // the instruction that halts doesn't finish the frame
+                    if self.halted.is_none() && self.is_frame_over() {
+                        self.end_frame();
+                        self.frame_ops = 0;
+                        frames += 1;
+                    }
+                    continue;
+                }
+            };
+            break reason;
+        };
+        RunOutcome {
+            reason,
+            instructions,
+            frames,
+        }
+    }
+
+    /// Run the next instruction of the current frame, starting a new frame if needed, returning
+    /// false if no instruction ran
+    fn step_frame(&mut self) -> Result<bool, Chip8Error> {
+        if self.frame_ops == 0 {
+            self.begin_frame();
+        }
+        if self.is_frame_over() {
+            return Ok(false);
+        }
+        self.step_in_frame()?;
+        Ok(self.halted.is_none())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FrameReport, HaltCondition, RunOutcome, StopReason};
+    use crate::{Chip8, Chip8Error, HaltReason};
+
+    #[test]
+    fn test_run_until() {
+        let mut chip8 = Chip8::new().unwrap().clock_hz(240);
+        // count V0 up in a loop and store it at 0x300 once it reaches 3
+        chip8
+            .load_rom(&[
+                0x70, 0x01, 0x30, 0x03, 0x12, 0x00, 0xA3, 0x00, 0xF0, 0x55, 0x12, 0x0A,
+            ])
+            .unwrap();
+
+        let outcome = chip8.run_until(&HaltCondition::Instructions(6));
+        assert_eq!(outcome.instructions, 6);
+        assert_eq!(outcome.frames, 1);
+        assert_eq!(chip8.pc, 0x200);
+
+        let outcome = chip8.run_until(&HaltCondition::PcReached(0x206));
+        assert_eq!(outcome.instructions, 2);
+        assert_eq!(chip8.v[0], 3);
+
+        let outcome = chip8.run_until(&HaltCondition::MemoryEquals {
+            addr: 0x300,
+            value: 3,
+        });
+        assert_eq!(outcome.instructions, 2);
+        assert_eq!(chip8.pc, 0x20A);
+
+        let outcome = chip8.run_until(&HaltCondition::Any(vec![
+            HaltCondition::PcReached(0x200),
+            HaltCondition::Frames(2),
+        ]));
+        assert_eq!(outcome.frames, 2);
+        assert_eq!(outcome.reason, StopReason::Condition);
+    }
+
+    #[test]
+    fn test_run_until_halted() {
+        let mut chip8 = Chip8::new().unwrap().detect_idle_loops(true);
+        chip8.load_rom(&[0x60, 0x01, 0x12, 0x02]).unwrap();
+
+        let outcome = chip8.run_until(&HaltCondition::Frames(10));
+        assert_eq!(
+            outcome,
+            RunOutcome {
+                reason: StopReason::Halted(HaltReason::IdleLoop { pc: 0x202 }),
+                instructions: 1,
+                frames: 0,
+            }
+        );
+
+        let mut chip8 = Chip8::new().unwrap();
+        chip8.load_rom(&[0x60, 0x01, 0x01, 0x24]).unwrap();
+        let outcome = chip8.run_until(&HaltCondition::Frames(10));
+        assert_eq!(
+            outcome.reason,
+            StopReason::Fault(Chip8Error::InvalidOpcode {
+                pc: 0x202,
+                opcode: 0x0124
+            })
+        );
+        assert_eq!(outcome.instructions, 1);
+    }
+
+    #[test]
+    fn test_run_frame() {
+        // draw the 0 glyph twice, then count V1 up forever with the sound timer running
+        let rom = [
+            0xD0, 0x05, 0xD0, 0x05, 0x60, 0x05, 0xF0, 0x18, 0x71, 0x01, 0x12, 0x08,
+        ];
+        let mut chip8 = Chip8::new().unwrap().clock_hz(480).display_wait(true);
+        chip8.load_rom(&rom).unwrap();
+
+        // display wait ends each frame at a DXYN
+        assert_eq!(
+            chip8.run_frame().unwrap(),
+            FrameReport {
+                drew: true,
+                sound: false,
+                halted: false
+            }
+        );
+        assert_eq!(chip8.pc, 0x202);
+        assert_eq!(chip8.run_frame().unwrap().drew, true);
+        assert_eq!(chip8.pc, 0x204);
+        let report = chip8.run_frame().unwrap();
+        assert_eq!(report.drew, false);
+        assert_eq!(report.sound, true);
+        assert_eq!(chip8.v[1], 3);
+
+        let mut chip8 = Chip8::new().unwrap().clock_hz(480);
+        chip8.load_rom(&rom).unwrap();
+        chip8.run_frame().unwrap();
+        assert_eq!(chip8.v[1], 2);
+
+        let mut chip8 = Chip8::new().unwrap().clock_hz(480).display_wait(true);
+        chip8.load_rom(&rom).unwrap();
+        let outcome = chip8.run_until(&HaltCondition::Frames(2));
+        assert_eq!(outcome.instructions, 2);
+    }
+
+    #[test]
+    fn test_vip_timing() {
+        #[rustfmt::skip]
+        let rom = [
+            0x70, 0x01, // v0 += 1
+            0x00, 0xE0, // clear the screen
+            0x12, 0x00, // jump to the start
+        ];
+        let mut chip8 = Chip8::new().unwrap().vip_timing(true);
+        chip8.load_rom(&rom).unwrap();
+
+        // the first clear fits in the frame with time to spare, the second runs over it
+        let outcome = chip8.run_until(&HaltCondition::Frames(1));
+        assert_eq!(outcome.instructions, 5);
+        assert_eq!(chip8.v[0], 2);
+        // so the next frame has less time, and only fits two more instructions
+        chip8.cycle().unwrap();
+        assert_eq!(chip8.v[0], 3);
+        assert_eq!(chip8.pc, 0x204);
+    }
+}
@@ -0,0 +1,473 @@
+use std::collections::BTreeMap;
+#[cfg(feature = "std")]
+use std::path::PathBuf;
+
+use anyhow::{bail, ensure, Context};
+
+use crate::{
+    Chip8, ColourZones, Frame, AUDIO_PATTERN_LENGTH, BACKGROUND_COLOURS, FOREGROUND_COLOURS,
+    REGISTER_COUNT, STACK_SIZE,
+};
+
+/// Identifies a chipper savestate
+const MAGIC: &[u8; 4] = b"C8ST";
+
+/// The savestate format version, bumped whenever the layout changes
+const VERSION: u8 = 4;
+
+#[cfg(feature = "std")]
+/// The file extension used for savestates stored on disk
+const EXTENSION: &str = "c8s";
+
+/// A plain copy of everything that makes up the state of the machine, which is what savestates
+/// store, and the stable way to move state between instances, tools and other languages
+/// Host input and configuration aren't included.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MachineState {
+    pub pc: u16,
+    pub i: u16,
+    pub sp: u8,
+    pub dt: u8,
+    pub st: u8,
+    pub v: [u8; REGISTER_COUNT],
+    pub stack: [u16; STACK_SIZE],
+    pub memory: Vec<u8>,
+    pub fb: Frame,
+    /// The display planes selected with FN01, one bit per plane
+    pub planes: u8,
+    pub pitch: u8,
+    pub pattern: Option<[u8; AUDIO_PATTERN_LENGTH]>,
+    /// The CHIP-8X colour board's colours
+    pub colours: ColourZones,
+}
+
+impl MachineState {
+    /// Serialize the state into a versioned binary savestate
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+        out.extend_from_slice(&self.pc.to_le_bytes());
+        out.extend_from_slice(&self.i.to_le_bytes());
+        out.push(self.sp);
+        out.push(self.dt);
+        out.push(self.st);
+        out.extend_from_slice(&self.v);
+        for addr in self.stack {
+            out.extend_from_slice(&addr.to_le_bytes());
+        }
+        out.extend_from_slice(&(self.memory.len() as u32).to_le_bytes());
+        out.extend_from_slice(&self.memory);
+        out.push(if self.fb.two_page {
+            2
+        } else {
+            self.fb.hires as u8
+        });
+        out.extend(self.fb.iter_rows().flatten());
+        out.push(self.planes);
+        out.push(self.pitch);
+        match self.pattern {
+            Some(pattern) => {
+                out.push(1);
+                out.extend_from_slice(&pattern);
+            }
+            None => out.push(0),
+        }
+        out.push(self.colours.background);
+        out.extend_from_slice(&self.colours.foreground);
+        out
+    }
+
+    /// Deserialize a savestate created with `encode`
+    pub fn decode(state: &[u8]) -> anyhow::Result<Self> {
+        let mut reader = Reader { data: state };
+        ensure!(reader.take(4)? == MAGIC, "not a chipper savestate");
+        let version = reader.u8()?;
+        ensure!(
+            version == VERSION,
+            "unsupported savestate version {} (expected {})",
+            version,
+            VERSION
+        );
+
+        let pc = reader.u16()?;
+        let i = reader.u16()?;
+        let sp = reader.u8()?;
+        let dt = reader.u8()?;
+        let st = reader.u8()?;
+        let v = reader.array()?;
+        let mut stack = [0; STACK_SIZE];
+        for addr in stack.iter_mut() {
+            *addr = reader.u16()?;
+        }
+        ensure!((sp as usize) <= STACK_SIZE, "stack pointer out of range");
+
+        let memory_size = reader.u32()? as usize;
+        let memory = reader.take(memory_size)?.to_vec();
+        let mut fb = match reader.u8()? {
+            0 => Frame::blank(false),
+            1 => Frame::blank(true),
+            2 => Frame::two_page(),
+            flag => bail!("invalid resolution flag {}", flag),
+        };
+        let pixels = reader.take(fb.width() * fb.height())?;
+        fb.pixels[..pixels.len()].copy_from_slice(pixels);
+        let planes = reader.u8()?;
+        ensure!(planes <= 0b11, "invalid plane selection {}", planes);
+        let pitch = reader.u8()?;
+        let pattern = match reader.u8()? {
+            0 => None,
+            1 => Some(reader.array()?),
+            flag => bail!("invalid audio pattern flag {}", flag),
+        };
+        let colours = ColourZones {
+            background: reader.u8()?,
+            foreground: reader.array()?,
+        };
+        ensure!(
+            (colours.background as usize) < BACKGROUND_COLOURS.len()
+                && colours
+                    .foreground
+                    .iter()
+                    .all(|colour| (*colour as usize) < FOREGROUND_COLOURS.len()),
+            "invalid colour board colours"
+        );
+        ensure!(reader.data.is_empty(), "trailing data after savestate");
+
+        Ok(Self {
+            pc,
+            i,
+            sp,
+            dt,
+            st,
+            v,
+            stack,
+            memory,
+            fb,
+            planes,
+            pitch,
+            pattern,
+            colours,
+        })
+    }
+}
+
+impl Chip8 {
+    /// Copy the current machine state
+    pub fn dump_state(&self) -> MachineState {
+        MachineState {
+            pc: self.pc,
+            i: self.i,
+            sp: self.sp,
+            dt: self.dt,
+            st: self.st,
+            v: self.v,
+            stack: self.stack,
+            memory: self.memory.data.to_vec(),
+            fb: self.display.fb,
+            planes: self.display.planes,
+            pitch: self.buzzer.pitch,
+            pattern: self.buzzer.pattern,
+            colours: self.colours,
+        }
+    }
+
+    /// Replace the machine state with `state`, which has to have the same memory size
+    /// Execution resumes if it had halted
+    pub fn restore_state(&mut self, state: MachineState) -> anyhow::Result<()> {
+        ensure!(
+            state.memory.len() == self.memory.data.len(),
+            "savestate has {} bytes of memory (expected {})",
+            state.memory.len(),
+            self.memory.data.len()
+        );
+        ensure!(
+            (state.sp as usize) <= STACK_SIZE,
+            "stack pointer out of range"
+        );
+
+        self.pc = state.pc;
+        self.i = state.i;
+        self.sp = state.sp;
+        self.dt = state.dt;
+        self.set_sound_timer(state.st);
+        self.v = state.v;
+        self.stack = state.stack;
+        self.memory.data.copy_from_slice(&state.memory);
+        self.display.fb = state.fb;
+        self.display.planes = state.planes;
+        self.display.mark_dirty();
+        self.buzzer.pitch = state.pitch;
+        self.buzzer.pattern = state.pattern;
+        self.colours = state.colours;
+        self.idle.reset();
+        self.halted = None;
+        Ok(())
+    }
+
+    /// Serialize the machine state into a versioned binary savestate
+    /// Host input and configuration aren't included, so a state can be loaded into an instance
+    /// with different quirks or key mappings
+    pub fn save_state(&self) -> Vec<u8> {
+        self.dump_state().encode()
+    }
+
+    /// Restore a savestate produced by `save_state`, leaving the machine untouched if it's invalid
+    pub fn load_state(&mut self, state: &[u8]) -> anyhow::Result<()> {
+        self.restore_state(MachineState::decode(state)?)
+    }
+
+    /// A hash of the machine state, which two instances running in lockstep can compare to check
+    /// they haven't drifted apart
+    pub fn state_hash(&self) -> u64 {
+        crate::hash::fnv1a(&self.save_state())
+    }
+
+    /// Save the machine state into `slot` of `store`
+    pub fn save_to(&self, store: &mut dyn StateStore, slot: &str) -> anyhow::Result<()> {
+        store.save(slot, &self.save_state())
+    }
+
+    /// Restore the machine state saved in `slot` of `store`
+    pub fn load_from(&mut self, store: &dyn StateStore, slot: &str) -> anyhow::Result<()> {
+        let state = store.load(slot)?;
+        self.load_state(&state)
+            .with_context(|| format!("load savestate '{}'", slot))
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> anyhow::Result<&'a [u8]> {
+        ensure!(self.data.len() >= len, "savestate is truncated");
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> anyhow::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> anyhow::Result<u16> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn array<const N: usize>(&mut self) -> anyhow::Result<[u8; N]> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn u32(&mut self) -> anyhow::Result<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
+
+/// Named savestate slots, so every frontend saves and loads states the same way
+pub trait StateStore {
+    /// Save `state` into `slot`, replacing whatever was saved there before
+    fn save(&mut self, slot: &str, state: &[u8]) -> anyhow::Result<()>;
+    /// Return the state saved in `slot`
+    fn load(&self, slot: &str) -> anyhow::Result<Vec<u8>>;
+    /// Return the names of every slot with a state saved in it, in sorted order
+    fn list(&self) -> anyhow::Result<Vec<String>>;
+    /// Delete the state saved in `slot`
+    fn delete(&mut self, slot: &str) -> anyhow::Result<()>;
+}
+
+/// Keeps savestates in memory for the lifetime of the store
+#[derive(Default)]
+pub struct MemoryStateStore {
+    slots: BTreeMap<String, Vec<u8>>,
+}
+
+impl MemoryStateStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl StateStore for MemoryStateStore {
+    fn save(&mut self, slot: &str, state: &[u8]) -> anyhow::Result<()> {
+        validate_slot(slot)?;
+        self.slots.insert(slot.to_string(), state.to_vec());
+        Ok(())
+    }
+
+    fn load(&self, slot: &str) -> anyhow::Result<Vec<u8>> {
+        self.slots
+            .get(slot)
+            .cloned()
+            .with_context(|| format!("no savestate in slot '{}'", slot))
+    }
+
+    fn list(&self) -> anyhow::Result<Vec<String>> {
+        Ok(self.slots.keys().cloned().collect())
+    }
+
+    fn delete(&mut self, slot: &str) -> anyhow::Result<()> {
+        self.slots
+            .remove(slot)
+            .with_context(|| format!("no savestate in slot '{}'", slot))?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+/// Keeps each savestate in its own file within a directory, which is created on the first save
+pub struct FsStateStore {
+    dir: PathBuf,
+}
+
+#[cfg(feature = "std")]
+impl FsStateStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn slot_path(&self, slot: &str) -> anyhow::Result<PathBuf> {
+        validate_slot(slot)?;
+        Ok(self.dir.join(format!("{}.{}", slot, EXTENSION)))
+    }
+}
+
+#[cfg(feature = "std")]
+impl StateStore for FsStateStore {
+    fn save(&mut self, slot: &str, state: &[u8]) -> anyhow::Result<()> {
+        let path = self.slot_path(slot)?;
+        std::fs::create_dir_all(&self.dir).context("create savestate directory")?;
+        std::fs::write(path, state).context("write savestate")
+    }
+
+    fn load(&self, slot: &str) -> anyhow::Result<Vec<u8>> {
+        let path = self.slot_path(slot)?;
+        std::fs::read(path).with_context(|| format!("no savestate in slot '{}'", slot))
+    }
+
+    fn list(&self) -> anyhow::Result<Vec<String>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut slots = Vec::new();
+        for entry in std::fs::read_dir(&self.dir).context("read savestate directory")? {
+            let path = entry.context("read savestate directory entry")?.path();
+            if path.extension().is_some_and(|ext| ext == EXTENSION) {
+                if let Some(slot) = path.file_stem().and_then(|stem| stem.to_str()) {
+                    slots.push(slot.to_string());
+                }
+            }
+        }
+        slots.sort();
+        Ok(slots)
+    }
+
+    fn delete(&mut self, slot: &str) -> anyhow::Result<()> {
+        let path = self.slot_path(slot)?;
+        std::fs::remove_file(path).with_context(|| format!("no savestate in slot '{}'", slot))
+    }
+}
+
+/// Slot names become file names, so they're limited to a portable set of characters
+fn validate_slot(slot: &str) -> anyhow::Result<()> {
+    ensure!(
+        !slot.is_empty()
+            && slot
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'),
+        "invalid slot name '{}' (expected letters, digits, '-' or '_')",
+        slot
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "std")]
+    use super::FsStateStore;
+    use super::{MachineState, MemoryStateStore, StateStore};
+    use crate::Chip8;
+
+    #[test]
+    fn test_save_load_state() {
+        let mut chip8 = Chip8::new().unwrap();
+        chip8
+            .load_rom(&[0x60, 0x2A, 0xA3, 0x00, 0xD0, 0x05, 0x12, 0x06])
+            .unwrap();
+        chip8.run_frames(1).unwrap();
+        let state = chip8.save_state();
+
+        let mut restored = Chip8::new().unwrap();
+        restored.load_state(&state).unwrap();
+        assert_eq!(restored.pc, chip8.pc);
+        assert_eq!(restored.i, 0x300);
+        assert_eq!(restored.v[0], 0x2A);
+        assert_eq!(restored.memory.data, chip8.memory.data);
+        assert_eq!(restored.display.fb, chip8.display.fb);
+        assert_eq!(restored.save_state(), state);
+
+        assert!(restored.load_state(&state[..state.len() - 1]).is_err());
+        assert!(restored.load_state(b"nope").is_err());
+    }
+
+    #[test]
+    fn test_dump_restore_state() {
+        let mut chip8 = Chip8::new().unwrap();
+        chip8.load_rom(&[0x60, 0x2A, 0x12, 0x02]).unwrap();
+        chip8.run_frames(1).unwrap();
+
+        let mut state = chip8.dump_state();
+        assert_eq!(state.v[0], 0x2A);
+        assert_eq!(MachineState::decode(&state.encode()).unwrap(), state);
+
+        state.i = 0x123;
+        let mut restored = Chip8::new().unwrap();
+        restored.restore_state(state.clone()).unwrap();
+        assert_eq!(restored.i, 0x123);
+        assert_eq!(restored.dump_state(), state);
+
+        state.sp = 0xFF;
+        assert!(restored.restore_state(state.clone()).is_err());
+        state.sp = 0;
+        state.memory.pop();
+        assert!(restored.restore_state(state).is_err());
+    }
+
+    #[test]
+    fn test_memory_state_store() {
+        let mut store = MemoryStateStore::new();
+        let mut chip8 = Chip8::new().unwrap();
+        chip8.v[3] = 7;
+        chip8.save_to(&mut store, "b").unwrap();
+        chip8.save_to(&mut store, "a").unwrap();
+        assert_eq!(store.list().unwrap(), ["a", "b"]);
+
+        chip8.v[3] = 0;
+        chip8.load_from(&store, "b").unwrap();
+        assert_eq!(chip8.v[3], 7);
+
+        store.delete("b").unwrap();
+        assert!(chip8.load_from(&store, "b").is_err());
+        assert!(store.save("../escape", &[]).is_err());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_fs_state_store() {
+        let dir = std::env::temp_dir().join(format!("chipper-states-{}", std::process::id()));
+        let mut store = FsStateStore::new(&dir);
+        assert_eq!(store.list().unwrap(), Vec::<String>::new());
+
+        store.save("quick", &[1, 2, 3]).unwrap();
+        assert_eq!(store.list().unwrap(), ["quick"]);
+        assert_eq!(store.load("quick").unwrap(), [1, 2, 3]);
+
+        store.delete("quick").unwrap();
+        assert!(store.load("quick").is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
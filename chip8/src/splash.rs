@@ -0,0 +1,72 @@
+/// The program run when no ROM is given, which draws "CHIP-8" and then shows the digit of each
+/// key pressed with a short beep, so a bare launch shows the keypad working rather than running
+/// through empty memory
+#[rustfmt::skip]
+pub const SPLASH_ROM: [u8; 84] = [
+    0x00, 0xE0, // 0x200: CLS
+    0xA2, 0x36, // 0x202: LD I, 0x236
+    0x60, 0x11, // 0x204: LD V0, 17
+    0x61, 0x0A, // 0x206: LD V1, 10
+    0x62, 0x06, // 0x208: LD V2, 6
+    0xD0, 0x15, // 0x20A: DRW V0, V1, 5
+    0x70, 0x05, // 0x20C: ADD V0, 5
+    0x63, 0x05, // 0x20E: LD V3, 5
+    0xF3, 0x1E, // 0x210: ADD I, V3
+    0x72, 0xFF, // 0x212: ADD V2, 0xFF
+    0x32, 0x00, // 0x214: SE V2, 0
+    0x12, 0x0A, // 0x216: JP 0x20A
+    0x65, 0x1E, // 0x218: LD V5, 30
+    0x66, 0x14, // 0x21A: LD V6, 20
+    0x68, 0x00, // 0x21C: LD V8, 0
+    0xF4, 0x0A, // 0x21E: LD V4, K
+    0x38, 0x00, // 0x220: SE V8, 0
+    0x22, 0x30, // 0x222: CALL 0x230 to erase the last digit
+    0x87, 0x40, // 0x224: LD V7, V4
+    0x22, 0x30, // 0x226: CALL 0x230
+    0x68, 0x01, // 0x228: LD V8, 1
+    0x69, 0x04, // 0x22A: LD V9, 4
+    0xF9, 0x18, // 0x22C: LD ST, V9
+    0x12, 0x1E, // 0x22E: JP 0x21E
+    0xF7, 0x29, // 0x230: LD F, V7
+    0xD5, 0x65, // 0x232: DRW V5, V6, 5
+    0x00, 0xEE, // 0x234: RET
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // 0x236: C
+    0x90, 0x90, 0xF0, 0x90, 0x90, // 0x23B: H
+    0xE0, 0x40, 0x40, 0x40, 0xE0, // 0x240: I
+    0xF0, 0x90, 0xF0, 0x80, 0x80, // 0x245: P
+    0x00, 0x00, 0xF0, 0x00, 0x00, // 0x24A: -
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 0x24F: 8
+];
+
+#[cfg(test)]
+mod tests {
+    use super::SPLASH_ROM;
+    use crate::{Chip8, Key};
+
+    #[test]
+    fn test_splash_rom() {
+        let mut chip8 = Chip8::new().unwrap();
+        chip8.load_rom(&SPLASH_ROM).unwrap();
+        chip8.run_frames(10).unwrap();
+        let fb = chip8.fb();
+        // the top left corner of the C, and the bar of the dash
+        assert_eq!(fb.get(17, 10), 1);
+        assert_eq!(fb.get(37, 12), 1);
+        assert_eq!(fb.get(30, 20), 0);
+
+        // the font's 1 is a column with a foot, which leaves its top left corner unlit
+        chip8.press_key_for(Key::from_value(0x1), 2);
+        chip8.run_frames(10).unwrap();
+        let fb = chip8.fb();
+        assert_eq!(fb.get(31, 24), 1);
+        assert_eq!(fb.get(30, 20), 0);
+        assert_eq!(chip8.is_sound_playing(), false);
+
+        // the 1 is erased before the 0 is drawn over it
+        chip8.press_key_for(Key::from_value(0x0), 2);
+        chip8.run_frames(3).unwrap();
+        let fb = chip8.fb();
+        assert_eq!(fb.get(30, 20), 1);
+        assert_eq!(fb.get(31, 22), 0);
+    }
+}
@@ -0,0 +1,100 @@
+#[cfg(feature = "std")]
+use std::path::PathBuf;
+
+#[cfg(feature = "std")]
+use anyhow::{bail, Context};
+
+#[cfg(feature = "std")]
+use crate::{rom_hash, HIRES_HEIGHT, HIRES_WIDTH, SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::{Chip8, Frame};
+
+/// The number of frames a ROM runs for before its thumbnail is captured
+pub const THUMBNAIL_FRAMES: u32 = 60;
+/// The seed CXNN uses while rendering thumbnails, so the same ROM always gets the same one
+pub const THUMBNAIL_SEED: u64 = 0;
+
+#[cfg(feature = "std")]
+const EXTENSION: &str = "thumb";
+
+/// Run `rom` headlessly for a second without input and capture what's on screen
+pub fn render_thumbnail(rom: &[u8]) -> anyhow::Result<Frame> {
+    let mut chip8 = Chip8::new()?.rng_seed(THUMBNAIL_SEED);
+    chip8.load_rom(rom)?;
+    chip8.run_frames(THUMBNAIL_FRAMES)?;
+    Ok(chip8.fb())
+}
+
+#[cfg(feature = "std")]
+/// Keeps rendered thumbnails in a directory, one file per ROM hash, so each ROM is only run once
+pub struct ThumbnailCache {
+    dir: PathBuf,
+}
+
+#[cfg(feature = "std")]
+impl ThumbnailCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Return the thumbnail for `rom`, rendering and caching it if it hasn't been already
+    pub fn get(&self, rom: &[u8]) -> anyhow::Result<Frame> {
+        let path = self.dir.join(format!("{}.{}", rom_hash(rom), EXTENSION));
+        if let Ok(data) = std::fs::read(&path) {
+            return decode(&data).with_context(|| format!("read thumbnail {}", path.display()));
+        }
+
+        let fb = render_thumbnail(rom).context("render thumbnail")?;
+        std::fs::create_dir_all(&self.dir).context("create thumbnail directory")?;
+        std::fs::write(&path, encode(&fb)).context("write thumbnail")?;
+        Ok(fb)
+    }
+}
+
+#[cfg(feature = "std")]
+/// Pack the framebuffer into one bit per pixel, lit in any plane or not, which also gives away
+/// its resolution
+fn encode(fb: &Frame) -> Vec<u8> {
+    fb.pixels[..fb.width() * fb.height()]
+        .chunks(8)
+        .map(|pixels| {
+            pixels
+                .iter()
+                .fold(0, |byte, pixel| byte << 1 | (*pixel != 0) as u8)
+        })
+        .collect()
+}
+
+#[cfg(feature = "std")]
+fn decode(data: &[u8]) -> anyhow::Result<Frame> {
+    let mut fb = match data.len() * 8 {
+        len if len == SCREEN_WIDTH * SCREEN_HEIGHT => Frame::new(),
+        len if len == SCREEN_WIDTH * HIRES_HEIGHT => Frame::two_page(),
+        len if len == HIRES_WIDTH * HIRES_HEIGHT => Frame::blank(true),
+        _ => bail!("thumbnail is {} bytes", data.len()),
+    };
+    for (i, pixel) in fb.pixels[..data.len() * 8].iter_mut().enumerate() {
+        *pixel = (data[i / 8] >> (7 - i % 8)) & 1;
+    }
+    Ok(fb)
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{render_thumbnail, ThumbnailCache};
+
+    #[test]
+    fn test_thumbnail_cache() {
+        // draw the font sprite for 0 at (0, 0), then loop forever
+        let rom = [0x60, 0x00, 0xF0, 0x29, 0xD0, 0x05, 0x12, 0x06];
+        let fb = render_thumbnail(&rom).unwrap();
+        assert_eq!(fb.iter_rows().next().unwrap()[..4], [1, 1, 1, 1]);
+        assert_eq!(fb.get(4, 0), 0);
+
+        let dir = std::env::temp_dir().join(format!("chipper-thumbnails-{}", std::process::id()));
+        let cache = ThumbnailCache::new(&dir);
+        assert_eq!(cache.get(&rom).unwrap(), fb);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        assert_eq!(cache.get(&rom).unwrap(), fb);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,58 @@
+/// The machine cycles the COSMAC VIP's 1802 runs in a 60 Hz frame, at 1.76 MHz and eight clock
+/// pulses a cycle
+pub const VIP_CYCLES_PER_FRAME: u32 = 3668;
+
+/// The machine cycles the VIP interpreter spends fetching and decoding every instruction
+const FETCH_CYCLES: u32 = 40;
+
+/// The approximate number of machine cycles the COSMAC VIP interpreter takes to run `opcode`,
+/// including fetching it. Skips are counted as if they didn't skip, and instructions the VIP
+/// doesn't have cost the same as a register load.
+pub fn vip_cycles(opcode: u16) -> u32 {
+    let x = ((opcode & 0x0F00) >> 8) as u32;
+    let n = (opcode & 0x000F) as u32;
+    let execute = match opcode & 0xF000 {
+        0x0000 => match opcode {
+            0x00E0 => 3078,
+            0x00EE => 10,
+            _ => 6,
+        },
+        0x1000 => 12,
+        0x2000 => 26,
+        0x3000 | 0x4000 => 10,
+        0x5000 | 0x9000 => 14,
+        0x6000 => 6,
+        0x7000 => 10,
+        0x8000 => 44,
+        0xA000 => 12,
+        0xB000 => 22,
+        0xC000 => 36,
+        0xD000 => 68 + n * 46,
+        0xE000 => 14,
+        _ => match opcode & 0x00FF {
+            0x07 | 0x15 | 0x18 => 10,
+            0x0A => 19,
+            0x1E | 0x29 => 16,
+            0x33 => 152,
+            0x55 | 0x65 => 14 + 14 * (x + 1),
+            _ => 6,
+        },
+    };
+    FETCH_CYCLES + execute
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{vip_cycles, VIP_CYCLES_PER_FRAME};
+
+    #[test]
+    fn test_vip_cycles() {
+        assert_eq!(vip_cycles(0x6012), 46);
+        // drawing costs more the taller the sprite
+        assert!(vip_cycles(0xD01F) > vip_cycles(0xD011));
+        // saving registers costs more the more registers are saved
+        assert!(vip_cycles(0xFF55) > vip_cycles(0xF055));
+        // clearing the screen takes most of a frame
+        assert!(vip_cycles(0x00E0) * 2 > VIP_CYCLES_PER_FRAME);
+    }
+}
@@ -0,0 +1,259 @@
+use std::fmt::Display;
+use std::path::Path;
+#[cfg(feature = "std")]
+use std::path::PathBuf;
+use std::str::FromStr;
+
+#[cfg(feature = "std")]
+use anyhow::bail;
+use anyhow::{ensure, Context};
+
+use crate::Chip8;
+
+/// A value read out of the machine for a watch
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WatchExpr {
+    /// `len` bytes of memory from `addr`, read as a big-endian number
+    Memory {
+        addr: u16,
+        len: u8,
+    },
+    /// Register VX
+    Register(u8),
+    Index,
+    Pc,
+    DelayTimer,
+    SoundTimer,
+}
+
+impl WatchExpr {
+    pub fn read(&self, chip8: &Chip8) -> u64 {
+        match *self {
+            WatchExpr::Memory { addr, len } => {
+                let memory = chip8.memory();
+                (addr as usize..addr as usize + len as usize)
+                    .map(|addr| memory.get(addr).copied().unwrap_or(0))
+                    .fold(0, |value, byte| value << 8 | byte as u64)
+            }
+            WatchExpr::Register(x) => chip8.registers()[x as usize] as u64,
+            WatchExpr::Index => chip8.index() as u64,
+            WatchExpr::Pc => chip8.pc() as u64,
+            WatchExpr::DelayTimer => chip8.delay_timer() as u64,
+            WatchExpr::SoundTimer => chip8.sound_timer() as u64,
+        }
+    }
+}
+
+impl FromStr for WatchExpr {
+    type Err = anyhow::Error;
+
+    /// Parse a hex address with an optional `:LEN` byte count, e.g. `0x3F0:2`, or one of `VX`,
+    /// `I`, `PC`, `DT` or `ST`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        match s.to_ascii_uppercase().as_str() {
+            "I" => return Ok(WatchExpr::Index),
+            "PC" => return Ok(WatchExpr::Pc),
+            "DT" => return Ok(WatchExpr::DelayTimer),
+            "ST" => return Ok(WatchExpr::SoundTimer),
+            upper => {
+                if let Some(x) = upper.strip_prefix('V').filter(|x| x.len() == 1) {
+                    let x = u8::from_str_radix(x, 16)
+                        .with_context(|| format!("invalid register '{}'", s))?;
+                    return Ok(WatchExpr::Register(x));
+                }
+            }
+        }
+
+        let (addr, len) = s.split_once(':').unwrap_or((s, "1"));
+        let digits = addr.trim().trim_start_matches("0x");
+        let addr = u16::from_str_radix(digits, 16).with_context(|| {
+            format!(
+                "unknown watch '{}' (expected ADDR[:LEN], VX, I, PC, DT or ST)",
+                s
+            )
+        })?;
+        let len = len
+            .trim()
+            .parse::<u8>()
+            .with_context(|| format!("invalid length '{}'", len))?;
+        ensure!(
+            (1..=8).contains(&len),
+            "watch length must be between 1 and 8 bytes, got {}",
+            len
+        );
+        Ok(WatchExpr::Memory { addr, len })
+    }
+}
+
+impl Display for WatchExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WatchExpr::Memory { addr, len: 1 } => write!(f, "{:#05x}", addr),
+            WatchExpr::Memory { addr, len } => write!(f, "{:#05x}:{}", addr, len),
+            WatchExpr::Register(x) => write!(f, "V{:X}", x),
+            WatchExpr::Index => write!(f, "I"),
+            WatchExpr::Pc => write!(f, "PC"),
+            WatchExpr::DelayTimer => write!(f, "DT"),
+            WatchExpr::SoundTimer => write!(f, "ST"),
+        }
+    }
+}
+
+/// A named value to export, given as `NAME=EXPR`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Watch {
+    pub name: String,
+    pub expr: WatchExpr,
+}
+
+impl FromStr for Watch {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, expr) = s.split_once('=').context("expected NAME=EXPR")?;
+        let name = name.trim();
+        ensure!(
+            !name.is_empty() && !name.contains(['"', '\\']),
+            "invalid watch name '{}'",
+            name
+        );
+        Ok(Self {
+            name: name.to_string(),
+            expr: expr.parse()?,
+        })
+    }
+}
+
+/// How exported values are laid out
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WatchFormat {
+    /// A `name: value` line per watch
+    Text,
+    /// A JSON object mapping each name onto its value
+    Json,
+}
+
+impl WatchFormat {
+    /// JSON for `.json` files, text for anything else
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension() {
+            Some(extension) if extension.eq_ignore_ascii_case("json") => WatchFormat::Json,
+            _ => WatchFormat::Text,
+        }
+    }
+}
+
+/// Render the values of `watches` in `chip8` in `format`
+pub fn format_watches(watches: &[Watch], chip8: &Chip8, format: WatchFormat) -> String {
+    let values = watches
+        .iter()
+        .map(|watch| (watch.name.as_str(), watch.expr.read(chip8)));
+    match format {
+        WatchFormat::Text => values
+            .map(|(name, value)| format!("{}: {}\n", name, value))
+            .collect(),
+        WatchFormat::Json => {
+            let fields: Vec<String> = values
+                .map(|(name, value)| format!("\"{}\": {}", name, value))
+                .collect();
+            format!("{{{}}}\n", fields.join(", "))
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+/// Keeps a file up to date with the values of some watches, for streaming software to read.
+/// The file is only rewritten when a value changes, and is replaced in one go so readers never
+/// see it half written.
+pub struct WatchExporter {
+    watches: Vec<Watch>,
+    path: PathBuf,
+    format: WatchFormat,
+    /// What was last written, if anything has been
+    last: Option<String>,
+}
+
+#[cfg(feature = "std")]
+impl WatchExporter {
+    /// Export `watches` to `path`, in the format its extension calls for
+    pub fn new(watches: Vec<Watch>, path: PathBuf) -> anyhow::Result<Self> {
+        ensure!(!watches.is_empty(), "no watches to export");
+        for (i, watch) in watches.iter().enumerate() {
+            if watches[..i].iter().any(|other| other.name == watch.name) {
+                bail!("watch '{}' is given more than once", watch.name);
+            }
+        }
+        Ok(Self {
+            watches,
+            format: WatchFormat::from_path(&path),
+            path,
+            last: None,
+        })
+    }
+
+    /// Write the current values out if they've changed. Call this after each `cycle`.
+    pub fn update(&mut self, chip8: &Chip8) -> anyhow::Result<()> {
+        let text = format_watches(&self.watches, chip8, self.format);
+        if self.last.as_ref() == Some(&text) {
+            return Ok(());
+        }
+
+        let mut temp = self.path.clone().into_os_string();
+        temp.push(".tmp");
+        std::fs::write(&temp, &text).context("write watch values")?;
+        std::fs::rename(&temp, &self.path).context("replace watch file")?;
+        self.last = Some(text);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_watches, Watch, WatchExpr, WatchFormat};
+    use crate::Chip8;
+
+    #[test]
+    fn test_parse_watch() {
+        let watch: Watch = "score = 0x3f0:2".parse().unwrap();
+        assert_eq!(watch.name, "score");
+        assert_eq!(
+            watch.expr,
+            WatchExpr::Memory {
+                addr: 0x3F0,
+                len: 2
+            }
+        );
+        assert_eq!(
+            "lives=vA".parse::<Watch>().unwrap().expr,
+            WatchExpr::Register(0xA)
+        );
+        assert_eq!("t=DT".parse::<Watch>().unwrap().expr, WatchExpr::DelayTimer);
+        assert!("score".parse::<Watch>().is_err());
+        assert!("score=0x3f0:9".parse::<Watch>().is_err());
+        assert!("score=VG".parse::<Watch>().is_err());
+        assert!("\"=I".parse::<Watch>().is_err());
+    }
+
+    #[test]
+    fn test_format_watches() {
+        // store V0 = 0x12 and V1 = 0x34 at 0x300
+        let rom = [0x60, 0x12, 0x61, 0x34, 0xA3, 0x00, 0xF1, 0x55, 0x12, 0x08];
+        let mut chip8 = Chip8::new().unwrap();
+        chip8.load_rom(&rom).unwrap();
+        chip8.cycle().unwrap();
+
+        let watches: Vec<Watch> = ["score=0x300:2", "lives=V1"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        assert_eq!(
+            format_watches(&watches, &chip8, WatchFormat::Text),
+            "score: 4660\nlives: 52\n"
+        );
+        assert_eq!(
+            format_watches(&watches, &chip8, WatchFormat::Json),
+            "{\"score\": 4660, \"lives\": 52}\n"
+        );
+    }
+}
@@ -0,0 +1,97 @@
+#[cfg(feature = "std")]
+use std::fs::File;
+#[cfg(feature = "std")]
+use std::io::BufWriter;
+use std::io::{Seek, SeekFrom, Write};
+#[cfg(feature = "std")]
+use std::path::Path;
+
+#[cfg(feature = "std")]
+use anyhow::Context;
+
+/// The size of the RIFF and format headers that precede the sample data
+const HEADER_SIZE: u32 = 44;
+
+/// Writes mono 16-bit PCM audio to a WAV stream
+/// The sizes in the header are only known once recording stops, so `finish` must be called to
+/// patch them in
+pub struct WavWriter<W: Write + Seek> {
+    out: W,
+    data_size: u32,
+}
+
+#[cfg(feature = "std")]
+impl WavWriter<BufWriter<File>> {
+    /// Create a WAV file at `path`, overwriting it if it already exists
+    pub fn create(path: &Path, sample_rate: u32) -> anyhow::Result<Self> {
+        let file = File::create(path).context("create wav file")?;
+        WavWriter::new(BufWriter::new(file), sample_rate)
+    }
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    pub fn new(mut out: W, sample_rate: u32) -> anyhow::Result<Self> {
+        const CHANNELS: u16 = 1;
+        const BITS_PER_SAMPLE: u16 = 16;
+        let block_align = CHANNELS * BITS_PER_SAMPLE / 8;
+
+        out.write_all(b"RIFF")?;
+        out.write_all(&(HEADER_SIZE - 8).to_le_bytes())?;
+        out.write_all(b"WAVE")?;
+        out.write_all(b"fmt ")?;
+        out.write_all(&16u32.to_le_bytes())?;
+        out.write_all(&1u16.to_le_bytes())?; // PCM
+        out.write_all(&CHANNELS.to_le_bytes())?;
+        out.write_all(&sample_rate.to_le_bytes())?;
+        out.write_all(&(sample_rate * block_align as u32).to_le_bytes())?;
+        out.write_all(&block_align.to_le_bytes())?;
+        out.write_all(&BITS_PER_SAMPLE.to_le_bytes())?;
+        out.write_all(b"data")?;
+        out.write_all(&0u32.to_le_bytes())?;
+
+        Ok(Self { out, data_size: 0 })
+    }
+
+    /// Append samples in the range -1.0..=1.0, clipping anything outside of it
+    pub fn write_samples(&mut self, samples: &[f32]) -> anyhow::Result<()> {
+        for sample in samples {
+            let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            self.out.write_all(&value.to_le_bytes())?;
+        }
+        self.data_size += samples.len() as u32 * 2;
+        Ok(())
+    }
+
+    /// Patch the chunk sizes into the header and flush the stream, returning it
+    pub fn finish(mut self) -> anyhow::Result<W> {
+        self.out.seek(SeekFrom::Start(4))?;
+        self.out
+            .write_all(&(HEADER_SIZE - 8 + self.data_size).to_le_bytes())?;
+        self.out.seek(SeekFrom::Start(HEADER_SIZE as u64 - 4))?;
+        self.out.write_all(&self.data_size.to_le_bytes())?;
+        self.out.seek(SeekFrom::End(0))?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::WavWriter;
+
+    #[test]
+    fn test_write() {
+        let mut wav = WavWriter::new(Cursor::new(Vec::new()), 44100).unwrap();
+        wav.write_samples(&[0.0, 1.0, -2.0]).unwrap();
+        let data = wav.finish().unwrap().into_inner();
+
+        assert_eq!(data.len(), 44 + 6);
+        assert_eq!(&data[0..4], b"RIFF");
+        assert_eq!(data[4..8], (36u32 + 6).to_le_bytes());
+        assert_eq!(data[24..28], 44100u32.to_le_bytes());
+        assert_eq!(data[40..44], 6u32.to_le_bytes());
+        assert_eq!(data[44..], [0x00, 0x00, 0xFF, 0x7F, 0x01, 0x80]);
+    }
+}
@@ -0,0 +1,663 @@
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use anyhow::Context;
+use chip8::{
+    assemble, disassemble_rom, format_frame, parse_frame, rom_hash, verify_round_trip, Analysis,
+    BoundsVerifier, Chip8, Frame, FrameComparison, HaltCondition, InputMacro, Lockstep, Movie,
+    MoviePlayer, StopReason,
+};
+use clap::{Parser, Subcommand, ValueEnum};
+
+/// A quirk that can be switched on from the command line
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Quirk {
+    LegacyShift,
+    JumpAddOffset,
+    MemoryIncrementI,
+    DisplayWait,
+    WrapSprites,
+    UnmaskedFont,
+}
+
+impl Quirk {
+    /// The name of the quirk's command line flag
+    fn name(&self) -> &'static str {
+        match self {
+            Quirk::LegacyShift => "legacy-shift",
+            Quirk::JumpAddOffset => "jump-add-offset",
+            Quirk::MemoryIncrementI => "memory-increment-i",
+            Quirk::DisplayWait => "display-wait",
+            Quirk::WrapSprites => "wrap-sprites",
+            Quirk::UnmaskedFont => "unmasked-font",
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run a ROM under two quirk configurations in lockstep and report the first instruction
+    /// they disagree on
+    Diff {
+        rom: PathBuf,
+        #[arg(
+            long,
+            value_delimiter = ',',
+            value_name = "QUIRKS",
+            help = "The quirks enabled for the first instance, separated by commas"
+        )]
+        a: Vec<Quirk>,
+        #[arg(
+            long,
+            value_delimiter = ',',
+            value_name = "QUIRKS",
+            help = "The quirks enabled for the second instance, separated by commas"
+        )]
+        b: Vec<Quirk>,
+        #[arg(
+            long,
+            default_value = "600",
+            help = "The maximum number of frames to run"
+        )]
+        frames: u64,
+        #[arg(
+            long,
+            default_value = "0",
+            help = "The RNG seed shared by both instances"
+        )]
+        seed: u64,
+        #[arg(
+            long = "macro",
+            value_name = "PATH",
+            help = "An input macro played on both instances"
+        )]
+        input_macro: Option<PathBuf>,
+    },
+    /// Run a ROM headlessly until a condition is met, then print the machine state
+    Dump {
+        rom: PathBuf,
+        #[arg(
+            long,
+            value_delimiter = ',',
+            value_name = "QUIRKS",
+            help = "The quirks to enable, separated by commas"
+        )]
+        quirks: Vec<Quirk>,
+        #[arg(
+            long,
+            default_value = "600",
+            help = "The maximum number of frames to run"
+        )]
+        frames: u64,
+        #[arg(long, help = "Stop after this many instructions")]
+        instructions: Option<u64>,
+        #[arg(
+            long,
+            value_name = "ADDR",
+            value_parser = parse_addr,
+            help = "Stop when the program counter reaches the hex address ADDR"
+        )]
+        until_pc: Option<u16>,
+        #[arg(
+            long,
+            value_name = "ADDR=VALUE",
+            help = "Stop when the byte at the hex address ADDR holds the hex value VALUE"
+        )]
+        until_memory: Option<MemoryEquals>,
+        #[arg(long, help = "Halt on idle loops, such as a jump to itself")]
+        detect_idle_loops: bool,
+        #[arg(long, default_value = "0", help = "The RNG seed")]
+        seed: u64,
+        #[arg(
+            long = "macro",
+            value_name = "PATH",
+            help = "An input macro to play from the start"
+        )]
+        input_macro: Option<PathBuf>,
+        #[arg(
+            long,
+            value_name = "ADDR:LEN",
+            help = "Also print LEN bytes of memory from ADDR, both in hex"
+        )]
+        memory: Option<MemoryRange>,
+        #[arg(long, help = "Also print the display")]
+        display: bool,
+        #[arg(
+            long,
+            value_name = "PATH",
+            help = "Save the display to PATH as a frame capture, which 'chipper compare' can diff"
+        )]
+        capture: Option<PathBuf>,
+    },
+    /// Run a quirks test ROM under every combination of quirks and report the one it passes
+    /// most checks with, found by counting the pass marks on screen
+    Quirks {
+        rom: PathBuf,
+        #[arg(
+            long,
+            value_name = "PATH",
+            help = "The pass mark the ROM draws, as rows of '#' and '.', which can be copied from the output of 'chipper dump --display'"
+        )]
+        pass: PathBuf,
+        #[arg(
+            long = "macro",
+            value_name = "PATH",
+            help = "An input macro that navigates the ROM's menus to the results"
+        )]
+        input_macro: Option<PathBuf>,
+        #[arg(
+            long,
+            default_value = "600",
+            help = "The number of frames to run before reading the results"
+        )]
+        frames: u32,
+    },
+    /// Step through the first instructions of a ROM, explaining what each one does
+    Explain {
+        rom: PathBuf,
+        #[arg(
+            long,
+            value_delimiter = ',',
+            value_name = "QUIRKS",
+            help = "The quirks to enable, separated by commas"
+        )]
+        quirks: Vec<Quirk>,
+        #[arg(
+            long,
+            default_value = "20",
+            help = "The number of instructions to step through"
+        )]
+        steps: u64,
+        #[arg(long, default_value = "0", help = "The RNG seed")]
+        seed: u64,
+    },
+    /// Play a movie back headlessly, checking every state hash recorded in it to make sure the
+    /// replay matches the run it was recorded from
+    Replay { rom: PathBuf, movie: PathBuf },
+    /// Check a ROM without running it for memory accesses that can go out of bounds or read
+    /// memory nothing has written, and jumps that can't be followed
+    Verify { rom: PathBuf },
+    /// List a ROM's code and data without running it, marking the regions that hold sprites, text
+    /// or other data
+    Analyze {
+        rom: PathBuf,
+        #[arg(long, help = "Also draw every sprite found")]
+        sprites: bool,
+    },
+    /// Disassemble a ROM, assemble the listing again and check the bytes match, reporting every
+    /// instruction the disassembly can't express exactly
+    RoundTrip { rom: PathBuf },
+    /// Compare two frame captures, printing how many pixels differ and where, and failing if
+    /// more differ than allowed
+    Compare {
+        a: PathBuf,
+        b: PathBuf,
+        #[arg(
+            long,
+            default_value = "0",
+            help = "The number of differing pixels allowed before the comparison fails"
+        )]
+        tolerance: usize,
+    },
+}
+
+/// Parse a hex address, with or without a `0x` prefix
+fn parse_addr(s: &str) -> anyhow::Result<u16> {
+    let digits = s.trim().trim_start_matches("0x");
+    u16::from_str_radix(digits, 16).with_context(|| format!("invalid address '{}'", s))
+}
+
+/// A `ADDR=VALUE` memory condition given on the command line
+#[derive(Clone, Debug)]
+struct MemoryEquals {
+    addr: u16,
+    value: u8,
+}
+
+impl std::str::FromStr for MemoryEquals {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, value) = s.split_once('=').context("expected ADDR=VALUE")?;
+        let digits = value.trim().trim_start_matches("0x");
+        Ok(Self {
+            addr: parse_addr(addr)?,
+            value: u8::from_str_radix(digits, 16)
+                .with_context(|| format!("invalid value '{}'", value))?,
+        })
+    }
+}
+
+/// A `ADDR:LEN` memory range given on the command line
+#[derive(Clone, Debug)]
+struct MemoryRange {
+    addr: u16,
+    len: u16,
+}
+
+impl std::str::FromStr for MemoryRange {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, len) = s.split_once(':').context("expected ADDR:LEN")?;
+        Ok(Self {
+            addr: parse_addr(addr)?,
+            len: parse_addr(len)?,
+        })
+    }
+}
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+/// Build an instance with `quirks` enabled, loaded with `rom`
+fn instance(rom: &[u8], quirks: &[Quirk], seed: u64) -> anyhow::Result<Chip8> {
+    let mut chip8 = Chip8::new()
+        .context("construct new chip8 instance")?
+        .legacy_shift(quirks.contains(&Quirk::LegacyShift))
+        .jump_add_offset(quirks.contains(&Quirk::JumpAddOffset))
+        .memory_increment_i(quirks.contains(&Quirk::MemoryIncrementI))
+        .display_wait(quirks.contains(&Quirk::DisplayWait))
+        .wrap_sprites(quirks.contains(&Quirk::WrapSprites))
+        .unmasked_font(quirks.contains(&Quirk::UnmaskedFont))
+        .rng_seed(seed);
+    chip8.load_rom(rom).context("load rom")?;
+    Ok(chip8)
+}
+
+/// Load the input macro at `path`
+fn read_macro(path: &Path) -> anyhow::Result<InputMacro> {
+    let text = std::fs::read_to_string(path).context("read input macro")?;
+    text.parse().context("parse input macro")
+}
+
+fn diff(
+    rom: &Path,
+    a: &[Quirk],
+    b: &[Quirk],
+    frames: u64,
+    seed: u64,
+    input_macro: Option<&Path>,
+) -> anyhow::Result<bool> {
+    let rom = std::fs::read(rom).context("read rom file")?;
+    let mut lockstep = Lockstep::new(instance(&rom, a, seed)?, instance(&rom, b, seed)?);
+
+    if let Some(path) = input_macro {
+        let input_macro = read_macro(path)?;
+        lockstep.a.play_macro(input_macro.clone());
+        lockstep.b.play_macro(input_macro);
+    }
+
+    match lockstep.run(frames)? {
+        Some(divergence) => {
+            print!("{}", divergence);
+            Ok(false)
+        }
+        None => {
+            println!("no divergence after {} frames", lockstep.frames());
+            Ok(true)
+        }
+    }
+}
+
+/// The options of the dump command
+struct DumpOptions<'a> {
+    quirks: &'a [Quirk],
+    condition: HaltCondition,
+    detect_idle_loops: bool,
+    seed: u64,
+    input_macro: Option<&'a Path>,
+    memory: Option<&'a MemoryRange>,
+    display: bool,
+    capture: Option<&'a Path>,
+}
+
+fn dump(rom: &Path, options: DumpOptions) -> anyhow::Result<bool> {
+    let rom = std::fs::read(rom).context("read rom file")?;
+    let mut chip8 =
+        instance(&rom, options.quirks, options.seed)?.detect_idle_loops(options.detect_idle_loops);
+    if let Some(path) = options.input_macro {
+        chip8.play_macro(read_macro(path)?);
+    }
+
+    let outcome = chip8.run_until(&options.condition);
+    match outcome.reason {
+        StopReason::Condition => print!("stopped"),
+        StopReason::Halted(reason) => print!("halted: {}", reason),
+        StopReason::Fault(error) => print!("faulted: {}", error),
+    }
+    println!(
+        " after {} instructions ({} frames)",
+        outcome.instructions, outcome.frames
+    );
+
+    println!(
+        "PC {:#06x}  I {:#06x}  DT {:#04x}  ST {:#04x}",
+        chip8.pc(),
+        chip8.index(),
+        chip8.delay_timer(),
+        chip8.sound_timer()
+    );
+    for (x, value) in chip8.registers().iter().enumerate() {
+        print!(
+            "V{:X} {:02x}{}",
+            x,
+            value,
+            if x == 0xF { "\n" } else { "  " }
+        );
+    }
+    println!(
+        "stack: [{}]",
+        chip8
+            .call_stack()
+            .iter()
+            .map(|addr| format!("{:#06x}", addr))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+
+    if let Some(range) = options.memory {
+        let memory = chip8.memory();
+        let start = (range.addr as usize).min(memory.len());
+        let end = (start + range.len as usize).min(memory.len());
+        for (row, bytes) in memory[start..end].chunks(16).enumerate() {
+            let bytes: Vec<_> = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+            println!("{:#06x}: {}", start + row * 16, bytes.join(" "));
+        }
+    }
+
+    let frame = format_frame(&chip8.fb());
+    if options.display {
+        print!("{}", frame);
+    }
+    if let Some(path) = options.capture {
+        std::fs::write(path, &frame).context("write frame capture")?;
+    }
+
+    Ok(true)
+}
+
+/// Parse a sprite drawn as rows of '#' for set pixels and '.' for unset ones
+fn parse_sprite(text: &str) -> anyhow::Result<Vec<Vec<bool>>> {
+    let rows: Vec<Vec<bool>> = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            line.chars()
+                .map(|c| match c {
+                    '#' => Ok(true),
+                    '.' => Ok(false),
+                    _ => anyhow::bail!("unexpected '{}' in sprite, expected '#' or '.'", c),
+                })
+                .collect()
+        })
+        .collect::<anyhow::Result<_>>()?;
+    anyhow::ensure!(
+        rows.iter().any(|row| row.contains(&true)),
+        "sprite has no set pixels"
+    );
+    Ok(rows)
+}
+
+/// Count the places on screen where `sprite` is drawn
+fn count_sprite(fb: &Frame, sprite: &[Vec<bool>]) -> usize {
+    let height = sprite.len();
+    let width = sprite.iter().map(Vec::len).max().unwrap_or(0);
+    if height > fb.height() || width > fb.width() {
+        return 0;
+    }
+
+    let matches_at = |x: usize, y: usize| {
+        sprite.iter().enumerate().all(|(dy, row)| {
+            (0..width).all(|dx| {
+                let set = row.get(dx).copied().unwrap_or(false);
+                (fb.get(x + dx, y + dy) != 0) == set
+            })
+        })
+    };
+    (0..=fb.height() - height)
+        .flat_map(|y| (0..=fb.width() - width).map(move |x| (x, y)))
+        .filter(|(x, y)| matches_at(*x, *y))
+        .count()
+}
+
+fn quirks(
+    rom: &Path,
+    pass: &Path,
+    input_macro: Option<&Path>,
+    frames: u32,
+) -> anyhow::Result<bool> {
+    let rom = std::fs::read(rom).context("read rom file")?;
+    let sprite = std::fs::read_to_string(pass).context("read pass mark")?;
+    let sprite = parse_sprite(&sprite).context("parse pass mark")?;
+    let input_macro = input_macro.map(read_macro).transpose()?;
+
+    let mut results = Vec::new();
+    // every quirk clap accepts, so a new one is tried without being listed twice
+    let all = Quirk::value_variants();
+    for combination in 0..1 << all.len() {
+        let quirks: Vec<Quirk> = (0..all.len())
+            .filter(|n| combination & (1 << n) != 0)
+            .map(|n| all[n])
+            .collect();
+        let mut chip8 = instance(&rom, &quirks, 0)?;
+        if let Some(input_macro) = &input_macro {
+            chip8.play_macro(input_macro.clone());
+        }
+        // a combination the rom wasn't written for may fault, which counts the passes so far
+        let _ = chip8.run_frames(frames);
+
+        let passes = count_sprite(&chip8.fb(), &sprite);
+        let names: Vec<_> = quirks.iter().map(Quirk::name).collect();
+        let names = if names.is_empty() {
+            "no quirks".to_string()
+        } else {
+            names.join(",")
+        };
+        println!("{:>3} passes with {}", passes, names);
+        results.push((passes, names));
+    }
+
+    let best = results.iter().map(|(passes, _)| *passes).max().unwrap_or(0);
+    let expected: Vec<_> = results
+        .iter()
+        .filter(|(passes, _)| *passes == best)
+        .map(|(_, names)| names.as_str())
+        .collect();
+    match expected[..] {
+        _ if best == 0 => {
+            println!("no pass marks found, check the pass mark and how long the rom runs for");
+            Ok(false)
+        }
+        [names] => {
+            println!("expected: {}", names);
+            Ok(true)
+        }
+        _ => {
+            println!("ambiguous: {}", expected.join(" or "));
+            Ok(false)
+        }
+    }
+}
+
+fn explain(rom: &Path, quirks: &[Quirk], steps: u64, seed: u64) -> anyhow::Result<bool> {
+    let rom = std::fs::read(rom).context("read rom file")?;
+    let mut chip8 = instance(&rom, quirks, seed)?;
+    for _ in 0..steps {
+        match chip8.step_explained()? {
+            Some(explanation) => println!("{}", explanation),
+            None => break,
+        }
+    }
+    if let Some(reason) = chip8.halt_reason() {
+        println!("halted: {}", reason);
+    }
+    Ok(true)
+}
+
+fn replay(rom: &Path, movie: &Path) -> anyhow::Result<bool> {
+    let rom = std::fs::read(rom).context("read rom file")?;
+    let movie = Movie::load(movie)?;
+    movie.check_rom(&rom_hash(&rom))?;
+    if movie.checkpoints.is_empty() {
+        println!("warning: the movie has no checkpoints, so desyncs can't be detected");
+    }
+
+    let mut chip8 = movie.configure(Chip8::new().context("construct new chip8 instance")?);
+    chip8.load_rom(&rom).context("load rom")?;
+    let mut player = MoviePlayer::new(movie);
+    while player.next_frame(&mut chip8) {
+        chip8.cycle()?;
+        if let Err(desync) = player.verify(&chip8) {
+            println!("{}", desync);
+            return Ok(false);
+        }
+    }
+    println!(
+        "verified {} frames, {} checkpoints matched",
+        player.frame(),
+        player.checkpoints_verified()
+    );
+    Ok(true)
+}
+
+fn verify(rom: &Path) -> anyhow::Result<bool> {
+    let rom = std::fs::read(rom).context("read rom file")?;
+    let findings = BoundsVerifier::verify(&rom);
+    for finding in &findings {
+        println!("{}", finding);
+    }
+    match findings.len() {
+        0 => println!("no problems found"),
+        n => println!("{} problem{} found", n, if n == 1 { "" } else { "s" }),
+    }
+    Ok(findings.is_empty())
+}
+
+fn analyze(rom: &Path, sprites: bool) -> anyhow::Result<bool> {
+    let rom = std::fs::read(rom).context("read rom file")?;
+    let analysis = Analysis::new(&rom);
+    print!("{}", analysis.listing(&rom));
+    println!("\nuses {} instructions", analysis.variant);
+    if sprites {
+        for sprite in &analysis.sprites {
+            let width = if sprite.wide { 16 } else { 8 };
+            println!(
+                "\nsprite at {:#05x} ({}x{})",
+                sprite.addr, width, sprite.height
+            );
+            print!("{}", sprite.ascii_art(&rom));
+        }
+    }
+    Ok(true)
+}
+
+fn round_trip(rom: &Path) -> anyhow::Result<bool> {
+    let rom = std::fs::read(rom).context("read rom file")?;
+    let reassembled = assemble(&disassemble_rom(&rom)).context("assemble the disassembly")?;
+    let mismatches = verify_round_trip(&rom);
+    for mismatch in &mismatches {
+        println!("{}", mismatch);
+    }
+    if reassembled == rom {
+        println!("{} bytes round-tripped", rom.len());
+        Ok(true)
+    } else {
+        println!(
+            "{} of {} instructions didn't round-trip",
+            mismatches.len(),
+            rom.len() / 2
+        );
+        Ok(false)
+    }
+}
+
+/// Load the frame capture at `path`
+fn read_frame(path: &Path) -> anyhow::Result<Frame> {
+    let text = std::fs::read_to_string(path).context("read frame capture")?;
+    parse_frame(&text).with_context(|| format!("parse frame capture {}", path.display()))
+}
+
+fn compare(a: &Path, b: &Path, tolerance: usize) -> anyhow::Result<bool> {
+    let comparison = FrameComparison::new(&read_frame(a)?, &read_frame(b)?);
+    print!("{}", comparison);
+    Ok(comparison.differing <= tolerance)
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let result = match &args.command {
+        Command::Diff {
+            rom,
+            a,
+            b,
+            frames,
+            seed,
+            input_macro,
+        } => diff(rom, a, b, *frames, *seed, input_macro.as_deref()),
+        Command::Dump {
+            rom,
+            quirks,
+            frames,
+            instructions,
+            until_pc,
+            until_memory,
+            detect_idle_loops,
+            seed,
+            input_macro,
+            memory,
+            display,
+            capture,
+        } => {
+            let mut conditions = vec![HaltCondition::Frames(*frames)];
+            conditions.extend(instructions.map(HaltCondition::Instructions));
+            conditions.extend(until_pc.map(HaltCondition::PcReached));
+            conditions.extend(until_memory.as_ref().map(|m| HaltCondition::MemoryEquals {
+                addr: m.addr,
+                value: m.value,
+            }));
+            let options = DumpOptions {
+                quirks,
+                condition: HaltCondition::Any(conditions),
+                detect_idle_loops: *detect_idle_loops,
+                seed: *seed,
+                input_macro: input_macro.as_deref(),
+                memory: memory.as_ref(),
+                display: *display,
+                capture: capture.as_deref(),
+            };
+            dump(rom, options)
+        }
+        Command::Explain {
+            rom,
+            quirks,
+            steps,
+            seed,
+        } => explain(rom, quirks, *steps, *seed),
+        Command::Quirks {
+            rom,
+            pass,
+            input_macro,
+            frames,
+        } => quirks(rom, pass, input_macro.as_deref(), *frames),
+        Command::Replay { rom, movie } => replay(rom, movie),
+        Command::Verify { rom } => verify(rom),
+        Command::Analyze { rom, sprites } => analyze(rom, *sprites),
+        Command::RoundTrip { rom } => round_trip(rom),
+        Command::Compare { a, b, tolerance } => compare(a, b, *tolerance),
+    };
+
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(e) => {
+            eprintln!("{:?}", e);
+            ExitCode::from(2)
+        }
+    }
+}
@@ -0,0 +1,320 @@
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use anyhow::{bail, Context};
+use chip8::{
+    config_dir, Chip8, FontSet, Keymap, Layout, Palette, Waveform, BUZZER_FREQUENCY,
+    DEFAULT_ENVELOPE_MS, FRAME_RATE,
+};
+
+pub use chip8::Quirks;
+
+/// The names of every setting, as used in the settings file, in `KEY=VALUE` overrides and, in
+/// upper snake case after `CHIPPER_`, in environment variables
+pub const KEYS: [&str; 19] = [
+    "legacy-shift",
+    "jump-add-offset",
+    "memory-increment-i",
+    "display-wait",
+    "wrap-sprites",
+    "unmasked-font",
+    "ops-per-cycle",
+    "ips",
+    "font-set",
+    "palette",
+    "pixel-grid",
+    "keyboard-layout",
+    "volume",
+    "buzzer-frequency",
+    "buzzer-waveform",
+    "buzzer-envelope-ms",
+    "audio-sync",
+    "roms-dir",
+    "thumbnail-dir",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AudioSettings {
+    /// The output volume between 0.0 and 1.0
+    pub volume: f32,
+    /// The frequency of the buzzer tone in Hz
+    pub buzzer_frequency: f32,
+    pub buzzer_waveform: Waveform,
+    /// The length of the fade in and out when the buzzer starts and stops
+    pub buzzer_envelope_ms: f32,
+    /// Pace emulation by the audio output rather than a timer
+    pub sync: bool,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            volume: 1.0,
+            buzzer_frequency: BUZZER_FREQUENCY,
+            buzzer_waveform: Waveform::Square,
+            buzzer_envelope_ms: DEFAULT_ENVELOPE_MS,
+            sync: false,
+        }
+    }
+}
+
+/// Where frontends look for files, with `None` leaving it up to the frontend
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Paths {
+    /// The directory the ROM library is scanned from
+    pub roms_dir: Option<PathBuf>,
+    /// The directory library thumbnails are cached in
+    pub thumbnail_dir: Option<PathBuf>,
+}
+
+/// The settings shared by every frontend, so an option means the same thing wherever it's set.
+/// They're read from a settings file of `KEY VALUE` lines, then overridden by the environment
+/// and the command line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    pub quirks: Quirks,
+    pub ops_per_cycle: usize,
+    /// The speed in instructions per second, which takes precedence over `ops_per_cycle`
+    pub ips: Option<usize>,
+    /// The historical interpreter whose font is used
+    pub font_set: FontSet,
+    pub palette: Palette,
+    /// Draw lines between the pixels
+    pub pixel_grid: bool,
+    /// Map keys by their labels in this layout, rather than by their position
+    pub keyboard_layout: Option<Layout>,
+    pub audio: AudioSettings,
+    pub paths: Paths,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            quirks: Quirks::default(),
+            ops_per_cycle: 11,
+            ips: None,
+            font_set: FontSet::default(),
+            palette: Palette::default(),
+            pixel_grid: false,
+            keyboard_layout: None,
+            audio: AudioSettings::default(),
+            paths: Paths::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// Where the settings file is kept, if there's a configuration directory
+    pub fn path() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join("settings"))
+    }
+
+    /// Load the settings file, or the defaults if there isn't one
+    pub fn load() -> anyhow::Result<Self> {
+        let Some(path) = Self::path().filter(|path| path.exists()) else {
+            return Ok(Self::default());
+        };
+        let text = std::fs::read_to_string(&path).context("read settings")?;
+        Self::parse(&text).with_context(|| format!("parse settings {}", path.display()))
+    }
+
+    /// Save the settings for every frontend to load on its next launch
+    pub fn save(&self) -> anyhow::Result<()> {
+        let path = Self::path().context("no configuration directory available")?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).context("create settings directory")?;
+        }
+        std::fs::write(&path, self.to_string()).context("write settings")
+    }
+
+    /// Parse settings from `KEY VALUE` lines, with `#` starting a comment. Settings that aren't
+    /// given keep their defaults.
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let mut settings = Self::default();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            settings
+                .set(key, value.trim())
+                .with_context(|| format!("line {}", n + 1))?;
+        }
+        Ok(settings)
+    }
+
+    /// Change the setting named `key`, parsing `value` the way the settings file does
+    pub fn set(&mut self, key: &str, value: &str) -> anyhow::Result<()> {
+        match key {
+            "legacy-shift" => self.quirks.legacy_shift = parse_flag(value)?,
+            "jump-add-offset" => self.quirks.jump_add_offset = parse_flag(value)?,
+            "memory-increment-i" => self.quirks.memory_increment_i = parse_flag(value)?,
+            "display-wait" => self.quirks.display_wait = parse_flag(value)?,
+            "wrap-sprites" => self.quirks.wrap_sprites = parse_flag(value)?,
+            "unmasked-font" => self.quirks.unmasked_font = parse_flag(value)?,
+            "ops-per-cycle" => self.ops_per_cycle = parse_value(key, value)?,
+            "ips" => self.ips = parse_optional(key, value)?,
+            "font-set" => self.font_set = value.parse()?,
+            "palette" => self.palette = value.parse()?,
+            "pixel-grid" => self.pixel_grid = parse_flag(value)?,
+            "keyboard-layout" => self.keyboard_layout = parse_optional(key, value)?,
+            "volume" => self.audio.volume = parse_value(key, value)?,
+            "buzzer-frequency" => self.audio.buzzer_frequency = parse_value(key, value)?,
+            "buzzer-waveform" => self.audio.buzzer_waveform = value.parse()?,
+            "buzzer-envelope-ms" => self.audio.buzzer_envelope_ms = parse_value(key, value)?,
+            "audio-sync" => self.audio.sync = parse_flag(value)?,
+            "roms-dir" => self.paths.roms_dir = (!value.is_empty()).then(|| value.into()),
+            "thumbnail-dir" => self.paths.thumbnail_dir = (!value.is_empty()).then(|| value.into()),
+            _ => bail!("unknown setting '{}'", key),
+        }
+        Ok(())
+    }
+
+    /// Apply `KEY=VALUE` overrides, such as those given on the command line
+    pub fn apply_overrides<S: AsRef<str>>(&mut self, overrides: &[S]) -> anyhow::Result<()> {
+        for expr in overrides {
+            let expr = expr.as_ref();
+            let (key, value) = expr
+                .split_once('=')
+                .with_context(|| format!("expected KEY=VALUE, got '{}'", expr))?;
+            self.set(key.trim(), value.trim())?;
+        }
+        Ok(())
+    }
+
+    /// Apply the `CHIPPER_` environment variable for each setting that has one set, such as
+    /// `CHIPPER_PALETTE` or `CHIPPER_AUDIO_SYNC`. A flag set to an empty value is switched on.
+    pub fn apply_env(&mut self) -> anyhow::Result<()> {
+        for key in KEYS {
+            let var = format!("CHIPPER_{}", key.replace('-', "_").to_ascii_uppercase());
+            if let Ok(value) = std::env::var(&var) {
+                self.set(key, &value)
+                    .with_context(|| format!("parse {}", var))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply the quirks, speed and audio settings to `chip8`
+    pub fn configure(&self, chip8: Chip8) -> Chip8 {
+        let chip8 = chip8
+            .quirks(self.quirks)
+            .clock_hz((self.ops_per_cycle * FRAME_RATE) as u32)
+            .font_set(self.font_set)
+            .buzzer_frequency(self.audio.buzzer_frequency)
+            .buzzer_waveform(self.audio.buzzer_waveform)
+            .buzzer_envelope_ms(self.audio.buzzer_envelope_ms)
+            .volume(self.audio.volume);
+        match self.ips {
+            Some(ips) => chip8.instructions_per_second(ips),
+            None => chip8,
+        }
+    }
+
+    /// The keymap for the chosen keyboard layout, if keys are mapped by label
+    pub fn keymap(&self) -> Option<Keymap> {
+        self.keyboard_layout.map(Keymap::from_layout)
+    }
+}
+
+/// Parse a flag, where an empty value switches it on
+fn parse_flag(value: &str) -> anyhow::Result<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "" | "true" | "on" | "yes" | "1" => Ok(true),
+        "false" | "off" | "no" | "0" => Ok(false),
+        _ => bail!("unknown flag value '{}' (expected true or false)", value),
+    }
+}
+
+fn parse_value<T: FromStr>(key: &str, value: &str) -> anyhow::Result<T> {
+    value
+        .parse()
+        .ok()
+        .with_context(|| format!("invalid {} '{}'", key, value))
+}
+
+/// Parse a value that an empty string leaves unset
+fn parse_optional<T: FromStr>(key: &str, value: &str) -> anyhow::Result<Option<T>> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+    parse_value(key, value).map(Some)
+}
+
+impl Display for Settings {
+    /// Write every setting in the format read by `Settings::parse`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let optional = |value: Option<String>| value.unwrap_or_default();
+        let path =
+            |path: &Option<PathBuf>| optional(path.as_ref().map(|p| p.display().to_string()));
+        let values = [
+            self.quirks.legacy_shift.to_string(),
+            self.quirks.jump_add_offset.to_string(),
+            self.quirks.memory_increment_i.to_string(),
+            self.quirks.display_wait.to_string(),
+            self.quirks.wrap_sprites.to_string(),
+            self.quirks.unmasked_font.to_string(),
+            self.ops_per_cycle.to_string(),
+            optional(self.ips.map(|ips| ips.to_string())),
+            self.font_set.to_string(),
+            self.palette.to_string(),
+            self.pixel_grid.to_string(),
+            optional(self.keyboard_layout.map(|layout| layout.to_string())),
+            self.audio.volume.to_string(),
+            self.audio.buzzer_frequency.to_string(),
+            self.audio.buzzer_waveform.to_string(),
+            self.audio.buzzer_envelope_ms.to_string(),
+            self.audio.sync.to_string(),
+            path(&self.paths.roms_dir),
+            path(&self.paths.thumbnail_dir),
+        ];
+        for (key, value) in KEYS.iter().zip(values) {
+            // unset values are left out, since an empty value would switch a flag on
+            if !value.is_empty() {
+                writeln!(f, "{} {}", key, value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Settings;
+    use chip8::{FontSet, Layout, Palette, Waveform};
+
+    #[test]
+    fn test_settings_round_trip() {
+        let mut settings = Settings::default();
+        assert_eq!(Settings::parse(&settings.to_string()).unwrap(), settings);
+
+        settings.quirks.display_wait = true;
+        settings.ips = Some(700);
+        settings.font_set = FontSet::Eti660;
+        settings.palette = "ff8000:202020".parse().unwrap();
+        settings.keyboard_layout = Some(Layout::Dvorak);
+        settings.audio.buzzer_waveform = Waveform::Sine;
+        settings.paths.roms_dir = Some("/games/chip8".into());
+        assert_eq!(Settings::parse(&settings.to_string()).unwrap(), settings);
+    }
+
+    #[test]
+    fn test_settings_overrides() {
+        let mut settings = Settings::parse("# defaults\npalette yellow\nlegacy-shift\n").unwrap();
+        assert_eq!(settings.palette, Palette::YELLOW);
+        assert_eq!(settings.quirks.legacy_shift, true);
+
+        settings
+            .apply_overrides(&["legacy-shift=false", "volume = 0.5"])
+            .unwrap();
+        assert_eq!(settings.quirks.legacy_shift, false);
+        assert_eq!(settings.audio.volume, 0.5);
+
+        assert!(settings.apply_overrides(&["volume"]).is_err());
+        assert!(settings.set("speed", "fast").is_err());
+        assert!(settings.set("audio-sync", "maybe").is_err());
+        assert!(Settings::parse("ops-per-cycle lots").is_err());
+    }
+}
@@ -0,0 +1,108 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use chip8::{Frame, ThumbnailCache};
+
+/// A ROM found in the library directory
+pub struct Entry {
+    pub path: PathBuf,
+    pub title: String,
+    /// The author and year, for ROMs named like "Title [Author, Year].ch8"
+    pub credits: Option<String>,
+    pub platform: &'static str,
+    pub size: u64,
+    /// What's on screen a second into the ROM, if it could be run
+    pub thumbnail: Option<Frame>,
+}
+
+/// The platform a ROM was written for, going by its file extension
+fn platform(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    match extension.as_str() {
+        "ch8" | "c8" => Some("CHIP-8"),
+        "c8x" => Some("CHIP-8X"),
+        "sc8" => Some("SUPER-CHIP"),
+        "xo8" => Some("XO-CHIP"),
+        _ => None,
+    }
+}
+
+/// Split a file stem like "Title [Author, Year]" into the title and the credits
+fn parse_name(stem: &str) -> (String, Option<String>) {
+    match stem.split_once('[') {
+        Some((title, credits)) => (
+            title.trim().to_string(),
+            Some(credits.trim_end_matches(']').trim().to_string()).filter(|c| !c.is_empty()),
+        ),
+        None => (stem.trim().to_string(), None),
+    }
+}
+
+/// Find every ROM under `dir`, sorted by title, with their thumbnails from `thumbnails`
+pub fn scan(dir: &Path, thumbnails: &ThumbnailCache) -> anyhow::Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let read_dir =
+            std::fs::read_dir(&dir).with_context(|| format!("read directory {}", dir.display()))?;
+        for item in read_dir {
+            let item = item.context("read directory entry")?;
+            let path = item.path();
+            let metadata = item.metadata().context("read file metadata")?;
+            if metadata.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+            let Some(platform) = platform(&path) else {
+                continue;
+            };
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            let (title, credits) = parse_name(&stem);
+            let thumbnail = match std::fs::read(&path)
+                .context("read ROM")
+                .and_then(|rom| thumbnails.get(&rom))
+            {
+                Ok(thumbnail) => Some(thumbnail),
+                Err(e) => {
+                    eprintln!("thumbnail for {} failed: {:?}", path.display(), e);
+                    None
+                }
+            };
+            entries.push(Entry {
+                title,
+                credits,
+                platform,
+                size: metadata.len(),
+                thumbnail,
+                path,
+            });
+        }
+    }
+    entries.sort_by_key(|entry| entry.title.to_lowercase());
+    Ok(entries)
+}
+
+/// The ROMs in the library directory, and which of them is selected
+pub struct Library {
+    pub entries: Vec<Entry>,
+    pub selected: usize,
+}
+
+impl Library {
+    pub fn new(dir: &Path, thumbnails: &ThumbnailCache) -> anyhow::Result<Self> {
+        Ok(Self {
+            entries: scan(dir, thumbnails)?,
+            selected: 0,
+        })
+    }
+
+    /// Move the selection by `offset` entries, stopping at either end
+    pub fn move_selection(&mut self, offset: isize) {
+        let last = self.entries.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(offset).min(last);
+    }
+
+    pub fn selected(&self) -> Option<&Entry> {
+        self.entries.get(self.selected)
+    }
+}
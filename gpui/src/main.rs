@@ -1,90 +1,733 @@
-use std::{path::PathBuf, str::FromStr, time::Duration};
+mod library;
+
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use anyhow::Context;
-use chip8::{Chip8, FrameBuffer, Key};
+use chip8::{
+    ensure_not_cartridge, rom_hash, Chip8, EmulatorCore, Event, Frame, FsFlagStore, HaltReason,
+    Keymap, Layout, Magnifier, MemoryStateStore, Movie, MoviePlayer, Palette, Player,
+    ThumbnailCache, Watch, WatchExporter, WindowGeometry, WindowLayout, SCREEN_HEIGHT,
+    SCREEN_WIDTH,
+};
+use chipper_config::Settings;
 use gpui::{
     actions, canvas, div, fill, point, prelude::*, px, size, App, Application, Bounds, FocusHandle,
-    KeyBinding, KeyDownEvent, KeyUpEvent, Menu, MenuItem, Pixels, Window, WindowBounds,
-    WindowOptions,
+    Hsla, KeyBinding, KeyDownEvent, KeyUpEvent, Menu, MenuItem, Modifiers, MouseButton,
+    MouseMoveEvent, Pixels, Window, WindowBounds, WindowOptions,
 };
+use library::Library;
+use rodio::{OutputStream, Sink};
 
 const SCALE_FACTOR: f32 = 16.;
 const FRAME_INTERVAL: Duration = Duration::new(0, 1_000_000_000u32 / 60);
+const SAMPLE_RATE: u32 = 44100;
+/// The maximum number of frames of audio queued in the sink before new frames are dropped
+const MAX_QUEUED_AUDIO_FRAMES: usize = 3;
+/// How often the sink is checked for room while emulation is paced by the audio output
+const AUDIO_POLL_INTERVAL: Duration = Duration::from_millis(1);
+/// How far back rewinding can go
+const REWIND_SECONDS: usize = 60;
+/// The number of frames between each second rewound while alt-backspace is held
+const REWIND_STEP_FRAMES: u32 = 6;
+/// The name the window layout is saved under
+const LAYOUT_NAME: &str = "gpui";
+/// How long messages stay in the overlay, in frames
+const OVERLAY_FRAMES: u32 = 120;
+/// The number of frames the window has to stay put before its layout is saved
+const LAYOUT_SETTLE_FRAMES: u32 = 60;
+/// How far the magnifier zooms in around the mouse cursor
+const MAGNIFIER_ZOOM: f32 = 3.;
 
-actions!(chipper, [Quit, CloseWindow]);
+actions!(
+    chipper,
+    [
+        Quit,
+        CloseWindow,
+        ToggleMute,
+        ToggleAutofire,
+        SaveState,
+        LoadState,
+        OpenLibrary,
+        ToggleMagnifier,
+        SpeedUp,
+        SlowDown
+    ]
+);
 
 struct Chipper {
     focus_handle: FocusHandle,
     chip8: Chip8,
+    keymap: Keymap,
+    /// Savestates are kept for as long as the window is open
+    states: MemoryStateStore,
+    /// The number of frames alt-backspace has been held for, if it's held
+    rewind_frames: Option<u32>,
+    /// The ROMs in the library directory, shown instead of the game while browsing
+    library: Library,
+    browsing: bool,
+    /// Set when the program faults, which stops it until another is launched or the state is
+    /// rewound or loaded, as the faulting instruction would fault again every frame
+    stopped: bool,
+    palette: Palette,
+    /// Draw lines between the pixels
+    pixel_grid: bool,
+    /// Follows the mouse cursor, and is only drawn while the magnifier is toggled on
+    magnifier: Magnifier,
+    magnifying: bool,
+    /// The window layout last saved, which is saved again once the window has stopped moving
+    layout: WindowLayout,
+    /// The number of frames the window has stayed put since it last moved, if it's moved since
+    /// the layout was saved
+    layout_settled_frames: Option<u32>,
+    /// The message shown over the game, such as the speed after changing it
+    overlay: String,
+    /// The number of frames left until the overlay is hidden
+    overlay_frames: u32,
+    /// The movie the input of every frame is being recorded into, saved when the game is left
+    recording: Option<Movie>,
+    /// The movie being played back, which replaces the keypad input until it finishes
+    playback: Option<MoviePlayer>,
+    /// Writes the values in CHIPPER_WATCH out for streaming overlays to show
+    watches: Option<WatchExporter>,
+    /// The shared settings, from the settings file and the CHIPPER_ environment variables
+    settings: Settings,
+    sink: Sink,
+    _stream: OutputStream,
+}
+
+/// A fresh interpreter with the frontend's settings
+fn new_chip8(settings: &Settings) -> Chip8 {
+    let chip8 = Chip8::new()
+        .context("Failed to create new Chip8 instance")
+        .unwrap()
+        .rewind_seconds(REWIND_SECONDS)
+        .detect_idle_loops(true);
+    settings.configure(chip8)
+}
+
+/// The settings file, with any setting overridden by its CHIPPER_ environment variable, such as
+/// CHIPPER_PALETTE or CHIPPER_ROMS_DIR
+fn load_settings() -> anyhow::Result<Settings> {
+    let mut settings = Settings::load()?;
+    settings.apply_env()?;
+    Ok(settings)
+}
+
+/// Export the comma separated `NAME=EXPR` watches in CHIPPER_WATCH to CHIPPER_WATCH_OUTPUT,
+/// if both are set
+fn watch_exporter() -> anyhow::Result<Option<WatchExporter>> {
+    let (Ok(watches), Some(path)) = (
+        std::env::var("CHIPPER_WATCH"),
+        std::env::var_os("CHIPPER_WATCH_OUTPUT"),
+    ) else {
+        return Ok(None);
+    };
+    let watches = watches
+        .split(',')
+        .map(str::parse::<Watch>)
+        .collect::<anyhow::Result<_>>()
+        .context("parse CHIPPER_WATCH")?;
+    Ok(Some(WatchExporter::new(watches, PathBuf::from(path))?))
+}
+
+/// The directory the library is scanned from
+fn library_dir(settings: &Settings) -> PathBuf {
+    settings
+        .paths
+        .roms_dir
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("../roms"))
+}
+
+/// The directory library thumbnails are cached in
+fn thumbnail_dir(settings: &Settings) -> PathBuf {
+    settings
+        .paths
+        .thumbnail_dir
+        .clone()
+        .unwrap_or_else(|| std::env::temp_dir().join("chipper-thumbnails"))
 }
 
 impl Chipper {
+    /// Queue the audio generated for the frame that was just emulated
+    fn queue_audio(&mut self) {
+        // Audio is rendered every frame to keep it in step with the emulated frames, but only queued
+        // while the sink isn't already backed up
+        let mut samples = vec![0.0; (SAMPLE_RATE / 60) as usize];
+        self.chip8.audio(&mut samples, SAMPLE_RATE);
+        if self.sink.len() < MAX_QUEUED_AUDIO_FRAMES {
+            self.sink
+                .append(rodio::buffer::SamplesBuffer::new(1, SAMPLE_RATE, samples));
+        }
+    }
+
+    /// Queue a frame of silence, which keeps the audio clock ticking while no frame is emulated
+    fn queue_silence(&mut self) {
+        let samples = vec![0.0; (SAMPLE_RATE / 60) as usize];
+        self.sink
+            .append(rodio::buffer::SamplesBuffer::new(1, SAMPLE_RATE, samples));
+    }
+
+    /// Whether the next frame is due. When pacing by the audio output, that's once the output has
+    /// played one of the queued frames of audio, and every frame queues one.
+    fn frame_due(&self) -> bool {
+        !self.settings.audio.sync || self.sink.len() < MAX_QUEUED_AUDIO_FRAMES
+    }
+
+    /// Start the selected ROM on a fresh interpreter
+    fn launch(&mut self) {
+        let Some(path) = self.library.selected().map(|entry| entry.path.clone()) else {
+            return;
+        };
+        match self.start(&path) {
+            Ok(chip8) => self.chip8 = chip8,
+            Err(e) => {
+                eprintln!("launch failed: {:?}", e);
+                return;
+            }
+        }
+        self.rewind_frames = None;
+        self.browsing = false;
+        self.stopped = false;
+    }
+
+    /// A fresh interpreter running the ROM at `path`, which records or plays back the movie given
+    /// in CHIPPER_RECORD_MOVIE or CHIPPER_PLAY_MOVIE
+    fn start(&mut self, path: &Path) -> anyhow::Result<Chip8> {
+        self.finish_recording();
+        self.playback = None;
+
+        let rom = std::fs::read(path).context("read rom file")?;
+        ensure_not_cartridge(&rom)?;
+        let hash = rom_hash(&rom);
+        let mut chip8 = new_chip8(&self.settings);
+        if let Some(path) = std::env::var_os("CHIPPER_PLAY_MOVIE") {
+            let movie = Movie::load(path.as_ref()).context("load movie")?;
+            movie.check_rom(&hash)?;
+            chip8 = movie.configure(chip8);
+            self.playback = Some(MoviePlayer::new(movie));
+        } else if std::env::var_os("CHIPPER_RECORD_MOVIE").is_some() {
+            // the seed is stored in the movie, so playback generates the same random numbers
+            let seed = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |time| time.as_nanos() as u64);
+            chip8 = chip8.rng_seed(seed);
+            self.recording = Some(Movie::new(&hash, &chip8, seed));
+        } else if let Some(store) = FsFlagStore::for_rom(&hash) {
+            // flags saved by an earlier run would make movies diverge, so they're only kept
+            // between plain runs
+            chip8
+                .set_flag_store(Box::new(store))
+                .context("load rpl flags")?;
+        }
+        chip8.load_rom(&rom).context("load rom")?;
+        Ok(chip8)
+    }
+
+    /// Save the movie being recorded, if there is one
+    fn finish_recording(&mut self) {
+        let Some(movie) = self.recording.take() else {
+            return;
+        };
+        let Some(path) = std::env::var_os("CHIPPER_RECORD_MOVIE") else {
+            return;
+        };
+        if let Err(e) = movie.save(path.as_ref()) {
+            eprintln!("saving movie failed: {:?}", e);
+        }
+    }
+
+    /// Whether a movie is being recorded or played back, which anything that changes the machine
+    /// outside of the recorded input would desync
+    fn movie_active(&self) -> bool {
+        self.recording.is_some() || self.playback.is_some()
+    }
+
+    fn browse_key_down(&mut self, event: &KeyDownEvent, cx: &mut gpui::Context<Self>) {
+        match event.keystroke.key.as_str() {
+            "up" => self.library.move_selection(-1),
+            "down" => self.library.move_selection(1),
+            "pageup" => self.library.move_selection(-10),
+            "pagedown" => self.library.move_selection(10),
+            "enter" => self.launch(),
+            _ => return,
+        }
+        cx.notify();
+    }
+
+    fn open_library(
+        &mut self,
+        _: &OpenLibrary,
+        _window: &mut Window,
+        cx: &mut gpui::Context<Self>,
+    ) {
+        self.finish_recording();
+        self.playback = None;
+        self.browsing = true;
+        self.rewind_frames = None;
+        cx.notify();
+    }
+
     fn key_down(
         &mut self,
         event: &KeyDownEvent,
         _window: &mut Window,
-        _cx: &mut gpui::Context<Self>,
+        cx: &mut gpui::Context<Self>,
     ) {
+        if self.browsing {
+            if !is_hotkey(&event.keystroke.modifiers) {
+                self.browse_key_down(event, cx);
+            }
+            return;
+        }
+
+        // keystrokes with modifiers belong to the hotkey layer and never reach the keypad
+        if is_hotkey(&event.keystroke.modifiers) {
+            // rewinding lasts as long as the key is held, so it can't be an action
+            if event.keystroke.key == "backspace"
+                && !event.is_held
+                && hotkeys_enabled()
+                && !self.movie_active()
+            {
+                self.rewind_frames = Some(0);
+            }
+            return;
+        }
+        // the movie supplies the input while it plays
+        if self.playback.is_some() {
+            return;
+        }
+
         // TODO: Unfortunately there doesn't seem to be a way to use scancodes in gpui right now,
         // so we're just using the key label
         self.chip8
-            .keydown(Key::from_label(event.keystroke.key.as_str()))
+            .key_event(
+                Player::One,
+                self.keymap.key(event.keystroke.key.as_str()),
+                true,
+            )
             .context("Failed to handle key down event")
             .unwrap();
     }
 
     fn key_up(&mut self, event: &KeyUpEvent, _window: &mut Window, _cx: &mut gpui::Context<Self>) {
+        if event.keystroke.key == "backspace" {
+            self.rewind_frames = None;
+        }
+        if self.browsing || is_hotkey(&event.keystroke.modifiers) || self.playback.is_some() {
+            return;
+        }
+
         // TODO: Unfortunately there doesn't seem to be a way to use scancodes in gpui right now,
         // so we're just using the key label
         self.chip8
-            .keyup(Key::from_label(event.keystroke.key.as_str()))
+            .key_event(
+                Player::One,
+                self.keymap.key(event.keystroke.key.as_str()),
+                false,
+            )
             .context("Failed to handle key up event")
             .unwrap();
     }
+
+    fn speed_up(&mut self, _: &SpeedUp, _window: &mut Window, cx: &mut gpui::Context<Self>) {
+        if self.movie_active() {
+            return;
+        }
+        self.chip8.adjust_speed(1);
+        self.show_overlay(format!("{} IPS", self.chip8.ips()));
+        cx.notify();
+    }
+
+    fn slow_down(&mut self, _: &SlowDown, _window: &mut Window, cx: &mut gpui::Context<Self>) {
+        if self.movie_active() {
+            return;
+        }
+        self.chip8.adjust_speed(-1);
+        self.show_overlay(format!("{} IPS", self.chip8.ips()));
+        cx.notify();
+    }
+
+    /// Show `message` over the top left of the screen for a couple of seconds
+    fn show_overlay(&mut self, message: String) {
+        self.overlay = message;
+        self.overlay_frames = OVERLAY_FRAMES;
+    }
+
+    fn toggle_magnifier(
+        &mut self,
+        _: &ToggleMagnifier,
+        _window: &mut Window,
+        cx: &mut gpui::Context<Self>,
+    ) {
+        self.magnifying = !self.magnifying;
+        cx.notify();
+    }
+
+    fn mouse_move(
+        &mut self,
+        event: &MouseMoveEvent,
+        window: &mut Window,
+        _cx: &mut gpui::Context<Self>,
+    ) {
+        let viewport = window.viewport_size();
+        self.magnifier.set_focus(
+            event.position.x.0 / viewport.width.0 * SCREEN_WIDTH as f32,
+            event.position.y.0 / viewport.height.0 * SCREEN_HEIGHT as f32,
+        );
+    }
+
+    fn toggle_mute(&mut self, _: &ToggleMute, _window: &mut Window, _cx: &mut gpui::Context<Self>) {
+        self.chip8.toggle_mute();
+    }
+
+    fn toggle_autofire(
+        &mut self,
+        _: &ToggleAutofire,
+        _window: &mut Window,
+        _cx: &mut gpui::Context<Self>,
+    ) {
+        // toggle autofire for whichever keypad keys are held
+        let held: Vec<u8> = self.chip8.held_keys().collect();
+        for key in held {
+            self.chip8.toggle_autofire(key);
+        }
+    }
+
+    /// Save the window layout once the window has been left alone for a second, so dragging it
+    /// around doesn't write it out every frame
+    fn track_layout(&mut self, window: &Window) {
+        let (bounds, fullscreen) = match window.window_bounds() {
+            WindowBounds::Fullscreen(bounds) => (bounds, true),
+            WindowBounds::Windowed(bounds) | WindowBounds::Maximized(bounds) => (bounds, false),
+        };
+        let layout = WindowLayout {
+            main: Some(WindowGeometry {
+                x: bounds.origin.x.0 as i32,
+                y: bounds.origin.y.0 as i32,
+                width: bounds.size.width.0 as u32,
+                height: bounds.size.height.0 as u32,
+            }),
+            fullscreen,
+            panels: Vec::new(),
+        };
+
+        if layout != self.layout {
+            self.layout = layout;
+            self.layout_settled_frames = Some(0);
+        } else if let Some(frames) = self.layout_settled_frames.as_mut() {
+            *frames += 1;
+            if *frames >= LAYOUT_SETTLE_FRAMES {
+                self.layout_settled_frames = None;
+                if let Err(e) = self.layout.save(LAYOUT_NAME) {
+                    eprintln!("save window layout failed: {:?}", e);
+                }
+            }
+        }
+    }
+
+    /// Run a frame, or step back through time while rewinding
+    fn frame(&mut self) {
+        self.overlay_frames = self.overlay_frames.saturating_sub(1);
+        if self.browsing {
+            if self.settings.audio.sync {
+                self.queue_silence();
+            }
+            return;
+        }
+        let Some(frames) = self.rewind_frames.as_mut() else {
+            if self.stopped {
+                if self.settings.audio.sync {
+                    self.queue_silence();
+                }
+                return;
+            }
+            if let Some(movie) = self.recording.as_mut() {
+                movie.record_frame(&self.chip8);
+            }
+            if let Some(playback) = self.playback.as_mut() {
+                if !playback.next_frame(&mut self.chip8) {
+                    let message = format!(
+                        "Movie finished after {} frames, {} checkpoints verified",
+                        playback.frame(),
+                        playback.checkpoints_verified()
+                    );
+                    self.playback = None;
+                    self.show_overlay(message);
+                }
+            }
+            if let Err(e) = self.chip8.run_frame() {
+                eprintln!("emulation stopped: {}", e);
+                self.stopped = true;
+                self.show_overlay("Emulation stopped".to_string());
+            }
+            for event in self.chip8.events() {
+                if let Event::FlagSaveFailed(e) = event {
+                    eprintln!("saving flags failed: {}", e);
+                }
+            }
+            let finished = self.chip8.events().iter().find_map(|e| match e {
+                Event::Halted(HaltReason::Exited { .. }) => Some("Program exited"),
+                Event::Halted(HaltReason::IdleLoop { .. }) => Some("Program finished"),
+                _ => None,
+            });
+            if let Some(message) = finished {
+                self.show_overlay(message.to_string());
+            }
+            if let Some(watches) = self.watches.as_mut() {
+                if let Err(e) = watches.update(&self.chip8) {
+                    eprintln!("exporting watches failed: {:?}", e);
+                    self.watches = None;
+                }
+            }
+            if let Some(movie) = self.recording.as_mut() {
+                movie.record_checkpoint(&self.chip8);
+            }
+            if let Some(Err(desync)) = self
+                .playback
+                .as_mut()
+                .map(|playback| playback.verify(&self.chip8))
+            {
+                eprintln!("movie playback failed: {}", desync);
+                self.playback = None;
+                self.show_overlay(format!("Desync at frame {}", desync.frame));
+            }
+            self.queue_audio();
+            return;
+        };
+
+        if *frames % REWIND_STEP_FRAMES == 0 {
+            match self.chip8.rewind(1) {
+                Ok(_) => self.stopped = false,
+                Err(e) => eprintln!("rewind failed: {:?}", e),
+            }
+        }
+        *frames += 1;
+        if self.settings.audio.sync {
+            self.queue_silence();
+        }
+    }
+
+    fn save_state(&mut self, _: &SaveState, _window: &mut Window, _cx: &mut gpui::Context<Self>) {
+        self.chip8
+            .save_to(&mut self.states, "quick")
+            .context("Failed to save state")
+            .unwrap();
+    }
+
+    fn load_state(&mut self, _: &LoadState, _window: &mut Window, cx: &mut gpui::Context<Self>) {
+        if self.movie_active() {
+            return;
+        }
+        match self.chip8.load_from(&self.states, "quick") {
+            Ok(_) => self.stopped = false,
+            Err(e) => eprintln!("load state failed: {:?}", e),
+        }
+        cx.notify();
+    }
 }
 
-impl Render for Chipper {
-    fn render(&mut self, _window: &mut Window, cx: &mut gpui::Context<Self>) -> impl IntoElement {
-        let fb = self.chip8.fb();
-
-        let paint_framebuffer =
-            move |bounds: Bounds<Pixels>, fb: FrameBuffer, window: &mut Window, _: &mut App| {
-                let start_y = bounds.origin.y.0;
-                let height = bounds.size.height.0;
-                let start_x = bounds.origin.x.0;
-                let width = bounds.size.width.0;
-
-                let pixel_height = height / 32.0;
-                let pixel_width = width / 64.0;
-
-                for y in 0..32 {
-                    for x in 0..64 {
-                        if fb[y][x] == 1 {
-                            let rect = Bounds::new(
-                                point(
-                                    px(start_x + x as f32 * pixel_width),
-                                    px(start_y + y as f32 * pixel_height),
-                                ),
-                                size(px(pixel_width), px(pixel_height)),
+fn is_hotkey(modifiers: &Modifiers) -> bool {
+    modifiers.control || modifiers.alt || modifiers.platform || modifiers.function
+}
+
+/// The emulator hotkeys can be turned off for games that need every key
+fn hotkeys_enabled() -> bool {
+    std::env::var_os("CHIPPER_NO_HOTKEYS").is_none()
+}
+
+/// How the screen is drawn
+#[derive(Clone, Copy)]
+struct ScreenStyle {
+    palette: Palette,
+    pixel_grid: bool,
+    /// Zooms in on part of the screen, if the magnifier is on
+    magnifier: Option<Magnifier>,
+}
+
+impl ScreenStyle {
+    /// The plain style library thumbnails are drawn in
+    fn thumbnail(palette: Palette) -> Self {
+        Self {
+            palette,
+            pixel_grid: false,
+            magnifier: None,
+        }
+    }
+}
+
+fn colour(rgba: [u8; 4]) -> Hsla {
+    gpui::rgba(u32::from_be_bytes(rgba)).into()
+}
+
+/// Paint `fb` scaled to fill `bounds`, or just the magnifier's view of it while that's on
+fn paint_framebuffer(bounds: Bounds<Pixels>, fb: Frame, style: ScreenStyle, window: &mut Window) {
+    let (view_x, view_y, view_width, view_height) = match style.magnifier {
+        Some(magnifier) => magnifier.view(),
+        None => (0.0, 0.0, SCREEN_WIDTH as f32, SCREEN_HEIGHT as f32),
+    };
+    // the view is in low resolution pixels, so it covers twice as many in high resolution, and
+    // twice as many rows on the 64x64 screen
+    let (scale_x, scale_y) = fb.scale();
+    let (scale_x, scale_y) = (scale_x as f32, scale_y as f32);
+    let (view_x, view_y) = (view_x * scale_x, view_y * scale_y);
+    let (view_width, view_height) = (view_width * scale_x, view_height * scale_y);
+    let pixel_width = bounds.size.width.0 / view_width;
+    let pixel_height = bounds.size.height.0 / view_height;
+    let start_x = bounds.origin.x.0 - view_x * pixel_width;
+    let start_y = bounds.origin.y.0 - view_y * pixel_height;
+
+    window.paint_quad(fill(bounds, colour(style.palette.off)));
+    for (x, y) in fb.iter_set_pixels() {
+        let rect = Bounds::new(
+            point(
+                px(start_x + x as f32 * pixel_width),
+                px(start_y + y as f32 * pixel_height),
+            ),
+            size(px(pixel_width), px(pixel_height)),
+        );
+        window.paint_quad(fill(rect, colour(style.palette.colour(fb.get(x, y)))));
+    }
+
+    if style.pixel_grid {
+        let grid = colour(style.palette.grid);
+        for x in 1..fb.width() {
+            let rect = Bounds::new(
+                point(px(start_x + x as f32 * pixel_width), bounds.origin.y),
+                size(px(1.), bounds.size.height),
+            );
+            window.paint_quad(fill(rect, grid));
+        }
+        for y in 1..fb.height() {
+            let rect = Bounds::new(
+                point(bounds.origin.x, px(start_y + y as f32 * pixel_height)),
+                size(bounds.size.width, px(1.)),
+            );
+            window.paint_quad(fill(rect, grid));
+        }
+    }
+}
+
+impl Chipper {
+    /// The list of ROMs, with the selected one highlighted
+    fn render_library(&self, cx: &mut gpui::Context<Self>) -> gpui::Div {
+        if self.library.entries.is_empty() {
+            return div().p_4().child(format!(
+                "No ROMs found in {} - set CHIPPER_ROMS_DIR to your ROMs directory",
+                library_dir(&self.settings).display()
+            ));
+        }
+
+        div()
+            .flex()
+            .flex_col()
+            .p_4()
+            .children(
+                self.library
+                    .entries
+                    .iter()
+                    .enumerate()
+                    .map(|(index, entry)| {
+                        let mut details = vec![entry.platform.to_string()];
+                        details.extend(entry.credits.clone());
+                        details.push(format!("{} bytes", entry.size));
+                        let thumbnail = entry.thumbnail.unwrap_or_default();
+                        let style = ScreenStyle::thumbnail(self.palette);
+                        let row = div()
+                            .flex()
+                            .items_center()
+                            .gap_2()
+                            .p_1()
+                            .child(
+                                div()
+                                    .w(px(SCREEN_WIDTH as f32 * 2.))
+                                    .h(px(SCREEN_HEIGHT as f32 * 2.))
+                                    .child(
+                                        canvas(
+                                            move |_, _, _| thumbnail,
+                                            move |bounds, fb, window, _| {
+                                                paint_framebuffer(bounds, fb, style, window)
+                                            },
+                                        )
+                                        .size_full(),
+                                    ),
+                            )
+                            .child(div().flex_grow().child(entry.title.clone()))
+                            .child(details.join(" - "))
+                            .on_mouse_down(
+                                MouseButton::Left,
+                                cx.listener(move |this, _, _, cx| {
+                                    this.library.selected = index;
+                                    this.launch();
+                                    cx.notify();
+                                }),
                             );
-                            window.paint_quad(fill(rect, gpui::white()));
+                        if index == self.library.selected {
+                            row.bg(gpui::white()).text_color(gpui::black())
+                        } else {
+                            row
                         }
-                    }
-                }
-            };
+                    }),
+            )
+    }
+}
 
+impl Render for Chipper {
+    fn render(&mut self, _window: &mut Window, cx: &mut gpui::Context<Self>) -> impl IntoElement {
+        let fb = self.chip8.framebuffer();
+        let style = ScreenStyle {
+            palette: self.palette,
+            pixel_grid: self.pixel_grid,
+            magnifier: self.magnifying.then_some(self.magnifier),
+        };
         div()
-            .on_action(|_: &Quit, _, app| {
-                app.quit();
-            })
-            .on_action(|_: &CloseWindow, window, _| {
+            .on_action(cx.listener(|chipper, _: &Quit, _, cx| {
+                chipper.finish_recording();
+                cx.quit();
+            }))
+            .on_action(cx.listener(|chipper, _: &CloseWindow, window, _| {
+                chipper.finish_recording();
                 window.remove_window();
-            })
+            }))
+            .on_action(cx.listener(Self::toggle_mute))
+            .on_action(cx.listener(Self::toggle_autofire))
+            .on_action(cx.listener(Self::save_state))
+            .on_action(cx.listener(Self::load_state))
+            .on_action(cx.listener(Self::open_library))
+            .on_action(cx.listener(Self::toggle_magnifier))
+            .on_action(cx.listener(Self::speed_up))
+            .on_action(cx.listener(Self::slow_down))
+            .on_mouse_move(cx.listener(Self::mouse_move))
             .on_key_down(cx.listener(Self::key_down))
             .on_key_up(cx.listener(Self::key_up))
             .track_focus(&self.focus_handle)
             .size_full()
-            .child(canvas(move |_, _, _| fb, paint_framebuffer).size_full())
+            .bg(gpui::black())
+            .text_color(gpui::white())
+            .child(if self.browsing {
+                self.render_library(cx).size_full().into_any_element()
+            } else {
+                canvas(
+                    move |_, _, _| fb,
+                    move |bounds, fb, window, _| paint_framebuffer(bounds, fb, style, window),
+                )
+                .size_full()
+                .into_any_element()
+            })
+            .when(self.overlay_frames > 0, |root| {
+                root.relative().child(
+                    div()
+                        .absolute()
+                        .top_2()
+                        .left_2()
+                        .px_1()
+                        .bg(gpui::black())
+                        .child(self.overlay.clone()),
+                )
+            })
     }
 }
 
@@ -99,19 +742,56 @@ fn main() {
             items: vec![MenuItem::action("Quit", Quit)],
         }]);
 
-        let bounds = Bounds::centered(
-            None,
-            size(
-                px(chip8::SCREEN_WIDTH as f32 * SCALE_FACTOR),
-                px(chip8::SCREEN_HEIGHT as f32 * SCALE_FACTOR),
+        let settings = load_settings().context("Failed to load settings").unwrap();
+        let interval = if settings.audio.sync {
+            AUDIO_POLL_INTERVAL
+        } else {
+            FRAME_INTERVAL
+        };
+
+        let layout = match WindowLayout::load(LAYOUT_NAME) {
+            Ok(layout) => layout.unwrap_or_default(),
+            Err(e) => {
+                eprintln!("load window layout failed: {:?}", e);
+                WindowLayout::default()
+            }
+        };
+        let bounds = match layout.main {
+            Some(geometry) => Bounds::new(
+                point(px(geometry.x as f32), px(geometry.y as f32)),
+                size(px(geometry.width as f32), px(geometry.height as f32)),
             ),
-            cx,
-        );
+            None => Bounds::centered(
+                None,
+                size(
+                    px(SCREEN_WIDTH as f32 * SCALE_FACTOR),
+                    px(SCREEN_HEIGHT as f32 * SCALE_FACTOR),
+                ),
+                cx,
+            ),
+        };
+        let window_bounds = if layout.fullscreen {
+            WindowBounds::Fullscreen(bounds)
+        } else {
+            WindowBounds::Windowed(bounds)
+        };
 
         cx.bind_keys([
             KeyBinding::new("cmd-q", Quit, None),
             KeyBinding::new("cmd-w", CloseWindow, None),
         ]);
+        if hotkeys_enabled() {
+            cx.bind_keys([
+                KeyBinding::new("alt-m", ToggleMute, None),
+                KeyBinding::new("alt-t", ToggleAutofire, None),
+                KeyBinding::new("alt-s", SaveState, None),
+                KeyBinding::new("alt-l", LoadState, None),
+                KeyBinding::new("alt-o", OpenLibrary, None),
+                KeyBinding::new("alt-z", ToggleMagnifier, None),
+                KeyBinding::new("alt-=", SpeedUp, None),
+                KeyBinding::new("alt--", SlowDown, None),
+            ]);
+        }
 
         cx.on_window_closed(|cx| {
             if cx.windows().is_empty() {
@@ -123,19 +803,29 @@ fn main() {
         let window = cx
             .open_window(
                 WindowOptions {
-                    window_bounds: Some(WindowBounds::Windowed(bounds)),
+                    window_bounds: Some(window_bounds),
                     ..Default::default()
                 },
                 |window, cx| {
-                    let mut chip8 = Chip8::new()
-                        .context("Failed to create new Chip8 instance")
+                    let library = Library::new(
+                        &library_dir(&settings),
+                        &ThumbnailCache::new(thumbnail_dir(&settings)),
+                    )
+                    .context("Failed to scan the ROM library")
+                    .unwrap();
+
+                    let (_stream, stream_handle) = OutputStream::try_default()
+                        .context("Failed to create default output stream")
+                        .unwrap();
+                    let sink = Sink::try_new(&stream_handle)
+                        .context("Failed to create audio sink")
                         .unwrap();
-                    chip8
-                        .load_rom_from_file(
-                            PathBuf::from_str("../roms/programs/Keypad Test [Hap, 2006].ch8")
-                                .unwrap(),
-                        )
-                        .context("Failed to load ROM from file")
+
+                    // Key labels depend on the host layout, so pick the matching preset
+                    let keyboard_layout = settings.keyboard_layout.unwrap_or_else(Layout::detect);
+
+                    let watches = watch_exporter()
+                        .context("Failed to start exporting watches")
                         .unwrap();
 
                     cx.new(|cx| {
@@ -143,7 +833,27 @@ fn main() {
                         focus_handle.focus(window);
                         Chipper {
                             focus_handle,
-                            chip8,
+                            chip8: new_chip8(&settings),
+                            keymap: Keymap::from_layout(keyboard_layout),
+                            states: MemoryStateStore::new(),
+                            rewind_frames: None,
+                            library,
+                            browsing: true,
+                            stopped: false,
+                            palette: settings.palette,
+                            pixel_grid: settings.pixel_grid,
+                            magnifier: Magnifier::new(MAGNIFIER_ZOOM),
+                            magnifying: false,
+                            layout,
+                            layout_settled_frames: None,
+                            overlay: String::new(),
+                            overlay_frames: 0,
+                            recording: None,
+                            playback: None,
+                            watches,
+                            settings: settings.clone(),
+                            sink,
+                            _stream,
                         }
                     })
                 },
@@ -153,17 +863,20 @@ fn main() {
 
         cx.spawn(move |mut cx| async move {
             loop {
-                cx.update_window(window.into(), |root_view, _, cx| {
+                cx.update_window(window.into(), |root_view, window, cx| {
                     if let Ok(chipper_view) = root_view.downcast::<Chipper>() {
                         chipper_view.update(cx, |chipper, cx| {
-                            chipper.chip8.cycle();
-                            cx.notify();
+                            if chipper.frame_due() {
+                                chipper.frame();
+                                chipper.track_layout(window);
+                                cx.notify();
+                            }
                         });
                     }
                 })
                 .ok();
 
-                gpui::Timer::after(FRAME_INTERVAL).await;
+                gpui::Timer::after(interval).await;
             }
         })
         .detach();
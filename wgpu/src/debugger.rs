@@ -0,0 +1,187 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::{bail, Context};
+use chip8::{disassemble, Chip8, WindowGeometry};
+use pixels::{Pixels, SurfaceTexture};
+use winit::{
+    dpi::{LogicalSize, PhysicalPosition},
+    event_loop::ActiveEventLoop,
+    window::Window,
+};
+
+use crate::text::{draw_text, CHAR_WIDTH, LINE_HEIGHT};
+
+/// The number of characters that fit on a line of a debugger window
+const COLUMNS: usize = 30;
+/// The number of lines that fit in a debugger window
+const LINES: usize = 16;
+/// The number of window pixels per frame pixel
+const SCALE_FACTOR: u32 = 3;
+const TEXT_RGBA: [u8; 4] = [192, 192, 192, 255];
+const BACKGROUND_RGBA: [u8; 4] = [16, 16, 16, 255];
+
+/// A view of the machine state that can be opened in its own window
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Panel {
+    Registers,
+    Disassembly,
+    Memory,
+}
+
+impl FromStr for Panel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "registers" => Ok(Panel::Registers),
+            "disassembly" => Ok(Panel::Disassembly),
+            "memory" => Ok(Panel::Memory),
+            _ => bail!(
+                "unknown panel '{}' (expected registers, disassembly or memory)",
+                s
+            ),
+        }
+    }
+}
+
+impl Panel {
+    /// The name the panel is given on the command line and in the saved window layout
+    pub fn name(&self) -> &'static str {
+        match self {
+            Panel::Registers => "registers",
+            Panel::Disassembly => "disassembly",
+            Panel::Memory => "memory",
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            Panel::Registers => "CHIP-8 registers",
+            Panel::Disassembly => "CHIP-8 disassembly",
+            Panel::Memory => "CHIP-8 memory",
+        }
+    }
+
+    /// The text shown in the panel for the current state of `chip8`
+    fn lines(&self, chip8: &Chip8) -> Vec<String> {
+        match self {
+            Panel::Registers => {
+                let (depth, max_depth) = chip8.call_depth();
+                let mut lines = vec![
+                    format!("PC {:04X}  I {:04X}", chip8.pc(), chip8.index()),
+                    format!(
+                        "DT {:02X}  ST {:02X}  SP {}/{}",
+                        chip8.delay_timer(),
+                        chip8.sound_timer(),
+                        depth,
+                        max_depth
+                    ),
+                    String::new(),
+                ];
+                for (row, values) in chip8.registers().chunks(4).enumerate() {
+                    let cells: Vec<String> = values
+                        .iter()
+                        .enumerate()
+                        .map(|(i, value)| format!("V{:X} {:02X}", row * 4 + i, value))
+                        .collect();
+                    lines.push(cells.join("  "));
+                }
+                lines.push(String::new());
+                for addr in chip8.call_stack().iter().rev() {
+                    lines.push(format!("STACK {:04X}", addr));
+                }
+                lines
+            }
+            Panel::Disassembly => {
+                // start a few instructions back so the context leading up to PC is visible
+                let pc = chip8.pc() as usize;
+                let start = pc.saturating_sub(LINES / 4 * 2);
+                let memory = chip8.memory();
+                (start..memory.len().saturating_sub(1))
+                    .step_by(2)
+                    .take(LINES)
+                    .map(|addr| {
+                        let opcode = u16::from_be_bytes([memory[addr], memory[addr + 1]]);
+                        let marker = if addr == pc { ">" } else { " " };
+                        let mnemonic = disassemble(opcode).unwrap_or_default();
+                        format!("{}{:04X} {:04X} {}", marker, addr, opcode, mnemonic)
+                    })
+                    .collect()
+            }
+            Panel::Memory => {
+                // the memory around I, since that's what most instructions read and write
+                let start = (chip8.index() as usize & !0x7).saturating_sub(2 * 8);
+                let memory = chip8.memory();
+                (start..memory.len())
+                    .step_by(8)
+                    .take(LINES)
+                    .map(|addr| {
+                        let end = (addr + 8).min(memory.len());
+                        let bytes: Vec<String> = memory[addr..end]
+                            .iter()
+                            .map(|byte| format!("{:02X}", byte))
+                            .collect();
+                        format!("{:04X}: {}", addr, bytes.join(" "))
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// A panel open in a window of its own, so the game view stays unobstructed
+pub struct DebugWindow {
+    pub panel: Panel,
+    pub window: Arc<Window>,
+    pixels: Pixels<'static>,
+}
+
+impl DebugWindow {
+    /// Open `panel` in a new window, at the position in `geometry` if it's given
+    pub fn open(
+        event_loop: &ActiveEventLoop,
+        panel: Panel,
+        geometry: Option<WindowGeometry>,
+    ) -> anyhow::Result<Self> {
+        let (width, height) = (COLUMNS * CHAR_WIDTH + 2, LINES * LINE_HEIGHT + 2);
+        let mut attributes = Window::default_attributes()
+            .with_title(panel.title())
+            .with_inner_size(LogicalSize::new(
+                width as u32 * SCALE_FACTOR,
+                height as u32 * SCALE_FACTOR,
+            ))
+            .with_resizable(false);
+        if let Some(geometry) = geometry {
+            attributes = attributes.with_position(PhysicalPosition::new(geometry.x, geometry.y));
+        }
+        let window = Arc::new(
+            event_loop
+                .create_window(attributes)
+                .context("create debugger window")?,
+        );
+
+        let window_size = window.inner_size();
+        let surface_texture =
+            SurfaceTexture::new(window_size.width, window_size.height, window.clone());
+        let pixels = Pixels::new(width as u32, height as u32, surface_texture)
+            .context("create debugger pixels instance")?;
+        Ok(Self {
+            panel,
+            window,
+            pixels,
+        })
+    }
+
+    pub fn render(&mut self, chip8: &Chip8) -> anyhow::Result<()> {
+        let width = COLUMNS * CHAR_WIDTH + 2;
+        let frame = self.pixels.frame_mut();
+        for pixel in frame.chunks_exact_mut(4) {
+            pixel.copy_from_slice(&BACKGROUND_RGBA);
+        }
+        for (i, line) in self.panel.lines(chip8).iter().take(LINES).enumerate() {
+            draw_text(frame, width, (1, 1 + i * LINE_HEIGHT), 1, line, TEXT_RGBA);
+        }
+        self.pixels.render().context("render debugger window")
+    }
+}
@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use anyhow::{bail, Context};
+use winit::keyboard::KeyCode;
+
+/// The physical keys that can be bound to emulator actions
+const BINDABLE_KEYS: &[KeyCode] = &[
+    KeyCode::KeyA,
+    KeyCode::KeyB,
+    KeyCode::KeyC,
+    KeyCode::KeyD,
+    KeyCode::KeyE,
+    KeyCode::KeyF,
+    KeyCode::KeyG,
+    KeyCode::KeyH,
+    KeyCode::KeyI,
+    KeyCode::KeyJ,
+    KeyCode::KeyK,
+    KeyCode::KeyL,
+    KeyCode::KeyM,
+    KeyCode::KeyN,
+    KeyCode::KeyO,
+    KeyCode::KeyP,
+    KeyCode::KeyQ,
+    KeyCode::KeyR,
+    KeyCode::KeyS,
+    KeyCode::KeyT,
+    KeyCode::KeyU,
+    KeyCode::KeyV,
+    KeyCode::KeyW,
+    KeyCode::KeyX,
+    KeyCode::KeyY,
+    KeyCode::KeyZ,
+    KeyCode::Digit0,
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+    KeyCode::F1,
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+    KeyCode::F9,
+    KeyCode::F10,
+    KeyCode::F11,
+    KeyCode::F12,
+    KeyCode::Escape,
+    KeyCode::Tab,
+    KeyCode::Backspace,
+    KeyCode::Enter,
+    KeyCode::Space,
+    KeyCode::Backquote,
+    KeyCode::Minus,
+    KeyCode::Equal,
+    KeyCode::BracketLeft,
+    KeyCode::BracketRight,
+    KeyCode::Backslash,
+    KeyCode::Semicolon,
+    KeyCode::Quote,
+    KeyCode::Comma,
+    KeyCode::Period,
+    KeyCode::Slash,
+    KeyCode::Insert,
+    KeyCode::Delete,
+    KeyCode::Home,
+    KeyCode::End,
+    KeyCode::PageUp,
+    KeyCode::PageDown,
+    KeyCode::Pause,
+    KeyCode::ArrowUp,
+    KeyCode::ArrowDown,
+    KeyCode::ArrowLeft,
+    KeyCode::ArrowRight,
+];
+
+/// Parse a physical key name as used by winit (e.g. `KeyM`, `F9`), or a single letter or digit
+fn parse_key_code(name: &str) -> anyhow::Result<KeyCode> {
+    let name = match name.chars().collect::<Vec<_>>().as_slice() {
+        [c] if c.is_ascii_alphabetic() => format!("key{}", c),
+        [c] if c.is_ascii_digit() => format!("digit{}", c),
+        _ => name.to_string(),
+    };
+    BINDABLE_KEYS
+        .iter()
+        .copied()
+        .find(|code| format!("{:?}", code).eq_ignore_ascii_case(&name))
+        .with_context(|| format!("unknown key '{}'", name))
+}
+
+/// Something the emulator does in response to a hotkey, rather than passing the key to the program
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    ToggleMute,
+    ToggleAutofire,
+    PlayMacro,
+    SaveState,
+    LoadState,
+    /// Runs the game backwards for as long as the key is held
+    Rewind,
+    /// Zooms in around the mouse cursor
+    ToggleMagnifier,
+    /// Opens or closes the debugger windows
+    ToggleDebugger,
+    ToggleFullscreen,
+    SpeedUp,
+    SlowDown,
+    /// Pauses emulation so the input for each frame can be composed before advancing to it
+    ToggleFrameAdvance,
+    AdvanceFrame,
+}
+
+impl FromStr for Action {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "mute" => Ok(Action::ToggleMute),
+            "autofire" => Ok(Action::ToggleAutofire),
+            "macro" => Ok(Action::PlayMacro),
+            "save-state" => Ok(Action::SaveState),
+            "load-state" => Ok(Action::LoadState),
+            "rewind" => Ok(Action::Rewind),
+            "magnifier" => Ok(Action::ToggleMagnifier),
+            "debugger" => Ok(Action::ToggleDebugger),
+            "fullscreen" => Ok(Action::ToggleFullscreen),
+            "faster" => Ok(Action::SpeedUp),
+            "slower" => Ok(Action::SlowDown),
+            "frame-advance" => Ok(Action::ToggleFrameAdvance),
+            "advance" => Ok(Action::AdvanceFrame),
+            _ => bail!(
+                "unknown hotkey action '{}' (expected mute, autofire, macro, save-state, load-state, rewind, magnifier, debugger, fullscreen, faster, slower, frame-advance or advance)",
+                s
+            ),
+        }
+    }
+}
+
+/// Maps physical keys onto emulator actions
+/// Keys bound here are consumed before the keypad mapping is consulted, so they never reach the
+/// program
+pub struct Hotkeys {
+    bindings: HashMap<KeyCode, Action>,
+}
+
+impl Hotkeys {
+    /// A layer with no hotkeys, leaving every key to the program
+    pub fn none() -> Self {
+        Self {
+            bindings: HashMap::new(),
+        }
+    }
+
+    /// Bind `code` to `action`, replacing whichever key the action was bound to before
+    pub fn bind(&mut self, action: Action, code: KeyCode) {
+        self.bindings.retain(|_, bound| *bound != action);
+        self.bindings.insert(code, action);
+    }
+
+    pub fn action(&self, code: KeyCode) -> Option<Action> {
+        self.bindings.get(&code).copied()
+    }
+}
+
+impl Default for Hotkeys {
+    fn default() -> Self {
+        let mut hotkeys = Self::none();
+        hotkeys.bind(Action::ToggleMute, KeyCode::KeyM);
+        hotkeys.bind(Action::ToggleAutofire, KeyCode::KeyT);
+        hotkeys.bind(Action::PlayMacro, KeyCode::F9);
+        hotkeys.bind(Action::SaveState, KeyCode::F5);
+        hotkeys.bind(Action::LoadState, KeyCode::F7);
+        hotkeys.bind(Action::Rewind, KeyCode::Backspace);
+        hotkeys.bind(Action::ToggleMagnifier, KeyCode::KeyZ);
+        hotkeys.bind(Action::ToggleDebugger, KeyCode::F12);
+        hotkeys.bind(Action::ToggleFullscreen, KeyCode::F11);
+        hotkeys.bind(Action::SpeedUp, KeyCode::Equal);
+        hotkeys.bind(Action::SlowDown, KeyCode::Minus);
+        hotkeys.bind(Action::ToggleFrameAdvance, KeyCode::F6);
+        hotkeys.bind(Action::AdvanceFrame, KeyCode::F10);
+        hotkeys
+    }
+}
+
+/// A single `action=key` hotkey given on the command line
+#[derive(Clone, Debug)]
+pub struct HotkeyBinding {
+    pub action: Action,
+    pub code: KeyCode,
+}
+
+impl FromStr for HotkeyBinding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (action, key) = s.split_once('=').context("expected ACTION=KEY")?;
+        Ok(Self {
+            action: action.trim().parse()?,
+            code: parse_key_code(key.trim())?,
+        })
+    }
+}
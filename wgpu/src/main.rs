@@ -1,24 +1,75 @@
-use std::{path::PathBuf, sync::Arc, time};
+mod debugger;
+mod hotkeys;
+mod netplay;
+mod profile;
+mod reload;
+mod text;
 
-use anyhow::Context;
-use chip8::{Chip8, Key};
-use clap::{command, Parser};
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time,
+};
+
+use anyhow::{ensure, Context};
+use chip8::{
+    ensure_not_cartridge, is_octo_cartridge, AddressOverflowPolicy, Analysis, Chip8, Event,
+    FontSet, FsFlagStore, FsStateStore, HaltReason, InputMacro, InvalidOpcodePolicy, Key,
+    KeyWaitPolicy, Keymap, Layout, Magnifier, Metrics, MetricsServer, Movie, MoviePlayer,
+    OctoCartridge, OctoOptions, Palette, Player, ProgramImage, Quirks, RomDatabase, RomMenu,
+    RomProfile, Variant, Watch, WatchExporter, WavWriter, Waveform, WindowGeometry, WindowLayout,
+    FRAME_RATE, ROM_ADDR, SPLASH_ROM, XO_CHIP_MEM_SIZE,
+};
+use chipper_config::Settings;
+use clap::{parser::ValueSource, ArgMatches, CommandFactory, FromArgMatches, Parser};
+use debugger::{DebugWindow, Panel};
+use hotkeys::{Action, HotkeyBinding, Hotkeys};
+use netplay::Netplay;
 use pixels::{Pixels, SurfaceTexture};
-use rodio::{OutputStream, Sink};
+use reload::RomWatcher;
+use rodio::{
+    cpal::traits::{DeviceTrait, HostTrait},
+    OutputStream, OutputStreamHandle, Sink,
+};
+use text::draw_text;
 use winit::{
     application::ApplicationHandler,
-    dpi::LogicalSize,
+    dpi::{LogicalSize, PhysicalPosition, PhysicalSize},
     event::WindowEvent,
     event_loop::{self, EventLoop},
+    keyboard::{Key as LogicalKey, NamedKey, PhysicalKey},
     platform::{
+        modifier_supplement::KeyEventExtModifierSupplement,
         pump_events::{EventLoopExtPumpEvents, PumpStatus},
         scancode::PhysicalKeyExtScancode,
     },
-    window::Window,
+    window::{Fullscreen, Window},
 };
 
 const SCALE_FACTOR: u32 = 10;
+/// The number of pixels in the pixels frame per CHIP-8 pixel, so the grid and magnifier have room
+/// to draw
+const CELL_SIZE: usize = SCALE_FACTOR as usize;
 const FRAME_INTERVAL: time::Duration = time::Duration::new(0, 1_000_000_000u32 / 60);
+const SAMPLE_RATE: u32 = 44100;
+/// The colour of the border drawn around the screen while the sound timer is active
+const SOUND_INDICATOR_RGBA: [u8; 4] = [255, 64, 64, 255];
+/// The maximum number of frames of audio queued in the sink before new frames are dropped
+const MAX_QUEUED_AUDIO_FRAMES: usize = 3;
+/// How often the sink is checked for room while emulation is paced by the audio output
+const AUDIO_POLL_INTERVAL: time::Duration = time::Duration::from_millis(1);
+/// The name the window layout is saved under
+const LAYOUT_NAME: &str = "wgpu";
+/// How to keep a cartridge's settings once it's been exported from Octo as a ROM
+const CARTRIDGE_HINT: &str =
+    "run the .ch8 Octo exports with --octo-options pointing at the cartridge to keep its settings";
+/// How long messages stay in the overlay, in frames
+const OVERLAY_FRAMES: u32 = 120;
+/// The number of frames between each second rewound while the rewind hotkey is held, so rewinding
+/// runs at ten times normal speed
+const REWIND_STEP_FRAMES: u32 = 6;
 
 struct AppConfig {
     pub window: winit::window::WindowAttributes,
@@ -45,6 +96,68 @@ struct State {
     pub(crate) window: Arc<Window>,
     pub(crate) pixels: Pixels<'static>,
     pub(crate) sink: Sink,
+    /// The number of dirty frames that haven't been presented because of frame skipping
+    pub(crate) skipped_frames: usize,
+    /// Receives a copy of the generated audio while recording
+    pub(crate) wav: Option<WavWriter<BufWriter<File>>>,
+    /// Draw a border around the screen while the sound timer is active
+    pub(crate) sound_indicator: bool,
+    /// Convert every row on the next render rather than only the dirty ones
+    pub(crate) full_redraw: bool,
+    /// Maps key labels onto the keypad when a layout is chosen, otherwise scancodes are used
+    pub(crate) keymap: Option<Keymap>,
+    /// Maps key labels onto the second player's keypad, checked before the first player's mapping
+    pub(crate) p2_keymap: Keymap,
+    /// The input macro played with the macro hotkey
+    pub(crate) input_macro: Option<InputMacro>,
+    /// Emulator actions bound to physical keys, which take precedence over the keypad mapping
+    pub(crate) hotkeys: Hotkeys,
+    /// Where the savestate hotkeys save to and load from, if there's a ROM to key them by
+    pub(crate) states: Option<FsStateStore>,
+    /// Whether the rewind hotkey is held, which stops emulation while it steps back through time
+    pub(crate) rewinding: bool,
+    /// The number of frames the rewind hotkey has been held for
+    pub(crate) rewind_frames: u32,
+    /// The lockstep session with the other player, if netplay is active
+    pub(crate) netplay: Option<Netplay>,
+    /// The keys held locally during netplay, which are sent to the peer rather than applied directly
+    pub(crate) local_mask: u16,
+    /// Whether the title is showing the prompt to press a key
+    pub(crate) waiting_for_key: bool,
+    pub(crate) palette: Palette,
+    /// Draw lines between the pixels
+    pub(crate) pixel_grid: bool,
+    /// Follows the mouse cursor, and is only drawn while the magnifier is toggled on
+    pub(crate) magnifier: Magnifier,
+    pub(crate) magnifying: bool,
+    /// The debugger panels open in windows of their own
+    pub(crate) debug_windows: Vec<DebugWindow>,
+    /// Where the game window was before it went fullscreen, which is what gets saved while it's
+    /// fullscreen
+    pub(crate) windowed_geometry: Option<WindowGeometry>,
+    /// The message drawn over the top left of the screen, such as the speed after changing it
+    pub(crate) overlay: String,
+    /// The number of frames left until the overlay is hidden
+    pub(crate) overlay_frames: u32,
+    /// Whether emulation only advances a frame at a time, with keypad keys toggling the input
+    /// latched for the next frame rather than following the physical keys
+    pub(crate) frame_advance: bool,
+    /// The number of frames requested with the advance hotkey that haven't run yet
+    pub(crate) pending_frames: u32,
+    /// The movie the input of every frame is being recorded into
+    pub(crate) recording: Option<Movie>,
+    /// The movie being played back, which replaces the keypad input until it finishes
+    pub(crate) playback: Option<MoviePlayer>,
+    /// Writes the watched values out for streaming overlays to show
+    pub(crate) watches: Option<WatchExporter>,
+    /// What's been counted for the metrics endpoint
+    pub(crate) metrics: Metrics,
+    /// Answers scrapes of the metrics, if an address to serve them on was given
+    pub(crate) metrics_server: Option<MetricsServer>,
+    /// Watches the ROM file so it's reloaded when it changes, if hot reloading is on
+    pub(crate) rom_watcher: Option<RomWatcher>,
+    /// The menu running in place of a ROM until one is chosen from it
+    pub(crate) menu: Option<RomMenu>,
     _stream: OutputStream,
 }
 
@@ -62,22 +175,174 @@ impl App {
     }
 
     pub fn init(&mut self, event_loop: &event_loop::ActiveEventLoop) -> anyhow::Result<()> {
-        let mut chip8 = Chip8::new()
-            .context("construct new chip8 instance")?
-            .legacy_shift(self.config.args.legacy_shift)
-            .jump_add_offset(self.config.args.jump_add_offset)
-            .memory_increment_i(self.config.args.memory_increment_i)
-            .print_operations(self.config.args.print_operations)
-            .ops_per_cycle(self.config.args.ops_per_cycle);
-
-        if let Some(path) = self.config.args.load.to_owned() {
+        let mut chip8 = new_chip8(&self.config.args)?;
+
+        let mut keymap = self.config.args.keyboard_layout.map(Keymap::from_layout);
+        let mut rom_hash = None;
+        let mut menu = None;
+        let image = match (&self.config.args.load, &self.config.args.image) {
+            (Some(path), _) => {
+                let rom = std::fs::read(path).context("read rom file")?;
+                ensure_not_cartridge(&rom).context(CARTRIDGE_HINT)?;
+                warn_variant(&rom, &self.config.args);
+                Some(rom_image(&rom, &self.config.args))
+            }
+            (_, Some(path)) => Some(ProgramImage::from_manifest_file(path)?),
+            (None, None) => None,
+        };
+        if let Some(image) = image {
+            let rom_profile;
+            (chip8, rom_profile) = auto_configure(chip8, &image, &self.config.args)?;
+            chip8.load_image(&image).context("load rom")?;
+
+            let hash = image.hash();
+            if let Some(profile) =
+                profile::load_input_profile(&hash).context("load input profile")?
+            {
+                keymap = Some(profile);
+            } else if let Some(rom_profile) = rom_profile.filter(|p| !p.keys.is_empty()) {
+                // a saved input profile is the player's own choice, so the database's keys are
+                // only used without one
+                let keymap = keymap.get_or_insert_with(|| Keymap::from_layout(Layout::detect()));
+                rom_profile.bind_keys(keymap);
+            }
+            rom_hash = Some(hash);
+        } else if let Some(dir) = &self.config.args.menu {
+            let rom_menu = RomMenu::scan(dir).context("list roms for the menu")?;
+            if rom_menu.entries().is_empty() {
+                chip8
+                    .load_rom_at(ROM_ADDR, &SPLASH_ROM)
+                    .context("load splash rom")?;
+            } else {
+                chip8
+                    .load_rom_at(ROM_ADDR, &rom_menu.rom())
+                    .context("load menu rom")?;
+                menu = Some(rom_menu);
+            }
+        } else {
+            // rather than running through empty memory, show something that responds to the keypad
             chip8
-                .load_rom_from_file(path)
-                .context("load rom from file")?;
+                .load_rom_at(ROM_ADDR, &SPLASH_ROM)
+                .context("load splash rom")?;
+        }
+
+        let netplay = match (
+            &self.config.args.netplay_host,
+            &self.config.args.netplay_join,
+        ) {
+            (None, None) => None,
+            (host, join) => {
+                let hash = rom_hash
+                    .as_deref()
+                    .context("netplay requires a loaded rom")?;
+                let quirks = chip8.movie_quirks();
+                let (netplay, seed) = match (host, join) {
+                    (Some(addr), _) => {
+                        Netplay::host(addr, hash, &quirks, self.config.args.input_delay)?
+                    }
+                    (_, Some(addr)) => Netplay::join(addr, hash, &quirks)?,
+                    _ => unreachable!(),
+                };
+                chip8 = chip8.rng_seed(seed);
+                Some(netplay)
+            }
+        };
+
+        let mut recording = None;
+        let mut playback = None;
+        if let Some(path) = &self.config.args.play_movie {
+            let movie = Movie::load(path).context("load movie")?;
+            let hash = rom_hash
+                .as_deref()
+                .context("playing a movie requires a loaded rom")?;
+            movie.check_rom(hash)?;
+            chip8 = movie.configure(chip8);
+            playback = Some(MoviePlayer::new(movie));
+        } else if self.config.args.record_movie.is_some() {
+            let hash = rom_hash
+                .as_deref()
+                .context("recording a movie requires a loaded rom")?;
+            // the seed is stored in the movie, so playback generates the same random numbers
+            let seed = self.config.args.seed.unwrap_or_else(|| {
+                time::SystemTime::now()
+                    .duration_since(time::UNIX_EPOCH)
+                    .map_or(0, |time| time.as_nanos() as u64)
+            });
+            chip8 = chip8.rng_seed(seed);
+            recording = Some(Movie::new(hash, &chip8, seed));
+        }
+        // flags saved by an earlier run would make movies and netplay diverge, so they're only
+        // kept between plain runs
+        if recording.is_none() && playback.is_none() && netplay.is_none() {
+            if let Some(store) = rom_hash.as_deref().and_then(FsFlagStore::for_rom) {
+                chip8
+                    .set_flag_store(Box::new(store))
+                    .context("load rpl flags")?;
+            }
+        }
+
+        if !self.config.args.bind.is_empty() {
+            let keymap = keymap.get_or_insert_with(|| Keymap::from_layout(Layout::detect()));
+            for binding in &self.config.args.bind {
+                keymap.bind(&binding.label, binding.key);
+            }
+        }
+
+        let input_macro = match &self.config.args.input_macro {
+            Some(path) => {
+                let text = std::fs::read_to_string(path).context("read input macro")?;
+                Some(text.parse::<InputMacro>().context("parse input macro")?)
+            }
+            None => None,
+        };
+
+        let mut hotkeys = if self.config.args.no_hotkeys {
+            Hotkeys::none()
+        } else {
+            Hotkeys::default()
+        };
+        for binding in &self.config.args.hotkey {
+            hotkeys.bind(binding.action, binding.code);
+        }
+
+        let states = rom_hash
+            .as_deref()
+            .and_then(profile::state_dir)
+            .map(FsStateStore::new);
+
+        let mut p2_keymap = Keymap::empty();
+        for binding in &self.config.args.bind_p2 {
+            p2_keymap.bind(&binding.label, binding.key);
+        }
+
+        if self.config.args.save_input_profile {
+            let hash = rom_hash.context("saving an input profile requires a loaded rom")?;
+            let keymap = keymap
+                .as_ref()
+                .context("saving an input profile requires --keyboard-layout or --bind")?;
+            let path = profile::save_input_profile(&hash, keymap).context("save input profile")?;
+            println!("Saved input profile to {}", path.display());
         }
 
+        let layout = match WindowLayout::load(LAYOUT_NAME) {
+            Ok(layout) => layout.unwrap_or_default(),
+            Err(e) => {
+                eprintln!("load window layout failed: {:?}", e);
+                WindowLayout::default()
+            }
+        };
+
+        let mut attributes = self.config.window.to_owned();
+        if let Some(geometry) = layout.main {
+            attributes = attributes
+                .with_position(PhysicalPosition::new(geometry.x, geometry.y))
+                .with_inner_size(PhysicalSize::new(geometry.width, geometry.height));
+        }
+        if layout.fullscreen {
+            attributes = attributes.with_fullscreen(Some(Fullscreen::Borderless(None)));
+        }
         let window = event_loop
-            .create_window(self.config.window.to_owned())
+            .create_window(attributes)
             .context("create window")?;
         let window = Arc::new(window);
 
@@ -86,28 +351,115 @@ impl App {
             SurfaceTexture::new(window_size.width, window_size.height, window.clone());
 
         let pixels = Pixels::new(
-            chip8::SCREEN_WIDTH as u32,
-            chip8::SCREEN_HEIGHT as u32,
+            (chip8::SCREEN_WIDTH * CELL_SIZE) as u32,
+            (chip8::SCREEN_HEIGHT * CELL_SIZE) as u32,
             surface_texture,
         )
         .context("create pixels instance")?;
 
+        // the panels given on the command line replace the ones open when chipper last closed
+        let panels: Vec<(Panel, Option<WindowGeometry>)> =
+            if self.config.args.debug_window.is_empty() {
+                layout
+                    .panels
+                    .iter()
+                    .filter_map(|(name, geometry)| Some((name.parse().ok()?, *geometry)))
+                    .collect()
+            } else {
+                self.config
+                    .args
+                    .debug_window
+                    .iter()
+                    .map(|panel| (*panel, None))
+                    .collect()
+            };
+        let debug_windows = panels
+            .into_iter()
+            .map(|(panel, geometry)| DebugWindow::open(event_loop, panel, geometry))
+            .collect::<anyhow::Result<_>>()?;
+
         let (_stream, stream_handle) =
-            OutputStream::try_default().context("create default output stream")?;
+            open_output_stream(self.config.args.audio_device.as_deref())?;
         let sink = Sink::try_new(&stream_handle).context("create audio sink")?;
-        sink.append(rodio::source::SineWave::new(440.0));
-        sink.pause();
+
+        let wav = match &self.config.args.record_audio {
+            Some(path) => Some(WavWriter::create(path, SAMPLE_RATE).context("start recording")?),
+            None => None,
+        };
+
+        let watches = match &self.config.args.watch_output {
+            Some(path) => Some(
+                WatchExporter::new(self.config.args.watch.clone(), path.clone())
+                    .context("start exporting watches")?,
+            ),
+            None => None,
+        };
+
+        // a palette set in Octo is part of how the program is meant to look
+        let palette = match &self.config.args.octo_options {
+            Some(path) => load_octo_options(path)?.palette()?,
+            None => None,
+        }
+        .unwrap_or(self.config.args.palette);
+
+        let metrics_server = match &self.config.args.metrics {
+            Some(addr) => Some(MetricsServer::bind(addr).context("start serving metrics")?),
+            None => None,
+        };
+
+        let rom_watcher = match &self.config.args.load {
+            Some(path) if self.config.args.hot_reload => {
+                Some(RomWatcher::new(path).context("watch rom for changes")?)
+            }
+            _ => None,
+        };
 
         self.state = Some(State {
             chip8,
             window,
             pixels,
             sink,
+            skipped_frames: 0,
+            wav,
+            sound_indicator: self.config.args.sound_indicator,
+            // rendering only touches dirty rows, so the first frame has to fill in the rest
+            full_redraw: true,
+            keymap,
+            p2_keymap,
+            input_macro,
+            hotkeys,
+            states,
+            rewinding: false,
+            rewind_frames: 0,
+            netplay,
+            local_mask: 0,
+            waiting_for_key: false,
+            palette,
+            pixel_grid: self.config.args.pixel_grid,
+            magnifier: Magnifier::new(self.config.args.magnifier_zoom),
+            magnifying: false,
+            debug_windows,
+            windowed_geometry: layout.main,
+            overlay: String::new(),
+            overlay_frames: 0,
+            frame_advance: self.config.args.frame_advance,
+            pending_frames: 0,
+            recording,
+            playback,
+            watches,
+            metrics: Metrics::default(),
+            metrics_server,
+            rom_watcher,
+            menu,
             _stream,
         });
 
-        App::render(self.state.as_mut().unwrap());
-        self.state.as_ref().unwrap().window.request_redraw();
+        let state = self.state.as_mut().unwrap();
+        if state.frame_advance {
+            App::show_frame_advance(state);
+        }
+        App::render(state);
+        state.window.request_redraw();
 
         Ok(())
     }
@@ -124,14 +476,52 @@ impl ApplicationHandler for App {
     fn window_event(
         &mut self,
         event_loop: &event_loop::ActiveEventLoop,
-        _window_id: winit::window::WindowId,
+        window_id: winit::window::WindowId,
         event: winit::event::WindowEvent,
     ) {
+        if let Some(state) = self.state.as_mut() {
+            if let Some(index) = state
+                .debug_windows
+                .iter()
+                .position(|debug| debug.window.id() == window_id)
+            {
+                match event {
+                    // closing a debugger window leaves the game running
+                    WindowEvent::CloseRequested => {
+                        state.debug_windows.remove(index);
+                    }
+                    WindowEvent::RedrawRequested => {
+                        if let Err(e) = state.debug_windows[index].render(&state.chip8) {
+                            eprintln!("debugger render failed: {:?}", e);
+                        }
+                    }
+                    _ => (),
+                }
+                return;
+            }
+        }
+
         match event {
             WindowEvent::CloseRequested => {
+                if let Some(state) = self.state.as_ref() {
+                    if let Err(e) = App::window_layout(state).save(LAYOUT_NAME) {
+                        eprintln!("save window layout failed: {:?}", e);
+                    }
+                }
                 println!("Exiting...");
                 event_loop.exit();
             }
+            WindowEvent::Resized(size) => {
+                let Some(state) = self.state.as_mut() else {
+                    return;
+                };
+
+                if let Err(e) = state.pixels.resize_surface(size.width, size.height) {
+                    eprintln!("resize failed: {:?}", e);
+                }
+                state.full_redraw = true;
+                state.window.request_redraw();
+            }
             WindowEvent::RedrawRequested => {
                 let Some(state) = self.state.as_mut() else {
                     return;
@@ -140,28 +530,223 @@ impl ApplicationHandler for App {
                 state.window.pre_present_notify();
                 App::render(state);
             }
+            WindowEvent::CursorMoved { position, .. } => {
+                let Some(state) = self.state.as_mut() else {
+                    return;
+                };
+
+                let size = state.window.inner_size();
+                state.magnifier.set_focus(
+                    (position.x / size.width as f64 * chip8::SCREEN_WIDTH as f64) as f32,
+                    (position.y / size.height as f64 * chip8::SCREEN_HEIGHT as f64) as f32,
+                );
+                if state.magnifying {
+                    state.window.request_redraw();
+                }
+            }
             WindowEvent::KeyboardInput {
                 device_id: _,
                 event,
                 is_synthetic: _,
             } => {
-                if let Some(scancode) = event.physical_key.to_scancode() {
-                    let Some(state) = self.state.as_mut() else {
+                let Some(state) = self.state.as_mut() else {
+                    return;
+                };
+
+                let action = match event.physical_key {
+                    PhysicalKey::Code(code) => state.hotkeys.action(code),
+                    PhysicalKey::Unidentified(_) => None,
+                };
+                if let Some(action) = action {
+                    // anything that changes the state on one side only would desync the session,
+                    // and anything the movie doesn't capture would desync it from the recorded run
+                    let desyncs = matches!(
+                        action,
+                        Action::PlayMacro
+                            | Action::LoadState
+                            | Action::Rewind
+                            | Action::SpeedUp
+                            | Action::SlowDown
+                    );
+                    let movie = state.recording.is_some() || state.playback.is_some();
+                    if (state.netplay.is_some() || movie) && desyncs
+                        || state.netplay.is_some()
+                            && matches!(action, Action::ToggleFrameAdvance | Action::AdvanceFrame)
+                    {
                         return;
+                    }
+                    if action == Action::Rewind {
+                        state.rewinding = event.state.is_pressed();
+                        state.rewind_frames = 0;
+                        return;
+                    }
+                    if event.state.is_pressed() && !event.repeat {
+                        match action {
+                            Action::ToggleMute => state.chip8.toggle_mute(),
+                            Action::ToggleAutofire => {
+                                // toggle autofire for whichever keypad keys are held
+                                let held: Vec<u8> = state.chip8.held_keys().collect();
+                                for key in held {
+                                    state.chip8.toggle_autofire(key);
+                                }
+                            }
+                            Action::PlayMacro => {
+                                // restart the macro if it's already playing
+                                if let Some(input_macro) = &state.input_macro {
+                                    state.chip8.play_macro(input_macro.clone());
+                                }
+                            }
+                            Action::SaveState => {
+                                let slot = &self.config.args.state_slot;
+                                if let Some(states) = state.states.as_mut() {
+                                    match state.chip8.save_to(states, slot) {
+                                        Ok(()) => println!("Saved state to slot '{}'", slot),
+                                        Err(e) => eprintln!("save state failed: {:?}", e),
+                                    }
+                                }
+                            }
+                            Action::Rewind => {}
+                            Action::ToggleDebugger => {
+                                if !state.debug_windows.is_empty() {
+                                    state.debug_windows.clear();
+                                    return;
+                                }
+                                let mut panels = self.config.args.debug_window.clone();
+                                if panels.is_empty() {
+                                    panels =
+                                        vec![Panel::Registers, Panel::Disassembly, Panel::Memory];
+                                }
+                                for panel in panels {
+                                    match DebugWindow::open(event_loop, panel, None) {
+                                        Ok(debug) => state.debug_windows.push(debug),
+                                        Err(e) => eprintln!("open debugger failed: {:?}", e),
+                                    }
+                                }
+                            }
+                            Action::ToggleFullscreen => {
+                                if state.window.fullscreen().is_some() {
+                                    state.window.set_fullscreen(None);
+                                } else {
+                                    state.windowed_geometry = window_geometry(&state.window);
+                                    state
+                                        .window
+                                        .set_fullscreen(Some(Fullscreen::Borderless(None)));
+                                }
+                            }
+                            Action::SpeedUp | Action::SlowDown => {
+                                let steps = if action == Action::SpeedUp { 1 } else { -1 };
+                                state.chip8.adjust_speed(steps);
+                                let message = format!("{} IPS", state.chip8.ips());
+                                App::show_overlay(state, message);
+                            }
+                            Action::ToggleFrameAdvance => {
+                                state.frame_advance = !state.frame_advance;
+                                state.pending_frames = 0;
+                                if state.frame_advance {
+                                    App::show_frame_advance(state);
+                                } else {
+                                    // let go of whatever was composed and follow the keys again
+                                    state.chip8.latch_input(Player::One, 0);
+                                    state.chip8.latch_input(Player::Two, 0);
+                                    App::show_overlay(state, "RUNNING".to_string());
+                                }
+                            }
+                            Action::AdvanceFrame => {
+                                if state.frame_advance {
+                                    state.pending_frames += 1;
+                                }
+                            }
+                            Action::ToggleMagnifier => {
+                                state.magnifying = !state.magnifying;
+                                state.full_redraw = true;
+                                state.window.request_redraw();
+                            }
+                            Action::LoadState => {
+                                let slot = &self.config.args.state_slot;
+                                if let Some(states) = state.states.as_ref() {
+                                    match state.chip8.load_from(states, slot) {
+                                        Ok(()) => {
+                                            state.full_redraw = true;
+                                            state.window.request_redraw();
+                                        }
+                                        Err(e) => eprintln!("load state failed: {:?}", e),
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    return;
+                }
+
+                // the movie supplies the input while it plays
+                if state.playback.is_some() {
+                    if !event.repeat {
+                        state.metrics.dropped_inputs += 1;
+                    }
+                    return;
+                }
+
+                // try the physical key name first so e.g. the numeric keypad can be told apart
+                // from the digits on the main keyboard
+                let physical = match event.physical_key {
+                    PhysicalKey::Code(code) => Some(format!("{:?}", code)),
+                    PhysicalKey::Unidentified(_) => None,
+                };
+                let logical = key_label(event.key_without_modifiers());
+                let labels: Vec<&str> = physical
+                    .iter()
+                    .chain(logical.iter())
+                    .map(String::as_str)
+                    .collect();
+
+                let (player, key) = if labels.iter().any(|label| state.p2_keymap.is_bound(label)) {
+                    (Player::Two, state.p2_keymap.key_for_any(labels))
+                } else {
+                    let key = match &state.keymap {
+                        Some(keymap) => keymap.key_for_any(labels),
+                        None => match event.physical_key.to_scancode() {
+                            Some(scancode) => Key::from_scancode(scancode),
+                            None => return,
+                        },
                     };
+                    (Player::One, key)
+                };
 
-                    if event.state.is_pressed() {
-                        if event.repeat {
-                            return;
-                        }
-                        if let Err(e) = state.chip8.keydown(Key::from_scancode(scancode)) {
-                            eprintln!("keydown failed: {:?}", e);
+                if state.frame_advance {
+                    // keys toggle in the input for the next frame, so several can be composed
+                    // one at a time
+                    if let Some(key) = key.value() {
+                        if event.state.is_pressed() && !event.repeat {
+                            state.chip8.toggle_latched_key(player, key);
+                            App::show_frame_advance(state);
                         }
-                    } else {
-                        if let Err(e) = state.chip8.keyup(Key::from_scancode(scancode)) {
-                            eprintln!("keyup failed: {:?}", e);
+                    }
+                    return;
+                }
+
+                if state.netplay.is_some() {
+                    // whichever mapping the key came from, it's for the local player's keypad
+                    if let Some(key) = key.value() {
+                        if event.state.is_pressed() {
+                            state.local_mask |= 1 << key;
+                        } else {
+                            state.local_mask &= !(1 << key);
                         }
                     }
+                    return;
+                }
+
+                if event.state.is_pressed() {
+                    if event.repeat {
+                        return;
+                    }
+                    if let Err(e) = state.chip8.player_keydown(player, key) {
+                        eprintln!("keydown failed: {:?}", e);
+                    }
+                } else {
+                    if let Err(e) = state.chip8.player_keyup(player, key) {
+                        eprintln!("keyup failed: {:?}", e);
+                    }
                 }
             }
             _ => (),
@@ -170,19 +755,212 @@ impl ApplicationHandler for App {
 }
 
 impl App {
+    /// Show `message` in the overlay for a couple of seconds
+    pub fn show_overlay(state: &mut State, message: String) {
+        state.overlay = message;
+        state.overlay_frames = OVERLAY_FRAMES;
+        state.window.request_redraw();
+    }
+
+    /// Show the input composed for the next frame in the overlay
+    pub fn show_frame_advance(state: &mut State) {
+        let keys: Vec<String> = [Player::One, Player::Two]
+            .into_iter()
+            .map(|player| {
+                let mask = state.chip8.next_frame_input(player);
+                (0..0x10)
+                    .filter(|key| mask & (1 << key) != 0)
+                    .map(|key| format!("{:X}", key))
+                    .collect()
+            })
+            .collect();
+        let message = if keys[1].is_empty() {
+            format!("PAUSED [{}]", keys[0])
+        } else {
+            format!("PAUSED [{}] [{}]", keys[0], keys[1])
+        };
+        App::show_overlay(state, message);
+    }
+
+    /// Whether the next frame can run, which while advancing a frame at a time is only once the
+    /// advance hotkey has been pressed
+    pub fn frame_advance_ready(state: &mut State) -> bool {
+        if !state.frame_advance {
+            return true;
+        }
+        if state.pending_frames == 0 {
+            return false;
+        }
+        state.pending_frames -= 1;
+        true
+    }
+
+    /// Write the watched values out, if any are being exported
+    pub fn export_watches(state: &mut State) {
+        if let Some(watches) = state.watches.as_mut() {
+            if let Err(e) = watches.update(&state.chip8) {
+                eprintln!("exporting watches failed: {:?}", e);
+                state.watches = None;
+            }
+        }
+    }
+
+    /// Start the ROM at `path` in a fresh instance configured by `args` and show `message`,
+    /// keeping the keymap, palette and everything else outside the emulator
+    pub fn load_rom(
+        state: &mut State,
+        args: &Args,
+        path: &Path,
+        message: &str,
+    ) -> anyhow::Result<()> {
+        let rom = std::fs::read(path).context("read rom file")?;
+        ensure_not_cartridge(&rom).context(CARTRIDGE_HINT)?;
+        warn_variant(&rom, args);
+        let image = rom_image(&rom, args);
+        let (mut chip8, _) = auto_configure(new_chip8(args)?, &image, args)?;
+        chip8.load_image(&image).context("load rom")?;
+        if state.recording.is_none() && state.playback.is_none() && state.netplay.is_none() {
+            if let Some(store) = FsFlagStore::for_rom(&image.hash()) {
+                chip8
+                    .set_flag_store(Box::new(store))
+                    .context("load rpl flags")?;
+            }
+        }
+
+        state.chip8 = chip8;
+        state.states = profile::state_dir(&image.hash()).map(FsStateStore::new);
+        state.rewinding = false;
+        state.pending_frames = 0;
+        state.full_redraw = true;
+        if state.frame_advance {
+            App::show_frame_advance(state);
+        } else {
+            App::show_overlay(state, message.to_string());
+        }
+        Ok(())
+    }
+
+    /// Queue a frame of silence, which keeps the audio clock ticking while no frame is emulated
+    pub fn queue_silence(state: &mut State) {
+        let samples = vec![0.0; (SAMPLE_RATE / 60) as usize];
+        state
+            .sink
+            .append(rodio::buffer::SamplesBuffer::new(1, SAMPLE_RATE, samples));
+    }
+
+    /// Where the windows are now, to be put back on the next launch
+    pub fn window_layout(state: &State) -> WindowLayout {
+        let fullscreen = state.window.fullscreen().is_some();
+        WindowLayout {
+            main: if fullscreen {
+                state.windowed_geometry
+            } else {
+                window_geometry(&state.window)
+            },
+            fullscreen,
+            panels: state
+                .debug_windows
+                .iter()
+                .map(|debug| {
+                    (
+                        debug.panel.name().to_string(),
+                        window_geometry(&debug.window),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// Exchange input with the netplay peer and apply it, returning false if the frame has to wait
+    /// for the peer's input
+    pub fn netplay_frame(state: &mut State) -> bool {
+        let Some(netplay) = state.netplay.as_mut() else {
+            return true;
+        };
+        match netplay.advance(state.local_mask) {
+            Ok(Some((p1, p2))) => {
+                state.chip8.set_keypad_mask(Player::One, p1);
+                state.chip8.set_keypad_mask(Player::Two, p2);
+                true
+            }
+            Ok(None) => false,
+            Err(e) => {
+                eprintln!("netplay failed: {:?}", e);
+                state.netplay = None;
+                true
+            }
+        }
+    }
+
     pub fn render(state: &mut State) {
+        // Only the rows that changed since the last render need converting, the rest of the
+        // pixels frame still holds the previous contents. The magnifier moves with the cursor, so
+        // everything is converted while it's on.
+        let overlay = state.overlay_frames > 0;
+        let rows: Vec<usize> = if state.full_redraw || state.magnifying || overlay {
+            (0..state.chip8.screen_height()).collect()
+        } else {
+            state.chip8.dirty_rows().collect()
+        };
+        state.full_redraw = false;
         let fb = state.chip8.fb();
-        for (i, pixel) in state.pixels.frame_mut().chunks_exact_mut(4).enumerate() {
-            let x = i % chip8::SCREEN_WIDTH;
-            let y = i / chip8::SCREEN_WIDTH;
 
-            let rgba = if fb[y][x] == 1 {
-                [255, 255, 255, 255]
-            } else {
-                [0, 0, 0, 255]
-            };
+        // the frame stays the same size, so high resolution pixels are drawn half as big, and
+        // the pixels of the 64x64 screen half as tall like on the VIP
+        let (scale_x, scale_y) = fb.scale();
+        let (cell_x, cell_y) = (CELL_SIZE / scale_x, CELL_SIZE / scale_y);
+        let indicator = state.sound_indicator && state.chip8.is_sound_playing();
+        let magnifier = state.magnifying.then_some(state.magnifier);
+        // the CHIP-8 pixel drawn at a pixel of the frame
+        let source = |x: usize, y: usize| match magnifier {
+            Some(magnifier) => magnifier.source(
+                x as f32 / CELL_SIZE as f32,
+                y as f32 / CELL_SIZE as f32,
+                &fb,
+            ),
+            None => (x / cell_x, y / cell_y),
+        };
+        let palette = state.palette;
+        let colours = state.chip8.chip8x_colours().copied();
+        let frame = state.pixels.frame_mut();
+        let width = chip8::SCREEN_WIDTH * CELL_SIZE;
+        for y in rows.into_iter().flat_map(|y| y * cell_y..(y + 1) * cell_y) {
+            let dst = &mut frame[y * width * 4..(y + 1) * width * 4];
+            for (x, pixel) in dst.chunks_exact_mut(4).enumerate() {
+                let (src_x, src_y) = source(x, y);
+                // a grid line is drawn wherever the frame moves on to the next CHIP-8 pixel
+                let grid = state.pixel_grid
+                    && ((x > 0 && source(x - 1, y).0 != src_x)
+                        || (y > 0 && source(x, y - 1).1 != src_y));
+                let (cell_x, cell_y) = (x / CELL_SIZE, y / CELL_SIZE);
+                let border = cell_x == 0
+                    || cell_y == 0
+                    || cell_x == chip8::SCREEN_WIDTH - 1
+                    || cell_y == chip8::SCREEN_HEIGHT - 1;
+
+                let index = fb.get(src_x, src_y);
+                let rgba = if grid {
+                    palette.grid
+                } else if index != 0 {
+                    colours.map_or(palette.colour(index), |colours| {
+                        colours.foreground(src_x, src_y, &fb)
+                    })
+                } else if indicator && border {
+                    SOUND_INDICATOR_RGBA
+                } else {
+                    colours.map_or(palette.off, |colours| colours.background())
+                };
+
+                pixel.copy_from_slice(&rgba);
+            }
+        }
 
-            pixel.copy_from_slice(&rgba);
+        if overlay {
+            let scale = CELL_SIZE / 4;
+            let (x, y) = (CELL_SIZE, CELL_SIZE);
+            let shadow = (x + scale / 2, y + scale / 2);
+            draw_text(frame, width, shadow, scale, &state.overlay, palette.off);
+            draw_text(frame, width, (x, y), scale, &state.overlay, palette.on);
         }
 
         state.pixels.render().unwrap();
@@ -194,16 +972,218 @@ impl App {
 struct Args {
     #[arg(short, long, value_name = "PATH", help = "Load ROM into memory", value_hint = clap::ValueHint::FilePath)]
     load: Option<PathBuf>,
-    #[arg(long, help_heading = "Quirks", help = "Toggle shift operation modes")]
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with = "load",
+        help = "Load a program made of several segments from a manifest, with a 'load ADDR FILE' line per segment and an optional 'entry ADDR' line",
+        value_hint = clap::ValueHint::FilePath
+    )]
+    image: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Apply the quirks, speed and colours from the options JSON Octo exports along with a program, or from an Octo cartridge (.gif)",
+        value_hint = clap::ValueHint::FilePath
+    )]
+    octo_options: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Pick the quirks, speed and keys of known ROMs from the community CHIP-8 database's programs.json, or from 'HASH PRESET TICKRATE TITLE' lines keyed by SHA-1. Quirks and speeds given on the command line still win",
+        value_hint = clap::ValueHint::FilePath
+    )]
+    rom_database: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "DIR",
+        conflicts_with_all = ["load", "image"],
+        help = "Pick a ROM from DIR on the CHIP-8 screen, moving with 2 and 8 and choosing with 5",
+        value_hint = clap::ValueHint::DirPath
+    )]
+    menu: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "PRESET",
+        help_heading = "Quirks",
+        help = "Start from the quirks of an interpreter (vip, schip, xochip), which the flags for single quirks override"
+    )]
+    quirks: Option<Quirks>,
+    #[arg(
+        long,
+        help_heading = "Quirks",
+        action = clap::ArgAction::Set,
+        num_args = 0..=1,
+        require_equals = true,
+        default_value_t = false,
+        default_missing_value = "true",
+        help = "Toggle shift operation modes"
+    )]
     legacy_shift: bool,
-    #[arg(long, help_heading = "Quirks", help = "Toggle jump operation modes")]
+    #[arg(
+        long,
+        help_heading = "Quirks",
+        action = clap::ArgAction::Set,
+        num_args = 0..=1,
+        require_equals = true,
+        default_value_t = false,
+        default_missing_value = "true",
+        help = "Toggle jump operation modes"
+    )]
     jump_add_offset: bool,
     #[arg(
         long,
         help_heading = "Quirks",
+        action = clap::ArgAction::Set,
+        num_args = 0..=1,
+        require_equals = true,
+        default_value_t = false,
+        default_missing_value = "true",
         help = "Toggle memory read/write operation modes"
     )]
     memory_increment_i: bool,
+    #[arg(
+        long,
+        help_heading = "Quirks",
+        action = clap::ArgAction::Set,
+        num_args = 0..=1,
+        require_equals = true,
+        default_value_t = false,
+        default_missing_value = "true",
+        help = "End each frame at a draw, like the COSMAC VIP waiting for the vertical blank"
+    )]
+    display_wait: bool,
+    #[arg(
+        long,
+        help_heading = "Quirks",
+        action = clap::ArgAction::Set,
+        num_args = 0..=1,
+        require_equals = true,
+        default_value_t = false,
+        default_missing_value = "true",
+        help = "Wrap sprites around the edges of the screen rather than clipping them"
+    )]
+    wrap_sprites: bool,
+    #[arg(
+        long,
+        help_heading = "Quirks",
+        action = clap::ArgAction::Set,
+        num_args = 0..=1,
+        require_equals = true,
+        default_value_t = false,
+        default_missing_value = "true",
+        help = "Let FX29 use all of VX rather than its low nibble, pointing past the font for values above F"
+    )]
+    unmasked_font: bool,
+    #[arg(
+        long,
+        value_name = "LAYOUT",
+        help = "Map keys by their labels in this layout (qwerty, azerty, qwertz, dvorak, colemak) or use the numeric keypad (numpad) instead of mapping by position"
+    )]
+    keyboard_layout: Option<Layout>,
+    #[arg(
+        long,
+        value_name = "LABEL=KEY",
+        help = "Bind the key with LABEL (e.g. up, space, q) to a CHIP-8 key, can be repeated"
+    )]
+    bind: Vec<profile::Binding>,
+    #[arg(
+        long,
+        value_name = "LABEL=KEY",
+        help = "Bind the key with LABEL to a key on the second player's keypad, can be repeated"
+    )]
+    bind_p2: Vec<profile::Binding>,
+    #[arg(
+        long,
+        help = "Save the key mapping as the input profile for the loaded ROM, which is applied automatically whenever it's loaded"
+    )]
+    save_input_profile: bool,
+    #[arg(
+        long,
+        default_value = "10",
+        value_name = "PRESSES",
+        help = "The presses per second of autofire keys, hold keys and press the autofire hotkey (T by default) to toggle autofire on them"
+    )]
+    autofire_rate: u32,
+    #[arg(
+        long,
+        default_value = "lowest",
+        value_name = "POLICY",
+        help = "How FX0A picks between several held keys (lowest, most-recent or first-released)"
+    )]
+    key_wait_policy: KeyWaitPolicy,
+    #[arg(
+        long,
+        default_value = "error",
+        value_name = "POLICY",
+        help = "What happens on an opcode that isn't an instruction (error, halt or skip)"
+    )]
+    invalid_opcode_policy: InvalidOpcodePolicy,
+    #[arg(
+        long,
+        default_value = "error",
+        value_name = "POLICY",
+        help = "What happens when the program counter or I runs past the end of memory (error, halt, wrap or mask to 12 bits)"
+    )]
+    address_overflow_policy: AddressOverflowPolicy,
+    #[arg(
+        long,
+        default_value = "300",
+        value_name = "FRAMES",
+        help = "How long FX0A waits without any key activity before prompting to press a key, 0 never prompts"
+    )]
+    key_wait_timeout: u32,
+    #[arg(
+        long = "macro",
+        value_name = "PATH",
+        help = "Load an input macro (e.g. \"press 5 for 3 frames, wait 10, press A\") to play with the macro hotkey (F9 by default)"
+    )]
+    input_macro: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "ACTION=KEY",
+        help = "Bind an emulator action (mute, autofire, macro, save-state, load-state, rewind, magnifier, debugger, fullscreen, faster, slower, frame-advance or advance) to a physical key (e.g. KeyM, F9), can be repeated"
+    )]
+    hotkey: Vec<HotkeyBinding>,
+    #[arg(
+        long,
+        default_value = "quick",
+        value_name = "SLOT",
+        help = "The savestate slot used by the save-state and load-state hotkeys (F5 and F7 by default)"
+    )]
+    state_slot: String,
+    #[arg(
+        long,
+        default_value = "60",
+        value_name = "SECONDS",
+        help = "How far back the rewind hotkey (Backspace by default) can go, 0 disables rewinding"
+    )]
+    rewind_seconds: usize,
+    #[arg(
+        long,
+        value_name = "ADDR",
+        conflicts_with = "netplay_join",
+        help = "Host a two-player netplay session on ADDR (e.g. 0.0.0.0:7070) as player one, both players need the same rom and options"
+    )]
+    netplay_host: Option<String>,
+    #[arg(
+        long,
+        value_name = "ADDR",
+        help = "Join the netplay session hosted on ADDR as player two"
+    )]
+    netplay_join: Option<String>,
+    #[arg(
+        long,
+        default_value = "2",
+        value_name = "FRAMES",
+        help = "The number of frames local input is delayed by during netplay, higher values hide more latency"
+    )]
+    input_delay: u8,
+    #[arg(
+        long,
+        help = "Disable the default hotkeys (M mutes, T toggles autofire, F9 plays the macro, F5 and F7 save and load state, Backspace rewinds, Z toggles the magnifier, F12 toggles the debugger, F11 toggles fullscreen, = and - change the speed, F6 toggles frame advance and F10 advances a frame) so every key reaches the program"
+    )]
+    no_hotkeys: bool,
     #[arg(long, help = "Toggle logging executed operations to stdout")]
     print_operations: bool,
     #[arg(
@@ -213,19 +1193,610 @@ struct Args {
         help = "The number of operations to be performed every cycle"
     )]
     ops_per_cycle: usize,
+    #[arg(
+        long,
+        value_name = "IPS",
+        conflicts_with = "ops_per_cycle",
+        help = "The number of instructions run per second, which needn't be a multiple of the frame rate (e.g. 720, or 1800000 for turbo). The = and - keys adjust it while running"
+    )]
+    ips: Option<usize>,
+    #[arg(
+        long,
+        conflicts_with_all = ["ops_per_cycle", "ips"],
+        help = "Give each instruction the time it took on the COSMAC VIP, running as many each frame as the VIP would instead of a fixed number"
+    )]
+    vip_timing: bool,
+    #[arg(
+        long,
+        help = "Start paused, advancing a frame at a time with F10 and toggling the keys held for the next frame with the keypad keys. F6 switches between this and running normally"
+    )]
+    frame_advance: bool,
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["play_movie", "netplay_host", "netplay_join"],
+        help = "Record the input of every frame to a .c8m movie file, saved on exit. Rewinding, loading states and changing the speed are disabled while recording",
+        value_hint = clap::ValueHint::FilePath
+    )]
+    record_movie: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["netplay_host", "netplay_join"],
+        help = "Play back a .c8m movie recorded with the loaded rom, using the quirks and seed it was recorded with",
+        value_hint = clap::ValueHint::FilePath
+    )]
+    play_movie: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "SEED",
+        conflicts_with_all = ["play_movie", "netplay_host", "netplay_join"],
+        help = "Seed the random numbers CXNN generates, so every run with the same input plays out the same way"
+    )]
+    seed: Option<u64>,
+    #[arg(
+        long = "watch",
+        visible_alias = "hot-reload",
+        requires = "load",
+        conflicts_with_all = ["record_movie", "play_movie", "netplay_host", "netplay_join"],
+        help = "Reload and restart the ROM whenever its file changes, such as when an assembler rebuilds it"
+    )]
+    hot_reload: bool,
+    #[arg(
+        long,
+        value_name = "ADDR",
+        help = "Serve Prometheus metrics at /metrics on ADDR (e.g. 127.0.0.1:9184), such as the frames emulated, halts and dropped inputs"
+    )]
+    metrics: Option<String>,
+    #[arg(
+        long = "watch-value",
+        value_name = "NAME=EXPR",
+        requires = "watch_output",
+        help_heading = "Streaming",
+        help = "A value to export, where EXPR is a hex address with an optional byte count (e.g. score=0x3f0:2, read big-endian), a register (e.g. lives=V5), I, PC, DT or ST"
+    )]
+    watch: Vec<Watch>,
+    #[arg(
+        long,
+        value_name = "PATH",
+        requires = "watch",
+        help_heading = "Streaming",
+        help = "The file the watched values are kept up to date in, as JSON if it ends in .json and otherwise a 'name: value' line each",
+        value_hint = clap::ValueHint::FilePath
+    )]
+    watch_output: Option<PathBuf>,
+    #[arg(
+        long,
+        default_value = "4096",
+        value_name = "BYTES",
+        help = "The size of memory in bytes, up to 65536 for XO-CHIP programs"
+    )]
+    memory_size: usize,
+    #[arg(
+        long,
+        default_value = "16",
+        value_name = "CALLS",
+        help = "How deep subroutine calls can nest before the program halts, 12 on the COSMAC VIP"
+    )]
+    stack_depth: usize,
+    #[arg(
+        long,
+        help = "Halt on writes below 0x200 instead of silently corrupting the font"
+    )]
+    write_protect: bool,
+    #[arg(
+        long,
+        help = "Halt once the program settles into a loop it can never leave, such as a jump to itself"
+    )]
+    detect_idle_loops: bool,
+    #[arg(
+        long,
+        help = "Run CHIP-8X programs, drawn in the colours they set rather than the palette"
+    )]
+    chip8x: bool,
+    #[arg(
+        long,
+        help = "Run programs for the two-page hires CHIP-8 interpreter, which start with a jump to 0x260 and draw on a 64x64 screen"
+    )]
+    two_page_hires: bool,
+    #[arg(
+        long,
+        default_value = "50",
+        value_name = "ADDR",
+        value_parser = parse_hex_addr,
+        help = "The hex address the font is stored at, some interpreters stored it at 0"
+    )]
+    font_addr: usize,
+    #[arg(
+        long,
+        default_value = "schip",
+        value_name = "SET",
+        help = "Use the font of a historical interpreter: vip, dream6800, eti660 or schip"
+    )]
+    font_set: FontSet,
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with = "font_set",
+        help = "Replace the built-in font with the 80 bytes of glyphs in PATH, such as a historical interpreter's",
+        value_hint = clap::ValueHint::FilePath
+    )]
+    font: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Replace the built-in big font with the 160 bytes of glyphs in PATH",
+        value_hint = clap::ValueHint::FilePath
+    )]
+    big_font: Option<PathBuf>,
+    #[arg(
+        long,
+        default_value = "200",
+        value_name = "ADDR",
+        value_parser = parse_hex_addr,
+        help = "The hex address ROMs are loaded and started at, 600 for ETI-660 programs"
+    )]
+    rom_addr: usize,
+    #[arg(
+        long,
+        value_name = "ADDR",
+        value_parser = parse_hex_addr,
+        help = "The hex address ROMs start running at, when it isn't the one they're loaded at"
+    )]
+    start_pc: Option<usize>,
+    #[arg(
+        long,
+        default_value = "0",
+        value_name = "FRAMES",
+        help = "The number of dirty frames to skip between presented frames"
+    )]
+    frame_skip: usize,
+    #[arg(
+        long,
+        default_value = "440",
+        value_name = "HZ",
+        help_heading = "Audio",
+        help = "The frequency of the buzzer tone"
+    )]
+    buzzer_frequency: f32,
+    #[arg(
+        long,
+        default_value = "square",
+        value_name = "WAVEFORM",
+        help_heading = "Audio",
+        help = "The shape of the buzzer tone: square, triangle or sine"
+    )]
+    buzzer_waveform: Waveform,
+    #[arg(
+        long,
+        default_value = "5",
+        value_name = "MS",
+        help_heading = "Audio",
+        help = "The length of the fade in/out applied when the buzzer starts and stops"
+    )]
+    buzzer_envelope_ms: f32,
+    #[arg(
+        long,
+        default_value = "1.0",
+        value_name = "VOLUME",
+        help_heading = "Audio",
+        help = "The output volume between 0.0 and 1.0, press M to toggle mute"
+    )]
+    volume: f32,
+    #[arg(
+        long,
+        value_name = "PATH",
+        help_heading = "Audio",
+        help = "Record the generated audio to a WAV file",
+        value_hint = clap::ValueHint::FilePath
+    )]
+    record_audio: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "NAME",
+        help_heading = "Audio",
+        help = "The name of the audio output device to use instead of the default"
+    )]
+    audio_device: Option<String>,
+    #[arg(
+        long,
+        help_heading = "Audio",
+        help = "List the available audio output devices and exit"
+    )]
+    list_audio_devices: bool,
+    #[arg(
+        long,
+        help_heading = "Audio",
+        help = "Pace emulation by the audio output consuming samples rather than a timer, so the timers and buzzer never drift from the sound"
+    )]
+    audio_sync: bool,
+    #[arg(
+        long,
+        help_heading = "Accessibility",
+        help = "Flash a border around the screen while sound is playing"
+    )]
+    sound_indicator: bool,
+    #[arg(
+        long,
+        default_value = "classic",
+        value_name = "PALETTE",
+        help_heading = "Accessibility",
+        help = "The screen colours: classic, inverted, yellow, blue, or ON:OFF hex colours such as ffff00:000000, optionally followed by :SECOND:BOTH colours for XO-CHIP's second plane"
+    )]
+    palette: Palette,
+    #[arg(
+        long,
+        help_heading = "Accessibility",
+        help = "Draw lines between the pixels"
+    )]
+    pixel_grid: bool,
+    #[arg(
+        long,
+        default_value = "3",
+        value_name = "ZOOM",
+        help_heading = "Accessibility",
+        help = "How far the magnifier zooms in around the mouse cursor, press Z to toggle it"
+    )]
+    magnifier_zoom: f32,
+    #[arg(
+        long,
+        value_name = "PANEL",
+        help_heading = "Debugging",
+        help = "Open a debugger panel in its own window: registers, disassembly or memory. Can be given more than once, and F12 toggles them"
+    )]
+    debug_window: Vec<Panel>,
+    /// The ids of the options given on the command line, which win over the ROM database
+    #[arg(skip)]
+    given: Vec<String>,
+}
+
+/// Create an instance with the quirks, speed and audio options in `args`
+fn new_chip8(args: &Args) -> anyhow::Result<Chip8> {
+    let mut chip8 = Chip8::new()
+        .context("construct new chip8 instance")?
+        .legacy_shift(args.legacy_shift)
+        .jump_add_offset(args.jump_add_offset)
+        .memory_increment_i(args.memory_increment_i)
+        .display_wait(args.display_wait)
+        .wrap_sprites(args.wrap_sprites)
+        .unmasked_font(args.unmasked_font)
+        .print_operations(args.print_operations)
+        .clock_hz((args.ops_per_cycle * FRAME_RATE) as u32)
+        .vip_timing(args.vip_timing)
+        .memory_size(args.memory_size)
+        .stack_depth(args.stack_depth)
+        .write_protect(args.write_protect)
+        .detect_idle_loops(args.detect_idle_loops)
+        .chip8x(args.chip8x)
+        .two_page_hires(args.two_page_hires)
+        // the rom address goes first, as the font is kept below it
+        .rom_address(args.rom_addr)
+        .font_address(args.font_addr)
+        .font_set(args.font_set)
+        .buzzer_frequency(args.buzzer_frequency)
+        .buzzer_waveform(args.buzzer_waveform)
+        .buzzer_envelope_ms(args.buzzer_envelope_ms)
+        .volume(args.volume)
+        .autofire_rate(args.autofire_rate)
+        .key_wait_policy(args.key_wait_policy)
+        .invalid_opcode_policy(args.invalid_opcode_policy)
+        .address_overflow_policy(args.address_overflow_policy)
+        .key_wait_timeout(args.key_wait_timeout)
+        .rewind_seconds(args.rewind_seconds);
+    if let Some(ips) = args.ips {
+        chip8.set_instructions_per_second(ips);
+    }
+    if let Some(seed) = args.seed {
+        chip8 = chip8.rng_seed(seed);
+    }
+    if let Some(path) = &args.font {
+        chip8.set_font(&read_font(path)?).context("set font")?;
+    }
+    if let Some(path) = &args.big_font {
+        chip8
+            .set_big_font(&read_font(path)?)
+            .context("set big font")?;
+    }
+    if let Some(path) = &args.octo_options {
+        chip8 = load_octo_options(path)?.configure(chip8);
+    }
+    Ok(chip8)
+}
+
+/// Lay out `rom` at the ROM address, starting at the start address if one was given
+fn rom_image(rom: &[u8], args: &Args) -> ProgramImage {
+    let mut image = ProgramImage::from_rom_at(rom, args.rom_addr as u16);
+    if let Some(pc) = args.start_pc {
+        image.entry = pc as u16;
+    }
+    image
+}
+
+/// Read a font file, which has to be exactly the size of the font it replaces
+fn read_font<const N: usize>(path: &Path) -> anyhow::Result<[u8; N]> {
+    let data = std::fs::read(path).context("read font file")?;
+    ensure!(
+        data.len() == N,
+        "font file is {} bytes, expected {}",
+        data.len(),
+        N
+    );
+    Ok(data.try_into().expect("font file length was checked"))
+}
+
+/// Warn when `rom` uses the instructions of a later variant than the quirks and memory are set up
+/// for, suggesting the options it probably needs
+fn warn_variant(rom: &[u8], args: &Args) {
+    let variant = Analysis::new(rom).variant;
+    let preset = match variant {
+        Variant::Chip8 => return,
+        Variant::Schip => "schip",
+        Variant::XoChip => "xochip",
+    };
+    let mut suggestions = Vec::new();
+    if args.quirks != Some(variant.quirks()) {
+        suggestions.push(format!("--quirks {}", preset));
+    }
+    if variant == Variant::XoChip && args.memory_size < XO_CHIP_MEM_SIZE {
+        suggestions.push(format!("--memory-size {}", XO_CHIP_MEM_SIZE));
+    }
+    if !suggestions.is_empty() {
+        eprintln!(
+            "warning: this looks like a {} program, which probably needs {}",
+            variant,
+            suggestions.join(" ")
+        );
+    }
+}
+
+/// Apply the profile `image` has in the ROM database, if a database was given and knows it,
+/// returning the profile too for its keys. Quirks and speeds given on the command line still win
+/// over the profile's.
+fn auto_configure(
+    chip8: Chip8,
+    image: &ProgramImage,
+    args: &Args,
+) -> anyhow::Result<(Chip8, Option<RomProfile>)> {
+    let Some(path) = &args.rom_database else {
+        return Ok((chip8, None));
+    };
+    let database = RomDatabase::load(path)?;
+    let Some(profile) = database.get(&image.hash()) else {
+        return Ok((chip8, None));
+    };
+    eprintln!("recognised {}", profile.title);
+    let mut chip8 = profile.configure(chip8);
+
+    let given = |id: &str| args.given.iter().any(|given| given == id);
+    // a preset sets every quirk, so it counts as giving each of them
+    let quirk_given = |id: &str| given(id) || given("quirks");
+    if quirk_given("legacy_shift") {
+        chip8 = chip8.legacy_shift(args.legacy_shift);
+    }
+    if quirk_given("jump_add_offset") {
+        chip8 = chip8.jump_add_offset(args.jump_add_offset);
+    }
+    if quirk_given("memory_increment_i") {
+        chip8 = chip8.memory_increment_i(args.memory_increment_i);
+    }
+    if quirk_given("display_wait") {
+        chip8 = chip8.display_wait(args.display_wait);
+    }
+    if quirk_given("wrap_sprites") {
+        chip8 = chip8.wrap_sprites(args.wrap_sprites);
+    }
+    if quirk_given("unmasked_font") {
+        chip8 = chip8.unmasked_font(args.unmasked_font);
+    }
+    if given("ops_per_cycle") {
+        chip8 = chip8.clock_hz((args.ops_per_cycle * FRAME_RATE) as u32);
+    }
+    if let (true, Some(ips)) = (given("ips"), args.ips) {
+        chip8.set_instructions_per_second(ips);
+    }
+    Ok((chip8, Some(profile.clone())))
+}
+
+/// The options in a JSON file Octo exported, or the ones embedded in an Octo cartridge
+fn load_octo_options(path: &Path) -> anyhow::Result<OctoOptions> {
+    let bytes = std::fs::read(path).context("read octo options")?;
+    if is_octo_cartridge(&bytes) {
+        return Ok(OctoCartridge::decode(&bytes)?.options);
+    }
+    let text = String::from_utf8(bytes).context("octo options aren't text")?;
+    OctoOptions::parse(&text)
+}
+
+/// The position and size of `window`, or `None` if the platform can't tell where it is
+fn window_geometry(window: &Window) -> Option<WindowGeometry> {
+    let position = window.outer_position().ok()?;
+    let size = window.inner_size();
+    Some(WindowGeometry {
+        x: position.x,
+        y: position.y,
+        width: size.width,
+        height: size.height,
+    })
+}
+
+impl Args {
+    /// Take each option that wasn't given on the command line from the shared settings, so the
+    /// settings file and the CHIPPER_ environment variables set the defaults
+    fn merge_settings(&mut self, settings: &Settings, matches: &ArgMatches) {
+        fn merge<T>(value: &mut T, setting: T, given: bool) {
+            if !given {
+                *value = setting;
+            }
+        }
+        let given = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+
+        // a preset replaces the quirks from the settings, and a single quirk given on its own,
+        // e.g. --display-wait=false, overrides both
+        let quirks = self.quirks.unwrap_or(settings.quirks);
+        merge(
+            &mut self.legacy_shift,
+            quirks.legacy_shift,
+            given("legacy_shift"),
+        );
+        merge(
+            &mut self.jump_add_offset,
+            quirks.jump_add_offset,
+            given("jump_add_offset"),
+        );
+        merge(
+            &mut self.memory_increment_i,
+            quirks.memory_increment_i,
+            given("memory_increment_i"),
+        );
+        merge(
+            &mut self.display_wait,
+            quirks.display_wait,
+            given("display_wait"),
+        );
+        merge(
+            &mut self.wrap_sprites,
+            quirks.wrap_sprites,
+            given("wrap_sprites"),
+        );
+        merge(
+            &mut self.unmasked_font,
+            quirks.unmasked_font,
+            given("unmasked_font"),
+        );
+        // the speed given on the command line wins whichever way it was given
+        let speed_given = given("ops_per_cycle") || given("ips");
+        merge(&mut self.ops_per_cycle, settings.ops_per_cycle, speed_given);
+        merge(&mut self.ips, settings.ips, speed_given);
+        merge(&mut self.font_set, settings.font_set, given("font_set"));
+        merge(&mut self.palette, settings.palette, given("palette"));
+        merge(
+            &mut self.pixel_grid,
+            settings.pixel_grid,
+            given("pixel_grid"),
+        );
+        merge(
+            &mut self.keyboard_layout,
+            settings.keyboard_layout,
+            given("keyboard_layout"),
+        );
+
+        let audio = &settings.audio;
+        merge(&mut self.volume, audio.volume, given("volume"));
+        merge(
+            &mut self.buzzer_frequency,
+            audio.buzzer_frequency,
+            given("buzzer_frequency"),
+        );
+        merge(
+            &mut self.buzzer_waveform,
+            audio.buzzer_waveform,
+            given("buzzer_waveform"),
+        );
+        merge(
+            &mut self.buzzer_envelope_ms,
+            audio.buzzer_envelope_ms,
+            given("buzzer_envelope_ms"),
+        );
+        merge(&mut self.audio_sync, audio.sync, given("audio_sync"));
+
+        // the menu only replaces the splash screen, so a ROM given on the command line still wins
+        if !given("load") && !given("image") {
+            merge(
+                &mut self.menu,
+                settings.paths.roms_dir.clone(),
+                given("menu"),
+            );
+        }
+    }
+}
+
+/// The settings file, with any setting overridden by its CHIPPER_ environment variable
+fn load_settings() -> anyhow::Result<Settings> {
+    let mut settings = Settings::load()?;
+    settings.apply_env()?;
+    Ok(settings)
+}
+
+/// Parse a hex address, with or without a `0x` prefix
+fn parse_hex_addr(s: &str) -> anyhow::Result<usize> {
+    let digits = s.strip_prefix("0x").unwrap_or(s);
+    usize::from_str_radix(digits, 16).with_context(|| format!("invalid address '{}'", s))
+}
+
+/// Convert a logical key into the label used by keymaps
+fn key_label(key: LogicalKey) -> Option<String> {
+    match key {
+        LogicalKey::Character(s) => Some(s.to_lowercase()),
+        LogicalKey::Named(NamedKey::Enter) => Some("enter".to_string()),
+        LogicalKey::Named(NamedKey::Space) => Some("space".to_string()),
+        LogicalKey::Named(NamedKey::ArrowUp) => Some("up".to_string()),
+        LogicalKey::Named(NamedKey::ArrowDown) => Some("down".to_string()),
+        LogicalKey::Named(NamedKey::ArrowLeft) => Some("left".to_string()),
+        LogicalKey::Named(NamedKey::ArrowRight) => Some("right".to_string()),
+        _ => None,
+    }
+}
+
+/// Open an output stream on the device called `name`, or on the default device if none is given
+fn open_output_stream(name: Option<&str>) -> anyhow::Result<(OutputStream, OutputStreamHandle)> {
+    let Some(name) = name else {
+        return OutputStream::try_default().context("create default output stream");
+    };
+
+    let device = rodio::cpal::default_host()
+        .output_devices()
+        .context("enumerate audio output devices")?
+        .find(|device| device.name().is_ok_and(|n| n == name))
+        .with_context(|| format!("audio output device '{}' not found", name))?;
+    OutputStream::try_from_device(&device).context("create output stream")
+}
+
+fn list_audio_devices() -> anyhow::Result<()> {
+    let host = rodio::cpal::default_host();
+    let default = host.default_output_device().and_then(|d| d.name().ok());
+    for device in host
+        .output_devices()
+        .context("enumerate audio output devices")?
+    {
+        let name = device.name().context("read audio output device name")?;
+        if default.as_ref() == Some(&name) {
+            println!("{} (default)", name);
+        } else {
+            println!("{}", name);
+        }
+    }
+    Ok(())
 }
 
 fn main() -> std::process::ExitCode {
     env_logger::init();
 
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    args.given = matches
+        .ids()
+        .filter(|id| matches.value_source(id.as_str()) == Some(ValueSource::CommandLine))
+        .map(|id| id.to_string())
+        .collect();
+    match load_settings() {
+        Ok(settings) => args.merge_settings(&settings, &matches),
+        Err(e) => eprintln!("loading settings failed: {:?}", e),
+    }
+    if args.list_audio_devices {
+        if let Err(e) = list_audio_devices() {
+            eprintln!("listing audio devices failed: {:?}", e);
+            return std::process::ExitCode::FAILURE;
+        }
+        return std::process::ExitCode::SUCCESS;
+    }
+
     let mut event_loop = EventLoop::new().unwrap();
 
-    let args = Args::parse();
     let config = AppConfig::new(args);
 
     let mut app = App::new(config);
 
-    loop {
+    let exit_code = loop {
         let timeout = Some(time::Duration::ZERO);
         let status = event_loop.pump_app_events(timeout, &mut app);
 
@@ -234,17 +1805,210 @@ fn main() -> std::process::ExitCode {
         }
 
         if let Some(state) = app.state.as_mut() {
-            state.chip8.cycle();
+            if let Some(path) = state
+                .rom_watcher
+                .as_mut()
+                .and_then(|watcher| watcher.changed().then(|| watcher.path().to_path_buf()))
+            {
+                // a broken build leaves the last one running, so the next save can fix it
+                if let Err(e) = App::load_rom(state, &app.config.args, &path, "RELOADED") {
+                    eprintln!("reloading rom failed: {:?}", e);
+                }
+            }
+        }
+
+        // When pacing by the audio output, a frame is only due once the output has played one of
+        // the queued frames of audio. Every frame queues one, so the output's clock sets the pace.
+        let audio_sync = app.config.args.audio_sync;
+        let due = !audio_sync
+            || app
+                .state
+                .as_ref()
+                .is_some_and(|state| state.sink.len() < MAX_QUEUED_AUDIO_FRAMES);
+        // during netplay a frame only runs once the peer's input for it has arrived
+        let ready = due
+            && app.state.as_mut().is_some_and(|state| {
+                state.rewinding || (App::frame_advance_ready(state) && App::netplay_frame(state))
+            });
+        if let Some(state) = app.state.as_mut().filter(|_| due && !ready && audio_sync) {
+            App::queue_silence(state);
+        } else if let Some(state) = app.state.as_mut().filter(|state| ready && state.rewinding) {
+            if audio_sync {
+                App::queue_silence(state);
+            }
+            if state.rewind_frames % REWIND_STEP_FRAMES == 0 {
+                match state.chip8.rewind(1) {
+                    Ok(_) => {
+                        state.full_redraw = true;
+                        state.window.request_redraw();
+                        App::export_watches(state);
+                    }
+                    Err(e) => {
+                        eprintln!("rewind failed: {:?}", e);
+                        state.rewinding = false;
+                    }
+                }
+            }
+            state.rewind_frames += 1;
+        } else if let Some(state) = app.state.as_mut().filter(|_| ready) {
+            if let Some(movie) = state.recording.as_mut() {
+                movie.record_frame(&state.chip8);
+            }
+            if let Some(playback) = state.playback.as_mut() {
+                if !playback.next_frame(&mut state.chip8) {
+                    println!(
+                        "Movie finished after {} frames, {} checkpoints verified",
+                        playback.frame(),
+                        playback.checkpoints_verified()
+                    );
+                    state.playback = None;
+                }
+            }
+            let fault = state.chip8.cycle().err();
+            if let Some(path) = state
+                .menu
+                .as_ref()
+                .and_then(|menu| menu.chosen(&state.chip8))
+                .map(Path::to_path_buf)
+            {
+                state.menu = None;
+                let title = path.file_stem().unwrap_or_default().to_string_lossy();
+                let message = title.to_ascii_uppercase();
+                if let Err(e) = App::load_rom(state, &app.config.args, &path, &message) {
+                    eprintln!("loading rom failed: {:?}", e);
+                }
+            }
+            state.metrics.record_frame(&state.chip8);
+            state.metrics.clients = state.netplay.is_some() as usize;
+            App::export_watches(state);
+            if let Some(movie) = state.recording.as_mut() {
+                movie.record_checkpoint(&state.chip8);
+            }
+            if let Some(Err(desync)) = state
+                .playback
+                .as_mut()
+                .map(|playback| playback.verify(&state.chip8))
+            {
+                eprintln!("movie playback failed: {}", desync);
+                state.playback = None;
+                App::show_overlay(state, format!("DESYNC AT FRAME {}", desync.frame));
+            }
+            for debug in &state.debug_windows {
+                debug.window.request_redraw();
+            }
+            if state.frame_advance {
+                App::show_frame_advance(state);
+            } else if state.overlay_frames > 0 {
+                state.overlay_frames -= 1;
+                if state.overlay_frames == 0 {
+                    // the overlay is drawn over every row, so they all have to be redrawn to clear it
+                    state.full_redraw = true;
+                }
+                state.window.request_redraw();
+            }
+            for event in state.chip8.events() {
+                match event {
+                    Event::Halted(reason) => eprintln!("halted: {}", reason),
+                    Event::FlagSaveFailed(e) => eprintln!("saving flags failed: {}", e),
+                    _ => {}
+                }
+            }
+            let finished = state.chip8.events().iter().find_map(|e| match e {
+                Event::Halted(HaltReason::Exited { .. }) => Some("PROGRAM EXITED"),
+                Event::Halted(HaltReason::IdleLoop { .. }) => Some("PROGRAM FINISHED"),
+                _ => None,
+            });
+            if let Some(message) = finished {
+                App::show_overlay(state, message.to_string());
+            }
+            if let Some(e) = fault {
+                // the faulting instruction would fault again every frame, so pause on it
+                eprintln!("emulation stopped: {}", e);
+                state.frame_advance = true;
+                App::show_overlay(state, "EMULATION STOPPED".to_string());
+            }
+            if state.waiting_for_key != state.chip8.is_waiting_for_key() {
+                state.waiting_for_key = !state.waiting_for_key;
+                state.window.set_title(if state.waiting_for_key {
+                    "CHIP-8 - press any key"
+                } else {
+                    "CHIP-8"
+                });
+            }
+            if let Some(netplay) = state.netplay.as_mut() {
+                if let Err(e) = netplay.record_hash(state.chip8.state_hash()) {
+                    eprintln!("netplay failed: {:?}", e);
+                    state.netplay = None;
+                }
+            }
+            if state.sound_indicator
+                && state
+                    .chip8
+                    .events()
+                    .iter()
+                    .any(|e| matches!(e, Event::SoundStarted | Event::SoundStopped))
+            {
+                // the border spans every row, so the whole frame has to be converted
+                state.full_redraw = true;
+                state.window.request_redraw();
+            }
             if state.chip8.is_fb_dirty() {
-                state.window.clone().request_redraw();
+                // Emulation keeps running at full speed, only presenting is skipped. The dirty
+                // rows accumulate until the next presented frame, so nothing is lost.
+                if state.skipped_frames >= app.config.args.frame_skip {
+                    state.skipped_frames = 0;
+                    state.window.clone().request_redraw();
+                } else {
+                    state.skipped_frames += 1;
+                }
             }
-            if state.chip8.is_sound_playing() {
-                state.sink.play();
-            } else {
-                state.sink.pause();
+            // Audio is rendered every frame to keep it in step with the emulated frames, but
+            // only queued while the sink isn't already backed up, which it never is when the
+            // frame was paced by it
+            let mut samples = vec![0.0; (SAMPLE_RATE / 60) as usize];
+            state.chip8.render_audio(&mut samples, SAMPLE_RATE);
+            if let Some(wav) = state.wav.as_mut() {
+                if let Err(e) = wav.write_samples(&samples) {
+                    eprintln!("audio recording failed: {:?}", e);
+                    state.wav = None;
+                }
+            }
+            if state.sink.len() < MAX_QUEUED_AUDIO_FRAMES {
+                state
+                    .sink
+                    .append(rodio::buffer::SamplesBuffer::new(1, SAMPLE_RATE, samples));
             }
         }
 
-        std::thread::sleep(FRAME_INTERVAL);
+        // scrapes are answered even while paused, so a stalled instance still reports
+        if let Some(state) = app.state.as_mut() {
+            if let Some(server) = state.metrics_server.as_ref() {
+                if let Err(e) = server.poll(&state.metrics) {
+                    eprintln!("serving metrics failed: {:?}", e);
+                    state.metrics_server = None;
+                }
+            }
+        }
+
+        std::thread::sleep(if audio_sync {
+            AUDIO_POLL_INTERVAL
+        } else {
+            FRAME_INTERVAL
+        });
+    };
+
+    if let Some(wav) = app.state.as_mut().and_then(|state| state.wav.take()) {
+        if let Err(e) = wav.finish() {
+            eprintln!("finishing audio recording failed: {:?}", e);
+        }
+    }
+
+    let recording = app.state.as_mut().and_then(|state| state.recording.take());
+    if let (Some(movie), Some(path)) = (recording, &app.config.args.record_movie) {
+        if let Err(e) = movie.save(path) {
+            eprintln!("saving movie failed: {:?}", e);
+        }
     }
+
+    exit_code
 }
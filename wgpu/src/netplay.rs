@@ -0,0 +1,444 @@
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+
+use anyhow::{bail, ensure, Context};
+use chip8::{MovieQuirks, Player};
+
+/// Identifies the netplay handshake
+const MAGIC: &[u8; 4] = b"C8NP";
+
+/// The protocol version, bumped whenever the messages change
+const VERSION: u8 = 2;
+
+/// Sent back in place of the version when the joining player turns the session down
+const REJECTED: u8 = 0;
+
+/// The number of frames between state hash comparisons
+const HASH_INTERVAL: u64 = 60;
+
+const INPUT_TAG: u8 = 0;
+const HASH_TAG: u8 = 1;
+
+/// A message exchanged every frame once the handshake is done
+enum Message {
+    /// The keypad mask of the sender's player for `frame`
+    Input { frame: u64, mask: u16 },
+    /// The sender's state hash after running `frame` frames
+    Hash { frame: u64, hash: u64 },
+}
+
+impl Message {
+    fn write(&self, stream: &mut TcpStream) -> anyhow::Result<()> {
+        let mut out = Vec::new();
+        match self {
+            Message::Input { frame, mask } => {
+                out.push(INPUT_TAG);
+                out.extend_from_slice(&frame.to_le_bytes());
+                out.extend_from_slice(&mask.to_le_bytes());
+            }
+            Message::Hash { frame, hash } => {
+                out.push(HASH_TAG);
+                out.extend_from_slice(&frame.to_le_bytes());
+                out.extend_from_slice(&hash.to_le_bytes());
+            }
+        }
+        stream.write_all(&out).context("send netplay message")
+    }
+
+    fn read(stream: &mut TcpStream) -> anyhow::Result<Self> {
+        let mut header = [0; 9];
+        stream
+            .read_exact(&mut header)
+            .context("receive netplay message")?;
+        let frame = u64::from_le_bytes(header[1..9].try_into().unwrap());
+        match header[0] {
+            INPUT_TAG => {
+                let mut mask = [0; 2];
+                stream
+                    .read_exact(&mut mask)
+                    .context("receive netplay input")?;
+                Ok(Message::Input {
+                    frame,
+                    mask: u16::from_le_bytes(mask),
+                })
+            }
+            HASH_TAG => {
+                let mut hash = [0; 8];
+                stream
+                    .read_exact(&mut hash)
+                    .context("receive netplay hash")?;
+                Ok(Message::Hash {
+                    frame,
+                    hash: u64::from_le_bytes(hash),
+                })
+            }
+            tag => bail!("unknown netplay message {}", tag),
+        }
+    }
+}
+
+/// A two-player lockstep session over TCP
+/// Both sides run identical cores seeded with the same value. Each frame's local input is sent
+/// `delay` frames ahead of when it's applied, so a frame only runs once the peer's input for it
+/// has arrived, and state hashes are compared periodically to catch desyncs.
+pub struct Netplay {
+    stream: TcpStream,
+    messages: Receiver<anyhow::Result<Message>>,
+    local_player: Player,
+    delay: u64,
+    /// The number of frames run so far
+    frame: u64,
+    local_inputs: BTreeMap<u64, u16>,
+    remote_inputs: BTreeMap<u64, u16>,
+    local_hashes: BTreeMap<u64, u64>,
+    remote_hashes: BTreeMap<u64, u64>,
+}
+
+impl Netplay {
+    /// Wait for a player to join on `addr`, returning the session and the seed both cores use
+    /// The host plays as player one and decides the input delay. The joining player has to be
+    /// running the same rom with the same `quirks` and speed.
+    pub fn host(
+        addr: &str,
+        rom_hash: &str,
+        quirks: &MovieQuirks,
+        delay: u8,
+    ) -> anyhow::Result<(Self, u64)> {
+        let listener = TcpListener::bind(addr).context("bind netplay address")?;
+        println!("Waiting for a player to join on {}", addr);
+        Self::accept(&listener, rom_hash, quirks, delay)
+    }
+
+    /// Wait for a player to join on `listener`, for `host`
+    fn accept(
+        listener: &TcpListener,
+        rom_hash: &str,
+        quirks: &MovieQuirks,
+        delay: u8,
+    ) -> anyhow::Result<(Self, u64)> {
+        let (mut stream, peer) = listener.accept().context("accept netplay connection")?;
+
+        let seed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64);
+        let mut hello = Vec::new();
+        hello.extend_from_slice(MAGIC);
+        hello.push(VERSION);
+        hello.push(delay);
+        hello.extend_from_slice(&seed.to_le_bytes());
+        hello.extend_from_slice(&encode_quirks(quirks));
+        hello.extend_from_slice(rom_hash.as_bytes());
+        stream.write_all(&hello).context("send netplay handshake")?;
+
+        // the peer replies with the magic and version once it's checked the rom, quirks and
+        // speed match
+        let mut reply = [0; 5];
+        stream
+            .read_exact(&mut reply)
+            .context("receive netplay handshake")?;
+        ensure!(
+            reply[0..4] == *MAGIC && reply[4] == VERSION,
+            "{} rejected the session",
+            peer
+        );
+
+        println!("{} joined as player two", peer);
+        Ok((Self::new(stream, Player::One, delay)?, seed))
+    }
+
+    /// Join the session hosted on `addr`, returning it and the seed both cores use
+    /// The session is turned down unless the host runs the same rom with the same `quirks` and
+    /// speed, as the cores would drift apart from the first frame otherwise.
+    pub fn join(addr: &str, rom_hash: &str, quirks: &MovieQuirks) -> anyhow::Result<(Self, u64)> {
+        let mut stream = TcpStream::connect(addr).context("connect to netplay host")?;
+
+        let mut hello = [0; 19];
+        stream
+            .read_exact(&mut hello)
+            .context("receive netplay handshake")?;
+        ensure!(
+            &hello[0..4] == MAGIC,
+            "{} isn't a chipper netplay host",
+            addr
+        );
+        ensure!(
+            hello[4] == VERSION,
+            "host uses netplay version {} (expected {})",
+            hello[4],
+            VERSION
+        );
+        let delay = hello[5];
+        let seed = u64::from_le_bytes(hello[6..14].try_into().unwrap());
+        let host_quirks: [u8; 5] = hello[14..19].try_into().unwrap();
+
+        let mut host_rom = vec![0; rom_hash.len()];
+        stream
+            .read_exact(&mut host_rom)
+            .context("receive netplay rom hash")?;
+        let local_quirks = encode_quirks(quirks);
+        let mismatch = if host_rom != rom_hash.as_bytes() {
+            Some("the host is running a different rom".to_string())
+        } else if host_quirks[0] != local_quirks[0] {
+            Some("the host is running with different quirks".to_string())
+        } else if host_quirks[1..] != local_quirks[1..] {
+            Some(format!(
+                "the host runs {} instructions per second (expected {})",
+                u32::from_le_bytes(host_quirks[1..].try_into().unwrap()),
+                quirks.clock_hz
+            ))
+        } else {
+            None
+        };
+
+        let mut reply = MAGIC.to_vec();
+        reply.push(if mismatch.is_some() {
+            REJECTED
+        } else {
+            VERSION
+        });
+        stream.write_all(&reply).context("send netplay handshake")?;
+        if let Some(mismatch) = mismatch {
+            bail!("{}", mismatch);
+        }
+
+        println!("Joined {} as player two", addr);
+        Ok((Self::new(stream, Player::Two, delay)?, seed))
+    }
+
+    fn new(stream: TcpStream, local_player: Player, delay: u8) -> anyhow::Result<Self> {
+        stream
+            .set_nodelay(true)
+            .context("configure netplay socket")?;
+
+        // reads block, so they happen on their own thread to keep the window responsive
+        let (sender, messages) = mpsc::channel();
+        let mut reader = stream.try_clone().context("clone netplay socket")?;
+        std::thread::spawn(move || loop {
+            let message = Message::read(&mut reader);
+            let failed = message.is_err();
+            if sender.send(message).is_err() || failed {
+                break;
+            }
+        });
+
+        // nobody can have pressed anything during the first frames
+        let delay = delay as u64;
+        Ok(Self {
+            stream,
+            messages,
+            local_player,
+            delay,
+            frame: 0,
+            local_inputs: (0..delay).map(|frame| (frame, 0)).collect(),
+            remote_inputs: (0..delay).map(|frame| (frame, 0)).collect(),
+            local_hashes: BTreeMap::new(),
+            remote_hashes: BTreeMap::new(),
+        })
+    }
+
+    /// Send this frame's local input and return the keypad masks of players one and two for the
+    /// next frame, or `None` if the peer's input for it hasn't arrived yet
+    pub fn advance(&mut self, local_mask: u16) -> anyhow::Result<Option<(u16, u16)>> {
+        self.receive()?;
+
+        let target = self.frame + self.delay;
+        if let Entry::Vacant(entry) = self.local_inputs.entry(target) {
+            entry.insert(local_mask);
+            Message::Input {
+                frame: target,
+                mask: local_mask,
+            }
+            .write(&mut self.stream)?;
+        }
+
+        let Some(remote) = self.remote_inputs.remove(&self.frame) else {
+            return Ok(None);
+        };
+        let local = self.local_inputs.remove(&self.frame).unwrap_or_default();
+        self.frame += 1;
+        Ok(Some(match self.local_player {
+            Player::One => (local, remote),
+            Player::Two => (remote, local),
+        }))
+    }
+
+    /// Record the state hash after the frame that was just run, comparing it with the peer's
+    pub fn record_hash(&mut self, hash: u64) -> anyhow::Result<()> {
+        if !self.frame.is_multiple_of(HASH_INTERVAL) {
+            return Ok(());
+        }
+        self.local_hashes.insert(self.frame, hash);
+        Message::Hash {
+            frame: self.frame,
+            hash,
+        }
+        .write(&mut self.stream)?;
+        self.compare_hashes()
+    }
+
+    fn receive(&mut self) -> anyhow::Result<()> {
+        loop {
+            match self.messages.try_recv() {
+                Ok(message) => match message.context("peer disconnected")? {
+                    Message::Input { frame, mask } => {
+                        self.remote_inputs.insert(frame, mask);
+                    }
+                    Message::Hash { frame, hash } => {
+                        self.remote_hashes.insert(frame, hash);
+                    }
+                },
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => bail!("peer disconnected"),
+            }
+        }
+        self.compare_hashes()
+    }
+
+    fn compare_hashes(&mut self) -> anyhow::Result<()> {
+        let frames: Vec<u64> = self
+            .local_hashes
+            .keys()
+            .filter(|frame| self.remote_hashes.contains_key(frame))
+            .copied()
+            .collect();
+        for frame in frames {
+            let local = self.local_hashes.remove(&frame);
+            let remote = self.remote_hashes.remove(&frame);
+            ensure!(local == remote, "desync detected at frame {}", frame);
+        }
+        Ok(())
+    }
+}
+
+/// Pack the quirks into a byte of flags followed by the clock speed, for the handshake
+fn encode_quirks(quirks: &MovieQuirks) -> [u8; 5] {
+    let flags = [
+        quirks.legacy_shift,
+        quirks.jump_add_offset,
+        quirks.memory_increment_i,
+        quirks.display_wait,
+        quirks.wrap_sprites,
+        quirks.unmasked_font,
+        quirks.vip_timing,
+    ]
+    .iter()
+    .enumerate()
+    .fold(0, |flags, (bit, set)| flags | (*set as u8) << bit);
+    let mut encoded = [flags, 0, 0, 0, 0];
+    encoded[1..].copy_from_slice(&quirks.clock_hz.to_le_bytes());
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+    use std::time::{Duration, Instant};
+
+    use chip8::{Chip8, MovieQuirks};
+
+    use super::Netplay;
+
+    const ROM_HASH: &str = "0123456789abcdef0123456789abcdef01234567";
+
+    /// Host a session on a loopback port and join it, returning the host's and the joining
+    /// player's ends
+    fn session(
+        host_quirks: MovieQuirks,
+        join_quirks: MovieQuirks,
+    ) -> (anyhow::Result<Netplay>, anyhow::Result<Netplay>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let host = std::thread::spawn(move || {
+            Netplay::accept(&listener, ROM_HASH, &host_quirks, 2).map(|(netplay, _)| netplay)
+        });
+        let joined = Netplay::join(&addr, ROM_HASH, &join_quirks).map(|(netplay, _)| netplay);
+        (host.join().unwrap(), joined)
+    }
+
+    /// Advance a frame once the peer's input for it arrives
+    fn advance(netplay: &mut Netplay, mask: u16) -> anyhow::Result<(u16, u16)> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            if let Some(masks) = netplay.advance(mask)? {
+                return Ok(masks);
+            }
+            assert!(Instant::now() < deadline, "the peer's input never arrived");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    /// The error a session that should have been turned down failed with
+    fn error(session: anyhow::Result<Netplay>) -> String {
+        match session {
+            Ok(_) => panic!("the session started"),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_netplay() {
+        let quirks = Chip8::new().unwrap().movie_quirks();
+        let (host, joined) = session(quirks, quirks);
+        let (mut host, mut joined) = (host.unwrap(), joined.unwrap());
+
+        // each player's input arrives on both sides two frames after it's sent
+        for frame in 0..120u16 {
+            let expected = if frame < 2 {
+                (0, 0)
+            } else {
+                (frame - 2, 0x100 + frame - 2)
+            };
+            assert_eq!(advance(&mut host, frame).unwrap(), expected);
+            assert_eq!(advance(&mut joined, 0x100 + frame).unwrap(), expected);
+
+            // the hashes match after frame 60, then the cores go their separate ways
+            let (host_hash, joined_hash) = if frame < 100 { (1, 1) } else { (1, 2) };
+            host.record_hash(host_hash).unwrap();
+            joined.record_hash(joined_hash).unwrap();
+        }
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let error = loop {
+            if let Err(e) = host.advance(0) {
+                break e;
+            }
+            assert!(Instant::now() < deadline, "the desync was never detected");
+            std::thread::sleep(Duration::from_millis(1));
+        };
+        assert_eq!(error.to_string(), "desync detected at frame 120");
+    }
+
+    #[test]
+    fn test_netplay_mismatch() {
+        let quirks = Chip8::new().unwrap().movie_quirks();
+        let (host, joined) = session(
+            quirks,
+            MovieQuirks {
+                clock_hz: quirks.clock_hz * 2,
+                ..quirks
+            },
+        );
+        assert!(error(host).ends_with("rejected the session"));
+        assert_eq!(
+            error(joined),
+            format!(
+                "the host runs {} instructions per second (expected {})",
+                quirks.clock_hz,
+                quirks.clock_hz * 2
+            )
+        );
+
+        let (host, joined) = session(
+            quirks,
+            MovieQuirks {
+                vip_timing: !quirks.vip_timing,
+                ..quirks
+            },
+        );
+        assert!(host.is_err());
+        assert_eq!(error(joined), "the host is running with different quirks");
+    }
+}
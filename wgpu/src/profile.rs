@@ -0,0 +1,63 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use chip8::{config_dir, Keymap};
+
+/// Return the directory the savestates for the ROM with the SHA-1 `hash` are kept in
+pub fn state_dir(hash: &str) -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("states").join(hash))
+}
+
+/// Return the path of the input profile for the ROM with the SHA-1 `hash`
+fn input_profile_path(hash: &str) -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("input").join(format!("{}.keymap", hash)))
+}
+
+/// Load the input profile saved for the ROM with the SHA-1 `hash`, if there is one
+pub fn load_input_profile(hash: &str) -> anyhow::Result<Option<Keymap>> {
+    let Some(path) = input_profile_path(hash) else {
+        return Ok(None);
+    };
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let text = std::fs::read_to_string(&path).context("read input profile")?;
+    let keymap =
+        Keymap::parse(&text).with_context(|| format!("parse input profile {}", path.display()))?;
+    Ok(Some(keymap))
+}
+
+/// Save `keymap` as the input profile for the ROM with the SHA-1 `hash`, returning its path
+pub fn save_input_profile(hash: &str, keymap: &Keymap) -> anyhow::Result<PathBuf> {
+    let path = input_profile_path(hash).context("no configuration directory available")?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).context("create input profile directory")?;
+    }
+    std::fs::write(&path, keymap.to_string()).context("write input profile")?;
+    Ok(path)
+}
+
+/// A single `label=key` binding given on the command line
+#[derive(Clone, Debug)]
+pub struct Binding {
+    pub label: String,
+    pub key: u8,
+}
+
+impl std::str::FromStr for Binding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // split on the last `=` so the `=` key itself can be bound
+        let (label, key) = s.rsplit_once('=').context("expected LABEL=KEY")?;
+        let key = u8::from_str_radix(key.trim(), 16)
+            .ok()
+            .filter(|key| *key <= 0xF)
+            .with_context(|| format!("invalid key '{}', expected a hex digit", key.trim()))?;
+        Ok(Self {
+            label: label.trim().to_string(),
+            key,
+        })
+    }
+}
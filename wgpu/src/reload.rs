@@ -0,0 +1,72 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+/// How long the ROM has to go unchanged before it's reloaded, so a build that writes it in
+/// several steps is only loaded once it's finished
+const SETTLE_TIME: Duration = Duration::from_millis(100);
+
+/// Watches the file a ROM was loaded from, such as the output of an external assembler
+pub struct RomWatcher {
+    path: PathBuf,
+    changes: Receiver<()>,
+    /// When the last change that hasn't been reloaded yet was seen
+    last_change: Option<Instant>,
+    /// Stops watching when dropped
+    _watcher: RecommendedWatcher,
+}
+
+impl RomWatcher {
+    pub fn new(path: &Path) -> anyhow::Result<Self> {
+        let path = path.canonicalize().context("resolve rom path")?;
+        // the directory is watched rather than the file, since tools that replace the file by
+        // renaming a new one over it would otherwise end the watch
+        let dir = path.parent().context("rom path has no directory")?;
+
+        let (sender, changes) = mpsc::channel();
+        let rom = path.clone();
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                let Ok(event) = event else {
+                    return;
+                };
+                if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+                    && event.paths.contains(&rom)
+                {
+                    let _ = sender.send(());
+                }
+            })
+            .context("create file watcher")?;
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .context("watch rom directory")?;
+
+        Ok(Self {
+            path,
+            changes,
+            last_change: None,
+            _watcher: watcher,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether the ROM has changed and settled since this last returned true
+    pub fn changed(&mut self) -> bool {
+        if self.changes.try_iter().count() > 0 {
+            self.last_change = Some(Instant::now());
+        }
+        match self.last_change {
+            Some(time) if time.elapsed() >= SETTLE_TIME => {
+                self.last_change = None;
+                true
+            }
+            _ => false,
+        }
+    }
+}
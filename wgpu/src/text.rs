@@ -0,0 +1,34 @@
+use chip8::glyph;
+
+/// The width of a character cell in pixels, including the gap after the glyph
+pub const CHAR_WIDTH: usize = 5;
+/// The height of a line in pixels, including the gap below the glyphs
+pub const LINE_HEIGHT: usize = 7;
+
+/// Draw `text` into an RGBA `frame` that's `width` pixels wide, with the top left of the first
+/// character at (`x`, `y`) and each glyph pixel drawn as a `scale` by `scale` square. Anything
+/// past the edges of the frame is clipped.
+pub fn draw_text(
+    frame: &mut [u8],
+    width: usize,
+    (x, y): (usize, usize),
+    scale: usize,
+    text: &str,
+    rgba: [u8; 4],
+) {
+    let height = frame.len() / 4 / width;
+    for (i, c) in text.chars().enumerate() {
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for column in (0..4).filter(|column| bits & (0x80 >> column) != 0) {
+                let left = x + (i * CHAR_WIDTH + column) * scale;
+                let top = y + row * scale;
+                for py in (top..top + scale).filter(|py| *py < height) {
+                    for px in (left..left + scale).filter(|px| *px < width) {
+                        let index = (py * width + px) * 4;
+                        frame[index..index + 4].copy_from_slice(&rgba);
+                    }
+                }
+            }
+        }
+    }
+}